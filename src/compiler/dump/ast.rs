@@ -0,0 +1,366 @@
+use super::{ident, Node, Value};
+use crate::compiler::{
+    keywords::Keyword,
+    objects::types::Abi,
+    treegen::ast::*,
+};
+
+pub fn dump_items(items: &[Item]) -> Vec<Node> {
+    items.iter().map(item).collect()
+}
+
+fn list<T>(items: &[T], node: impl Fn(&T) -> Node) -> Vec<Node> {
+    items.iter().map(node).collect()
+}
+
+fn idents(idents: &[Ident]) -> Vec<Node> {
+    list(idents, ident)
+}
+
+fn docs(docs: &[String]) -> Value {
+    Value::List(docs.iter().map(|doc| Value::from(doc.as_str())).collect())
+}
+
+pub(super) fn keywords(keywords: &[Keyword]) -> Value {
+    Value::List(keywords.iter().map(|keyword| Value::from(keyword.text())).collect())
+}
+
+pub(super) fn storage(storage: Storage) -> &'static str {
+    match storage {
+        Storage::Local => "local",
+        Storage::Static => "static",
+    }
+}
+
+pub(super) fn mutability(mutability: Mutability) -> &'static str {
+    match mutability {
+        Mutability::Var => "var",
+        Mutability::Const => "const",
+    }
+}
+
+pub(super) fn abi(abi: Abi) -> &'static str {
+    match abi {
+        Abi::Gurn => "gurn",
+        Abi::C => "c",
+    }
+}
+
+fn visibility(visibility: Visibility) -> &'static str {
+    match visibility {
+        Visibility::Default => "default",
+        Visibility::Pub => "pub",
+        Visibility::Private => "private",
+    }
+}
+
+fn decorator(decorator: &Decorator) -> Node {
+    Node::new("Decorator", decorator.span)
+        .field("name", &decorator.name)
+        .field("args", decorator.args.as_deref().map(|args| list(args, expr)))
+}
+
+// The fields every declaration starts with
+fn declaration(kind: &'static str, span: Span, doc: &[String], decorators: &[Decorator]) -> Node {
+    Node::new(kind, span).field("docs", docs(doc)).field("decorators", list(decorators, decorator))
+}
+
+fn item(item: &Item) -> Node {
+    match item {
+        Item::Struct(decl) => declaration("Struct", decl.span, &decl.docs, &decl.decorators)
+            .field("visibility", visibility(decl.visibility))
+            .field("name", &decl.name)
+            .field("generics", list(&decl.generics, generic_param))
+            .field("implements", idents(&decl.implements))
+            .field("where", decl.where_clause.as_ref().map(where_clause))
+            .field("fields", list(&decl.fields, field_decl)),
+        Item::Enum(decl) => declaration("Enum", decl.span, &decl.docs, &decl.decorators)
+            .field("visibility", visibility(decl.visibility))
+            .field("backing", decl.backing.as_ref().map(type_expr))
+            .field("name", &decl.name)
+            .field("generics", list(&decl.generics, generic_param))
+            .field("where", decl.where_clause.as_ref().map(where_clause))
+            .field("variants", list(&decl.variants, variant)),
+        Item::Trait(decl) => declaration("Trait", decl.span, &decl.docs, &decl.decorators)
+            .field("visibility", visibility(decl.visibility))
+            .field("name", &decl.name)
+            .field("generics", list(&decl.generics, generic_param))
+            .field("where", decl.where_clause.as_ref().map(where_clause))
+            .field("items", list(&decl.items, assoc_item)),
+        Item::Impl(decl) => declaration("Impl", decl.span, &decl.docs, &decl.decorators)
+            .field("generics", list(&decl.generics, generic_param))
+            .field("trait", decl.trait_name.as_ref())
+            .field("self_type", type_expr(&decl.self_ty))
+            .field("where", decl.where_clause.as_ref().map(where_clause))
+            .field("items", list(&decl.items, assoc_item)),
+        Item::Function(decl) => function(decl),
+        Item::Var(decl) => var(decl),
+        Item::Error(error, span) => Node::new("Error", *span).field("message", error.message.as_str()),
+    }
+}
+
+fn field_decl(decl: &FieldDecl) -> Node {
+    declaration("Field", decl.span, &decl.docs, &decl.decorators)
+        .field("visibility", visibility(decl.visibility))
+        .field("type", type_expr(&decl.ty))
+        .field("name", &decl.name)
+        .field("default", decl.default.as_ref().map(expr))
+}
+
+fn variant(decl: &VariantDecl) -> Node {
+    let (payload, items) = match &decl.payload {
+        VariantPayload::Unit => ("unit", Vec::new()),
+        VariantPayload::Tuple(types) => ("tuple", list(types, type_expr)),
+        VariantPayload::Struct(fields) => ("struct", list(fields, field_decl)),
+    };
+    declaration("Variant", decl.span, &decl.docs, &decl.decorators)
+        .field("name", &decl.name)
+        .field("payload", payload)
+        .field("items", items)
+        .field("discriminant", decl.discriminant.as_ref().map(expr))
+}
+
+fn assoc_item(item: &AssocItem) -> Node {
+    match item {
+        AssocItem::Method(decl) => function(decl),
+        AssocItem::Type(decl) => Node::new("AssocType", decl.span)
+            .field("docs", docs(&decl.docs))
+            .field("type", decl.ty.as_ref().map(type_expr))
+            .field("name", &decl.name),
+        AssocItem::Const(decl) => Node::new("AssocConst", decl.span)
+            .field("docs", docs(&decl.docs))
+            .field("type", type_expr(&decl.ty))
+            .field("name", &decl.name)
+            .field("value", decl.value.as_ref().map(expr)),
+    }
+}
+
+fn function(decl: &FnDecl) -> Node {
+    let body = match &decl.body {
+        Some(FnBody::Block(body)) => Value::Node(block(body)),
+        Some(FnBody::Expr(body)) => Value::Node(expr(body)),
+        None => Value::Null,
+    };
+    declaration("Function", decl.span, &decl.docs, &decl.decorators)
+        .field("visibility", visibility(decl.visibility))
+        .field("modifiers", keywords(&decl.modifiers))
+        .field("return_type", type_expr(&decl.return_type))
+        .field("name", &decl.name)
+        .field("generics", list(&decl.generics, generic_param))
+        .field("params", list(&decl.params, param))
+        .field("where", decl.where_clause.as_ref().map(where_clause))
+        .field("body", body)
+}
+
+fn param(param: &Param) -> Node {
+    Node::new("Param", param.span)
+        .field("modifiers", keywords(&param.modifiers))
+        .field("type", type_expr(&param.ty))
+        .field("name", &param.name)
+}
+
+fn var(decl: &VarDecl) -> Node {
+    Node::new("Var", decl.span)
+        .field("docs", docs(&decl.docs))
+        .field("storage", storage(decl.storage))
+        .field("mutability", mutability(decl.mutability))
+        .field("type", decl.ty.as_ref().map(type_expr))
+        .field("name", &decl.name)
+        .field("value", decl.value.as_ref().map(expr))
+}
+
+fn generic_param(param: &GenericParam) -> Node {
+    match &param.kind {
+        GenericParamKind::Type(bounds) => Node::new("TypeParam", param.span).field("name", &param.name).field("bounds", idents(bounds)),
+        GenericParamKind::Const(ty) => Node::new("ConstParam", param.span).field("type", type_expr(ty)).field("name", &param.name),
+    }
+}
+
+fn where_clause(clause: &WhereClause) -> Node {
+    let predicates = list(&clause.predicates, |predicate| {
+        Node::new("Predicate", predicate.span).field("type", type_expr(&predicate.ty)).field("bounds", idents(&predicate.bounds))
+    });
+    Node::new("Where", clause.span).field("predicates", predicates)
+}
+
+pub(super) fn type_expr(ty: &TypeExpr) -> Node {
+    let node = |kind| Node::new(kind, ty.span);
+    match &ty.kind {
+        TypeExprKind::Path(path, args) => node("PathType").field("path", idents(path)).field("args", list(args, type_expr)),
+        TypeExprKind::Pointer(to) => node("PointerType").field("to", type_expr(to)),
+        TypeExprKind::Reference(to) => node("ReferenceType").field("to", type_expr(to)),
+        TypeExprKind::Const(of) => node("ConstType").field("of", type_expr(of)),
+        TypeExprKind::Impl(bound) => node("ImplType").field("trait", bound),
+        TypeExprKind::Array(element, len) => {
+            let len = match len {
+                ArrayLen::Number(number) => number.text_content.clone(),
+                ArrayLen::Param(name) => name.name.clone(),
+            };
+            node("ArrayType").field("element", type_expr(element)).field("len", len)
+        }
+        TypeExprKind::Slice(element) => node("SliceType").field("element", type_expr(element)),
+        TypeExprKind::Tuple(items) => node("TupleType").field("items", list(items, type_expr)),
+        TypeExprKind::Function(abi, params, returns) => node("FnType")
+            .field("abi", self::abi(*abi))
+            .field("params", list(params, type_expr))
+            .field("returns", returns.as_deref().map(type_expr)),
+    }
+}
+
+fn block(block: &Block) -> Node {
+    Node::new("Block", block.span)
+        .field("stmts", list(&block.stmts, stmt))
+        .field("value", block.value.as_deref().map(expr))
+}
+
+fn stmt(stmt: &Stmt) -> Node {
+    let node = |kind| Node::new(kind, stmt.span);
+    match &stmt.kind {
+        StmtKind::Expr(value) => node("ExprStmt").field("expr", expr(value)),
+        StmtKind::Var(decl) => var(decl),
+        StmtKind::While(lp) => node("While")
+            .field("label", lp.label.as_ref())
+            .field("comptime", lp.comptime)
+            .field("cond", expr(&lp.cond))
+            .field("body", block(&lp.body)),
+        StmtKind::For(lp) => {
+            let node = node("For").field("label", lp.label.as_ref()).field("comptime", lp.comptime);
+            let node = match &lp.kind {
+                ForKind::Clauses { init, cond, step } => node
+                    .field("init", init.as_deref().map(self::stmt))
+                    .field("cond", cond.as_ref().map(expr))
+                    .field("step", step.as_ref().map(expr)),
+                ForKind::In { binding, iterable } => node.field("binding", binding).field("iterable", expr(iterable)),
+            };
+            node.field("body", block(&lp.body))
+        }
+        StmtKind::Break(label) => node("Break").field("label", label.as_ref()),
+        StmtKind::Continue(label) => node("Continue").field("label", label.as_ref()),
+        StmtKind::Return(value) => node("Return").field("value", value.as_ref().map(expr)),
+        StmtKind::Block(body) => block(body),
+        StmtKind::Defer(body) => node("Defer").field("body", block(body)),
+        StmtKind::Error(error) => node("Error").field("message", error.message.as_str()),
+    }
+}
+
+fn expr(expr: &Expr) -> Node {
+    let node = |kind| Node::new(kind, expr.span);
+    match &expr.kind {
+        ExprKind::Number(number) => node("Number").field("text", number.text_content.as_str()),
+        ExprKind::Str(string) => node("String").field("value", string.string_text_contents.as_str()),
+        ExprKind::Name(name) => node("Name").field("name", name.as_str()),
+        ExprKind::Unary(operator, value) => node("Unary").field("operator", operator.text()).field("operand", self::expr(value)),
+        ExprKind::Postfix(operator, value) => node("Postfix").field("operator", operator.text()).field("operand", self::expr(value)),
+        ExprKind::Binary(operator, lhs, rhs) => {
+            node("Binary").field("operator", operator.text()).field("lhs", self::expr(lhs)).field("rhs", self::expr(rhs))
+        }
+        ExprKind::Call(callee, args) => node("Call").field("callee", self::expr(callee)).field("args", list(args, self::expr)),
+        ExprKind::Index(value, index) => node("Index").field("value", self::expr(value)).field("index", self::expr(index)),
+        ExprKind::Field(value, name) => node("Field").field("value", self::expr(value)).field("field", name),
+        ExprKind::Tuple(items) => node("Tuple").field("items", list(items, self::expr)),
+        ExprKind::Array(items) => node("Array").field("items", list(items, self::expr)),
+        ExprKind::Match(value, arms) => node("Match").field("value", self::expr(value)).field("arms", list(arms, match_arm)),
+        ExprKind::If(conditional) => {
+            let branches = list(&conditional.branches, |branch| {
+                Node::new("Branch", branch.span).field("cond", self::expr(&branch.cond)).field("body", block(&branch.body))
+            });
+            node("If")
+                .field("comptime", conditional.comptime)
+                .field("branches", branches)
+                .field("else", conditional.else_branch.as_ref().map(block))
+        }
+        ExprKind::Closure(closure) => {
+            let params = list(&closure.params, |param| {
+                let span = param.ty.as_ref().map_or(param.name.span, |ty| ty.span.to(param.name.span));
+                Node::new("Param", span).field("type", param.ty.as_ref().map(type_expr)).field("name", &param.name)
+            });
+            node("Closure").field("params", params).field("body", block(&closure.body))
+        }
+        ExprKind::Propagate(value, cleanup) => node("Propagate").field("value", self::expr(value)).field("cleanup", block(cleanup)),
+    }
+}
+
+fn match_arm(arm: &MatchArm) -> Node {
+    Node::new("Arm", arm.span)
+        .field("pattern", pattern(&arm.pattern))
+        .field("guard", arm.guard.as_ref().map(expr))
+        .field("body", expr(&arm.body))
+}
+
+fn pattern(pattern: &Pattern) -> Node {
+    let node = |kind| Node::new(kind, pattern.span);
+    let args = |args: &[PatternArg]| {
+        list(args, |arg| Node::new("PatternArg", arg.span).field("name", arg.name.as_ref()).field("pattern", self::pattern(&arg.pattern)))
+    };
+    match &pattern.kind {
+        PatternKind::Wildcard => node("WildcardPattern"),
+        PatternKind::Binding(name) => node("BindingPattern").field("name", name),
+        PatternKind::Literal(number) => node("LiteralPattern").field("text", number.text_content.as_str()),
+        PatternKind::Range(start, end, inclusive) => node("RangePattern")
+            .field("start", start.text_content.as_str())
+            .field("end", end.text_content.as_str())
+            .field("inclusive", *inclusive),
+        PatternKind::Tuple(items) => node("TuplePattern").field("items", list(items, self::pattern)),
+        PatternKind::Struct(name, fields) => node("StructPattern").field("name", name).field("args", args(fields)),
+        PatternKind::Variant(ty, variant, fields) => node("VariantPattern")
+            .field("type", ty)
+            .field("variant", variant)
+            .field("args", fields.as_deref().map(args)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        dump::{render, Format},
+        parsing::tokenizer::tokenize_text,
+        treegen::items::parse_file,
+    };
+
+    fn dump(text: &str, format: Format) -> String {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        render(dump_items(&parse_file(&tokens, text.len()).0), format)
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            dump("u8 f() => a + 1;", Format::Json),
+            "[{\"kind\":\"Function\",\"span\":[0,16],\"docs\":[],\"decorators\":[],\"visibility\":\"default\",\"modifiers\":[],\
+             \"return_type\":{\"kind\":\"PathType\",\"span\":[0,2],\"path\":[{\"kind\":\"Ident\",\"span\":[0,2],\"name\":\"u8\"}],\"args\":[]},\
+             \"name\":{\"kind\":\"Ident\",\"span\":[3,4],\"name\":\"f\"},\"generics\":[],\"params\":[],\"where\":null,\
+             \"body\":{\"kind\":\"Binary\",\"span\":[10,15],\"operator\":\"+\",\
+             \"lhs\":{\"kind\":\"Name\",\"span\":[10,11],\"name\":\"a\"},\"rhs\":{\"kind\":\"Number\",\"span\":[14,15],\"text\":\"1\"}}}]\n"
+        );
+    }
+
+    #[test]
+    fn sexpr() {
+        // What failed to parse is still in the dump
+        assert_eq!(
+            dump("void f() { while (x) { } }\nstruct", Format::Sexpr),
+            r#"(
+  (Function 0 26
+    :docs ()
+    :decorators ()
+    :visibility "default"
+    :modifiers ()
+    :return_type (PathType 0 4 :path ((Ident 0 4 :name "void")) :args ())
+    :name (Ident 5 6 :name "f")
+    :generics ()
+    :params ()
+    :where nil
+    :body (Block 9 26
+      :stmts (
+        (While 11 24
+          :label nil
+          :comptime false
+          :cond (Name 18 19 :name "x")
+          :body (Block 21 24 :stmts () :value nil)))
+      :value nil))
+  (Error 27 33 :message "expected a declaration"))
+"#
+        );
+    }
+}
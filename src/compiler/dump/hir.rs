@@ -0,0 +1,157 @@
+use super::{
+    ast::{abi, keywords, mutability, storage, type_expr},
+    Node,
+};
+use crate::compiler::hir::{BlockId, ExprId, ExprKind, Function, Hir, Param, PatId, PatKind, StmtId, StmtKind};
+
+// Every function as a tree, each node of a body carries the `id` it has in its arena
+pub fn dump_hir(hir: &Hir) -> Vec<Node> {
+    hir.functions.iter().map(|function| self::function(hir, function)).collect()
+}
+
+fn function(hir: &Hir, function: &Function) -> Node {
+    Node::new("Function", function.span)
+        .field("owner", function.owner.as_ref().map(type_expr))
+        .field("name", &function.name)
+        .field("params", function.params.iter().map(param).collect::<Vec<_>>())
+        .field("return_type", type_expr(&function.return_type))
+        .field("body", function.body.map(|body| block(hir, body)))
+        .field("attributes", function.attributes.function_names())
+}
+
+fn param(param: &Param) -> Node {
+    let span = param.ty.as_ref().map_or(param.name.span, |ty| ty.span.to(param.name.span));
+    Node::new("Param", span)
+        .field("modifiers", keywords(&param.modifiers))
+        .field("type", param.ty.as_ref().map(type_expr))
+        .field("name", &param.name)
+}
+
+fn block(hir: &Hir, id: BlockId) -> Node {
+    let block = &hir.blocks[id];
+    Node::new("Block", block.span)
+        .field("id", id.index())
+        .field("stmts", block.stmts.iter().map(|stmt| self::stmt(hir, *stmt)).collect::<Vec<_>>())
+        .field("value", block.value.map(|value| expr(hir, value)))
+}
+
+fn stmt(hir: &Hir, id: StmtId) -> Node {
+    let stmt = &hir.stmts[id];
+    match &stmt.kind {
+        StmtKind::Expr(value) => Node::new("ExprStmt", stmt.span).field("id", id.index()).field("expr", expr(hir, *value)),
+        StmtKind::Local(local) => Node::new("Local", stmt.span)
+            .field("id", id.index())
+            .field("storage", storage(local.storage))
+            .field("mutability", mutability(local.mutability))
+            .field("type", local.ty.as_ref().map(type_expr))
+            .field("name", &local.name)
+            .field("value", local.value.map(|value| expr(hir, value))),
+    }
+}
+
+fn expr(hir: &Hir, id: ExprId) -> Node {
+    let expr = &hir.exprs[id];
+    let node = |kind| Node::new(kind, expr.span).field("id", id.index());
+    let exprs = |items: &[ExprId]| items.iter().map(|item| self::expr(hir, *item)).collect::<Vec<_>>();
+    match &expr.kind {
+        ExprKind::Number(number) => node("Number").field("text", number.text_content.as_str()),
+        ExprKind::Str(string) => node("String").field("value", string.string_text_contents.as_str()),
+        ExprKind::Name(name) => node("Name").field("name", name.as_str()),
+        ExprKind::Unary(operator, value) => node("Unary").field("operator", operator.text()).field("operand", self::expr(hir, *value)),
+        ExprKind::Binary(operator, lhs, rhs) => node("Binary")
+            .field("operator", operator.text())
+            .field("lhs", self::expr(hir, *lhs))
+            .field("rhs", self::expr(hir, *rhs)),
+        ExprKind::Call(callee, args) => node("Call").field("callee", self::expr(hir, *callee)).field("args", exprs(args)),
+        ExprKind::FnAddr(name) => node("FnAddr").field("name", name.as_str()),
+        ExprKind::CallIndirect(call_abi, callee, args) => node("CallIndirect")
+            .field("abi", abi(*call_abi))
+            .field("callee", self::expr(hir, *callee))
+            .field("args", exprs(args)),
+        ExprKind::Index(value, index) => node("Index").field("value", self::expr(hir, *value)).field("index", self::expr(hir, *index)),
+        ExprKind::Field(value, name) => node("Field").field("value", self::expr(hir, *value)).field("field", name),
+        ExprKind::Tuple(items) => node("Tuple").field("items", exprs(items)),
+        ExprKind::Array(items) => node("Array").field("items", exprs(items)),
+        ExprKind::Match(value, arms) => {
+            let arms = arms
+                .iter()
+                .map(|arm| {
+                    Node::new("Arm", arm.span)
+                        .field("pattern", pat(hir, arm.pat))
+                        .field("guard", arm.guard.map(|guard| self::expr(hir, guard)))
+                        .field("body", self::expr(hir, arm.body))
+                })
+                .collect::<Vec<_>>();
+            node("Match").field("value", self::expr(hir, *value)).field("arms", arms)
+        }
+        ExprKind::If { comptime, cond, then, otherwise } => node("If")
+            .field("comptime", *comptime)
+            .field("cond", self::expr(hir, *cond))
+            .field("then", block(hir, *then))
+            .field("else", otherwise.map(|otherwise| block(hir, otherwise))),
+        ExprKind::Loop { label, comptime, body, step } => node("Loop")
+            .field("label", label.as_ref())
+            .field("comptime", *comptime)
+            .field("body", block(hir, *body))
+            .field("step", step.map(|step| block(hir, step))),
+        ExprKind::Break(label) => node("Break").field("label", label.as_ref()),
+        ExprKind::Continue(label) => node("Continue").field("label", label.as_ref()),
+        ExprKind::Return(value) => node("Return").field("value", value.map(|value| self::expr(hir, value))),
+        ExprKind::Block(body) => node("BlockExpr").field("block", block(hir, *body)),
+        ExprKind::Closure(params, body) => {
+            node("Closure").field("params", params.iter().map(param).collect::<Vec<_>>()).field("body", block(hir, *body))
+        }
+        ExprKind::Error => node("Error"),
+    }
+}
+
+fn pat(hir: &Hir, id: PatId) -> Node {
+    let pat = &hir.pats[id];
+    let node = |kind| Node::new(kind, pat.span).field("id", id.index());
+    let args = |args: &[crate::compiler::hir::PatArg]| {
+        args.iter()
+            .map(|arg| {
+                let span = arg.name.as_ref().map_or(hir.pats[arg.pat].span, |name| name.span.to(hir.pats[arg.pat].span));
+                Node::new("PatternArg", span).field("name", arg.name.as_ref()).field("pattern", self::pat(hir, arg.pat))
+            })
+            .collect::<Vec<_>>()
+    };
+    match &pat.kind {
+        PatKind::Wildcard => node("WildcardPattern"),
+        PatKind::Binding(name) => node("BindingPattern").field("name", name),
+        PatKind::Literal(number) => node("LiteralPattern").field("text", number.text_content.as_str()),
+        PatKind::Range(start, end, inclusive) => node("RangePattern")
+            .field("start", start.text_content.as_str())
+            .field("end", end.text_content.as_str())
+            .field("inclusive", *inclusive),
+        PatKind::Tuple(items) => node("TuplePattern").field("items", items.iter().map(|item| self::pat(hir, *item)).collect::<Vec<_>>()),
+        PatKind::Struct(name, fields) => node("StructPattern").field("name", name).field("args", args(fields)),
+        PatKind::Variant(ty, variant, fields) => node("VariantPattern")
+            .field("type", ty)
+            .field("variant", variant)
+            .field("args", fields.as_deref().map(args)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        dump::{render, Format},
+        hir::lower::lower_items,
+        parsing::tokenizer::tokenize_text,
+        treegen::items::parse_items,
+    };
+
+    #[test]
+    fn ids() {
+        let text = "void f() { while (x) { g(); } }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let hir = lower_items(&parse_items(&tokens, text.len()).unwrap());
+        let dumped = render(dump_hir(&hir), Format::Sexpr);
+        assert!(dumped.contains("(Loop 11 29\n"), "{}", dumped);
+        assert!(dumped.contains(":expr (If 18 19\n"), "{}", dumped);
+        assert!(dumped.contains(":cond (Name 18 19 :id 0 :name \"x\")\n"), "{}", dumped);
+        assert!(dumped.contains("(Call 23 26 :id 4 :callee (Name 23 24 :id 3 :name \"g\") :args ())"), "{}", dumped);
+    }
+}
@@ -0,0 +1,254 @@
+// Tokens, syntax trees and the HIR as JSON or S-expressions, for tools that don't link the compiler.
+// Both formats write the same tree of nodes, the layout is described in docs/dump-format.md
+pub mod ast;
+pub mod hir;
+pub mod tokens;
+
+use std::fmt::Write;
+
+use crate::compiler::treegen::ast::{Ident, Span};
+
+// Lines of an S-expression dump are broken up once they grow past this
+const WIDTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Sexpr,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "sexpr" => Some(Format::Sexpr),
+            _ => None,
+        }
+    }
+}
+
+// One node of a dump, fields keep the order they were added in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    kind: &'static str,
+    span: Span,
+    fields: Vec<(&'static str, Value)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(usize),
+    Str(String),
+    Node(Node),
+    List(Vec<Value>),
+}
+
+impl Node {
+    pub fn new(kind: &'static str, span: Span) -> Self {
+        Self { kind, span, fields: Vec::new() }
+    }
+
+    pub fn field(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.fields.push((name, value.into()));
+        self
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<Node> for Value {
+    fn from(value: Node) -> Self {
+        Value::Node(value)
+    }
+}
+
+impl From<&Ident> for Value {
+    fn from(value: &Ident) -> Self {
+        Value::Node(ident(value))
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Value::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+pub fn ident(ident: &Ident) -> Node {
+    Node::new("Ident", ident.span).field("name", ident.name.as_str())
+}
+
+// Every node in `nodes`, followed by a newline
+pub fn render(nodes: Vec<Node>, format: Format) -> String {
+    let value = Value::from(nodes);
+    let mut out = String::new();
+    match format {
+        Format::Json => json(&value, &mut out),
+        Format::Sexpr => sexpr(&value, 0, 0, &mut out),
+    }
+    out.push('\n');
+    out
+}
+
+fn json_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// `{"kind":"Name","span":[4,5],"name":"x"}`, on a single line
+fn json(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(value) => write!(out, "{}", value).unwrap(),
+        Value::Int(value) => write!(out, "{}", value).unwrap(),
+        Value::Str(text) => json_string(text, out),
+        Value::Node(node) => {
+            write!(out, "{{\"kind\":\"{}\",\"span\":[{},{}]", node.kind, node.span.start, node.span.end).unwrap();
+            for (name, value) in &node.fields {
+                write!(out, ",\"{}\":", name).unwrap();
+                json(value, out);
+            }
+            out.push('}');
+        }
+        Value::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                json(item, out);
+            }
+            out.push(']');
+        }
+    }
+}
+
+// `(Name 4 5 :name "x")` on one line when it fits from `column` on, otherwise one field or list item per line,
+// two columns right of `indent`, where the line the node or list starts on is indented to
+fn sexpr(value: &Value, indent: usize, column: usize, out: &mut String) {
+    let flat = sexpr_flat(value);
+    if column + flat.len() <= WIDTH {
+        out.push_str(&flat);
+        return;
+    }
+    match value {
+        Value::Node(node) => {
+            let inner = indent + 2;
+            write!(out, "({} {} {}", node.kind, node.span.start, node.span.end).unwrap();
+            for (name, value) in &node.fields {
+                write!(out, "\n{}:{} ", " ".repeat(inner), name).unwrap();
+                sexpr(value, inner, inner + name.len() + 2, out);
+            }
+            out.push(')');
+        }
+        Value::List(items) => {
+            let inner = indent + 2;
+            out.push('(');
+            for item in items {
+                write!(out, "\n{}", " ".repeat(inner)).unwrap();
+                sexpr(item, inner, inner, out);
+            }
+            out.push(')');
+        }
+        // Only nodes and lists are ever too long to share a line
+        _ => out.push_str(&flat),
+    }
+}
+
+fn sexpr_flat(value: &Value) -> String {
+    match value {
+        Value::Null => "nil".to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Int(value) => value.to_string(),
+        Value::Str(text) => {
+            let mut out = String::new();
+            json_string(text, &mut out);
+            out
+        }
+        Value::Node(node) => {
+            let mut out = format!("({} {} {}", node.kind, node.span.start, node.span.end);
+            for (name, value) in &node.fields {
+                write!(out, " :{} {}", name, sexpr_flat(value)).unwrap();
+            }
+            out.push(')');
+            out
+        }
+        Value::List(items) => format!("({})", items.iter().map(sexpr_flat).collect::<Vec<_>>().join(" ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Node> {
+        let name = Node::new("Name", Span::new(0, 1)).field("name", "a\"b");
+        vec![Node::new("Call", Span::new(0, 6))
+            .field("callee", name)
+            .field("args", Vec::<Node>::new())
+            .field("label", None::<Node>)
+            .field("comptime", true)
+            .field("id", 3)]
+    }
+
+    #[test]
+    fn json_layout() {
+        assert_eq!(
+            render(sample(), Format::Json),
+            "[{\"kind\":\"Call\",\"span\":[0,6],\"callee\":{\"kind\":\"Name\",\"span\":[0,1],\"name\":\"a\\\"b\"},\
+             \"args\":[],\"label\":null,\"comptime\":true,\"id\":3}]\n"
+        );
+    }
+
+    #[test]
+    fn sexpr_layout() {
+        assert_eq!(render(sample(), Format::Sexpr), "((Call 0 6 :callee (Name 0 1 :name \"a\\\"b\") :args () :label nil :comptime true :id 3))\n");
+
+        // Too long for one line, every field and list item gets its own
+        let long = "x".repeat(WIDTH);
+        let nodes = vec![Node::new("Tuple", Span::new(0, 9)).field("items", vec![Node::new("Name", Span::new(1, 2)).field("name", long.as_str())])];
+        assert_eq!(
+            render(nodes, Format::Sexpr),
+            format!("(\n  (Tuple 0 9\n    :items (\n      (Name 1 2\n        :name \"{}\"))))\n", long)
+        );
+    }
+}
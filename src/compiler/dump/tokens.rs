@@ -0,0 +1,60 @@
+use super::Node;
+use crate::compiler::{
+    parsing::tokenizer::{Token, TokenData},
+    treegen::ast::Span,
+};
+
+// Every token, whitespace and comments included, brackets hold the tokens inside them
+pub fn dump_tokens(tokens: &[Token]) -> Vec<Node> {
+    tokens.iter().map(token).collect()
+}
+
+fn token(token: &Token) -> Node {
+    let span = Span::of_token(token);
+    match &token.data {
+        TokenData::Keyword(keyword) => Node::new("Keyword", span).field("text", keyword.text()),
+        TokenData::TextCluster(text) => Node::new("Ident", span).field("name", text.clone()),
+        TokenData::NumberLiteral(number) => Node::new("Number", span).field("text", number.text_content.as_str()),
+        TokenData::StringLiteral(string) => Node::new("String", span).field("value", string.string_text_contents.as_str()),
+        TokenData::Whitespace(text) => Node::new("Whitespace", span).field("text", text.as_str()),
+        TokenData::Operator(operator) => Node::new("Operator", span).field("text", operator.text()),
+        TokenData::Semicolon => Node::new("Semicolon", span),
+        // The tokenizer's `Colon` is `,`
+        TokenData::Colon => Node::new("Comma", span),
+        TokenData::AtSign => Node::new("At", span),
+        TokenData::Dot => Node::new("Dot", span),
+        TokenData::ColonSign => Node::new("Colon", span),
+        TokenData::Label(name) => Node::new("Label", span).field("name", name.as_str()),
+        TokenData::DocComment(text) => Node::new("DocComment", span).field("text", text.as_str()),
+        TokenData::Bracket(open, inner) => Node::new("Bracket", span)
+            .field("open", (*open as char).to_string())
+            .field("tokens", inner.as_deref().map(dump_tokens)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        dump::{render, Format},
+        parsing::tokenizer::tokenize_text,
+    };
+
+    #[test]
+    fn tokens() {
+        let text = "f(a, 1); // done";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        assert_eq!(
+            render(dump_tokens(&tokens), Format::Sexpr),
+            r#"(
+  (Ident 0 1 :name "f")
+  (Bracket 1 7
+    :open "("
+    :tokens ((Ident 2 3 :name "a") (Comma 3 4) (Whitespace 4 5 :text " ") (Number 5 6 :text "1")))
+  (Semicolon 7 8)
+  (Whitespace 8 9 :text " ")
+  (Whitespace 9 16 :text "// done"))
+"#
+        );
+    }
+}
@@ -0,0 +1,629 @@
+use super::{Arm, Block, BlockId, Expr, ExprId, ExprKind, Function, Hir, Local, Param, Pat, PatArg, PatId, PatKind, Stmt, StmtId, StmtKind};
+use crate::compiler::{
+    lowering::defer::lower_defers,
+    objects::types::Abi,
+    operators::Operator,
+    parsing::number_parser::NumberLiteral,
+    semantic::decorators::DecoratorRegistry,
+    treegen::{
+        ast::{self, AssocItem, FnBody, FnDecl, ForKind, Ident, Item, Mutability, NodeId, Span, Storage, TypeExpr, TypeExprKind},
+        parser::FreshIds,
+    },
+};
+
+// Names of the variables lowering introduces, like the ones `lower_defers` uses
+const START_TEMP: &str = "__start";
+const END_TEMP: &str = "__end";
+const ITER_TEMP: &str = "__iter";
+const VALUE_TEMP: &str = "__value";
+const ERROR_TEMP: &str = "__error";
+
+// There is no standard library yet, these are the types `?` and `!` are written for
+const MAYBE: (&str, &str, &str) = ("Maybe", "Some", "Nothing");
+const RESULT: (&str, &str, &str) = ("Result", "Ok", "Err");
+
+// Every function in `items`, methods included. Nodes are allocated children first, in source order,
+// so the same items always get the same ids
+pub fn lower_items(items: &[Item]) -> Hir {
+    lower_checked_items(items, &[], &[])
+}
+
+// `lower_items` with what the type checker found out about functions as values. The names in `function_values`
+// become `FnAddr`s and the calls in `indirect_calls` go through a fn pointer, the ids being those of the AST
+pub fn lower_checked_items(items: &[Item], function_values: &[NodeId], indirect_calls: &[(NodeId, Abi)]) -> Hir {
+    let ids = FreshIds::after_items(items);
+    let mut lowerer = Lowerer { hir: Hir::default(), function_values, indirect_calls, ids };
+    for item in items {
+        match item {
+            Item::Function(decl) => lowerer.function(decl, None),
+            Item::Impl(decl) => lowerer.methods(&decl.items, &decl.self_ty),
+            Item::Trait(decl) => {
+                let owner = TypeExpr { kind: TypeExprKind::Path(vec![decl.name.clone()], Vec::new()), span: decl.name.span, id: lowerer.ids.next() };
+                lowerer.methods(&decl.items, &owner)
+            }
+            Item::Struct(_) | Item::Enum(_) | Item::Var(_) | Item::Error(..) => {}
+        }
+    }
+    lowerer.hir
+}
+
+struct Lowerer<'c> {
+    hir: Hir,
+    function_values: &'c [NodeId],
+    indirect_calls: &'c [(NodeId, Abi)],
+    // For the AST nodes made on the way, copies of deferred code lead back to the ids the checker saw
+    ids: FreshIds,
+}
+
+fn ident(name: &str, span: Span) -> Ident {
+    Ident { name: name.to_string(), span }
+}
+
+impl Lowerer<'_> {
+    fn methods(&mut self, items: &[AssocItem], owner: &TypeExpr) {
+        for item in items {
+            if let AssocItem::Method(decl) = item {
+                self.function(decl, Some(owner.clone()));
+            }
+        }
+    }
+
+    fn function(&mut self, decl: &FnDecl, owner: Option<TypeExpr>) {
+        let body = decl.body.as_ref().map(|body| {
+            let block = match body {
+                FnBody::Block(block) => block.clone(),
+                FnBody::Expr(expr) => ast::Block { stmts: Vec::new(), value: Some(Box::new(expr.clone())), span: expr.span, id: self.ids.next() },
+            };
+            let block = lower_defers(&block, &mut self.ids);
+            self.block(&block)
+        });
+        let params = decl
+            .params
+            .iter()
+            .map(|param| Param { modifiers: param.modifiers.clone(), ty: Some(param.ty.clone()), name: param.name.clone() })
+            .collect();
+        self.hir.functions.push(Function {
+            owner,
+            name: decl.name.clone(),
+            params,
+            return_type: decl.return_type.clone(),
+            body,
+            // Wrong arguments are reported when the decorators are checked
+            attributes: DecoratorRegistry::default().attributes(&decl.decorators).unwrap_or_default(),
+            span: decl.span,
+        });
+    }
+
+    fn alloc(&mut self, kind: ExprKind, span: Span) -> ExprId {
+        self.hir.exprs.alloc(Expr { kind, span })
+    }
+
+    fn alloc_stmt(&mut self, kind: StmtKind, span: Span) -> StmtId {
+        self.hir.stmts.alloc(Stmt { kind, span })
+    }
+
+    fn alloc_block(&mut self, stmts: Vec<StmtId>, value: Option<ExprId>, span: Span) -> BlockId {
+        self.hir.blocks.alloc(Block { stmts, value, span })
+    }
+
+    fn expr_stmt(&mut self, kind: ExprKind, span: Span) -> StmtId {
+        let expr = self.alloc(kind, span);
+        self.alloc_stmt(StmtKind::Expr(expr), span)
+    }
+
+    fn local(&mut self, mutability: Mutability, name: &str, value: Option<ExprId>, span: Span) -> StmtId {
+        let local = Local { storage: Storage::Local, mutability, ty: None, name: ident(name, span), value };
+        self.alloc_stmt(StmtKind::Local(local), span)
+    }
+
+    fn name(&mut self, name: &str, span: Span) -> ExprId {
+        self.alloc(ExprKind::Name(name.to_string()), span)
+    }
+
+    fn block(&mut self, block: &ast::Block) -> BlockId {
+        let mut stmts = Vec::new();
+        for stmt in &block.stmts {
+            stmts.push(self.stmt(stmt));
+        }
+        let value = block.value.as_ref().map(|value| self.expr(value));
+        self.alloc_block(stmts, value, block.span)
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) -> StmtId {
+        let span = stmt.span;
+        let kind = match &stmt.kind {
+            ast::StmtKind::Expr(expr) => {
+                let expr = self.expr(expr);
+                return self.alloc_stmt(StmtKind::Expr(expr), span);
+            }
+            ast::StmtKind::Var(decl) => {
+                let value = decl.value.as_ref().map(|value| self.expr(value));
+                let local = Local { storage: decl.storage, mutability: decl.mutability, ty: decl.ty.clone(), name: decl.name.clone(), value };
+                return self.alloc_stmt(StmtKind::Local(local), span);
+            }
+            ast::StmtKind::While(lp) => {
+                let check = self.check(&lp.cond);
+                let body = self.loop_body(vec![check], &lp.body);
+                ExprKind::Loop { label: lp.label.clone(), comptime: lp.comptime, body, step: None }
+            }
+            ast::StmtKind::For(lp) => return self.for_loop(lp),
+            ast::StmtKind::Break(label) => ExprKind::Break(label.clone()),
+            ast::StmtKind::Continue(label) => ExprKind::Continue(label.clone()),
+            ast::StmtKind::Return(value) => ExprKind::Return(value.as_ref().map(|value| self.expr(value))),
+            ast::StmtKind::Block(block) => ExprKind::Block(self.block(block)),
+            ast::StmtKind::Error(_) => ExprKind::Error,
+            ast::StmtKind::Defer(_) => unreachable!("defers are lowered before the rest"),
+        };
+        self.expr_stmt(kind, span)
+    }
+
+    // `if (cond) {} else { break; }`, how a loop stops
+    fn check(&mut self, cond: &ast::Expr) -> StmtId {
+        let span = cond.span;
+        let cond = self.expr(cond);
+        let then = self.alloc_block(Vec::new(), None, span);
+        let stop = self.expr_stmt(ExprKind::Break(None), span);
+        let otherwise = self.alloc_block(vec![stop], None, span);
+        self.expr_stmt(ExprKind::If { comptime: false, cond, then, otherwise: Some(otherwise) }, span)
+    }
+
+    // The user's body after the statements that decide whether it runs
+    fn loop_body(&mut self, mut stmts: Vec<StmtId>, body: &ast::Block) -> BlockId {
+        let span = body.span;
+        let body = self.block(body);
+        stmts.push(self.expr_stmt(ExprKind::Block(body), span));
+        self.alloc_block(stmts, None, span)
+    }
+
+    fn for_loop(&mut self, lp: &ast::ForLoop) -> StmtId {
+        let span = lp.span;
+        let mut outer = Vec::new();
+        let (body, step) = match &lp.kind {
+            // `{ init; loop { if (cond) {} else { break; } body } step { step; } }`
+            ForKind::Clauses { init, cond, step } => {
+                if let Some(init) = init {
+                    outer.push(self.stmt(init));
+                }
+                let check = cond.as_ref().map(|cond| self.check(cond));
+                let body = self.loop_body(check.into_iter().collect(), &lp.body);
+                let step = step.as_ref().map(|step| {
+                    let expr = self.expr(step);
+                    let stmt = self.alloc_stmt(StmtKind::Expr(expr), step.span);
+                    self.alloc_block(vec![stmt], None, step.span)
+                });
+                (body, step)
+            }
+            // `{ const __start = a; const __end = b; var x = __start; loop { if (x < __end) {} else { break; } body } step { x += 1; } }`.
+            // An inclusive range compares with `x <= __end`, and stops in the step before `x` can go past the end
+            ForKind::In { binding, iterable: ast::Expr { kind: ast::ExprKind::Binary(operator @ (Operator::Range | Operator::RangeInclusive), start, end), .. } } => {
+                let inclusive = *operator == Operator::RangeInclusive;
+                let start = self.expr(start);
+                outer.push(self.local(Mutability::Const, START_TEMP, Some(start), span));
+                let end = self.expr(end);
+                outer.push(self.local(Mutability::Const, END_TEMP, Some(end), span));
+                let start = self.name(START_TEMP, span);
+                outer.push(self.local(Mutability::Var, &binding.name, Some(start), binding.span));
+
+                let compare = if inclusive { Operator::LesserThanEq } else { Operator::LesserThan };
+                let cond = ast::Expr {
+                    kind: ast::ExprKind::Binary(
+                        compare,
+                        Box::new(ast::Expr { kind: ast::ExprKind::Name(binding.name.clone()), span: binding.span, id: self.ids.next() }),
+                        Box::new(ast::Expr { kind: ast::ExprKind::Name(END_TEMP.to_string()), span, id: self.ids.next() }),
+                    ),
+                    span,
+                    id: self.ids.next(),
+                };
+                let check = self.check(&cond);
+                let body = self.loop_body(vec![check], &lp.body);
+
+                let mut stmts = Vec::new();
+                if inclusive {
+                    let item = self.name(&binding.name, binding.span);
+                    let end = self.name(END_TEMP, span);
+                    let cond = self.alloc(ExprKind::Binary(Operator::EqualityCheck, item, end), span);
+                    let stop = self.expr_stmt(ExprKind::Break(None), span);
+                    let then = self.alloc_block(vec![stop], None, span);
+                    stmts.push(self.expr_stmt(ExprKind::If { comptime: false, cond, then, otherwise: None }, span));
+                }
+                let item = self.name(&binding.name, binding.span);
+                let one = self.alloc(ExprKind::Number(NumberLiteral::new("1").unwrap().1), span);
+                stmts.push(self.expr_stmt(ExprKind::Binary(Operator::AddEq, item, one), span));
+                let step = self.alloc_block(stmts, None, span);
+                (body, Some(step))
+            }
+            // `{ var __iter = iterable; loop { var x; if (__iter.next(&x)) {} else { break; } body } }`
+            ForKind::In { binding, iterable } => {
+                let iterable = self.expr(iterable);
+                outer.push(self.local(Mutability::Var, ITER_TEMP, Some(iterable), span));
+
+                let item = self.local(Mutability::Var, &binding.name, None, binding.span);
+                let iter = self.name(ITER_TEMP, span);
+                let next = self.alloc(ExprKind::Field(iter, ident("next", span)), span);
+                let item_name = self.name(&binding.name, binding.span);
+                let item_ref = self.alloc(ExprKind::Unary(Operator::Reference, item_name), binding.span);
+                let cond = self.alloc(ExprKind::Call(next, vec![item_ref]), span);
+                let then = self.alloc_block(Vec::new(), None, span);
+                let stop = self.expr_stmt(ExprKind::Break(None), span);
+                let otherwise = self.alloc_block(vec![stop], None, span);
+                let check = self.expr_stmt(ExprKind::If { comptime: false, cond, then, otherwise: Some(otherwise) }, span);
+                (self.loop_body(vec![item, check], &lp.body), None)
+            }
+        };
+        let lp = self.expr_stmt(ExprKind::Loop { label: lp.label.clone(), comptime: lp.comptime, body, step }, span);
+        if outer.is_empty() {
+            return lp;
+        }
+        // The loop's own variables go out of scope with it
+        outer.push(lp);
+        let block = self.alloc_block(outer, None, span);
+        self.expr_stmt(ExprKind::Block(block), span)
+    }
+
+    fn exprs(&mut self, exprs: &[ast::Expr]) -> Vec<ExprId> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn expr(&mut self, expr: &ast::Expr) -> ExprId {
+        let span = expr.span;
+        let kind = match &expr.kind {
+            ast::ExprKind::Number(number) => ExprKind::Number(number.clone()),
+            ast::ExprKind::Str(string) => ExprKind::Str(string.clone()),
+            ast::ExprKind::Name(name) if self.function_values.contains(&self.ids.original(expr.id)) => ExprKind::FnAddr(name.clone()),
+            ast::ExprKind::Name(name) => ExprKind::Name(name.clone()),
+            ast::ExprKind::Unary(operator, value) => ExprKind::Unary(*operator, self.expr(value)),
+            ast::ExprKind::Postfix(Operator::OptionalOperator, value) => return self.unwrap(value, MAYBE, None, span),
+            ast::ExprKind::Postfix(_, value) => return self.unwrap(value, RESULT, None, span), // `!`
+            ast::ExprKind::Propagate(value, cleanup) => return self.unwrap(value, RESULT, Some(cleanup), span),
+            ast::ExprKind::Binary(operator, lhs, rhs) => {
+                let lhs = self.expr(lhs);
+                ExprKind::Binary(*operator, lhs, self.expr(rhs))
+            }
+            ast::ExprKind::Call(callee, args) => {
+                let callee = self.expr(callee);
+                let args = self.exprs(args);
+                let original = self.ids.original(expr.id);
+                match self.indirect_calls.iter().find(|(id, _)| *id == original) {
+                    Some(&(_, abi)) => ExprKind::CallIndirect(abi, callee, args),
+                    None => ExprKind::Call(callee, args),
+                }
+            }
+            ast::ExprKind::Index(value, index) => {
+                let value = self.expr(value);
+                ExprKind::Index(value, self.expr(index))
+            }
+            ast::ExprKind::Field(value, field) => ExprKind::Field(self.expr(value), field.clone()),
+            ast::ExprKind::Tuple(items) => ExprKind::Tuple(self.exprs(items)),
+            ast::ExprKind::Array(items) => ExprKind::Array(self.exprs(items)),
+            ast::ExprKind::Match(value, arms) => {
+                let value = self.expr(value);
+                let arms = arms
+                    .iter()
+                    .map(|arm| {
+                        let pat = self.pattern(&arm.pattern);
+                        let guard = arm.guard.as_ref().map(|guard| self.expr(guard));
+                        Arm { pat, guard, body: self.expr(&arm.body), span: arm.span }
+                    })
+                    .collect();
+                ExprKind::Match(value, arms)
+            }
+            ast::ExprKind::If(conditional) => return self.conditional(conditional),
+            ast::ExprKind::Closure(closure) => {
+                let params = closure
+                    .params
+                    .iter()
+                    .map(|param| Param { modifiers: Vec::new(), ty: param.ty.clone(), name: param.name.clone() })
+                    .collect();
+                ExprKind::Closure(params, self.block(&closure.body))
+            }
+        };
+        self.alloc(kind, span)
+    }
+
+    // `if (a) x else if (b) y else z` is `if (a) x else { if (b) y else z }`
+    fn conditional(&mut self, conditional: &ast::Conditional) -> ExprId {
+        let mut otherwise = conditional.else_branch.as_ref().map(|block| self.block(block));
+        let mut lowered = None;
+        for (i, branch) in conditional.branches.iter().enumerate().rev() {
+            let cond = self.expr(&branch.cond);
+            let then = self.block(&branch.body);
+            // The first branch's `if` spans the whole chain
+            let span = if i == 0 { conditional.span } else { branch.span.to(conditional.span) };
+            let expr = self.alloc(ExprKind::If { comptime: conditional.comptime, cond, then, otherwise }, span);
+            if i > 0 {
+                otherwise = Some(self.alloc_block(Vec::new(), Some(expr), span));
+            }
+            lowered = Some(expr);
+        }
+        lowered.unwrap()
+    }
+
+    // `value?` and `value!` as a match that hands back what the value holds, or returns its failure:
+    // `match (value) { Maybe.Some(__value) => __value, Maybe.Nothing => return Maybe.Nothing }` and
+    // `match (value) { Result.Ok(__value) => __value, Result.Err(__error) => { cleanup; return Result.Err(__error) } }`
+    fn unwrap(&mut self, value: &ast::Expr, (ty, hit, miss): (&str, &str, &str), cleanup: Option<&ast::Block>, span: Span) -> ExprId {
+        let value = self.expr(value);
+        let carries_error = ty == RESULT.0;
+
+        let binding = self.hir.pats.alloc(Pat { kind: PatKind::Binding(ident(VALUE_TEMP, span)), span });
+        let pat = self.variant_pat(ty, hit, Some(binding), span);
+        let body = self.name(VALUE_TEMP, span);
+        let hit = Arm { pat, guard: None, body, span };
+
+        let binding = carries_error.then(|| self.hir.pats.alloc(Pat { kind: PatKind::Binding(ident(ERROR_TEMP, span)), span }));
+        let pat = self.variant_pat(ty, miss, binding, span);
+        let ty_name = self.name(ty, span);
+        let mut failure = self.alloc(ExprKind::Field(ty_name, ident(miss, span)), span);
+        if carries_error {
+            let error = self.name(ERROR_TEMP, span);
+            failure = self.alloc(ExprKind::Call(failure, vec![error]), span);
+        }
+        let ret = self.alloc(ExprKind::Return(Some(failure)), span);
+        let body = match cleanup {
+            Some(cleanup) => {
+                let cleanup = self.block(cleanup);
+                let cleanup = self.expr_stmt(ExprKind::Block(cleanup), span);
+                let ret = self.alloc_stmt(StmtKind::Expr(ret), span);
+                let block = self.alloc_block(vec![cleanup, ret], None, span);
+                self.alloc(ExprKind::Block(block), span)
+            }
+            None => ret,
+        };
+        let miss = Arm { pat, guard: None, body, span };
+        self.alloc(ExprKind::Match(value, vec![hit, miss]), span)
+    }
+
+    fn variant_pat(&mut self, ty: &str, variant: &str, binding: Option<PatId>, span: Span) -> PatId {
+        let args = binding.map(|pat| vec![PatArg { name: None, pat }]);
+        self.hir.pats.alloc(Pat { kind: PatKind::Variant(ident(ty, span), ident(variant, span), args), span })
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern) -> PatId {
+        let kind = match &pattern.kind {
+            ast::PatternKind::Wildcard => PatKind::Wildcard,
+            ast::PatternKind::Binding(name) => PatKind::Binding(name.clone()),
+            ast::PatternKind::Literal(number) => PatKind::Literal(number.clone()),
+            ast::PatternKind::Range(start, end, inclusive) => PatKind::Range(start.clone(), end.clone(), *inclusive),
+            ast::PatternKind::Tuple(items) => PatKind::Tuple(items.iter().map(|item| self.pattern(item)).collect()),
+            ast::PatternKind::Struct(name, args) => PatKind::Struct(name.clone(), self.pattern_args(args)),
+            ast::PatternKind::Variant(ty, variant, args) => {
+                PatKind::Variant(ty.clone(), variant.clone(), args.as_ref().map(|args| self.pattern_args(args)))
+            }
+        };
+        self.hir.pats.alloc(Pat { kind, span: pattern.span })
+    }
+
+    fn pattern_args(&mut self, args: &[ast::PatternArg]) -> Vec<PatArg> {
+        args.iter().map(|arg| PatArg { name: arg.name.clone(), pat: self.pattern(&arg.pattern) }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        parsing::tokenizer::tokenize_text,
+        semantic::{table::TypeTable, typeck::Checker},
+        treegen::items::parse_items,
+    };
+
+    fn lower(text: &str) -> Hir {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        lower_items(&parse_items(&tokens, text.len()).unwrap_or_else(|error| panic!("{}\n{}", error, text)))
+    }
+
+    // The body of the only function, in a compact form
+    fn body(text: &str) -> String {
+        let hir = lower(text);
+        block(&hir, hir.functions[0].body.unwrap())
+    }
+
+    fn block(hir: &Hir, id: BlockId) -> String {
+        let block = &hir.blocks[id];
+        let mut parts: Vec<String> = block.stmts.iter().map(|stmt| self::stmt(hir, *stmt)).collect();
+        parts.extend(block.value.map(|value| expr(hir, value)));
+        format!("{{{}}}", parts.join("; "))
+    }
+
+    fn stmt(hir: &Hir, id: StmtId) -> String {
+        match &hir.stmts[id].kind {
+            StmtKind::Expr(value) => expr(hir, *value),
+            StmtKind::Local(local) => {
+                let keyword = if local.mutability == Mutability::Const { "const" } else { "var" };
+                match local.value {
+                    Some(value) => format!("({} {} {})", keyword, local.name.name, expr(hir, value)),
+                    None => format!("({} {})", keyword, local.name.name),
+                }
+            }
+        }
+    }
+
+    fn expr(hir: &Hir, id: ExprId) -> String {
+        let list = |items: &[ExprId]| items.iter().map(|item| format!(" {}", expr(hir, *item))).collect::<String>();
+        match &hir.exprs[id].kind {
+            ExprKind::Number(number) => number.text_content.clone(),
+            ExprKind::Str(string) => string.source(),
+            ExprKind::Name(name) => name.clone(),
+            ExprKind::Unary(operator, value) => format!("({} {})", operator.text(), expr(hir, *value)),
+            ExprKind::Binary(operator, lhs, rhs) => format!("({} {} {})", operator.text(), expr(hir, *lhs), expr(hir, *rhs)),
+            ExprKind::Call(callee, args) => format!("(call {}{})", expr(hir, *callee), list(args)),
+            ExprKind::FnAddr(name) => format!("(fn {})", name),
+            ExprKind::CallIndirect(abi, callee, args) => {
+                let abi = if *abi == Abi::C { " extern" } else { "" };
+                format!("(call-indirect{} {}{})", abi, expr(hir, *callee), list(args))
+            }
+            ExprKind::Index(value, index) => format!("(index {} {})", expr(hir, *value), expr(hir, *index)),
+            ExprKind::Field(value, field) => format!("{}.{}", expr(hir, *value), field.name),
+            ExprKind::Tuple(items) => format!("(tuple{})", list(items)),
+            ExprKind::Array(items) => format!("(array{})", list(items)),
+            ExprKind::Match(value, arms) => {
+                let arms: String = arms.iter().map(|arm| format!(" ({} => {})", pat(hir, arm.pat), expr(hir, arm.body))).collect();
+                format!("(match {}{})", expr(hir, *value), arms)
+            }
+            ExprKind::If { cond, then, otherwise, .. } => match otherwise {
+                Some(otherwise) => format!("(if {} {} {})", expr(hir, *cond), block(hir, *then), block(hir, *otherwise)),
+                None => format!("(if {} {})", expr(hir, *cond), block(hir, *then)),
+            },
+            ExprKind::Loop { body, step, .. } => match step {
+                Some(step) => format!("(loop {} {})", block(hir, *body), block(hir, *step)),
+                None => format!("(loop {})", block(hir, *body)),
+            },
+            ExprKind::Break(_) => "break".to_string(),
+            ExprKind::Continue(_) => "continue".to_string(),
+            ExprKind::Return(value) => value.map_or("return".to_string(), |value| format!("(return {})", expr(hir, value))),
+            ExprKind::Block(id) => block(hir, *id),
+            ExprKind::Closure(params, body) => {
+                let params: Vec<&str> = params.iter().map(|param| param.name.name.as_str()).collect();
+                format!("(fn ({}) {})", params.join(" "), block(hir, *body))
+            }
+            ExprKind::Error => "error".to_string(),
+        }
+    }
+
+    fn pat(hir: &Hir, id: PatId) -> String {
+        match &hir.pats[id].kind {
+            PatKind::Binding(name) => name.name.clone(),
+            PatKind::Variant(ty, variant, None) => format!("{}.{}", ty.name, variant.name),
+            PatKind::Variant(ty, variant, Some(args)) => {
+                let args: Vec<String> = args.iter().map(|arg| pat(hir, arg.pat)).collect();
+                format!("{}.{}({})", ty.name, variant.name, args.join(" "))
+            }
+            other => format!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(body("void f() { while (i < n) { g(); } }"), "{(loop {(if (< i n) {} {break}); {(call g)}})}");
+        assert_eq!(
+            body("void f() { for (u32 i = 0; i < n; i += 1) { g(i); } }"),
+            "{{(var i 0); (loop {(if (< i n) {} {break}); {(call g i)}} {(+= i 1)})}}"
+        );
+        assert_eq!(body("void f() { for (;;) { break; } }"), "{(loop {{break}})}");
+        assert_eq!(
+            body("void f() { for (x in 0..n) { g(x); } }"),
+            "{{(const __start 0); (const __end n); (var x __start); (loop {(if (< x __end) {} {break}); {(call g x)}} {(+= x 1)})}}"
+        );
+        assert_eq!(
+            body("void f() { for (x in 0..=255u8) { g(x); } }"),
+            "{{(const __start 0); (const __end 255u8); (var x __start); \
+             (loop {(if (<= x __end) {} {break}); {(call g x)}} {(if (== x __end) {break}); (+= x 1)})}}"
+        );
+        assert_eq!(
+            body("void f() { for (x in items) { g(x); } }"),
+            "{{(var __iter items); (loop {(var x); (if (call __iter.next (& x)) {} {break}); {(call g x)}})}}"
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(body("u8 f() { if (a) { 1 } else if (b) { 2 } else { 3 } }"), "{(if a {1} {(if b {2} {3})})}");
+        assert_eq!(body("void f() { if (a) g(); else if (b) h(); }"), "{(if a {(call g)} {(if b {(call h)})})}");
+    }
+
+    #[test]
+    fn propagation() {
+        assert_eq!(
+            body("u8 f() => g()?;"),
+            "{(match (call g) (Maybe.Some(__value) => __value) (Maybe.Nothing => (return Maybe.Nothing)))}"
+        );
+        assert_eq!(
+            body("u8 f() { var x = g()!; x }"),
+            "{(var x (match (call g) (Result.Ok(__value) => __value) (Result.Err(__error) => (return (call Result.Err __error))))); x}"
+        );
+        // The defer runs before the error is returned, and again at the end of the body
+        assert_eq!(
+            body("void f() { defer close(); var x = g()!; }"),
+            "{(var x (match (call g) (Result.Ok(__value) => __value) \
+             (Result.Err(__error) => {{{(call close)}}; (return (call Result.Err __error))}))); {(call close)}}"
+        );
+    }
+
+    #[test]
+    fn methods() {
+        let hir = lower("impl Point { u8 x() => 1; } trait Shape { u8 area(); u8 sides() => 0; } void f() {}");
+        let names: Vec<&str> = hir.functions.iter().map(|function| function.name.name.as_str()).collect();
+        assert_eq!(names, ["x", "area", "sides", "f"]);
+        assert!(hir.functions[1].body.is_none());
+        assert!(matches!(&hir.functions[2].owner, Some(TypeExpr { kind: TypeExprKind::Path(path, _), .. }) if path[0].name == "Shape"));
+        assert_eq!(hir.functions[3].owner, None);
+        // Decorators are read into the function
+        let hir = lower("@inline @export u8 f() => 1; @test () t() {} impl P { @cold void slow() {} }");
+        let attributes: Vec<Vec<&str>> = hir.functions.iter().map(|function| function.attributes.function_names()).collect();
+        assert_eq!(attributes, [vec!["inline", "export"], vec!["test"], vec!["cold"]]);
+    }
+
+    #[test]
+    fn function_pointers() {
+        let text = "bool less(i32 a, i32 b) => a < b;
+            void f(fn(i32, i32) -> bool g, extern fn() h) {
+                var l = less; l(1, 2); less(1, 2); g(3, 4); h();
+                defer h(); if (g(5, 6)) return; g(7, 8);
+            }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap();
+        let table = TypeTable::from_items(&items).unwrap();
+        let mut checker = Checker::new(&table);
+        let [Item::Function(less), Item::Function(f)] = &items[..] else { panic!() };
+        checker.declare_function(less).unwrap();
+        for param in &f.params {
+            checker.declare(&param.name.name, table.resolve_type_ref(&param.ty, &[]).unwrap());
+        }
+        let Some(FnBody::Block(body)) = &f.body else { panic!() };
+        checker.check_block(body).unwrap();
+
+        let hir = lower_checked_items(&items, checker.function_values(), checker.indirect_calls());
+        // Copies of deferred code have ids of their own and still lead back to what the checker saw
+        assert_eq!(
+            block(&hir, hir.functions[1].body.unwrap()),
+            "{(var l (fn less)); (call-indirect l 1 2); (call less 1 2); (call-indirect g 3 4); (call-indirect extern h); \
+             (if (call-indirect g 5 6) {{{(call-indirect extern h)}; return}}); (call-indirect g 7 8); {(call-indirect extern h)}}"
+        );
+        // Without the checker every call is a plain one
+        assert!(!format!("{:?}", lower(text)).contains("CallIndirect"));
+    }
+
+    #[test]
+    fn ids_and_spans() {
+        let text = "u8 f(u8 n) { var t = 0; for (x in 0..n) { t += match (x) { 0 => 1, _ => x }; } return t; }";
+        let hir = lower(text);
+        // Lowering the same items gives the same nodes under the same ids
+        assert_eq!(format!("{:?}", hir), format!("{:?}", lower(text)));
+
+        // Every node but the body is used exactly once, children before their parents
+        let mut uses = vec![0; hir.exprs.len()];
+        for (id, node) in hir.exprs.iter() {
+            let children: Vec<ExprId> = match &node.kind {
+                ExprKind::Binary(_, lhs, rhs) => vec![*lhs, *rhs],
+                ExprKind::Match(value, arms) => std::iter::once(*value).chain(arms.iter().map(|arm| arm.body)).collect(),
+                ExprKind::Unary(_, value) | ExprKind::Return(Some(value)) | ExprKind::If { cond: value, .. } => vec![*value],
+                _ => Vec::new(),
+            };
+            for child in children {
+                assert!(child < id);
+                uses[child.index()] += 1;
+            }
+        }
+        for (_, block) in hir.blocks.iter() {
+            if let Some(value) = block.value {
+                uses[value.index()] += 1;
+            }
+        }
+        for (_, stmt) in hir.stmts.iter() {
+            match &stmt.kind {
+                StmtKind::Expr(value) | StmtKind::Local(Local { value: Some(value), .. }) => uses[value.index()] += 1,
+                StmtKind::Local(_) => {}
+            }
+        }
+        assert!(uses.iter().all(|count| *count == 1), "{:?}", uses);
+
+        // Spans point back at the source, what lowering adds uses the span of what it replaces
+        let source = |span: Span| &text[span.start..span.end];
+        let (_, lp) = hir.exprs.iter().find(|(_, expr)| matches!(expr.kind, ExprKind::Loop { .. })).unwrap();
+        assert!(source(lp.span).starts_with("for (x in 0..n)"));
+        let (_, arms) = hir.exprs.iter().find(|(_, expr)| matches!(expr.kind, ExprKind::Match(..))).unwrap();
+        assert!(source(arms.span).starts_with("match (x)"));
+        let (_, one) = hir.exprs.iter().filter(|(_, expr)| matches!(&expr.kind, ExprKind::Number(number) if number.text_content == "1")).last().unwrap();
+        assert_eq!(source(one.span), "for (x in 0..n) { t += match (x) { 0 => 1, _ => x }; }");
+    }
+}
@@ -0,0 +1,255 @@
+// The desugared tree semantic analysis works on. Every node lives in an arena of its kind and is named by a
+// `ArenaId`, so side tables for types and resolutions can be keyed by it. Compared to the AST there are no `else if`
+// chains, `while` or `for` loops, `defer`s or `?`/`!` operators, and `return`, `break` and `continue` are expressions
+pub mod lower;
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+use crate::compiler::{
+    keywords::Keyword,
+    objects::types::Abi,
+    operators::Operator,
+    parsing::{number_parser::NumberLiteral, string_parser::StringLiteral},
+    semantic::decorators::Attributes,
+    treegen::ast::{Ident, Mutability, Span, Storage, TypeExpr},
+};
+
+// An index into the `Arena<T>` the node was allocated in, it stays valid as more nodes are added
+pub struct ArenaId<T> {
+    index: u32,
+    node: PhantomData<fn() -> T>,
+}
+
+pub type ExprId = ArenaId<Expr>;
+pub type StmtId = ArenaId<Stmt>;
+pub type BlockId = ArenaId<Block>;
+pub type PatId = ArenaId<Pat>;
+
+impl<T> ArenaId<T> {
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// Derives would require `T` itself to implement these
+impl<T> Clone for ArenaId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaId<T> {}
+
+impl<T> PartialEq for ArenaId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for ArenaId<T> {}
+
+impl<T> PartialOrd for ArenaId<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for ArenaId<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for ArenaId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for ArenaId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.index)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena<T> {
+    nodes: Vec<T>,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self { nodes: Vec::new() }
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn alloc(&mut self, node: T) -> ArenaId<T> {
+        self.nodes.push(node);
+        ArenaId { index: (self.nodes.len() - 1) as u32, node: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Every node with its id, in the order they were allocated
+    pub fn iter(&self) -> impl Iterator<Item = (ArenaId<T>, &T)> {
+        self.nodes.iter().enumerate().map(|(index, node)| (ArenaId { index: index as u32, node: PhantomData }, node))
+    }
+}
+
+impl<T> Index<ArenaId<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: ArenaId<T>) -> &T {
+        &self.nodes[id.index()]
+    }
+}
+
+impl<T> IndexMut<ArenaId<T>> for Arena<T> {
+    fn index_mut(&mut self, id: ArenaId<T>) -> &mut T {
+        &mut self.nodes[id.index()]
+    }
+}
+
+// Every function of a file and the nodes of their bodies
+#[derive(Debug, Default)]
+pub struct Hir {
+    pub functions: Vec<Function>,
+    pub exprs: Arena<Expr>,
+    pub stmts: Arena<Stmt>,
+    pub blocks: Arena<Block>,
+    pub pats: Arena<Pat>,
+}
+
+// Free functions and methods alike
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    // The type of an `impl` block, or the trait a default method is written in
+    pub owner: Option<TypeExpr>,
+    pub name: Ident,
+    pub params: Vec<Param>,
+    pub return_type: TypeExpr,
+    // `None` for a signature without a body. `=> expr` is a block holding only its value
+    pub body: Option<BlockId>,
+    // What its decorators ask of code generation, `@inline`, `@cold`, `@export` and `@test`
+    pub attributes: Attributes,
+    pub span: Span,
+}
+
+// The type is only left out on closure parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub modifiers: Vec<Keyword>,
+    pub ty: Option<TypeExpr>,
+    pub name: Ident,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<StmtId>,
+    pub value: Option<ExprId>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Expr(ExprId),
+    Local(Local),
+}
+
+// A variable, without a type or value where lowering introduces one the checker infers, like a `for` loop's item
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub storage: Storage,
+    pub mutability: Mutability,
+    pub ty: Option<TypeExpr>,
+    pub name: Ident,
+    pub value: Option<ExprId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Number(NumberLiteral),
+    Str(StringLiteral),
+    Name(String),
+    Unary(Operator, ExprId),
+    Binary(Operator, ExprId, ExprId),
+    // A call to a function by its name, or to a closure
+    Call(ExprId, Vec<ExprId>),
+    // The address of a declared function, where its name is used as a value
+    FnAddr(String),
+    // A call through a fn pointer value, with the calling convention of its type
+    CallIndirect(Abi, ExprId, Vec<ExprId>),
+    Index(ExprId, ExprId),
+    Field(ExprId, Ident),
+    Tuple(Vec<ExprId>),
+    Array(Vec<ExprId>),
+    Match(ExprId, Vec<Arm>),
+    // A single condition, `else if` is an `If` in the `otherwise` block
+    If { comptime: bool, cond: ExprId, then: BlockId, otherwise: Option<BlockId> },
+    // Runs `body` until it breaks, and `step` after every run of it, `continue` included
+    Loop { label: Option<Ident>, comptime: bool, body: BlockId, step: Option<BlockId> },
+    Break(Option<Ident>),
+    Continue(Option<Ident>),
+    Return(Option<ExprId>),
+    Block(BlockId),
+    Closure(Vec<Param>, BlockId),
+    // What failed to parse
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pat: PatId,
+    pub guard: Option<ExprId>,
+    pub body: ExprId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pat {
+    pub kind: PatKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatKind {
+    Wildcard,
+    Binding(Ident),
+    Literal(NumberLiteral),
+    // The end is included when the flag is set
+    Range(NumberLiteral, NumberLiteral, bool),
+    Tuple(Vec<PatId>),
+    Struct(Ident, Vec<PatArg>),
+    Variant(Ident, Ident, Option<Vec<PatArg>>),
+}
+
+// `name = pattern` picks a field by name, without a name it goes by position
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatArg {
+    pub name: Option<Ident>,
+    pub pat: PatId,
+}
@@ -15,13 +15,20 @@ macro_rules! genKeyword {
                 $(
                     // This _should_ be evaluated at compile time
                     if Self::$element != Self::DUMMY{
-                        if x.starts_with($text){
+                        if x == $text{
                             return Some(Self::$element)
                         }
                     }
                 )*
                 None
             }
+            pub fn text(&self) -> &'static str{
+                match self{
+                    $(
+                        Self::$element => $text,
+                    )*
+                }
+            }
         }
     };
 }
@@ -34,8 +41,14 @@ genKeyword!(
     While, "while",
     Do, "do",
     For, "for",
+    In, "in",
     Match, "match",
+    Break, "break",
+    Continue, "continue",
+    Return, "return",
     Var, "var",
+    Fn, "fn",
+    Extern, "extern",
 
     Trait, "trait",
     Struct, "struct",
@@ -46,6 +59,8 @@ genKeyword!(
     Pub, "pub",
     Private, "private",
 
+    Typedef, "typedef",
+
     Where, "where",
     Const, "const",
     Defer, "defer",
@@ -0,0 +1,469 @@
+use crate::compiler::{
+    objects::types::Ty,
+    operators::Operator,
+    treegen::{
+        ast::{
+            Block, Closure, CondBranch, Conditional, Expr, ExprKind, ForKind, ForLoop, Ident, MatchArm, NodeId, Pattern, PatternKind, Span, Stmt,
+            StmtKind, VarDecl, WhileLoop,
+        },
+        parser::FreshIds,
+    },
+};
+
+// The parameter a lifted closure receives its captures through
+const ENV_PARAM: &str = "__env";
+
+// How a closure holds on to a variable of the code around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    // A copy made with the closure, for variables it only reads
+    ByValue,
+    // A pointer, for variables it assigns to or takes the address of, so the code around it sees the change
+    ByReference,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub name: String,
+    pub ty: Ty,
+    pub mode: CaptureMode,
+}
+
+// What the checker worked out for one closure, found again by the id of its expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosureInfo {
+    pub id: NodeId,
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    pub captures: Vec<Capture>,
+}
+
+// A closure lifted out of the function it was written in: a struct with a field per capture
+// and a plain function taking a pointer to that struct before its own parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedClosure {
+    pub env: String,
+    // A capture by reference is a pointer field
+    pub fields: Vec<(String, Ty)>,
+    pub function: String,
+    // `__env` first
+    pub params: Vec<(String, Ty)>,
+    pub ret: Ty,
+    pub body: Block,
+}
+
+// The variables of the code around `closure` that its body uses, in the order they first appear.
+// `outer` gives the type of a variable in scope where the closure is written
+pub fn analyze_captures(closure: &Closure, outer: &dyn Fn(&str) -> Option<Ty>) -> Vec<Capture> {
+    let mut walker = Walker { outer, bound: Vec::new(), captures: Vec::new(), conversion: None };
+    walker.closure_body(closure);
+    walker.captures
+}
+
+// Replaces every closure in `body` by `(function, &Env(captures))` and returns the lifted closures,
+// named after `prefix`. The calls with ids in `calls` go through closure values, `f(x)` becomes `f.0(f.1, x)`.
+// The nodes made on the way take new ids from `ids`
+pub fn convert_closures(
+    body: &Block,
+    closures: &[ClosureInfo],
+    calls: &[NodeId],
+    prefix: &str,
+    ids: &mut FreshIds,
+) -> (Block, Vec<ConvertedClosure>) {
+    let mut conversion = Conversion { closures, calls, prefix, ids, lifted: Vec::new() };
+    let nothing = |_: &str| None;
+    let mut walker = Walker { outer: &nothing, bound: Vec::new(), captures: Vec::new(), conversion: Some(&mut conversion) };
+    let body = walker.block(body);
+    (body, conversion.lifted)
+}
+
+struct Conversion<'i> {
+    closures: &'i [ClosureInfo],
+    calls: &'i [NodeId],
+    prefix: &'i str,
+    ids: &'i mut FreshIds,
+    lifted: Vec<ConvertedClosure>,
+}
+
+// Walks code while tracking the names it declares. Alone it collects captures,
+// while converting it also reads captures through `__env` and lifts nested closures
+struct Walker<'a, 'i> {
+    outer: &'a dyn Fn(&str) -> Option<Ty>,
+    bound: Vec<Vec<String>>,
+    captures: Vec<Capture>,
+    conversion: Option<&'a mut Conversion<'i>>,
+}
+
+fn pattern_names(pattern: &Pattern, names: &mut Vec<String>) {
+    match &pattern.kind {
+        PatternKind::Binding(name) => names.push(name.name.clone()),
+        PatternKind::Tuple(items) => items.iter().for_each(|item| pattern_names(item, names)),
+        PatternKind::Struct(_, args) | PatternKind::Variant(_, _, Some(args)) => args.iter().for_each(|arg| pattern_names(&arg.pattern, names)),
+        PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range(..) | PatternKind::Variant(_, _, None) => {}
+    }
+}
+
+fn field(ids: &mut FreshIds, base: Expr, name: &str) -> Expr {
+    let span = base.span;
+    Expr { kind: ExprKind::Field(Box::new(base), Ident { name: name.to_string(), span }), span, id: ids.next() }
+}
+
+fn name(ids: &mut FreshIds, name: &str, span: Span) -> Expr {
+    Expr { kind: ExprKind::Name(name.to_string()), span, id: ids.next() }
+}
+
+fn reference(ids: &mut FreshIds, expr: Expr) -> Expr {
+    let span = expr.span;
+    Expr { kind: ExprKind::Unary(Operator::Reference, Box::new(expr)), span, id: ids.next() }
+}
+
+impl Walker<'_, '_> {
+    fn bind(&mut self, name: &str) {
+        self.bound.last_mut().unwrap().push(name.to_string());
+    }
+
+    fn is_bound(&self, name: &str) -> bool {
+        self.bound.iter().any(|scope| scope.iter().any(|bound| bound == name))
+    }
+
+    // What a use of `name` in `at` becomes, `None` to keep it
+    fn use_name(&mut self, name: &str, mode: CaptureMode, at: &Expr) -> Option<Expr> {
+        if self.is_bound(name) {
+            return None;
+        }
+        match self.captures.iter_mut().find(|capture| capture.name == name) {
+            Some(capture) if mode == CaptureMode::ByReference => capture.mode = mode,
+            Some(_) => {}
+            None => {
+                let ty = (self.outer)(name)?;
+                self.captures.push(Capture { name: name.to_string(), ty, mode });
+            }
+        }
+        let ids = &mut *self.conversion.as_deref_mut()?.ids;
+        let capture = self.captures.iter().find(|capture| capture.name == name)?;
+        let env = self::name(ids, ENV_PARAM, at.span);
+        let value = field(ids, env, name);
+        Some(match capture.mode {
+            CaptureMode::ByValue => value,
+            CaptureMode::ByReference => Expr { kind: ExprKind::Unary(Operator::Dereference, Box::new(value)), span: at.span, id: ids.next() },
+        })
+    }
+
+    fn scoped<T>(&mut self, names: Vec<String>, walk: impl FnOnce(&mut Self) -> T) -> T {
+        self.bound.push(names);
+        let result = walk(self);
+        self.bound.pop();
+        result
+    }
+
+    fn closure_body(&mut self, closure: &Closure) -> Block {
+        let params = closure.params.iter().map(|param| param.name.name.clone()).collect();
+        self.scoped(params, |walker| walker.block(&closure.body))
+    }
+
+    fn block(&mut self, block: &Block) -> Block {
+        self.scoped(Vec::new(), |walker| Block {
+            stmts: block.stmts.iter().map(|stmt| walker.stmt(stmt)).collect(),
+            value: block.value.as_ref().map(|value| Box::new(walker.expr(value))),
+            span: block.span,
+            id: block.id,
+        })
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Stmt {
+        let kind = match &stmt.kind {
+            StmtKind::Expr(expr) => StmtKind::Expr(self.expr(expr)),
+            StmtKind::Var(decl) => {
+                let value = decl.value.as_ref().map(|value| self.expr(value));
+                self.bind(&decl.name.name);
+                StmtKind::Var(VarDecl { value, ..decl.clone() })
+            }
+            StmtKind::While(lp) => StmtKind::While(WhileLoop { cond: self.expr(&lp.cond), body: self.block(&lp.body), ..lp.clone() }),
+            StmtKind::For(lp) => self.scoped(Vec::new(), |walker| {
+                let kind = match &lp.kind {
+                    ForKind::Clauses { init, cond, step } => ForKind::Clauses {
+                        init: init.as_ref().map(|init| Box::new(walker.stmt(init))),
+                        cond: cond.as_ref().map(|cond| walker.expr(cond)),
+                        step: step.as_ref().map(|step| walker.expr(step)),
+                    },
+                    ForKind::In { binding, iterable } => {
+                        let iterable = walker.expr(iterable);
+                        walker.bind(&binding.name);
+                        ForKind::In { binding: binding.clone(), iterable }
+                    }
+                };
+                StmtKind::For(ForLoop { kind, body: walker.block(&lp.body), ..lp.clone() })
+            }),
+            StmtKind::Return(value) => StmtKind::Return(value.as_ref().map(|value| self.expr(value))),
+            StmtKind::Block(block) => StmtKind::Block(self.block(block)),
+            StmtKind::Defer(block) => StmtKind::Defer(self.block(block)),
+            StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Error(_) => stmt.kind.clone(),
+        };
+        Stmt { kind, span: stmt.span, id: stmt.id }
+    }
+
+    // An expression that is written to or has its address taken
+    fn place(&mut self, expr: &Expr) -> Expr {
+        let kind = match &expr.kind {
+            ExprKind::Name(name) => match self.use_name(name, CaptureMode::ByReference, expr) {
+                Some(replaced) => return replaced,
+                None => return expr.clone(),
+            },
+            ExprKind::Index(base, index) => ExprKind::Index(Box::new(self.place(base)), Box::new(self.expr(index))),
+            // Writing through a pointer leaves the pointer itself alone
+            ExprKind::Field(base, name) if self.is_pointer(base) => ExprKind::Field(Box::new(self.expr(base)), name.clone()),
+            ExprKind::Field(base, name) => ExprKind::Field(Box::new(self.place(base)), name.clone()),
+            _ => return self.expr(expr),
+        };
+        Expr { kind, span: expr.span, id: expr.id }
+    }
+
+    fn is_pointer(&self, expr: &Expr) -> bool {
+        let ty = match &expr.kind {
+            ExprKind::Name(name) if !self.is_bound(name) => self.captures.iter().find(|capture| capture.name == *name).map(|capture| capture.ty.clone()).or_else(|| (self.outer)(name)),
+            _ => None,
+        };
+        matches!(ty, Some(Ty::Pointer(_)))
+    }
+
+    fn boxed(&mut self, expr: &Expr) -> Box<Expr> {
+        Box::new(self.expr(expr))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Expr {
+        let kind = match &expr.kind {
+            ExprKind::Number(_) | ExprKind::Str(_) => expr.kind.clone(),
+            ExprKind::Name(name) => match self.use_name(name, CaptureMode::ByValue, expr) {
+                Some(replaced) => return replaced,
+                None => expr.kind.clone(),
+            },
+            ExprKind::Unary(Operator::Reference, value) => ExprKind::Unary(Operator::Reference, Box::new(self.place(value))),
+            ExprKind::Unary(operator, value) => ExprKind::Unary(*operator, self.boxed(value)),
+            ExprKind::Postfix(operator, value) => ExprKind::Postfix(*operator, self.boxed(value)),
+            // Assignments
+            ExprKind::Binary(operator, lhs, rhs) if operator.is_assignment() => {
+                ExprKind::Binary(*operator, Box::new(self.place(lhs)), self.boxed(rhs))
+            }
+            ExprKind::Binary(operator, lhs, rhs) => ExprKind::Binary(*operator, self.boxed(lhs), self.boxed(rhs)),
+            ExprKind::Call(callee, args) => {
+                let callee = self.expr(callee);
+                let mut args: Vec<Expr> = args.iter().map(|arg| self.expr(arg)).collect();
+                match self.conversion.as_deref_mut() {
+                    Some(conversion) if conversion.calls.contains(&conversion.ids.original(expr.id)) => {
+                        // The callee is a name or a field in practice, reading it twice is harmless
+                        let ids = &mut *conversion.ids;
+                        let copy = ids.copy_expr(&callee);
+                        args.insert(0, field(ids, copy, "1"));
+                        ExprKind::Call(Box::new(field(ids, callee, "0")), args)
+                    }
+                    _ => ExprKind::Call(Box::new(callee), args),
+                }
+            }
+            ExprKind::Index(value, index) => ExprKind::Index(self.boxed(value), self.boxed(index)),
+            ExprKind::Field(value, name) => ExprKind::Field(self.boxed(value), name.clone()),
+            ExprKind::Tuple(items) => ExprKind::Tuple(items.iter().map(|item| self.expr(item)).collect()),
+            ExprKind::Array(items) => ExprKind::Array(items.iter().map(|item| self.expr(item)).collect()),
+            ExprKind::Match(value, arms) => {
+                let value = self.boxed(value);
+                let arms = arms
+                    .iter()
+                    .map(|arm| {
+                        let mut names = Vec::new();
+                        pattern_names(&arm.pattern, &mut names);
+                        self.scoped(names, |walker| MatchArm {
+                            guard: arm.guard.as_ref().map(|guard| walker.expr(guard)),
+                            body: walker.expr(&arm.body),
+                            ..arm.clone()
+                        })
+                    })
+                    .collect();
+                ExprKind::Match(value, arms)
+            }
+            ExprKind::If(conditional) => ExprKind::If(Conditional {
+                branches: conditional
+                    .branches
+                    .iter()
+                    .map(|branch| CondBranch { cond: self.expr(&branch.cond), body: self.block(&branch.body), span: branch.span })
+                    .collect(),
+                else_branch: conditional.else_branch.as_ref().map(|block| self.block(block)),
+                ..conditional.clone()
+            }),
+            ExprKind::Propagate(value, cleanup) => ExprKind::Propagate(self.boxed(value), self.block(cleanup)),
+            ExprKind::Closure(closure) => match self.lift(expr.id, closure) {
+                Some(made) => return self.expr(&made),
+                // What an inner closure captures from this one is captured here too
+                None => ExprKind::Closure(Box::new(Closure { body: self.closure_body(closure), ..(**closure).clone() })),
+            },
+        };
+        Expr { kind, span: expr.span, id: expr.id }
+    }
+
+    // Moves `closure`, the expression `id`, into its own function, returning the expression that makes the closure value
+    fn lift(&mut self, id: NodeId, closure: &Closure) -> Option<Expr> {
+        let conversion = self.conversion.as_deref_mut()?;
+        let original = conversion.ids.original(id);
+        let info = conversion.closures.iter().find(|info| info.id == original)?.clone();
+        let captured = |name: &str| info.captures.iter().find(|capture| capture.name == name).map(|capture| capture.ty.clone());
+        let mut walker = Walker { outer: &captured, bound: Vec::new(), captures: info.captures.clone(), conversion: Some(&mut *conversion) };
+        let body = walker.closure_body(closure);
+
+        let function = format!("{}__closure{}", conversion.prefix, conversion.lifted.len());
+        let env = format!("{}_env", function);
+        let fields = info
+            .captures
+            .iter()
+            .map(|capture| match capture.mode {
+                CaptureMode::ByValue => (capture.name.clone(), capture.ty.clone()),
+                CaptureMode::ByReference => (capture.name.clone(), Ty::Pointer(Box::new(capture.ty.clone()))),
+            })
+            .collect();
+        let mut params = vec![(ENV_PARAM.to_string(), Ty::Pointer(Box::new(Ty::Named(env.clone(), Vec::new()))))];
+        params.extend(closure.params.iter().map(|param| param.name.name.clone()).zip(info.params.iter().cloned()));
+        conversion.lifted.push(ConvertedClosure { env: env.clone(), fields, function: function.clone(), params, ret: info.ret.clone(), body });
+
+        let (span, ids) = (closure.span, &mut *conversion.ids);
+        let args = info
+            .captures
+            .iter()
+            .map(|capture| {
+                let value = name(ids, &capture.name, span);
+                match capture.mode {
+                    CaptureMode::ByValue => value,
+                    CaptureMode::ByReference => reference(ids, value),
+                }
+            })
+            .collect();
+        // The environment lives in the frame that made the closure
+        let made = Expr { kind: ExprKind::Call(Box::new(name(ids, &env, span)), args), span, id: ids.next() };
+        let function = name(ids, &function, span);
+        let made = reference(ids, made);
+        Some(Expr { kind: ExprKind::Tuple(vec![function, made]), span, id: ids.next() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        objects::gurn_objects::Primitive,
+        parsing::tokenizer::tokenize_text,
+        semantic::{table::TypeTable, typeck::Checker},
+        treegen::{items::parse_items, stmt::parse_block, visit::Visit},
+    };
+
+    const DECLS: &str = "struct Point { i32 x, i32 y }";
+
+    // Compact text for the shapes these tests look at
+    fn show(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(number) => number.text_content.clone(),
+            ExprKind::Name(name) => name.clone(),
+            ExprKind::Unary(operator, value) => format!("{}{}", operator.text(), show(value)),
+            ExprKind::Binary(operator, lhs, rhs) => format!("({} {} {})", show(lhs), operator.text(), show(rhs)),
+            ExprKind::Call(callee, args) => format!("{}({})", show(callee), args.iter().map(show).collect::<Vec<_>>().join(", ")),
+            ExprKind::Field(value, name) => format!("{}.{}", show(value), name.name),
+            ExprKind::Tuple(items) => format!("({})", items.iter().map(show).collect::<Vec<_>>().join(", ")),
+            ExprKind::Closure(_) => "closure".to_string(),
+            _ => "?".to_string(),
+        }
+    }
+
+    fn show_stmt(stmt: &Stmt) -> String {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => format!("{};", show(expr)),
+            StmtKind::Var(decl) => format!("{} = {};", decl.name.name, decl.value.as_ref().map_or("_".to_string(), show)),
+            _ => "?".to_string(),
+        }
+    }
+
+    fn show_block(block: &Block) -> String {
+        let mut parts: Vec<String> = block.stmts.iter().map(show_stmt).collect();
+        parts.extend(block.value.as_ref().map(|value| show(value)));
+        parts.join(" ")
+    }
+
+    // Checks `text` as a function body and converts its closures
+    fn convert(text: &str) -> (Block, Vec<ConvertedClosure>, Vec<ClosureInfo>) {
+        let tokens = tokenize_text(DECLS.to_string()).unwrap();
+        let table = TypeTable::from_items(&parse_items(&tokens, DECLS.len()).unwrap()).unwrap();
+        let text = format!("{{{}}}", text);
+        let tokens = tokenize_text(text).unwrap();
+        let block = parse_block(&tokens[0]).unwrap();
+        let mut checker = Checker::new(&table);
+        checker.check_block(&block).unwrap();
+        let (body, lifted) = convert_closures(&block, checker.closures(), checker.closure_calls(), "main", &mut FreshIds::after_block(&block));
+        (body, lifted, checker.closures().to_vec())
+    }
+
+    fn modes(info: &ClosureInfo) -> Vec<(&str, CaptureMode)> {
+        info.captures.iter().map(|capture| (capture.name.as_str(), capture.mode)).collect()
+    }
+
+    #[test]
+    fn capture_analysis() {
+        use CaptureMode::*;
+        let (_, _, infos) = convert(
+            "var a = 1; var b = 2; var c = 3; Point p; *Point q = &p;
+            var f = fn(i32 x) { var c = x; b += c; var d = &p; q.x = a; x + c };",
+        );
+        assert_eq!(modes(&infos[0]), [("b", ByReference), ("p", ByReference), ("q", ByValue), ("a", ByValue)]);
+        assert_eq!(infos[0].captures[2].ty, Ty::Pointer(Box::new(Ty::Named("Point".to_string(), vec![]))));
+
+        // Read first and written later is still by reference
+        let (_, _, infos) = convert("var n = 0; var count = fn() { var seen = n; n = seen + 1; };");
+        assert_eq!(modes(&infos[0]), [("n", ByReference)]);
+
+        // Shadowed names and names bound by loops and patterns are not captures
+        let (_, _, infos) = convert(
+            "var a = 1; var i = 2; var f = fn(i32 a) { for (i in 0..a) { } var t = (a, i); match (t) { (i, 0) => i, _ => 0 } };",
+        );
+        assert_eq!(modes(&infos[0]), [("i", ByValue)]);
+
+        // What an inner closure uses from around the outer one, the outer one captures too
+        let (_, _, infos) = convert("var a = 1; var outer = fn(i32 x) { var inner = fn() => a + x; inner() };");
+        let inner = infos.iter().find(|info| info.params.is_empty()).unwrap();
+        let outer = infos.iter().find(|info| info.params.len() == 1).unwrap();
+        assert_eq!(modes(inner), [("a", ByValue), ("x", ByValue)]);
+        assert_eq!(modes(outer), [("a", ByValue)]);
+    }
+
+    #[test]
+    fn conversion() {
+        let (body, lifted, _) = convert("var scale = 3; var total = 0; var f = fn(i32 x) { total += x; x * scale }; var r = f(2);");
+        assert_eq!(
+            show_block(&body),
+            "scale = 3; total = 0; f = (main__closure0, &main__closure0_env(&total, scale)); r = f.0(f.1, 2);"
+        );
+        let closure = &lifted[0];
+        assert_eq!(closure.env, "main__closure0_env");
+        let i32 = Ty::Primitive(Primitive::I32);
+        assert_eq!(closure.fields, [("total".to_string(), Ty::Pointer(Box::new(i32.clone()))), ("scale".to_string(), i32.clone())]);
+        assert_eq!(closure.params[0], ("__env".to_string(), Ty::Pointer(Box::new(Ty::Named("main__closure0_env".to_string(), vec![])))));
+        assert_eq!(closure.params[1], ("x".to_string(), i32.clone()));
+        assert_eq!(closure.ret, i32);
+        assert_eq!(show_block(&closure.body), "(*__env.total += x); (x * __env.scale)");
+
+        // What conversion makes has ids of its own
+        struct NodeIds(Vec<NodeId>);
+        impl Visit for NodeIds {
+            fn visit_node_id(&mut self, id: &NodeId) {
+                self.0.push(*id);
+            }
+        }
+        let mut ids = NodeIds(Vec::new());
+        ids.visit_block(&body);
+        let count = ids.0.len();
+        ids.0.sort();
+        ids.0.dedup();
+        assert_eq!(ids.0.len(), count);
+
+        // The inner closure is lifted first, the outer one builds its environment from its own
+        let (body, lifted, _) = convert("var a = 1; var outer = fn(i32 x) { var inner = fn() => a + x; inner() };");
+        assert_eq!(show_block(&body), "a = 1; outer = (main__closure1, &main__closure1_env(a));");
+        assert_eq!(show_block(&lifted[0].body), "(__env.a + __env.x)");
+        assert_eq!(
+            show_block(&lifted[1].body),
+            "inner = (main__closure0, &main__closure0_env(__env.a, x)); inner.0(inner.1)"
+        );
+    }
+}
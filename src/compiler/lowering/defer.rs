@@ -0,0 +1,415 @@
+use crate::compiler::{
+    operators::Operator,
+    treegen::{
+        ast::{
+            Block, Closure, CondBranch, Conditional, Expr, ExprKind, ForKind, ForLoop, Ident, MatchArm, Mutability, Span, Stmt, StmtKind, Storage,
+            VarDecl, WhileLoop,
+        },
+        parser::FreshIds,
+    },
+};
+
+// Hold the value of a `return`, or of a block, while the defers run
+const RETURN_TEMP: &str = "__defer_return";
+const VALUE_TEMP: &str = "__defer_value";
+
+struct Scope {
+    // Lowered deferred code, in the order it was deferred
+    defers: Vec<Block>,
+    // Set on the body of a loop, with the loop's label
+    loop_label: Option<Option<String>>,
+}
+
+// Removes every `defer` from a function body. The deferred code is copied, last deferred first,
+// to every way out of its scope: the end of the scope, `return`, `break`, `continue` and `!`. Each copy and
+// every node made here takes new ids from `ids`
+pub fn lower_defers(body: &Block, ids: &mut FreshIds) -> Block {
+    Lowerer { scopes: Vec::new(), ids }.block(body, None)
+}
+
+struct Lowerer<'i> {
+    scopes: Vec<Scope>,
+    ids: &'i mut FreshIds,
+}
+
+impl Lowerer<'_> {
+    // One copy of deferred code, as a statement of its own
+    fn cleanup(&mut self, block: &Block) -> Stmt {
+        Stmt { kind: StmtKind::Block(self.ids.copy_block(block)), span: block.span, id: self.ids.next() }
+    }
+
+    fn temp(&mut self, name: &str, value: Expr) -> Stmt {
+        let span = value.span;
+        let decl = VarDecl {
+            docs: Vec::new(),
+            storage: Storage::Local,
+            mutability: Mutability::Const,
+            ty: None,
+            name: Ident { name: name.to_string(), span },
+            value: Some(value),
+            span,
+            id: self.ids.next(),
+        };
+        Stmt { kind: StmtKind::Var(decl), span, id: self.ids.next() }
+    }
+
+    fn name(&mut self, name: &str, span: Span) -> Expr {
+        Expr { kind: ExprKind::Name(name.to_string()), span, id: self.ids.next() }
+    }
+
+    fn block(&mut self, block: &Block, loop_label: Option<Option<String>>) -> Block {
+        self.scopes.push(Scope { defers: Vec::new(), loop_label });
+        let mut stmts = Vec::new();
+        for stmt in &block.stmts {
+            self.stmt(stmt, &mut stmts);
+        }
+        let mut value = block.value.as_ref().map(|value| Box::new(self.expr(value)));
+        let scope = self.scopes.pop().unwrap();
+
+        // A scope that ends in a jump already ran its defers
+        let jumps = matches!(block.stmts.last(), Some(Stmt { kind: StmtKind::Return(_) | StmtKind::Break(_) | StmtKind::Continue(_), .. }));
+        if !scope.defers.is_empty() && !jumps {
+            // The value is worked out before the defers run
+            if let Some(result) = value.take() {
+                let span = result.span;
+                stmts.push(self.temp(VALUE_TEMP, *result));
+                value = Some(Box::new(self.name(VALUE_TEMP, span)));
+            }
+            stmts.extend(scope.defers.iter().rev().map(|block| self.cleanup(block)));
+        }
+        Block { stmts, value, span: block.span, id: block.id }
+    }
+
+    // The defers that run when leaving the innermost `count` scopes
+    fn unwind(&mut self, count: usize) -> Vec<Stmt> {
+        let defers: Vec<Block> = self.scopes.iter().rev().take(count).flat_map(|scope| scope.defers.iter().rev().cloned()).collect();
+        defers.iter().map(|block| self.cleanup(block)).collect()
+    }
+
+    // How many scopes a `break` or `continue` leaves
+    fn jump_depth(&self, label: Option<&Ident>) -> usize {
+        let target = self.scopes.iter().rev().position(|scope| match (&scope.loop_label, label) {
+            (Some(_), None) => true,
+            (Some(Some(name)), Some(label)) => *name == label.name,
+            _ => false,
+        });
+        // The checker rejects jumps without a target
+        target.map_or(self.scopes.len(), |i| i + 1)
+    }
+
+    // `stmt` with the defers it runs first, as a scope of its own
+    fn after_unwind(&mut self, unwind: Vec<Stmt>, stmt: Stmt) -> Stmt {
+        let span = stmt.span;
+        let mut stmts = unwind;
+        stmts.push(stmt);
+        Stmt { kind: StmtKind::Block(Block { stmts, value: None, span, id: self.ids.next() }), span, id: self.ids.next() }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, out: &mut Vec<Stmt>) {
+        let span = stmt.span;
+        let kind = match &stmt.kind {
+            StmtKind::Defer(body) => {
+                // Deferred code can't leave its own block, so it is lowered on its own
+                let body = Lowerer { scopes: Vec::new(), ids: &mut *self.ids }.block(body, None);
+                self.scopes.last_mut().unwrap().defers.push(body);
+                return;
+            }
+            StmtKind::Return(value) => {
+                let unwind = self.unwind(self.scopes.len());
+                let value = value.as_ref().map(|value| self.expr(value));
+                if unwind.is_empty() {
+                    StmtKind::Return(value)
+                } else {
+                    let mut stmts = Vec::new();
+                    let value = value.map(|value| {
+                        let span = value.span;
+                        stmts.push(self.temp(RETURN_TEMP, value));
+                        self.name(RETURN_TEMP, span)
+                    });
+                    stmts.extend(unwind);
+                    let lowered = self.after_unwind(stmts, Stmt { kind: StmtKind::Return(value), span, id: stmt.id });
+                    out.push(lowered);
+                    return;
+                }
+            }
+            StmtKind::Break(label) | StmtKind::Continue(label) => {
+                let unwind = self.unwind(self.jump_depth(label.as_ref()));
+                let lowered = match unwind.is_empty() {
+                    true => stmt.clone(),
+                    false => self.after_unwind(unwind, stmt.clone()),
+                };
+                out.push(lowered);
+                return;
+            }
+            StmtKind::Expr(expr) => StmtKind::Expr(self.expr(expr)),
+            StmtKind::Error(_) => stmt.kind.clone(),
+            StmtKind::Var(decl) => StmtKind::Var(VarDecl { value: decl.value.as_ref().map(|value| self.expr(value)), ..decl.clone() }),
+            StmtKind::Block(block) => StmtKind::Block(self.block(block, None)),
+            StmtKind::While(lp) => StmtKind::While(WhileLoop {
+                cond: self.expr(&lp.cond),
+                body: self.block(&lp.body, Some(lp.label.as_ref().map(|label| label.name.clone()))),
+                ..lp.clone()
+            }),
+            StmtKind::For(lp) => {
+                let kind = match &lp.kind {
+                    ForKind::Clauses { init, cond, step } => ForKind::Clauses {
+                        init: init.as_ref().map(|init| {
+                            let mut lowered = Vec::new();
+                            self.stmt(init, &mut lowered);
+                            Box::new(lowered.pop().unwrap_or_else(|| (**init).clone()))
+                        }),
+                        cond: cond.as_ref().map(|cond| self.expr(cond)),
+                        step: step.as_ref().map(|step| self.expr(step)),
+                    },
+                    ForKind::In { binding, iterable } => ForKind::In { binding: binding.clone(), iterable: self.expr(iterable) },
+                };
+                let body = self.block(&lp.body, Some(lp.label.as_ref().map(|label| label.name.clone())));
+                StmtKind::For(ForLoop { kind, body, ..lp.clone() })
+            }
+        };
+        out.push(Stmt { kind, span, id: stmt.id });
+    }
+
+    fn boxed(&mut self, expr: &Expr) -> Box<Expr> {
+        Box::new(self.expr(expr))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Expr {
+        let kind = match &expr.kind {
+            ExprKind::Number(_) | ExprKind::Str(_) | ExprKind::Name(_) | ExprKind::Propagate(..) => expr.kind.clone(),
+            ExprKind::Postfix(Operator::ErrorOperator, value) => {
+                let value = self.boxed(value);
+                let unwind = self.unwind(self.scopes.len());
+                match unwind.is_empty() {
+                    true => ExprKind::Postfix(Operator::ErrorOperator, value),
+                    false => ExprKind::Propagate(value, Block { stmts: unwind, value: None, span: expr.span, id: self.ids.next() }),
+                }
+            }
+            ExprKind::Postfix(operator, value) => ExprKind::Postfix(*operator, self.boxed(value)),
+            ExprKind::Unary(operator, value) => ExprKind::Unary(*operator, self.boxed(value)),
+            ExprKind::Binary(operator, lhs, rhs) => ExprKind::Binary(*operator, self.boxed(lhs), self.boxed(rhs)),
+            ExprKind::Call(callee, args) => ExprKind::Call(self.boxed(callee), args.iter().map(|arg| self.expr(arg)).collect()),
+            ExprKind::Index(value, index) => ExprKind::Index(self.boxed(value), self.boxed(index)),
+            ExprKind::Field(value, field) => ExprKind::Field(self.boxed(value), field.clone()),
+            ExprKind::Tuple(items) => ExprKind::Tuple(items.iter().map(|item| self.expr(item)).collect()),
+            ExprKind::Array(items) => ExprKind::Array(items.iter().map(|item| self.expr(item)).collect()),
+            ExprKind::Match(value, arms) => ExprKind::Match(
+                self.boxed(value),
+                arms.iter()
+                    .map(|arm| MatchArm {
+                        guard: arm.guard.as_ref().map(|guard| self.expr(guard)),
+                        body: self.expr(&arm.body),
+                        ..arm.clone()
+                    })
+                    .collect(),
+            ),
+            ExprKind::If(conditional) => ExprKind::If(Conditional {
+                branches: conditional
+                    .branches
+                    .iter()
+                    .map(|branch| CondBranch { cond: self.expr(&branch.cond), body: self.block(&branch.body, None), span: branch.span })
+                    .collect(),
+                else_branch: conditional.else_branch.as_ref().map(|block| self.block(block, None)),
+                ..conditional.clone()
+            }),
+            // A `return` in a closure only leaves the closure
+            ExprKind::Closure(closure) => {
+                ExprKind::Closure(Box::new(Closure { body: lower_defers(&closure.body, self.ids), ..(**closure).clone() }))
+            }
+        };
+        Expr { kind, span: expr.span, id: expr.id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        parsing::tokenizer::tokenize_text,
+        treegen::{ast::NodeId, stmt::parse_block, visit::Visit},
+    };
+
+    enum Flow {
+        Normal,
+        Break(Option<String>),
+        Continue(Option<String>),
+        Return,
+    }
+
+    // Runs lowered code, recording the numbers passed to `log(n)`. Every loop runs its body twice,
+    // `yes` is true, `no` is false and `fail()` is an error
+    struct Run {
+        log: Vec<i64>,
+    }
+
+    impl Run {
+        fn block(&mut self, block: &Block) -> Flow {
+            for stmt in &block.stmts {
+                match self.stmt(stmt) {
+                    Flow::Normal => {}
+                    flow => return flow,
+                }
+            }
+            match &block.value {
+                Some(value) => self.expr(value),
+                None => Flow::Normal,
+            }
+        }
+
+        fn stmt(&mut self, stmt: &Stmt) -> Flow {
+            match &stmt.kind {
+                StmtKind::Expr(expr) => self.expr(expr),
+                StmtKind::Var(decl) => decl.value.as_ref().map_or(Flow::Normal, |value| self.expr(value)),
+                StmtKind::Block(block) => self.block(block),
+                StmtKind::Return(value) => {
+                    if let Some(value) = value {
+                        self.expr(value);
+                    }
+                    Flow::Return
+                }
+                StmtKind::Break(label) => Flow::Break(label.as_ref().map(|label| label.name.clone())),
+                StmtKind::Continue(label) => Flow::Continue(label.as_ref().map(|label| label.name.clone())),
+                StmtKind::While(lp) => self.repeat(&lp.label, &lp.body, None),
+                StmtKind::For(lp) => match &lp.kind {
+                    ForKind::Clauses { init, step, .. } => {
+                        if let Some(init) = init {
+                            self.stmt(init);
+                        }
+                        self.repeat(&lp.label, &lp.body, step.as_ref())
+                    }
+                    ForKind::In { .. } => self.repeat(&lp.label, &lp.body, None),
+                },
+                StmtKind::Error(_) => panic!("the evaluator does not run syntax errors"),
+                StmtKind::Defer(_) => panic!("`defer` was not lowered"),
+            }
+        }
+
+        // Two rounds of a loop's body, with `step` after each one that doesn't leave the loop
+        fn repeat(&mut self, label: &Option<Ident>, body: &Block, step: Option<&Expr>) -> Flow {
+            let mine = |target: &Option<String>| target.is_none() || target.as_ref() == label.as_ref().map(|label| &label.name);
+            for _ in 0..2 {
+                match self.block(body) {
+                    Flow::Break(target) if mine(&target) => break,
+                    Flow::Continue(target) if mine(&target) => {}
+                    Flow::Normal => {}
+                    flow => return flow,
+                }
+                if let Some(step) = step {
+                    self.expr(step);
+                }
+            }
+            Flow::Normal
+        }
+
+        fn expr(&mut self, expr: &Expr) -> Flow {
+            match &expr.kind {
+                ExprKind::Call(callee, args) => match (&callee.kind, &args[..]) {
+                    (ExprKind::Name(name), [Expr { kind: ExprKind::Number(number), .. }]) if name == "log" => {
+                        self.log.push(number.text_content.parse().unwrap());
+                        Flow::Normal
+                    }
+                    _ => Flow::Normal,
+                },
+                ExprKind::Postfix(Operator::ErrorOperator, value) => match self.fails(value) {
+                    true => Flow::Return,
+                    false => self.expr(value),
+                },
+                ExprKind::Propagate(value, cleanup) => match self.fails(value) {
+                    true => {
+                        self.block(cleanup);
+                        Flow::Return
+                    }
+                    false => self.expr(value),
+                },
+                ExprKind::If(conditional) => {
+                    for branch in &conditional.branches {
+                        if matches!(&branch.cond.kind, ExprKind::Name(name) if name == "yes") {
+                            return self.block(&branch.body);
+                        }
+                    }
+                    conditional.else_branch.as_ref().map_or(Flow::Normal, |block| self.block(block))
+                }
+                _ => Flow::Normal,
+            }
+        }
+
+        fn fails(&self, value: &Expr) -> bool {
+            matches!(&value.kind, ExprKind::Call(callee, _) if matches!(&callee.kind, ExprKind::Name(name) if name == "fail"))
+        }
+    }
+
+    fn lower(text: &str) -> (Block, Block, FreshIds) {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let block = parse_block(&tokens[0]).unwrap();
+        let mut ids = FreshIds::after_block(&block);
+        (lower_defers(&block, &mut ids), block, ids)
+    }
+
+    fn run(text: &str) -> Vec<i64> {
+        let (block, ..) = lower(&format!("{{{}}}", text));
+        let mut run = Run { log: Vec::new() };
+        run.block(&block);
+        run.log
+    }
+
+    #[test]
+    fn scope_exit_order() {
+        assert_eq!(run("defer log(1); { defer log(2); log(3); } defer { log(4); log(5); } log(6);"), [3, 2, 6, 4, 5, 1]);
+        assert_eq!(run("defer { defer log(1); log(2); } log(3);"), [3, 2, 1]);
+        // A block's value is worked out before its defers run
+        assert_eq!(run("var x = if (yes) { defer log(1); log(2) } else log(3); log(4);"), [2, 1, 4]);
+    }
+
+    #[test]
+    fn early_exits() {
+        assert_eq!(run("defer log(1); { defer log(2); if (yes) { defer log(3); return log(4); } } log(99);"), [4, 3, 2, 1]);
+        assert_eq!(run("defer log(1); while (more) { defer log(2); log(3); break; } log(4);"), [3, 2, 4, 1]);
+        assert_eq!(
+            run("defer log(1); 'outer: while (more) { defer log(2); while (more) { defer log(3); if (yes) continue 'outer; } log(99); } log(4);"),
+            [3, 2, 3, 2, 4, 1]
+        );
+        assert_eq!(run("defer log(1); { defer log(2); log(3); fine()!; fail()!; log(99); }"), [3, 2, 1]);
+        assert_eq!(run("defer log(1); if (no) return; log(2);"), [2, 1]);
+        assert_eq!(run("defer log(1); for (x in items) { defer log(2); log(3); if (yes) break; } log(4);"), [3, 2, 4, 1]);
+        assert_eq!(run("for (var i = 0; more; log(5)) { defer log(2); if (yes) continue; log(99); } log(4);"), [2, 5, 2, 5, 4]);
+        assert_eq!(run("defer log(1); for (x in items) { defer log(2); for (y in x) { defer log(3); return; } }"), [3, 2, 1]);
+    }
+
+    #[test]
+    fn lowered_shape() {
+        let (block, ..) = lower("{ defer log(1); return f(); }");
+        let [Stmt { kind: StmtKind::Block(exit), .. }] = &block.stmts[..] else { panic!("{:?}", block.stmts) };
+        let [Stmt { kind: StmtKind::Var(temp), .. }, Stmt { kind: StmtKind::Block(_), .. }, Stmt { kind: StmtKind::Return(Some(value)), .. }] = &exit.stmts[..] else {
+            panic!("{:?}", exit.stmts)
+        };
+        assert_eq!(temp.name.name, RETURN_TEMP);
+        assert_eq!(value.kind, ExprKind::Name(RETURN_TEMP.to_string()));
+
+        // Without defers nothing changes
+        let (lowered, block, _) = lower("{ while (a) { break; } return; }");
+        assert_eq!(lowered, block);
+    }
+
+    struct NodeIds(Vec<NodeId>);
+
+    impl Visit for NodeIds {
+        fn visit_node_id(&mut self, id: &NodeId) {
+            self.0.push(*id);
+        }
+    }
+
+    #[test]
+    fn fresh_ids() {
+        let (lowered, block, ids) = lower("{ defer log(1); if (yes) return f(); while (more) { defer log(2); break; } log(3) }");
+        let (mut written, mut after) = (NodeIds(Vec::new()), NodeIds(Vec::new()));
+        written.visit_block(&block);
+        after.visit_block(&lowered);
+        let mut unique = after.0.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), after.0.len());
+        // Copies of deferred code lead back to what was written
+        assert!(after.0.iter().filter(|id| !written.0.contains(id)).any(|id| written.0.contains(&ids.original(*id))));
+        assert!(after.0.iter().all(|id| *id != NodeId::UNSET));
+    }
+}
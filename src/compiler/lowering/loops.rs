@@ -0,0 +1,114 @@
+use crate::compiler::{
+    objects::types::Ty,
+    semantic::{
+        traits::{MethodSig, TraitTable},
+        SemanticError, SemanticResult,
+    },
+    treegen::ast::{Expr, Span},
+};
+
+const ITERATOR: &str = "Iterator";
+
+// A `for (x in ...)` loop after lowering
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForIn {
+    // `for (i in start..end)` counts in place without building a range value.
+    // An inclusive counter is compared with `end` after the body, so `0..=255u8` stops without wrapping
+    Counter { ty: Ty, start: Expr, end: Expr, inclusive: bool },
+    // Anything else runs `var iter = value; Item x; while (iter.next(&x)) { ... }`
+    Iterator { iterator: Ty, item: Ty, next: MethodSig },
+}
+
+impl ForIn {
+    // The type of the loop variable
+    pub fn item(&self) -> &Ty {
+        match self {
+            ForIn::Counter { ty, .. } => ty,
+            ForIn::Iterator { item, .. } => item,
+        }
+    }
+}
+
+// `start..end` with both ends already checked as `ty`
+pub fn lower_range(ty: Ty, start: &Expr, end: &Expr, inclusive: bool) -> ForIn {
+    ForIn::Counter { ty, start: start.clone(), end: end.clone(), inclusive }
+}
+
+// Looping over a value of type `ty`, through its `Iterator` implementation
+pub fn lower_iterator(ty: &Ty, traits: &TraitTable, span: Span) -> SemanticResult<ForIn> {
+    let Some(implementation) = traits.find_impl(ITERATOR, ty) else {
+        return Err(SemanticError::new(format!("`{}` can't be looped over, it does not implement `{}`", ty, ITERATOR), span));
+    };
+    let item = implementation.types.iter().find(|assoc| assoc.name == "Item").and_then(|assoc| assoc.ty.clone());
+    let next = implementation.methods.iter().find(|method| method.name == "next");
+    match (item, next) {
+        (Some(item), Some(next)) => Ok(ForIn::Iterator { iterator: ty.clone(), item, next: next.clone() }),
+        // Only reachable when a program declares its own `Iterator` without them
+        _ => Err(SemanticError::new(format!("`{}` needs `typedef Item;` and `next` to be looped over", ITERATOR), span)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        objects::gurn_objects::Primitive,
+        parsing::tokenizer::{tokenize_text, TokenData},
+        semantic::table::TypeTable,
+        treegen::{ast::ExprKind, items::parse_items},
+    };
+
+    fn traits(text: &str) -> TraitTable {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap();
+        TraitTable::from_items(&items, &TypeTable::from_items(&items).unwrap()).unwrap()
+    }
+
+    fn number(text: &str) -> Expr {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let TokenData::NumberLiteral(number) = &tokens[0].data else { panic!() };
+        Expr::new(ExprKind::Number(number.clone()), Span::of_token(&tokens[0]))
+    }
+
+    #[test]
+    fn iterators() {
+        let table = traits(
+            "struct Countdown { u32 left }
+            impl Iterator for Countdown {
+                typedef u32 Item;
+                bool next(Self* self, Item* item) => 1 < 2;
+            }
+            struct Plain { u8 x }",
+        );
+        let countdown = Ty::Named("Countdown".to_string(), vec![]);
+        let lowered = lower_iterator(&countdown, &table, Span::default()).unwrap();
+        let ForIn::Iterator { item, next, .. } = &lowered else { panic!() };
+        assert_eq!(item, &Ty::Primitive(Primitive::U32));
+        assert_eq!(next.params, [Ty::Pointer(Box::new(countdown.clone())), Ty::Pointer(Box::new(Ty::Primitive(Primitive::U32)))]);
+
+        let plain = Ty::Named("Plain".to_string(), vec![]);
+        assert_eq!(
+            lower_iterator(&plain, &table, Span::default()).unwrap_err().message,
+            "`Plain` can't be looped over, it does not implement `Iterator`"
+        );
+
+        let range = lower_range(Ty::Primitive(Primitive::U8), &number("0"), &number("255"), true);
+        assert_eq!(range.item(), &Ty::Primitive(Primitive::U8));
+    }
+
+    #[test]
+    fn iterator_signatures() {
+        let text = "struct Bad { u8 x }
+            impl Iterator for Bad {
+                typedef u16 Item;
+                bool next(Self* self, u8* item) => 1 < 2;
+            }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap();
+        let errors = TraitTable::from_items(&items, &TypeTable::from_items(&items).unwrap()).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "`next` does not match its declaration in the trait, expected `bool next(*Bad, *u16)`, found `bool next(*Bad, *u8)`"
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use crate::compiler::{
+    objects::types::Ty,
+    semantic::{
+        table::{integer_range, FieldInfo, PayloadInfo, TypeTable},
+        SemanticError, SemanticResult,
+    },
+    treegen::ast::{MatchArm, Pattern, PatternArg, PatternKind, Span},
+};
+
+// Where a value lives, relative to the value being matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Place {
+    Scrutinee,
+    // An element of a tuple, or a field of a struct in declaration order
+    Field(Box<Place>, usize),
+    // A value in the payload of the variant with this index, only readable once the tag was tested
+    Payload(Box<Place>, usize, usize),
+}
+
+impl Place {
+    fn field(&self, index: usize) -> Place {
+        Place::Field(Box::new(self.clone()), index)
+    }
+
+    fn payload(&self, variant: usize, index: usize) -> Place {
+        Place::Payload(Box::new(self.clone()), variant, index)
+    }
+
+    fn is_within(&self, ancestor: &Place) -> bool {
+        self == ancestor
+            || match self {
+                Place::Scrutinee => false,
+                Place::Field(parent, _) | Place::Payload(parent, ..) => parent.is_within(ancestor),
+            }
+    }
+}
+
+// A `match` after lowering, every test is on a single place
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    // No arm applies, only reachable from a `match` that is not exhaustive
+    Fail,
+    // Bind the values and run the arm, `otherwise` runs when the arm's guard is false
+    Arm { arm: usize, bindings: Vec<(String, Place)>, otherwise: Option<Box<Decision>> },
+    // Jump on the tag of an enum by variant index, `default` covers the variants without a case
+    Switch { place: Place, cases: Vec<(usize, Decision)>, default: Option<Box<Decision>> },
+    // Whether an integer lies in `start..=end`
+    Range { place: Place, start: i128, end: i128, then: Box<Decision>, otherwise: Box<Decision> },
+}
+
+// A pattern with names replaced by indices, ranges are inclusive
+#[derive(Debug, Clone)]
+enum Pat {
+    Any(Option<String>),
+    // With the smallest and largest value of the type
+    Range(i128, i128, (i128, i128)),
+    Fields(Vec<(usize, Pat)>),
+    Variant(usize, Vec<(usize, Pat)>),
+}
+
+#[derive(Debug, Clone)]
+enum Fact {
+    Variant(usize),
+    NotVariants(Vec<usize>),
+    Range { start: i128, end: i128, inside: bool, domain: (i128, i128) },
+}
+
+// What is left of an arm's pattern, only refutable tests stay in `columns`
+#[derive(Debug, Clone)]
+struct Row {
+    columns: Vec<(Place, Pat)>,
+    bindings: Vec<(String, Place)>,
+    arm: usize,
+    guarded: bool,
+}
+
+impl Row {
+    fn column(&self, place: &Place) -> Option<&Pat> {
+        self.columns.iter().find(|(other, _)| other == place).map(|(_, pat)| pat)
+    }
+
+    fn without(&self, place: &Place) -> Row {
+        let mut row = self.clone();
+        row.columns.retain(|(other, _)| other != place);
+        row
+    }
+
+    // The row as seen once `place` is known to be `variant`, `None` if it can't match then
+    fn specialize(&self, place: &Place, variant: usize) -> Option<Row> {
+        let Some(position) = self.columns.iter().position(|(other, _)| other == place) else {
+            return Some(self.clone());
+        };
+        let Pat::Variant(found, payload) = &self.columns[position].1 else {
+            return Some(self.clone());
+        };
+        if *found != variant {
+            return None;
+        }
+        let mut row = self.clone();
+        let mut columns = Vec::new();
+        for (index, pat) in payload {
+            flatten(place.payload(variant, *index), pat.clone(), &mut columns, &mut row.bindings);
+        }
+        row.columns.splice(position..=position, columns);
+        Some(row)
+    }
+}
+
+// Moves bindings out of the way and splits tuples and structs into their fields
+fn flatten(place: Place, pat: Pat, columns: &mut Vec<(Place, Pat)>, bindings: &mut Vec<(String, Place)>) {
+    match pat {
+        Pat::Any(Some(name)) => bindings.push((name, place)),
+        Pat::Any(None) => {}
+        Pat::Fields(fields) => {
+            for (index, pat) in fields {
+                flatten(place.field(index), pat, columns, bindings);
+            }
+        }
+        pat => columns.push((place, pat)),
+    }
+}
+
+struct MatchCompiler<'a> {
+    table: &'a TypeTable,
+    scrutinee: &'a Ty,
+    reachable: Vec<bool>,
+    // What the tests on the way to the decision being built found out
+    known: Vec<(Place, Fact)>,
+    // The first value found that no arm covers
+    missing: Option<String>,
+}
+
+// Compiles the arms into a decision tree, rejecting a `match` that misses a value or has an arm that can't be reached
+pub fn lower_match(scrutinee: &Ty, arms: &[MatchArm], table: &TypeTable, span: Span) -> SemanticResult<Decision> {
+    let mut compiler = MatchCompiler { table, scrutinee, reachable: vec![false; arms.len()], known: Vec::new(), missing: None };
+    let mut rows = Vec::new();
+    for (arm, arm_decl) in arms.iter().enumerate() {
+        let mut row = Row { columns: Vec::new(), bindings: Vec::new(), arm, guarded: arm_decl.guard.is_some() };
+        flatten(Place::Scrutinee, compiler.lower_pattern(&arm_decl.pattern, scrutinee)?, &mut row.columns, &mut row.bindings);
+        rows.push(row);
+    }
+    let decision = compiler.compile(rows);
+
+    if let Some(missing) = compiler.missing {
+        return Err(SemanticError::new(format!("this `match` does not cover `{}`", missing), span));
+    }
+    if let Some(arm) = compiler.reachable.iter().position(|reachable| !reachable) {
+        return Err(SemanticError::new("this arm can never match, the arms before it cover every value it does", arms[arm].pattern.span));
+    }
+    Ok(decision)
+}
+
+impl MatchCompiler<'_> {
+    fn lower_pattern(&self, pattern: &Pattern, ty: &Ty) -> SemanticResult<Pat> {
+        let mismatch = || SemanticError::new(format!("this pattern can't match `{}`", ty), pattern.span);
+        let number = |number: &crate::compiler::parsing::number_parser::NumberLiteral| match ty {
+            Ty::Primitive(primitive) if !primitive.is_float() && !number.has_decimal => {
+                number.parse_int::<i128>().map(|value| (value, integer_range(primitive))).map_err(|_| mismatch())
+            }
+            _ => Err(mismatch()),
+        };
+        Ok(match (&pattern.kind, ty) {
+            (PatternKind::Wildcard, _) => Pat::Any(None),
+            (PatternKind::Binding(name), _) => Pat::Any(Some(name.name.clone())),
+            (PatternKind::Literal(literal), _) => {
+                let (value, domain) = number(literal)?;
+                Pat::Range(value, value, domain)
+            }
+            (PatternKind::Range(start, end, inclusive), _) => {
+                let ((start, domain), (end, _)) = (number(start)?, number(end)?);
+                Pat::Range(start, if *inclusive { end } else { end - 1 }, domain)
+            }
+            (PatternKind::Tuple(items), Ty::Tuple(types)) if items.len() == types.len() => Pat::Fields(
+                items.iter().zip(types).enumerate().map(|(i, (item, ty))| Ok((i, self.lower_pattern(item, ty)?))).collect::<SemanticResult<_>>()?,
+            ),
+            (PatternKind::Struct(_, args), Ty::Named(name, generic_args)) => {
+                let info = self.table.structs.get(name).ok_or_else(mismatch)?;
+                Pat::Fields(self.lower_named(args, &info.fields, &info.generics, generic_args)?)
+            }
+            (PatternKind::Variant(_, variant, args), Ty::Named(name, generic_args)) => {
+                let info = self.table.enums.get(name).ok_or_else(mismatch)?;
+                let index = info.variants.iter().position(|other| other.name == variant.name).ok_or_else(mismatch)?;
+                let payload = match (&info.variants[index].payload, args) {
+                    (_, None) => Vec::new(),
+                    (PayloadInfo::Tuple(types), Some(args)) => args
+                        .iter()
+                        .zip(types)
+                        .enumerate()
+                        .map(|(i, (arg, ty))| Ok((i, self.lower_pattern(&arg.pattern, &ty.substitute(&info.generics, generic_args))?)))
+                        .collect::<SemanticResult<_>>()?,
+                    (PayloadInfo::Struct(fields), Some(args)) => self.lower_named(args, fields, &info.generics, generic_args)?,
+                    (PayloadInfo::Unit, Some(_)) => return Err(mismatch()),
+                };
+                Pat::Variant(index, payload)
+            }
+            _ => return Err(mismatch()),
+        })
+    }
+
+    // `field = pattern` and `field` arguments, by field index
+    fn lower_named(&self, args: &[PatternArg], fields: &[FieldInfo], generics: &[String], generic_args: &[Ty]) -> SemanticResult<Vec<(usize, Pat)>> {
+        args.iter()
+            .map(|arg| {
+                let (name, pat) = match (&arg.name, &arg.pattern.kind) {
+                    (Some(name), _) => (name, None),
+                    (None, PatternKind::Binding(name)) => (name, Some(Pat::Any(Some(name.name.clone())))),
+                    _ => return Err(SemanticError::new("name the field this pattern matches, `field = pattern`", arg.span)),
+                };
+                let index = fields
+                    .iter()
+                    .position(|field| field.name == name.name)
+                    .ok_or_else(|| SemanticError::new(format!("no field `{}`", name.name), name.span))?;
+                let pat = match pat {
+                    Some(pat) => pat,
+                    None => self.lower_pattern(&arg.pattern, &fields[index].ty.substitute(generics, generic_args))?,
+                };
+                Ok((index, pat))
+            })
+            .collect()
+    }
+
+    fn compile(&mut self, rows: Vec<Row>) -> Decision {
+        if !self.feasible() {
+            return Decision::Fail;
+        }
+        let Some(first) = rows.first() else {
+            if self.missing.is_none() {
+                self.missing = Some(self.witness(&Place::Scrutinee, self.scrutinee));
+            }
+            return Decision::Fail;
+        };
+        let Some((place, pat)) = first.columns.first().cloned() else {
+            self.reachable[first.arm] = true;
+            let otherwise = first.guarded.then(|| Box::new(self.compile(rows[1..].to_vec())));
+            return Decision::Arm { arm: first.arm, bindings: first.bindings.clone(), otherwise };
+        };
+        match pat {
+            Pat::Variant(..) => self.switch(place, &rows),
+            Pat::Range(start, end, domain) => self.range(place, start, end, domain, &rows),
+            Pat::Any(_) | Pat::Fields(_) => unreachable!("flatten removes irrefutable patterns"),
+        }
+    }
+
+    fn with_fact(&mut self, place: &Place, fact: Fact, rows: Vec<Row>) -> Decision {
+        self.known.push((place.clone(), fact));
+        let decision = self.compile(rows);
+        self.known.pop();
+        decision
+    }
+
+    fn switch(&mut self, place: Place, rows: &[Row]) -> Decision {
+        let mut seen = Vec::new();
+        for row in rows {
+            if let Some(Pat::Variant(variant, _)) = row.column(&place) {
+                if !seen.contains(variant) {
+                    seen.push(*variant);
+                }
+            }
+        }
+        let mut cases = Vec::new();
+        for &variant in &seen {
+            let specialized = rows.iter().filter_map(|row| row.specialize(&place, variant)).collect();
+            cases.push((variant, self.with_fact(&place, Fact::Variant(variant), specialized)));
+        }
+
+        let variant_count = match self.type_of(&place) {
+            Some(Ty::Named(name, _)) => self.table.enums.get(&name).map_or(0, |info| info.variants.len()),
+            _ => 0,
+        };
+        let default = (seen.len() < variant_count).then(|| {
+            let rest = rows.iter().filter(|row| row.column(&place).is_none()).cloned().collect();
+            Box::new(self.with_fact(&place, Fact::NotVariants(seen), rest))
+        });
+        Decision::Switch { place, cases, default }
+    }
+
+    fn range(&mut self, place: Place, start: i128, end: i128, domain: (i128, i128), rows: &[Row]) -> Decision {
+        let inside = rows
+            .iter()
+            .filter_map(|row| match row.column(&place) {
+                Some(Pat::Range(other_start, other_end, _)) if *other_start <= start && end <= *other_end => Some(row.without(&place)),
+                Some(Pat::Range(other_start, other_end, _)) if *other_start > end || start > *other_end => None,
+                _ => Some(row.clone()),
+            })
+            .collect();
+        let outside = rows
+            .iter()
+            .filter(|row| !matches!(row.column(&place), Some(Pat::Range(other_start, other_end, _)) if start <= *other_start && *other_end <= end))
+            .cloned()
+            .collect();
+        let then = self.with_fact(&place, Fact::Range { start, end, inside: true, domain }, inside);
+        let otherwise = self.with_fact(&place, Fact::Range { start, end, inside: false, domain }, outside);
+        Decision::Range { place, start, end, then: Box::new(then), otherwise: Box::new(otherwise) }
+    }
+
+    // The smallest integer at `place` that agrees with every range test so far
+    fn value_at(&self, place: &Place) -> Option<i128> {
+        let mut outside = Vec::new();
+        let mut bounds: Option<(i128, i128)> = None;
+        for (other, fact) in &self.known {
+            if let (true, Fact::Range { start, end, inside, domain }) = (other == place, fact) {
+                let (low, high) = bounds.unwrap_or(*domain);
+                if *inside {
+                    bounds = Some((low.max(*start), high.min(*end)));
+                } else {
+                    bounds = Some((low, high));
+                    outside.push((*start, *end));
+                }
+            }
+        }
+        let (mut value, high) = bounds?;
+        outside.sort();
+        for (start, end) in outside {
+            if start <= value && value <= end {
+                value = end.checked_add(1)?;
+            }
+        }
+        (value <= high).then_some(value)
+    }
+
+    fn feasible(&self) -> bool {
+        self.known
+            .iter()
+            .all(|(place, fact)| !matches!(fact, Fact::Range { .. }) || self.value_at(place).is_some())
+    }
+
+    fn type_of(&self, place: &Place) -> Option<Ty> {
+        Some(match place {
+            Place::Scrutinee => self.scrutinee.clone(),
+            Place::Field(parent, index) => match self.type_of(parent)? {
+                Ty::Tuple(items) => items.get(*index)?.clone(),
+                Ty::Named(name, args) => {
+                    let info = self.table.structs.get(&name)?;
+                    info.fields.get(*index)?.ty.substitute(&info.generics, &args)
+                }
+                _ => return None,
+            },
+            Place::Payload(parent, variant, index) => match self.type_of(parent)? {
+                Ty::Named(name, args) => {
+                    let info = self.table.enums.get(&name)?;
+                    let ty = match &info.variants.get(*variant)?.payload {
+                        PayloadInfo::Tuple(types) => types.get(*index)?,
+                        PayloadInfo::Struct(fields) => &fields.get(*index)?.ty,
+                        PayloadInfo::Unit => return None,
+                    };
+                    ty.substitute(&info.generics, &args)
+                }
+                _ => return None,
+            },
+        })
+    }
+
+    // A pattern for a value that reaches the current decision
+    fn witness(&self, place: &Place, ty: &Ty) -> String {
+        if !self.known.iter().any(|(known, _)| known.is_within(place)) {
+            return "_".to_string();
+        }
+        let fields = |fields: &[FieldInfo], generics: &[String], args: &[Ty], place: &dyn Fn(usize) -> Place| {
+            fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| format!("{} = {}", field.name, self.witness(&place(i), &field.ty.substitute(generics, args))))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match ty {
+            Ty::Primitive(_) => self.value_at(place).map_or("_".to_string(), |value| value.to_string()),
+            Ty::Tuple(items) => {
+                let items: Vec<String> = items.iter().enumerate().map(|(i, item)| self.witness(&place.field(i), item)).collect();
+                if items.len() == 1 {
+                    format!("({},)", items[0])
+                } else {
+                    format!("({})", items.join(", "))
+                }
+            }
+            Ty::Named(name, args) => {
+                if let Some(info) = self.table.structs.get(name) {
+                    return format!("{}({})", name, fields(&info.fields, &info.generics, args, &|i| place.field(i)));
+                }
+                let Some(info) = self.table.enums.get(name) else {
+                    return "_".to_string();
+                };
+                let variant = self.known.iter().rev().find_map(|(known, fact)| match fact {
+                    Fact::Variant(variant) if known == place => Some(*variant),
+                    Fact::NotVariants(seen) if known == place => (0..info.variants.len()).find(|variant| !seen.contains(variant)),
+                    _ => None,
+                });
+                let Some(variant) = variant else {
+                    return "_".to_string();
+                };
+                let payload = match &info.variants[variant].payload {
+                    PayloadInfo::Unit => String::new(),
+                    PayloadInfo::Tuple(types) => format!(
+                        "({})",
+                        types
+                            .iter()
+                            .enumerate()
+                            .map(|(i, ty)| self.witness(&place.payload(variant, i), &ty.substitute(&info.generics, args)))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    PayloadInfo::Struct(struct_fields) => format!("({})", fields(struct_fields, &info.generics, args, &|i| place.payload(variant, i))),
+                };
+                format!("{}.{}{}", name, info.variants[variant].name, payload)
+            }
+            _ => "_".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        objects::gurn_objects::Primitive,
+        parsing::tokenizer::tokenize_text,
+        treegen::{ast::ExprKind, expr::parse_expr, items::parse_items, parser::Cursor},
+    };
+
+    const DECLS: &str = "
+        struct Point { i32 x, i32 y }
+        enum Shape { Circle(Point, u8), Rect { u8 w, u8 h }, Empty }
+        enum Maybe<T> { Some(T), Nothing }";
+
+    fn lower(scrutinee: Ty, text: &str) -> SemanticResult<Decision> {
+        let tokens = tokenize_text(DECLS.to_string()).unwrap();
+        let table = TypeTable::from_items(&parse_items(&tokens, DECLS.len()).unwrap()).unwrap();
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let expr = parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap();
+        let ExprKind::Match(_, arms) = &expr.kind else { panic!() };
+        lower_match(&scrutinee, arms, &table, expr.span)
+    }
+
+    fn shape() -> Ty {
+        Ty::Named("Shape".to_string(), vec![])
+    }
+
+    fn message(scrutinee: Ty, text: &str) -> String {
+        lower(scrutinee, text).unwrap_err().message
+    }
+
+    #[test]
+    fn decision_trees() {
+        let decision = lower(shape(), "match (s) { Shape.Circle(_, r) => r, Shape.Rect(w) => w, _ => 0 }").unwrap();
+        let Decision::Switch { place: Place::Scrutinee, cases, default: Some(default) } = decision else { panic!() };
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].1, Decision::Arm { arm: 0, bindings: vec![("r".to_string(), Place::Scrutinee.payload(0, 1))], otherwise: None });
+        assert_eq!(cases[1].1, Decision::Arm { arm: 1, bindings: vec![("w".to_string(), Place::Scrutinee.payload(1, 0))], otherwise: None });
+        assert!(matches!(*default, Decision::Arm { arm: 2, .. }));
+
+        let byte = Ty::Primitive(Primitive::U8);
+        let decision = lower(byte.clone(), "match (b) { 0 => a, 1..=9 => b, n where n > 100 => c, _ => d }").unwrap();
+        let Decision::Range { start: 0, end: 0, otherwise, .. } = decision else { panic!() };
+        let Decision::Range { start: 1, end: 9, otherwise, .. } = *otherwise else { panic!() };
+        assert!(matches!(*otherwise, Decision::Arm { arm: 2, otherwise: Some(_), .. }));
+    }
+
+    #[test]
+    fn exhaustiveness() {
+        assert_eq!(message(shape(), "match (s) { Shape.Circle(p, r) => 1, Shape.Rect(w) => 2 }"), "this `match` does not cover `Shape.Empty`");
+        let maybe = Ty::Named("Maybe".to_string(), vec![shape()]);
+        assert_eq!(
+            message(maybe.clone(), "match (m) { Maybe.Nothing => 0, Maybe.Some(Shape.Circle(_, 0..=9)) => 1, Maybe.Some(Shape.Circle(_, 20..=255)) => 1, Maybe.Some(Shape.Rect(w)) => 2, Maybe.Some(Shape.Empty) => 3 }"),
+            "this `match` does not cover `Maybe.Some(Shape.Circle(_, 10))`"
+        );
+        assert_eq!(message(shape(), "match (s) { x where x == 1 => 1 }"), "this `match` does not cover `_`");
+
+        let byte = Ty::Primitive(Primitive::U8);
+        assert!(lower(byte.clone(), "match (b) { 0..128 => 0, 128..=255 => 1 }").is_ok());
+        assert_eq!(message(byte.clone(), "match (b) { 0..128 => 0, 129..=255 => 1 }"), "this `match` does not cover `128`");
+        let pair = Ty::Tuple(vec![byte.clone(), Ty::Named("Point".to_string(), vec![])]);
+        assert_eq!(message(pair, "match (t) { (0, _) => 0, (_, Point(x = 0)) => 1 }"), "this `match` does not cover `(1, Point(x = -2147483648, y = _))`");
+    }
+
+    #[test]
+    fn unreachable_arms() {
+        assert_eq!(message(shape(), "match (s) { _ => 0, Shape.Empty => 1 }"), "this arm can never match, the arms before it cover every value it does");
+        let byte = Ty::Primitive(Primitive::U8);
+        assert_eq!(message(byte.clone(), "match (b) { 0..=10 => 0, 5 => 1, _ => 2 }"), "this arm can never match, the arms before it cover every value it does");
+        assert!(lower(byte, "match (b) { n where n > 5 => 0, 5 => 1, _ => 2 }").is_ok());
+    }
+}
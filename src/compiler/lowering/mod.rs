@@ -0,0 +1,5 @@
+// Passes that rewrite checked syntax into simpler forms for the backends
+pub mod closures;
+pub mod defer;
+pub mod loops;
+pub mod matching;
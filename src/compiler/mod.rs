@@ -5,5 +5,9 @@ pub mod parsing;
 pub mod state;
 pub mod objects;
 pub mod treegen;
+pub mod semantic;
+pub mod lowering;
+pub mod hir;
+pub mod dump;
 mod errors;
 pub use errors::*;
\ No newline at end of file
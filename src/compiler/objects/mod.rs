@@ -1,2 +1,3 @@
 pub mod gurn_objects;
-pub mod symbols;
\ No newline at end of file
+pub mod symbols;
+pub mod types;
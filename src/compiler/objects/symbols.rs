@@ -1,7 +1,19 @@
+use std::{cell::RefCell, rc::Rc};
 
-pub struct ResolvedSymbol{
+use crate::compiler::{state::ScopeData, treegen::ast::Span};
 
+// A name used in the program, with the definition it refers to
+#[derive(Debug, Clone)]
+pub struct ResolvedSymbol{
+    pub name : String,
+    pub span : Span,
+    // The namespace the definition was found in
+    pub usage : KnownSymbolUsage,
+    pub definition : Rc<RefCell<ScopeData>>,
 }
+
+// The namespaces, a type and a function can share a name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KnownSymbolUsage{
     Function,
     Macro,
@@ -9,10 +21,41 @@ pub enum KnownSymbolUsage{
     Variable
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct SymbolContext{
-    usage : Option<KnownSymbolUsage>
+    // `None` where the name could be anything, like `Shape` in `Shape.Circle`
+    pub usage : Option<KnownSymbolUsage>
+}
+
+impl SymbolContext{
+    // The namespaces to look in, in order
+    pub fn namespaces(&self) -> &'static [KnownSymbolUsage]{
+        use KnownSymbolUsage::*;
+        match self.usage{
+            // Functions are values too, and closures are called like functions
+            Some(Variable) => &[Variable, Function],
+            Some(Function) => &[Function, Variable],
+            Some(Type) => &[Type],
+            Some(Macro) => &[Macro],
+            // Values first, then types, `Shape.Circle` reaches the enum even next to a function `Shape`
+            None => &[Variable, Type, Function, Macro],
+        }
+    }
 }
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct UnresolvedSymbol{
-    name : String,
+    pub name : String,
+    pub span : Span,
+    pub context : SymbolContext,
+}
 
-}
\ No newline at end of file
+impl UnresolvedSymbol{
+    // Looks the name up from `scope` outwards, handing itself back if nothing has that name
+    pub fn resolve(self, scope : &Rc<RefCell<ScopeData>>) -> Result<ResolvedSymbol, Self>{
+        match ScopeData::symbol_search(scope.clone(), &self.name, self.context.namespaces()){
+            Some((usage, definition)) => Ok(ResolvedSymbol{name : self.name, span : self.span, usage, definition}),
+            None => Err(self)
+        }
+    }
+}
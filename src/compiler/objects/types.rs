@@ -0,0 +1,89 @@
+use std::fmt;
+
+use super::gurn_objects::Primitive;
+
+// A type after name resolution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ty {
+    Primitive(Primitive),
+    // What comparisons produce
+    Bool,
+    // A declared struct or enum with its generic arguments
+    Named(String, Vec<Ty>),
+    // A generic parameter of the declaration being checked
+    Param(String),
+    Pointer(Box<Ty>),
+    // `()` is the unit type
+    Tuple(Vec<Ty>),
+    // What a closure evaluates to, the function together with its captures
+    Function(Vec<Ty>, Box<Ty>),
+    // `fn(u8, u16) -> bool`, the address of a declared function
+    FnPointer(Abi, Vec<Ty>, Box<Ty>),
+}
+
+// How arguments and return values are passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Abi {
+    Gurn,
+    // `extern`, for callbacks handed to C code
+    C,
+}
+
+impl Ty {
+    pub fn unit() -> Self {
+        Ty::Tuple(Vec::new())
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Ty::Primitive(primitive) if !primitive.is_float())
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Ty::Primitive(_))
+    }
+
+    // Replaces generic parameters by name
+    pub fn substitute(&self, params: &[String], args: &[Ty]) -> Ty {
+        match self {
+            Ty::Param(name) => params
+                .iter()
+                .position(|param| param == name)
+                .and_then(|i| args.get(i))
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            Ty::Named(name, generic_args) => Ty::Named(name.clone(), generic_args.iter().map(|ty| ty.substitute(params, args)).collect()),
+            Ty::Pointer(inner) => Ty::Pointer(Box::new(inner.substitute(params, args))),
+            Ty::Tuple(items) => Ty::Tuple(items.iter().map(|ty| ty.substitute(params, args)).collect()),
+            Ty::Function(inputs, output) => {
+                Ty::Function(inputs.iter().map(|ty| ty.substitute(params, args)).collect(), Box::new(output.substitute(params, args)))
+            }
+            Ty::FnPointer(abi, inputs, output) => Ty::FnPointer(
+                *abi,
+                inputs.iter().map(|ty| ty.substitute(params, args)).collect(),
+                Box::new(output.substitute(params, args)),
+            ),
+            Ty::Primitive(_) | Ty::Bool => self.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Primitive(primitive) => write!(f, "{}", primitive.name()),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Named(name, args) if args.is_empty() => write!(f, "{}", name),
+            Ty::Named(name, args) => write!(f, "{}<{}>", name, args.iter().map(Ty::to_string).collect::<Vec<_>>().join(", ")),
+            Ty::Param(name) => write!(f, "{}", name),
+            Ty::Pointer(inner) => write!(f, "*{}", inner),
+            Ty::Tuple(items) => write!(f, "({})", items.iter().map(Ty::to_string).collect::<Vec<_>>().join(", ")),
+            Ty::Function(inputs, output) => write!(f, "closure({}) -> {}", inputs.iter().map(Ty::to_string).collect::<Vec<_>>().join(", "), output),
+            Ty::FnPointer(abi, inputs, output) => {
+                if *abi == Abi::C {
+                    write!(f, "extern ")?;
+                }
+                write!(f, "fn({}) -> {}", inputs.iter().map(Ty::to_string).collect::<Vec<_>>().join(", "), output)
+            }
+        }
+    }
+}
@@ -63,10 +63,15 @@ pub enum Operator {
 
     EqualsArrow, // =>
     LineArrow, // ->
+
+    Range,          // ..
+    RangeInclusive, // ..=
 }
-const BINARY_OPERATORS: [(&'static str, Operator); 38] = [
+const BINARY_OPERATORS: [(&'static str, Operator); 40] = [
     ("=>", Operator::EqualsArrow),
     ("->", Operator::LineArrow),
+    ("..=", Operator::RangeInclusive),
+    ("..", Operator::Range),
 
 
     ("+=", Operator::AddEq),
@@ -139,3 +144,78 @@ pub fn operator_test(str : &str, potential_binary : bool, potential_pre_unary :
     }
     None
 }
+
+impl Operator {
+    // The source text of an operator, as the tokenizer would read it
+    pub fn text(&self) -> &'static str {
+        BINARY_OPERATORS
+            .iter()
+            .chain(UNARY_PRE.iter())
+            .chain(UNARY_POST.iter())
+            .find(|opr| opr.1 == *self)
+            .map(|opr| opr.0)
+            .unwrap_or("?")
+    }
+}
+
+// The tightest binding binary operators, `*`, `/` and `%`
+pub const MAX_BINARY_PRECEDENCE: u8 = 13;
+// `*`, `&` and `~` in front of an expression bind tighter than every binary operator
+pub const PREFIX_PRECEDENCE: u8 = MAX_BINARY_PRECEDENCE + 1;
+
+impl Operator {
+    // How tightly a binary operator binds, higher first. `None` if it can't join two expressions
+    pub fn binary_precedence(&self) -> Option<u8> {
+        use Operator::*;
+        Some(match self {
+            Assign | AddEq | SubEq | MultEq | DivEq | ModEq | BitwiseAndEq | XorEq | BitwiseOrEq
+            | BitwiseShiftRightEq | BitwiseShiftLeftEq | BitwiseUnsignedShiftRightEq
+            | BitwiseUnsignedShiftLeftEq | LogicalAndEq | LogicalOrEq => 1,
+            // `0..n + 1` is `0..(n + 1)`
+            Range | RangeInclusive => 2,
+            ConversionPipe => 3,
+            LogicalOr => 4,
+            LogicalAnd => 5,
+            BitwiseOr => 6,
+            Xor => 7,
+            BitwiseAnd => 8,
+            EqualityCheck | NotEqualityCheck => 9,
+            GreaterThan | GreaterThanEq | LesserThan | LesserThanEq => 10,
+            BitwiseShiftRight | BitwiseShiftLeft | BitwiseUnsignedShiftRight | BitwiseUnsignedShiftLeft => 11,
+            Add | Sub => 12,
+            Mult | Div | Mod => MAX_BINARY_PRECEDENCE,
+            Not | Dereference | Reference | OptionalOperator | ErrorOperator | EqualsArrow | LineArrow => return None,
+        })
+    }
+
+    // `=` and the compound assignments like `+=`
+    pub fn is_assignment(&self) -> bool {
+        use Operator::*;
+        matches!(
+            self,
+            Assign | AddEq | SubEq | MultEq | DivEq | ModEq | BitwiseAndEq | XorEq | BitwiseOrEq
+                | BitwiseShiftRightEq | BitwiseShiftLeftEq | BitwiseUnsignedShiftRightEq
+                | BitwiseUnsignedShiftLeftEq | LogicalAndEq | LogicalOrEq
+        )
+    }
+
+    // Assignments group right to left, `a = b = c` is `a = (b = c)`
+    pub fn is_right_associative(&self) -> bool {
+        self.is_assignment()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence_table() {
+        for (text, operator) in BINARY_OPERATORS {
+            if let Some(precedence) = operator.binary_precedence() {
+                assert!(precedence <= MAX_BINARY_PRECEDENCE, "`{}` binds tighter than prefix operators", text);
+                assert_eq!(operator.is_assignment(), precedence == 1, "`{}`", text);
+            }
+        }
+    }
+}
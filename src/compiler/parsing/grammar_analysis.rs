@@ -0,0 +1,312 @@
+use std::{fmt, mem::discriminant};
+
+use super::{pattern_matcher::Match, tokenizer::TokenData};
+
+// A class of token that a pattern can start with
+#[derive(Debug, Clone, PartialEq)]
+pub enum FirstToken {
+    Exact(TokenData), // From `Of`
+    Kind(TokenData),  // From `OfType`, only the variant matters
+    Whitespace,
+    Anything, // Globs, sizers and `Any`
+}
+impl FirstToken {
+    pub fn overlaps(&self, other: &Self) -> bool {
+        use FirstToken::*;
+        match (self, other) {
+            (Anything, _) | (_, Anything) => true,
+            (Whitespace, Whitespace) => true,
+            (Whitespace, Exact(data) | Kind(data)) | (Exact(data) | Kind(data), Whitespace) => {
+                matches!(data, TokenData::Whitespace(_))
+            }
+            (Exact(a), Exact(b)) => a == b,
+            (Exact(a) | Kind(a), Exact(b) | Kind(b)) => discriminant(a) == discriminant(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FirstSet {
+    pub tokens: Vec<FirstToken>,
+    // The pattern can succeed without consuming anything
+    pub nullable: bool,
+}
+impl FirstSet {
+    fn nullable() -> Self {
+        Self { tokens: Vec::new(), nullable: true }
+    }
+    fn of(token: FirstToken) -> Self {
+        Self { tokens: vec![token], nullable: false }
+    }
+    fn union(mut self, other: Self) -> Self {
+        for token in other.tokens {
+            if !self.tokens.contains(&token) {
+                self.tokens.push(token);
+            }
+        }
+        self.nullable |= other.nullable;
+        self
+    }
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.tokens.iter().any(|a| other.tokens.iter().any(|b| a.overlaps(b)))
+    }
+}
+
+pub fn first_set_of(element: &Match) -> FirstSet {
+    match element {
+        Match::IgnoreWhitespace => FirstSet::of(FirstToken::Whitespace).union(FirstSet::nullable()),
+        Match::Whitespace => FirstSet::of(FirstToken::Whitespace),
+        Match::Of(data) => data
+            .first()
+            .map_or_else(FirstSet::nullable, |first| FirstSet::of(FirstToken::Exact(first.clone()))),
+        Match::OfType(data) => data
+            .first()
+            .map_or_else(FirstSet::nullable, |first| FirstSet::of(FirstToken::Kind(first.clone()))),
+        Match::Bracket(opener, _) => FirstSet::of(FirstToken::Exact(TokenData::Bracket(*opener, None))),
+        Match::Optional(pat)
+        | Match::PossibleCommaSeparated(pat)
+        | Match::PossibleWhitespaceSeparated(pat)
+        | Match::SeparatedBy(_, pat, _) => first_set(pat).union(FirstSet::nullable()),
+        Match::Repeat { min, pat, .. } => {
+            let mut set = first_set(pat);
+            set.nullable |= *min == 0;
+            set
+        }
+        Match::Either(this, that) => first_set(this).union(first_set(that)),
+        Match::Glob => FirstSet::of(FirstToken::Anything).union(FirstSet::nullable()),
+        // Sizers are expected to consume at least one token
+        Match::GlobWithSizer(_) | Match::Any => FirstSet::of(FirstToken::Anything),
+        Match::Not(_) => FirstSet::nullable(),
+    }
+}
+
+pub fn first_set(pattern: &[Match]) -> FirstSet {
+    let mut set = FirstSet::nullable();
+    for element in pattern {
+        let element_set = first_set_of(element);
+        let nullable = element_set.nullable;
+        set = set.union(element_set);
+        if !nullable {
+            set.nullable = false;
+            return set;
+        }
+    }
+    set
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarIssueKind {
+    // The left branch matches everything the right one does
+    UnreachableEitherBranch,
+    // The optional content matches nothing, so the `None` case is never taken
+    UnskippableOptional,
+    // Followed by something that can match nothing, so the glob never grows
+    UnanchoredGlob,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrammarIssue {
+    pub kind: GrammarIssueKind,
+    // Where in the pattern, such as `[4].Optional[1]`
+    pub path: String,
+}
+impl fmt::Display for GrammarIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self.kind {
+            GrammarIssueKind::UnreachableEitherBranch => "right branch of Either is unreachable",
+            GrammarIssueKind::UnskippableOptional => "Optional can never be skipped",
+            GrammarIssueKind::UnanchoredGlob => "Glob has no terminating anchor",
+        };
+        write!(f, "{}: {}", self.path, description)
+    }
+}
+
+pub fn analyze_pattern(pattern: &[Match]) -> Vec<GrammarIssue> {
+    let mut issues = Vec::new();
+    analyze_sequence(pattern, &[], "", &mut issues);
+    issues
+}
+
+// `follow` is what the matcher chains after `pattern`, innermost first.
+// `Optional` and `Either` chain the rest of their sequence, everything else matches on its own.
+fn analyze_sequence(pattern: &[Match], follow: &[&[Match]], path: &str, issues: &mut Vec<GrammarIssue>) {
+    for (i, element) in pattern.iter().enumerate() {
+        let here = format!("{}[{}]", path, i);
+        let rest = &pattern[i + 1..];
+        let mut chained = vec![rest];
+        chained.extend_from_slice(follow);
+
+        let mut report = |kind| issues.push(GrammarIssue { kind, path: here.clone() });
+        match element {
+            Match::Glob => {
+                let continuation: Vec<Match> = chained.iter().flat_map(|s| s.iter().cloned()).collect();
+                if !continuation.is_empty() && first_set(&continuation).nullable {
+                    report(GrammarIssueKind::UnanchoredGlob);
+                }
+            }
+            Match::Optional(pat) => {
+                if first_set(pat).nullable {
+                    report(GrammarIssueKind::UnskippableOptional);
+                }
+                analyze_sequence(pat, &chained, &format!("{}.Optional", here), issues);
+            }
+            Match::Either(this, that) => {
+                let (this_first, that_first) = (first_set(this), first_set(that));
+                let may_collide = this_first.nullable || that_first.nullable || this_first.overlaps(&that_first);
+                if may_collide && seq_subsumes(this, that) {
+                    report(GrammarIssueKind::UnreachableEitherBranch);
+                }
+                analyze_sequence(this, &chained, &format!("{}.Left", here), issues);
+                analyze_sequence(that, &chained, &format!("{}.Right", here), issues);
+            }
+            Match::Bracket(_, pat) => analyze_sequence(pat, &[], &format!("{}.Bracket", here), issues),
+            Match::PossibleCommaSeparated(pat)
+            | Match::PossibleWhitespaceSeparated(pat)
+            | Match::Repeat { pat, .. }
+            | Match::Not(pat) => analyze_sequence(pat, &[], &format!("{}.Inner", here), issues),
+            Match::SeparatedBy(sep, pat, _) => {
+                analyze_sequence(sep, &[], &format!("{}.Separator", here), issues);
+                analyze_sequence(pat, &[], &format!("{}.Inner", here), issues);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn skip_ignored<'a, 'b>(mut pattern: &'b [Match<'a>]) -> &'b [Match<'a>] {
+    while let Some(Match::IgnoreWhitespace) = pattern.first() {
+        pattern = &pattern[1..];
+    }
+    pattern
+}
+
+fn concat<'a>(head: &[Match<'a>], tail: &[Match<'a>]) -> Vec<Match<'a>> {
+    head.iter().chain(tail.iter()).cloned().collect()
+}
+
+// Conservatively decides if every token sequence accepted by `that` is also accepted by `this`
+fn seq_subsumes(this: &[Match], that: &[Match]) -> bool {
+    let (this, that) = (skip_ignored(this), skip_ignored(that));
+
+    match (this.first(), that.first()) {
+        (None, _) => that.is_empty(),
+        // A trailing glob accepts anything
+        (Some(Match::Glob), _) if this.len() == 1 => true,
+        (Some(Match::Glob), _) => (0..=that.len()).any(|skip| seq_subsumes(&this[1..], &that[skip..])),
+        (Some(Match::Optional(pat)), _) => {
+            seq_subsumes(&concat(pat, &this[1..]), that) || seq_subsumes(&this[1..], that)
+        }
+        (Some(Match::Either(a, b)), _) => {
+            seq_subsumes(&concat(a, &this[1..]), that) || seq_subsumes(&concat(b, &this[1..]), that)
+        }
+        (_, None) => first_set(this).nullable,
+        (_, Some(Match::Optional(pat))) => {
+            seq_subsumes(this, &concat(pat, &that[1..])) && seq_subsumes(this, &that[1..])
+        }
+        (_, Some(Match::Either(a, b))) => {
+            seq_subsumes(this, &concat(a, &that[1..])) && seq_subsumes(this, &concat(b, &that[1..]))
+        }
+        (Some(a), Some(b)) => element_subsumes(a, b) && seq_subsumes(&this[1..], &that[1..]),
+    }
+}
+
+fn is_single_token(element: &Match) -> bool {
+    match element {
+        Match::Of(data) | Match::OfType(data) => data.len() == 1,
+        Match::Bracket(..) | Match::Any => true,
+        _ => false,
+    }
+}
+
+fn element_subsumes(this: &Match, that: &Match) -> bool {
+    match (this, that) {
+        (Match::Any, _) => is_single_token(that),
+        (Match::Whitespace, Match::Whitespace) => true,
+        (Match::Of(a), Match::Of(b)) => a == b,
+        (Match::OfType(a), Match::Of(b) | Match::OfType(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| discriminant(a) == discriminant(b))
+        }
+        // A bare bracket token does not care about its contents
+        (Match::Of(a), Match::Bracket(opener, _)) => {
+            matches!(&a[..], [TokenData::Bracket(c, _)] if c == opener)
+        }
+        (Match::Bracket(a, this), Match::Bracket(b, that)) => a == b && seq_subsumes(this, that),
+        (Match::GlobWithSizer(a), Match::GlobWithSizer(b)) => std::ptr::fn_addr_eq(*a, *b),
+        (Match::PossibleCommaSeparated(a), Match::PossibleCommaSeparated(b))
+        | (Match::PossibleWhitespaceSeparated(a), Match::PossibleWhitespaceSeparated(b)) => seq_subsumes(a, b),
+        (
+            Match::Repeat { min: a_min, max: a_max, pat: a },
+            Match::Repeat { min: b_min, max: b_max, pat: b },
+        ) => {
+            let max_covers = match (a_max, b_max) {
+                (None, _) => true,
+                (Some(a), Some(b)) => a >= b,
+                (Some(_), None) => false,
+            };
+            a_min <= b_min && max_covers && seq_subsumes(a, b)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::parsing::pattern_constants::ALL_PATTERNS;
+
+    fn kinds(pattern: &[Match]) -> Vec<GrammarIssueKind> {
+        analyze_pattern(pattern).into_iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn pattern_constants_are_clean() {
+        for (name, pattern) in ALL_PATTERNS {
+            let issues = analyze_pattern(pattern);
+            assert!(
+                issues.is_empty(),
+                "{} has grammar issues:\n{}",
+                name,
+                issues.iter().map(|issue| issue.to_string()).collect::<Vec<_>>().join("\n")
+            );
+        }
+    }
+
+    #[test]
+    fn first_sets() {
+        let set = first_set(gurn_grammar!(Comptime? ~ If));
+        assert!(!set.nullable);
+        assert_eq!(set.tokens.len(), 3);
+        assert!(set.overlaps(&first_set(gurn_grammar!(<keyword>))));
+        assert!(!set.overlaps(&first_set(gurn_grammar!(";"))));
+        assert!(first_set(gurn_grammar!(~ <keyword>* (";")?)).nullable);
+    }
+
+    #[test]
+    fn detects_unreachable_either() {
+        use GrammarIssueKind::*;
+        assert_eq!(kinds(gurn_grammar!((<keyword> | If))), vec![UnreachableEitherBranch]);
+        assert_eq!(kinds(gurn_grammar!((.. | ";") ";")), vec![UnreachableEitherBranch]);
+        assert_eq!(kinds(gurn_grammar!((If ~ Var? | If) ";")), vec![UnreachableEitherBranch]);
+        assert_eq!(kinds(gurn_grammar!((If | <keyword>))), vec![]);
+        assert_eq!(kinds(gurn_grammar!((If | If ~ Var))), vec![]);
+    }
+
+    #[test]
+    fn detects_unskippable_optional() {
+        use GrammarIssueKind::*;
+        assert_eq!(kinds(gurn_grammar!(If (~)? ";")), vec![UnskippableOptional]);
+        assert_eq!(kinds(gurn_grammar!(If (<ident>*)? ";")), vec![UnskippableOptional]);
+        assert_eq!(kinds(gurn_grammar!(If (<ident>+)? ";")), vec![]);
+    }
+
+    #[test]
+    fn detects_unanchored_glob() {
+        use GrammarIssueKind::*;
+        assert_eq!(kinds(gurn_grammar!(If .. ~)), vec![UnanchoredGlob]);
+        assert_eq!(kinds(gurn_grammar!(If (Var .. ~)? <keyword>*)), vec![UnanchoredGlob]);
+        // Trailing globs consume everything, and the anchor may come after an Optional
+        assert_eq!(kinds(gurn_grammar!(If ..)), vec![]);
+        assert_eq!(kinds(gurn_grammar!(If (Var ..)? ~ ";")), vec![]);
+        assert_eq!(kinds(gurn_grammar!(brace{..} ";")), vec![]);
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt::Write;
+
+use super::{
+    pattern_matcher::Match,
+    tokenizer::{Token, TokenData},
+    type_parser::type_size_function,
+};
+
+type Sizer = fn(&[Token]) -> Option<&[Token]>;
+
+// Sizers get rendered as nonterminals under these names
+pub const NAMED_SIZERS: &[(&str, Sizer)] = &[("type_size_function", type_size_function)];
+
+// A renderer independent view of a pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarNode {
+    Terminal(String),
+    TokenClass(&'static str),
+    NonTerminal(String),
+    Sequence(Vec<GrammarNode>),
+    Choice(Vec<GrammarNode>),
+    Optional(Box<GrammarNode>),
+    Repeat {
+        node: Box<GrammarNode>,
+        separator: Option<Box<GrammarNode>>,
+        min: usize,
+        max: Option<usize>,
+    },
+    NotFollowedBy(Box<GrammarNode>),
+}
+
+fn sizer_name(sizer: Sizer) -> String {
+    NAMED_SIZERS
+        .iter()
+        .find(|named| std::ptr::fn_addr_eq(named.1, sizer))
+        .map_or_else(|| "sizer".to_string(), |named| named.0.to_string())
+}
+
+fn closing_bracket(opener: u8) -> char {
+    match opener {
+        b'(' => ')',
+        b'{' => '}',
+        b'[' => ']',
+        _ => '?',
+    }
+}
+
+fn token_class(data: &TokenData) -> &'static str {
+    match data {
+        TokenData::Keyword(_) => "KEYWORD",
+        TokenData::TextCluster(_) => "IDENT",
+        TokenData::NumberLiteral(_) => "NUMBER",
+        TokenData::StringLiteral(_) => "STRING",
+        TokenData::Whitespace(_) => "WHITESPACE",
+        TokenData::Operator(_) => "OPERATOR",
+        TokenData::Semicolon => "SEMICOLON",
+        TokenData::Colon => "COMMA",
+        TokenData::AtSign => "AT_SIGN",
+        TokenData::Dot => "DOT",
+        TokenData::ColonSign => "COLON",
+        TokenData::Label(_) => "LABEL",
+        TokenData::DocComment(_) => "DOC_COMMENT",
+        TokenData::Bracket(..) => "BRACKET",
+    }
+}
+
+fn any_tokens() -> GrammarNode {
+    GrammarNode::Repeat {
+        node: Box::new(GrammarNode::TokenClass("ANY")),
+        separator: None,
+        min: 0,
+        max: None,
+    }
+}
+
+fn terminal(data: &TokenData) -> GrammarNode {
+    let text = match data {
+        TokenData::Keyword(keyword) => keyword.text().to_string(),
+        TokenData::TextCluster(Some(text)) => text.clone(),
+        TokenData::NumberLiteral(number) => number.text_content.clone(),
+        TokenData::StringLiteral(string) => string.source(),
+        TokenData::Operator(operator) => operator.text().to_string(),
+        TokenData::Semicolon => ";".to_string(),
+        TokenData::Colon => ",".to_string(),
+        TokenData::AtSign => "@".to_string(),
+        TokenData::Dot => ".".to_string(),
+        TokenData::ColonSign => ":".to_string(),
+        TokenData::Label(name) => format!("'{}", name),
+        // Any bracketed block, the tokenizer already guarantees they are balanced
+        TokenData::Bracket(opener, _) => {
+            return GrammarNode::Sequence(vec![
+                GrammarNode::Terminal((*opener as char).to_string()),
+                any_tokens(),
+                GrammarNode::Terminal(closing_bracket(*opener).to_string()),
+            ])
+        }
+        TokenData::TextCluster(None) | TokenData::Whitespace(_) | TokenData::DocComment(_) => return GrammarNode::TokenClass(token_class(data)),
+    };
+    GrammarNode::Terminal(text)
+}
+
+fn sequence(mut nodes: Vec<GrammarNode>) -> GrammarNode {
+    if nodes.len() == 1 {
+        return nodes.pop().unwrap();
+    }
+    GrammarNode::Sequence(
+        nodes
+            .into_iter()
+            .flat_map(|node| match node {
+                GrammarNode::Sequence(inner) => inner,
+                other => vec![other],
+            })
+            .collect(),
+    )
+}
+
+fn separated(node: GrammarNode, separator: GrammarNode, trailing_allowed: bool) -> GrammarNode {
+    let mut items = vec![GrammarNode::Repeat {
+        node: Box::new(node),
+        separator: Some(Box::new(separator.clone())),
+        min: 1,
+        max: None,
+    }];
+    if trailing_allowed {
+        items.push(GrammarNode::Optional(Box::new(separator)));
+    }
+    GrammarNode::Optional(Box::new(sequence(items)))
+}
+
+pub fn pattern_to_node(pattern: &[Match]) -> GrammarNode {
+    sequence(pattern.iter().filter_map(element_to_node).collect())
+}
+
+pub(super) fn element_to_node(element: &Match) -> Option<GrammarNode> {
+    Some(match element {
+        // Whitespace is insignificant in the exported grammar, unless it is required
+        Match::IgnoreWhitespace => return None,
+        Match::Whitespace => GrammarNode::TokenClass("WHITESPACE"),
+        Match::Of(data) => sequence(data.iter().map(terminal).collect()),
+        Match::OfType(data) => sequence(data.iter().map(|d| GrammarNode::TokenClass(token_class(d))).collect()),
+        Match::Optional(pat) => GrammarNode::Optional(Box::new(pattern_to_node(pat))),
+        Match::PossibleCommaSeparated(pat) => separated(pattern_to_node(pat), GrammarNode::Terminal(",".to_string()), true),
+        Match::PossibleWhitespaceSeparated(pat) => GrammarNode::Repeat {
+            node: Box::new(pattern_to_node(pat)),
+            separator: None,
+            min: 0,
+            max: None,
+        },
+        Match::SeparatedBy(sep, pat, trailing_allowed) => {
+            separated(pattern_to_node(pat), pattern_to_node(sep), *trailing_allowed)
+        }
+        Match::Repeat { min, max, pat } => GrammarNode::Repeat {
+            node: Box::new(pattern_to_node(pat)),
+            separator: None,
+            min: *min,
+            max: *max,
+        },
+        Match::Bracket(opener, pat) => sequence(vec![
+            GrammarNode::Terminal((*opener as char).to_string()),
+            pattern_to_node(pat),
+            GrammarNode::Terminal(closing_bracket(*opener).to_string()),
+        ]),
+        Match::Either(this, that) => {
+            let mut choices = vec![pattern_to_node(this)];
+            match pattern_to_node(that) {
+                GrammarNode::Choice(more) => choices.extend(more),
+                other => choices.push(other),
+            }
+            GrammarNode::Choice(choices)
+        }
+        Match::Glob => any_tokens(),
+        Match::GlobWithSizer(sizer) => GrammarNode::NonTerminal(sizer_name(*sizer)),
+        Match::Not(pat) => GrammarNode::NotFollowedBy(Box::new(pattern_to_node(pat))),
+        Match::Any => GrammarNode::TokenClass("ANY"),
+    })
+}
+
+// ===== EBNF =====
+
+fn ebnf_atom(node: &GrammarNode) -> String {
+    match node {
+        GrammarNode::Sequence(items) if items.len() != 1 => format!("( {} )", node_to_ebnf(node)),
+        GrammarNode::Choice(_) => format!("( {} )", node_to_ebnf(node)),
+        // Only `x*` and `x+` render as a single atom
+        GrammarNode::Repeat { separator: Some(_), .. } | GrammarNode::Repeat { max: Some(_), .. } => {
+            format!("( {} )", node_to_ebnf(node))
+        }
+        GrammarNode::Repeat { min, .. } if *min > 1 => format!("( {} )", node_to_ebnf(node)),
+        _ => node_to_ebnf(node),
+    }
+}
+
+pub fn node_to_ebnf(node: &GrammarNode) -> String {
+    match node {
+        GrammarNode::Terminal(text) if text.contains('"') => format!("'{}'", text),
+        GrammarNode::Terminal(text) => format!("\"{}\"", text),
+        GrammarNode::TokenClass(class) => class.to_string(),
+        GrammarNode::NonTerminal(name) => name.clone(),
+        GrammarNode::Sequence(items) => items.iter().map(ebnf_atom).collect::<Vec<_>>().join(" "),
+        GrammarNode::Choice(items) => items.iter().map(node_to_ebnf).collect::<Vec<_>>().join(" | "),
+        GrammarNode::Optional(inner) => format!("{}?", ebnf_atom(inner)),
+        GrammarNode::NotFollowedBy(inner) => format!("!{}", ebnf_atom(inner)),
+        GrammarNode::Repeat { node, separator, min, max } => {
+            let atom = ebnf_atom(node);
+            let mut parts = vec![atom.clone(); *min];
+            let next = match separator {
+                Some(separator) => format!("( {} {} )", ebnf_atom(separator), atom),
+                None => atom,
+            };
+            match max {
+                None if *min == 0 => parts.push(format!("{}*", next)),
+                None => {
+                    // `x x*` reads better as `x+`
+                    if separator.is_none() && *min == 1 {
+                        parts.pop();
+                        parts.push(format!("{}+", next));
+                    } else {
+                        parts.push(format!("{}*", next));
+                    }
+                }
+                Some(max) => parts.extend(vec![format!("{}?", next); max.saturating_sub(*min)]),
+            }
+            parts.join(" ")
+        }
+    }
+}
+
+// W3C style EBNF, one `NAME ::= ...` rule per pattern
+pub fn patterns_to_ebnf(patterns: &[(&str, &[Match])]) -> String {
+    let mut out = String::new();
+    out.push_str("/* Generated from pattern_constants.rs, do not edit by hand.\n");
+    out.push_str(" * Whitespace is insignificant unless WHITESPACE is written out,\n");
+    out.push_str(" * upper case names are token classes and ANY is any single token. */\n\n");
+    for (name, pattern) in patterns {
+        writeln!(out, "{} ::= {}", name, node_to_ebnf(&pattern_to_node(pattern))).unwrap();
+    }
+    out
+}
+
+// ===== Railroad diagrams =====
+
+const ARC: i32 = 10;
+const GAP: i32 = 10;
+const BOX_HALF_HEIGHT: i32 = 11;
+const CHAR_WIDTH: i32 = 8;
+
+// A laid out piece of diagram, entered at (0, 0) and left at (width, 0)
+struct Block {
+    width: i32,
+    up: i32,
+    down: i32,
+    svg: String,
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn translated(block: &Block, x: i32, y: i32) -> String {
+    format!("<g transform=\"translate({} {})\">{}</g>", x, y, block.svg)
+}
+
+fn text_box(text: &str, class: &str, radius: i32) -> Block {
+    let width = text.chars().count() as i32 * CHAR_WIDTH + 2 * GAP;
+    Block {
+        width,
+        up: BOX_HALF_HEIGHT,
+        down: BOX_HALF_HEIGHT,
+        svg: format!(
+            "<rect class=\"{}\" x=\"0\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/><text x=\"{}\" y=\"4\">{}</text>",
+            class,
+            -BOX_HALF_HEIGHT,
+            width,
+            2 * BOX_HALF_HEIGHT,
+            radius,
+            width / 2,
+            escape_xml(text)
+        ),
+    }
+}
+
+fn empty_block() -> Block {
+    Block { width: 0, up: 0, down: 0, svg: String::new() }
+}
+
+fn layout_sequence(items: &[GrammarNode]) -> Block {
+    let mut block = Block { width: 0, up: 0, down: 0, svg: String::new() };
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            write!(block.svg, "<path d=\"M{} 0h{}\"/>", block.width, GAP).unwrap();
+            block.width += GAP;
+        }
+        let inner = layout(item);
+        block.svg.push_str(&translated(&inner, block.width, 0));
+        block.width += inner.width;
+        block.up = block.up.max(inner.up);
+        block.down = block.down.max(inner.down);
+    }
+    block
+}
+
+fn layout_choice(items: Vec<Block>) -> Block {
+    let inner_width = items.iter().map(|item| item.width).max().unwrap_or(0);
+    let width = inner_width + 4 * ARC;
+    let mut svg = String::new();
+    let mut y = 0;
+    let mut down = 0;
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            y = (y + down + GAP + item.up).max(y + 2 * ARC);
+            write!(
+                svg,
+                "<path d=\"M0 0q{a} 0 {a} {a}V{}q0 {a} {a} {a}\"/><path d=\"M{} {}q{a} 0 {a} {n}V{a}q0 {n} {a} {n}\"/>",
+                y - ARC,
+                2 * ARC + inner_width,
+                y,
+                a = ARC,
+                n = -ARC
+            )
+            .unwrap();
+        } else {
+            write!(svg, "<path d=\"M0 0h{}\"/>", 2 * ARC).unwrap();
+            write!(svg, "<path d=\"M{} 0h{}\"/>", 2 * ARC + inner_width, 2 * ARC).unwrap();
+        }
+        svg.push_str(&translated(item, 2 * ARC, y));
+        write!(svg, "<path d=\"M{} {}h{}\"/>", 2 * ARC + item.width, y, inner_width - item.width).unwrap();
+        down = item.down;
+    }
+    Block { width, up: items.first().map_or(0, |item| item.up), down: y + down, svg }
+}
+
+fn layout_repeat(item: Block, separator: Option<Block>, label: Option<String>) -> Block {
+    let separator = separator.unwrap_or_else(empty_block);
+    let inner_width = item.width.max(separator.width);
+    let width = inner_width + 2 * ARC;
+    let back = (item.down + GAP + separator.up).max(2 * ARC);
+
+    let mut svg = String::new();
+    write!(svg, "<path d=\"M0 0h{}\"/>", ARC).unwrap();
+    svg.push_str(&translated(&item, ARC, 0));
+    write!(svg, "<path d=\"M{} 0h{}\"/>", ARC + item.width, width - ARC - item.width).unwrap();
+    // The path back, right to left
+    write!(
+        svg,
+        "<path d=\"M{} 0q{a} 0 {a} {a}V{}q0 {a} {n} {a}H{a}q{n} 0 {n} {n}V{a}q0 {n} {a} {n}\"/>",
+        ARC + inner_width,
+        back - ARC,
+        a = ARC,
+        n = -ARC
+    )
+    .unwrap();
+    let separator_x = ARC + (inner_width - separator.width) / 2;
+    write!(svg, "<rect class=\"gap\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"2\"/>", separator_x, back - 1, separator.width).unwrap();
+    svg.push_str(&translated(&separator, separator_x, back));
+
+    let mut down = back + separator.down;
+    if let Some(label) = label {
+        down += 2 * GAP;
+        write!(svg, "<text class=\"label\" x=\"{}\" y=\"{}\">{}</text>", width / 2, down - 4, escape_xml(&label)).unwrap();
+    }
+    Block { width, up: item.up, down, svg }
+}
+
+fn layout(node: &GrammarNode) -> Block {
+    match node {
+        GrammarNode::Terminal(text) => text_box(text, "terminal", ARC),
+        GrammarNode::TokenClass(class) => text_box(class, "token-class", 0),
+        GrammarNode::NonTerminal(name) => text_box(name, "nonterminal", 0),
+        GrammarNode::Sequence(items) => layout_sequence(items),
+        GrammarNode::Choice(items) => layout_choice(items.iter().map(layout).collect()),
+        GrammarNode::Optional(inner) => layout_choice(vec![empty_block(), layout(inner)]),
+        GrammarNode::Repeat { node, separator, min, max } => {
+            let label = match (min, max) {
+                (0 | 1, None) => None,
+                (min, None) => Some(format!("at least {}", min)),
+                (min, Some(max)) => Some(format!("{} to {} times", min, max)),
+            };
+            let repeat = layout_repeat(layout(node), separator.as_deref().map(layout), label);
+            if *min == 0 {
+                layout_choice(vec![empty_block(), repeat])
+            } else {
+                repeat
+            }
+        }
+        GrammarNode::NotFollowedBy(inner) => {
+            let inner = layout(inner);
+            let pad = GAP / 2;
+            let width = inner.width + 2 * pad;
+            let up = inner.up + pad + 2 * GAP;
+            let svg = format!(
+                "<rect class=\"lookahead\" x=\"0\" y=\"{}\" width=\"{}\" height=\"{}\"/><text class=\"label\" x=\"{}\" y=\"{}\">not followed by</text>{}",
+                -up,
+                width,
+                up + inner.down + pad,
+                width / 2,
+                -inner.up - pad - 4,
+                translated(&inner, pad, 0)
+            );
+            Block { width, up, down: inner.down + pad, svg }
+        }
+    }
+}
+
+const SVG_STYLE: &str = "path{fill:none;stroke:#222;stroke-width:2}\
+rect{fill:#fff;stroke:#222;stroke-width:2}\
+rect.terminal{fill:#e8f4e8}rect.token-class{fill:#e8ecf8}rect.nonterminal{fill:#fdf3dc}\
+rect.lookahead{fill:none;stroke-dasharray:4 3}rect.gap{fill:#fff;stroke:none}\
+text{font:14px monospace;text-anchor:middle}text.label{font-size:11px}text.title{text-anchor:start;font-weight:bold}";
+
+pub fn pattern_to_railroad_svg(name: &str, pattern: &[Match]) -> String {
+    let diagram = layout(&pattern_to_node(pattern));
+    let margin = 2 * GAP;
+    let title_height = 2 * GAP;
+    let width = diagram.width + 2 * margin + 2 * GAP;
+    let height = title_height + diagram.up + diagram.down + 2 * margin;
+    let baseline = title_height + margin + diagram.up;
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        width, height, width, height
+    )
+    .unwrap();
+    write!(svg, "<style>{}</style>", SVG_STYLE).unwrap();
+    write!(svg, "<text class=\"title\" x=\"{}\" y=\"{}\">{}</text>", GAP, title_height, escape_xml(name)).unwrap();
+    write!(svg, "<g transform=\"translate({} {})\">", margin, baseline).unwrap();
+    // Start and end markers
+    write!(svg, "<path d=\"M0 -8v16M0 0h{}\"/>", GAP).unwrap();
+    svg.push_str(&translated(&diagram, GAP, 0));
+    write!(svg, "<path d=\"M{} 0h{}m0 -8v16\"/>", GAP + diagram.width, GAP).unwrap();
+    svg.push_str("</g></svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::parsing::pattern_constants::*;
+
+    #[test]
+    fn ebnf_of_constants() {
+        assert_eq!(
+            node_to_ebnf(&pattern_to_node(DO_WHILE_LOOP)),
+            "\"do\" \"{\" ANY* \"}\" \"while\" \"(\" ANY* \")\" \";\""
+        );
+        assert_eq!(
+            node_to_ebnf(&pattern_to_node(IF_STATEMENT)),
+            "\"comptime\"? \"if\" \"(\" ANY* \")\" ( ANY* \";\" | \"{\" ANY* \"}\" )"
+        );
+
+        let ebnf = patterns_to_ebnf(ALL_PATTERNS);
+        assert!(ebnf.contains("FUNCTION_DECLARATION ::= ( \"@\" ( IDENT | KEYWORD ) ( \"(\" ANY* \")\" )? )* ( !\"fn\" KEYWORD )* type_size_function IDENT"));
+        assert!(ebnf.contains(
+            "\"(\" ( ( ( ( !\"fn\" KEYWORD )* type_size_function WHITESPACE IDENT ) ( \",\" ( ( !\"fn\" KEYWORD )* type_size_function WHITESPACE IDENT ) )* ) \",\"? )? \")\""
+        ));
+        assert_eq!(ebnf.lines().filter(|line| line.contains("::=")).count(), ALL_PATTERNS.len());
+    }
+
+    #[test]
+    fn ebnf_of_repetition() {
+        assert_eq!(node_to_ebnf(&pattern_to_node(gurn_grammar!(<ident>{2,4}))), "IDENT IDENT IDENT? IDENT?");
+        assert_eq!(node_to_ebnf(&pattern_to_node(gurn_grammar!(<ident>+ !";"))), "IDENT+ !\";\"");
+        assert_eq!(node_to_ebnf(&pattern_to_node(gurn_grammar!(<ident> % "|"))), "( IDENT ( \"|\" IDENT )* )?");
+    }
+
+    #[test]
+    fn railroad_svg() {
+        for (name, pattern) in ALL_PATTERNS {
+            let svg = pattern_to_railroad_svg(name, pattern);
+            assert!(svg.starts_with("<svg"));
+            assert!(svg.trim_end().ends_with("</svg>"));
+            assert!(svg.contains(name));
+        }
+        let svg = pattern_to_railroad_svg("FUNCTION_DECLARATION", FUNCTION_DECLARATION);
+        assert!(svg.contains(">type_size_function</text>"));
+        assert!(svg.contains(">=&gt;</text>"));
+    }
+}
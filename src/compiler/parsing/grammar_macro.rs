@@ -0,0 +1,304 @@
+// An EBNF-like front end for writing `&[Match]` patterns.
+//
+// Terms:
+//   If, Where, Comptime, ...   A keyword, by its `Keyword` variant name
+//   ";"  ","  "@"  "."         Semicolon, comma (`TokenData::Colon`), at sign and dot
+//   ":"                        An actual colon, `TokenData::ColonSign`
+//   "("  "{"  "["              A bracket token, without looking inside of it
+//   "=>" "<" "*" ...           An operator, by its source text
+//   ~                          IgnoreWhitespace
+//   ..                         Glob
+//   <ident> <keyword>          Captures any text cluster / any keyword
+//   <label>                    Any loop label, `'name`
+//   <ws>                       Required whitespace
+//   <any>                      Any single token
+//   <type>                     A type, via `type_parser::type_size_function`
+//   <some::sizer>              GlobWithSizer(some::sizer)
+//   paren(..) brace{..} square[..]
+//                              Bracket groups, the inner grammar must consume everything
+//   !term  !( ... )            Negative lookahead
+//
+// Postfix operators on a term or a `( ... )` group:
+//   ?          Optional
+//   *          PossibleWhitespaceSeparated
+//   ,*         PossibleCommaSeparated
+//   +          Repeat, one or more
+//   {2,4} {2,} {3}
+//              Repeat, with bounds
+//   % sep      SeparatedBy, without a trailing separator
+//   %% sep     SeparatedBy, allowing a trailing separator
+//
+// Alternatives are written `( a b | c d )` and become nested `Either`s,
+// keeping the left most alternative as the left branch. A group without
+// any `|` must carry a postfix operator.
+//
+// Example:
+//   gurn_grammar!(Comptime? ~ If ~ paren(..) ~ (.. ";" | brace{..}))
+macro_rules! gurn_grammar {
+    // Sequences
+    (@seq [$($acc:tt)*]) => { &[$($acc)*] };
+
+    (@seq [$($acc:tt)*] < ident > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::OfType(&[
+            $crate::compiler::parsing::tokenizer::TokenData::TextCluster(None)
+        ])] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] < keyword > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::OfType(&[
+            $crate::compiler::parsing::tokenizer::TokenData::Keyword($crate::compiler::keywords::Keyword::DUMMY)
+        ])] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] < label > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::OfType(&[
+            $crate::compiler::parsing::tokenizer::TokenData::Label(String::new())
+        ])] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] < ws > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Whitespace] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] < any > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Any] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] < type > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::GlobWithSizer(
+            $crate::compiler::parsing::type_parser::type_size_function
+        )] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] < $sizer:path > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::GlobWithSizer($sizer)] $($rest)*)
+    };
+
+    (@seq [$($acc:tt)*] paren ( $($inner:tt)* ) $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Bracket(
+            b'(', gurn_grammar!(@alt slice [] [] $($inner)*)
+        )] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] brace { $($inner:tt)* } $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Bracket(
+            b'{', gurn_grammar!(@alt slice [] [] $($inner)*)
+        )] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] square [ $($inner:tt)* ] $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Bracket(
+            b'[', gurn_grammar!(@alt slice [] [] $($inner)*)
+        )] $($rest)*)
+    };
+
+    // Negative lookahead
+    (@seq [$($acc:tt)*] ! ( $($inner:tt)* ) $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Not(
+            gurn_grammar!(@alt slice [] [] $($inner)*)
+        )] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] ! < $capture:tt > $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Not(
+            gurn_grammar!(@seq [] < $capture >)
+        )] $($rest)*)
+    };
+    (@seq [$($acc:tt)*] ! $term:tt $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [$crate::compiler::parsing::Match::Not(
+            &[gurn_grammar!(@term $term)]
+        )] $($rest)*)
+    };
+
+    // Groups
+    (@seq [$($acc:tt)*] ( $($inner:tt)* ) $($rest:tt)*) => {
+        gurn_grammar!(@post [$($acc)*]
+            [gurn_grammar!(@alt slice [] [] $($inner)*)]
+            (gurn_grammar!(@alt either [] [] $($inner)*))
+            $($rest)*
+        )
+    };
+
+    (@seq [$($acc:tt)*] $term:tt $($rest:tt)*) => {
+        gurn_grammar!(@term_post [$($acc)*] [gurn_grammar!(@term $term)] $($rest)*)
+    };
+
+    // Postfix operators, `@post [acc] [pattern slice] (used without a postfix) rest`
+    (@term_post [$($acc:tt)*] [$($term:tt)*] $($rest:tt)*) => {
+        gurn_grammar!(@post [$($acc)*] [&[$($term)*]] ($($term)*) $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) ? $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::Optional($($pat)*),] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) , * $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::PossibleCommaSeparated($($pat)*),] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) * $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::PossibleWhitespaceSeparated($($pat)*),] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) + $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::Repeat {
+            min: 1, max: None, pat: $($pat)*
+        },] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) { $min:literal , $max:literal } $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::Repeat {
+            min: $min, max: Some($max), pat: $($pat)*
+        },] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) { $min:literal , } $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::Repeat {
+            min: $min, max: None, pat: $($pat)*
+        },] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) { $count:literal } $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::Repeat {
+            min: $count, max: Some($count), pat: $($pat)*
+        },] $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) % % $($rest:tt)*) => {
+        gurn_grammar!(@sep [$($acc)*] [$($pat)*] true $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) % $($rest:tt)*) => {
+        gurn_grammar!(@sep [$($acc)*] [$($pat)*] false $($rest)*)
+    };
+    (@post [$($acc:tt)*] [$($pat:tt)*] ($($bare:tt)*) $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $($bare)*,] $($rest)*)
+    };
+
+    // The separator of `%` is a single term or a `( ... )` group
+    (@sep [$($acc:tt)*] [$($pat:tt)*] $trailing:literal ( $($sep:tt)* ) $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::SeparatedBy(
+            gurn_grammar!(@alt slice [] [] $($sep)*), $($pat)*, $trailing
+        ),] $($rest)*)
+    };
+    (@sep [$($acc:tt)*] [$($pat:tt)*] $trailing:literal $sep:tt $($rest:tt)*) => {
+        gurn_grammar!(@seq [$($acc)* $crate::compiler::parsing::Match::SeparatedBy(
+            &[gurn_grammar!(@term $sep)], $($pat)*, $trailing
+        ),] $($rest)*)
+    };
+
+    // Alternatives, `slice` mode gives a `&[Match]`, `either` mode a single `Match::Either`
+    (@alt $mode:ident [$($cur:tt)*] [$($alts:tt)*]) => {
+        gurn_grammar!(@build $mode $($alts)* [$($cur)*])
+    };
+    (@alt $mode:ident [$($cur:tt)*] [$($alts:tt)*] | $($rest:tt)*) => {
+        gurn_grammar!(@alt $mode [] [$($alts)* [$($cur)*]] $($rest)*)
+    };
+    (@alt $mode:ident [$($cur:tt)*] [$($alts:tt)*] $next:tt $($rest:tt)*) => {
+        gurn_grammar!(@alt $mode [$($cur)* $next] [$($alts)*] $($rest)*)
+    };
+
+    (@build slice [$($only:tt)*]) => { gurn_grammar!(@seq [] $($only)*) };
+    (@build slice $($alts:tt)+) => { &[gurn_grammar!(@build either $($alts)+)] };
+    (@build either [$($only:tt)*]) => {
+        compile_error!("A grammar group needs a `|`, `?`, `*` or `,*`")
+    };
+    (@build either [$($left:tt)*] $($right:tt)+) => {
+        $crate::compiler::parsing::Match::Either(
+            gurn_grammar!(@seq [] $($left)*),
+            gurn_grammar!(@build slice $($right)+),
+        )
+    };
+
+    // Single terms
+    (@term ~) => { $crate::compiler::parsing::Match::IgnoreWhitespace };
+    (@term ..) => { $crate::compiler::parsing::Match::Glob };
+    (@term ";") => { gurn_grammar!(@of Semicolon) };
+    (@term ",") => { gurn_grammar!(@of Colon) };
+    (@term "@") => { gurn_grammar!(@of AtSign) };
+    (@term ".") => { gurn_grammar!(@of Dot) };
+    (@term ":") => { gurn_grammar!(@of ColonSign) };
+    (@term "(") => { gurn_grammar!(@of Bracket(b'(', None)) };
+    (@term "{") => { gurn_grammar!(@of Bracket(b'{', None)) };
+    (@term "[") => { gurn_grammar!(@of Bracket(b'[', None)) };
+
+    (@term "=>") => { gurn_grammar!(@op EqualsArrow) };
+    (@term "->") => { gurn_grammar!(@op LineArrow) };
+    (@term "=") => { gurn_grammar!(@op Assign) };
+    (@term "+") => { gurn_grammar!(@op Add) };
+    (@term "+=") => { gurn_grammar!(@op AddEq) };
+    (@term "-") => { gurn_grammar!(@op Sub) };
+    (@term "-=") => { gurn_grammar!(@op SubEq) };
+    (@term "*") => { gurn_grammar!(@op Mult) };
+    (@term "*=") => { gurn_grammar!(@op MultEq) };
+    (@term "/") => { gurn_grammar!(@op Div) };
+    (@term "/=") => { gurn_grammar!(@op DivEq) };
+    (@term "%") => { gurn_grammar!(@op Mod) };
+    (@term "%=") => { gurn_grammar!(@op ModEq) };
+    (@term "&") => { gurn_grammar!(@op BitwiseAnd) };
+    (@term "&=") => { gurn_grammar!(@op BitwiseAndEq) };
+    (@term "^") => { gurn_grammar!(@op Xor) };
+    (@term "^=") => { gurn_grammar!(@op XorEq) };
+    (@term "|") => { gurn_grammar!(@op BitwiseOr) };
+    (@term "|=") => { gurn_grammar!(@op BitwiseOrEq) };
+    (@term ">>") => { gurn_grammar!(@op BitwiseShiftRight) };
+    (@term ">>=") => { gurn_grammar!(@op BitwiseShiftRightEq) };
+    (@term "<<") => { gurn_grammar!(@op BitwiseShiftLeft) };
+    (@term "<<=") => { gurn_grammar!(@op BitwiseShiftLeftEq) };
+    (@term ">>>") => { gurn_grammar!(@op BitwiseUnsignedShiftRight) };
+    (@term ">>>=") => { gurn_grammar!(@op BitwiseUnsignedShiftRightEq) };
+    (@term "<<<") => { gurn_grammar!(@op BitwiseUnsignedShiftLeft) };
+    (@term "<<<=") => { gurn_grammar!(@op BitwiseUnsignedShiftLeftEq) };
+    (@term "&&") => { gurn_grammar!(@op LogicalAnd) };
+    (@term "&&=") => { gurn_grammar!(@op LogicalAndEq) };
+    (@term "||") => { gurn_grammar!(@op LogicalOr) };
+    (@term "||=") => { gurn_grammar!(@op LogicalOrEq) };
+    (@term "==") => { gurn_grammar!(@op EqualityCheck) };
+    (@term "!=") => { gurn_grammar!(@op NotEqualityCheck) };
+    (@term ">") => { gurn_grammar!(@op GreaterThan) };
+    (@term ">=") => { gurn_grammar!(@op GreaterThanEq) };
+    (@term "<") => { gurn_grammar!(@op LesserThan) };
+    (@term "<=") => { gurn_grammar!(@op LesserThanEq) };
+    (@term "|>") => { gurn_grammar!(@op ConversionPipe) };
+    (@term "~") => { gurn_grammar!(@op Not) };
+    (@term "?") => { gurn_grammar!(@op OptionalOperator) };
+    (@term "!") => { gurn_grammar!(@op ErrorOperator) };
+
+    (@term $keyword:ident) => {
+        gurn_grammar!(@of Keyword($crate::compiler::keywords::Keyword::$keyword))
+    };
+    (@term $other:tt) => {
+        compile_error!(concat!("Unknown grammar term: ", stringify!($other)))
+    };
+
+    (@op $operator:ident) => {
+        gurn_grammar!(@of Operator($crate::compiler::operators::Operator::$operator))
+    };
+    (@of $($data:tt)*) => {
+        $crate::compiler::parsing::Match::Of(&[$crate::compiler::parsing::tokenizer::TokenData::$($data)*])
+    };
+
+    // Entry point, the whole grammar may itself be a list of alternatives
+    ($($grammar:tt)*) => { gurn_grammar!(@alt slice [] [] $($grammar)*) };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::parsing::pattern_constants::*;
+    use crate::compiler::parsing::tokenizer::tokenize_text;
+    use crate::compiler::parsing::*;
+
+    fn matches_fully(pattern: &[Match], text: &str) -> bool {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        matches!(test_tokens_against(pattern, &tokens), Some((rest, _)) if rest.is_empty())
+    }
+
+    #[test]
+    fn grammar_postfix_and_alternatives() {
+        const PATTERN: &[Match] = gurn_grammar!(Comptime? ~ Var ~ (<ident> | <keyword>) ~ ";");
+        assert!(matches_fully(PATTERN, "var foo;"));
+        assert!(matches_fully(PATTERN, "comptime var where ;"));
+        assert!(!matches_fully(PATTERN, "var 5;"));
+
+        const LIST: &[Match] = gurn_grammar!(paren((~ <ident> ~),* ~));
+        assert!(matches_fully(LIST, "(a, b, c)"));
+        assert!(matches_fully(LIST, "( a, b, )"));
+        assert!(matches_fully(LIST, "()"));
+    }
+
+    #[test]
+    fn grammar_rewritten_constants() {
+        assert!(matches_fully(IF_STATEMENT, "if (a == b) { c; }"));
+        assert!(matches_fully(IF_STATEMENT, "comptime if (a) b;"));
+        assert!(matches_fully(FOR_LOOP, "for (i; i < 5; i += 1) {}"));
+        assert!(!matches_fully(FOR_LOOP, "for (i; i < 5) {}"));
+        assert!(matches_fully(DO_WHILE_LOOP, "do{ a; }while (b);"));
+        assert!(matches_fully(FUNCTION_DECLARATION, "Type get_half_word() => halfWordSize;"));
+        assert!(matches_fully(
+            FUNCTION_DECLARATION,
+            "@cold pub u8* foo<T>(const T a, u8 b) where T { }"
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt::Write;
+
+use super::{
+    grammar_export::{element_to_node, node_to_ebnf},
+    pattern_matcher::Match,
+    tokenizer::Token,
+};
+
+const MAX_DESCRIPTION: usize = 48;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceOutcome {
+    // Where matching continues afterwards, `None` at the end of the input
+    Matched { end: Option<usize> },
+    Failed,
+}
+
+// A single combinator attempt
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceNode {
+    pub combinator: &'static str,
+    pub description: String,
+    // Source index of the first token it was tried on, `None` at the end of the input
+    pub position: Option<usize>,
+    pub outcome: TraceOutcome,
+    pub children: Vec<TraceEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEntry {
+    Attempt(TraceNode),
+    Backtrack { reason: &'static str, position: Option<usize> },
+}
+
+// Opt-in record of everything the matcher tried, see `test_tokens_against_traced`
+#[derive(Debug, Default)]
+pub struct MatchTrace {
+    entries: Vec<TraceEntry>,
+    open: Vec<TraceNode>,
+}
+
+fn position_of(tokens: &[Token]) -> Option<usize> {
+    tokens.first().map(|token| token.index)
+}
+
+fn describe(method: &Match) -> String {
+    let description = element_to_node(method).map_or_else(|| "~".to_string(), |node| node_to_ebnf(&node));
+    if description.chars().count() <= MAX_DESCRIPTION {
+        return description;
+    }
+    let mut short: String = description.chars().take(MAX_DESCRIPTION - 3).collect();
+    short.push_str("...");
+    short
+}
+
+impl MatchTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    fn push(&mut self, entry: TraceEntry) {
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub(super) fn enter(&mut self, method: &Match, tokens: &[Token]) {
+        self.open.push(TraceNode {
+            combinator: method.name(),
+            description: describe(method),
+            position: position_of(tokens),
+            outcome: TraceOutcome::Failed,
+            children: Vec::new(),
+        });
+    }
+
+    // `None` if the combinator failed, otherwise the tokens left after it
+    pub(super) fn exit(&mut self, rest: Option<&[Token]>) {
+        let Some(mut node) = self.open.pop() else {
+            return;
+        };
+        if let Some(rest) = rest {
+            node.outcome = TraceOutcome::Matched { end: position_of(rest) };
+        }
+        self.push(TraceEntry::Attempt(node));
+    }
+
+    pub(super) fn backtrack(&mut self, reason: &'static str, tokens: &[Token]) {
+        self.push(TraceEntry::Backtrack { reason, position: position_of(tokens) });
+    }
+
+    // Indented tree, one line per attempt or backtrack
+    pub fn render_tree(&self) -> String {
+        let mut out = String::new();
+        for entry in &self.entries {
+            render_tree_entry(entry, 0, &mut out);
+        }
+        out
+    }
+
+    // A JSON array of entries, attempts carry their nested `children`
+    pub fn render_json(&self) -> String {
+        let mut out = String::new();
+        render_json_entries(&self.entries, &mut out);
+        out
+    }
+}
+
+fn format_position(position: Option<usize>) -> String {
+    position.map_or_else(|| "end".to_string(), |position| position.to_string())
+}
+
+fn render_tree_entry(entry: &TraceEntry, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match entry {
+        TraceEntry::Attempt(node) => {
+            let outcome = match &node.outcome {
+                TraceOutcome::Matched { end } => format!("ok -> @{}", format_position(*end)),
+                TraceOutcome::Failed => "failed".to_string(),
+            };
+            writeln!(
+                out,
+                "{}{} {} @{} {}",
+                indent,
+                node.combinator,
+                node.description,
+                format_position(node.position),
+                outcome
+            )
+            .unwrap();
+            for child in &node.children {
+                render_tree_entry(child, depth + 1, out);
+            }
+        }
+        TraceEntry::Backtrack { reason, position } => {
+            writeln!(out, "{}<< backtrack, {} @{}", indent, reason, format_position(*position)).unwrap();
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_position(position: Option<usize>) -> String {
+    position.map_or_else(|| "null".to_string(), |position| position.to_string())
+}
+
+fn render_json_entries(entries: &[TraceEntry], out: &mut String) {
+    out.push('[');
+    for (i, entry) in entries.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        match entry {
+            TraceEntry::Attempt(node) => {
+                let (success, end) = match node.outcome {
+                    TraceOutcome::Matched { end } => (true, json_position(end)),
+                    TraceOutcome::Failed => (false, "null".to_string()),
+                };
+                write!(
+                    out,
+                    "{{\"combinator\":{},\"description\":{},\"position\":{},\"success\":{},\"end\":{},\"children\":",
+                    json_string(node.combinator),
+                    json_string(&node.description),
+                    json_position(node.position),
+                    success,
+                    end
+                )
+                .unwrap();
+                render_json_entries(&node.children, out);
+                out.push('}');
+            }
+            TraceEntry::Backtrack { reason, position } => {
+                write!(out, "{{\"backtrack\":{},\"position\":{}}}", json_string(reason), json_position(*position)).unwrap();
+            }
+        }
+    }
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::parsing::{test_tokens_against, test_tokens_against_traced, tokenizer::tokenize_text};
+
+    #[test]
+    fn trace_records_attempts_and_backtracks() {
+        const PATTERN: &[Match] = gurn_grammar!(Comptime? ~ If ~ (";" | paren(..)));
+        let tokens = tokenize_text("if (a)".to_string()).unwrap();
+
+        let mut trace = MatchTrace::new();
+        let traced = test_tokens_against_traced(PATTERN, &tokens, &mut trace);
+        assert_eq!(traced, test_tokens_against(PATTERN, &tokens));
+
+        // The optional chains everything after it, and falls back when `comptime` is missing
+        let [TraceEntry::Attempt(optional), TraceEntry::Attempt(..), TraceEntry::Attempt(..), TraceEntry::Attempt(..), TraceEntry::Attempt(either)] =
+            trace.entries()
+        else {
+            panic!("unexpected trace shape:\n{}", trace.render_tree());
+        };
+        assert_eq!(optional.combinator, "Optional");
+        assert_eq!(optional.position, Some(0));
+        assert!(matches!(optional.children.last(), Some(TraceEntry::Backtrack { reason: "skipping optional", .. })));
+        assert!(matches!(&optional.children[0], TraceEntry::Attempt(TraceNode { outcome: TraceOutcome::Failed, .. })));
+
+        assert_eq!(either.outcome, TraceOutcome::Matched { end: None });
+        assert!(either.children.iter().any(|child| matches!(child, TraceEntry::Backtrack { reason: "trying right branch", position: Some(3) })));
+    }
+
+    #[test]
+    fn trace_rendering() {
+        let tokens = tokenize_text("if x".to_string()).unwrap();
+        let mut trace = MatchTrace::new();
+        test_tokens_against_traced(gurn_grammar!(If ~ <keyword>), &tokens, &mut trace);
+
+        assert_eq!(
+            trace.render_tree(),
+            "Of \"if\" @0 ok -> @2\nIgnoreWhitespace ~ @2 ok -> @3\nOfType KEYWORD @3 failed\n"
+        );
+        assert_eq!(
+            trace.render_json(),
+            concat!(
+                "[{\"combinator\":\"Of\",\"description\":\"\\\"if\\\"\",\"position\":0,\"success\":true,\"end\":2,\"children\":[]},",
+                "{\"combinator\":\"IgnoreWhitespace\",\"description\":\"~\",\"position\":2,\"success\":true,\"end\":3,\"children\":[]},",
+                "{\"combinator\":\"OfType\",\"description\":\"KEYWORD\",\"position\":3,\"success\":false,\"end\":null,\"children\":[]}]"
+            )
+        );
+    }
+}
@@ -1,7 +1,9 @@
 #![allow(unused)]
+#[macro_use]
+mod grammar_macro;
 pub mod tokenizer;
 pub mod number_parser;
-mod string_parser;
+pub mod string_parser;
 
 
 mod pattern_matcher;
@@ -9,3 +11,6 @@ pub use pattern_matcher::*;
 pub mod type_parser;
 
 pub mod pattern_constants;
+pub mod grammar_analysis;
+pub mod grammar_export;
+pub mod match_trace;
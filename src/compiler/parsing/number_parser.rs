@@ -121,6 +121,11 @@ impl NumberLiteral {
 
         for char in current_string.char_indices().filter(|c| c.1 != '_') {
             if '.' == char.1 {
+                // `1..5` is a range, not a decimal
+                if hasSeenPeriod || current_string[char.0 + 1..].starts_with('.') {
+                    next_invalid = char.0;
+                    break;
+                }
                 hasSeenPeriod = true;
             } else if !base.is_valid(char.1) {
                 next_invalid = char.0;
@@ -246,6 +251,16 @@ impl Primitive {
     pub fn is_signed(&self) -> bool {
         self.is_float() || self.is_signed_int()
     }
+    // `u8`, `f32`, ... the same spelling as the number suffixes
+    pub fn from_name(name: &str) -> Option<Self> {
+        NUMBER_SUFFIX_DATA.iter().find(|suffix| suffix.0 == name).map(|suffix| suffix.1.clone())
+    }
+    pub fn all() -> impl Iterator<Item = Self> {
+        NUMBER_SUFFIX_DATA.iter().map(|suffix| suffix.1.clone())
+    }
+    pub fn name(&self) -> &'static str {
+        NUMBER_SUFFIX_DATA.iter().find(|suffix| suffix.1 == *self).map(|suffix| suffix.0).unwrap()
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -450,6 +465,13 @@ mod tests {
         assert_eq!(Primitive::REAL.to_size(), Some(8));
     }
 
+    #[test]
+    fn test_number_stops_before_range() {
+        let (length, literal) = NumberLiteral::new("1..5").unwrap();
+        assert_eq!((length, literal.has_decimal), (1, false));
+        assert_eq!(NumberLiteral::new("1.5..").unwrap().0, 3);
+    }
+
     #[test]
     fn test_number_type_is_float() {
         assert!(Primitive::F32.is_float());
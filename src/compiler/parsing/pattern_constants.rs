@@ -12,101 +12,128 @@ const TYPE_GLOB: Match<'static> = GlobWithSizer(type_size_function);
 
 
 macro_rules! basic_control_flow {
-    ($name : ident, $keyword : ident, $condition : expr) => {
-        pub const $name: &[Match] = &[
-            Optional(&[Of(&[TokenData::Keyword(Keyword::Comptime)])]),
-            IgnoreWhitespace,
-            Of(&[TokenData::Keyword(Keyword::$keyword)]),
-            IgnoreWhitespace,
-            Bracket(b'(', $condition),
-            IgnoreWhitespace,
-            Either(
-                &[Glob, Of(&[TokenData::Semicolon])], // Single line
-                &[Bracket(b'{', &[Glob])],            // Scoped if
-            ),
-        ];
+    ($name : ident, $keyword : ident, $($condition : tt)*) => {
+        pub const $name: &[Match] = gurn_grammar!(
+            Comptime? ~ $keyword ~ paren($($condition)*) ~
+            (
+                .. ";"      // Single line
+                | brace{..} // Scoped if
+            )
+        );
     };
 }
-basic_control_flow!(IF_STATEMENT, If, &[Glob]);
-basic_control_flow!(ELSE_STATEMENT, Else, &[Glob]);
-basic_control_flow!(ELSE_IF_STATEMENT, ElseIf, &[Glob]);
-basic_control_flow!(FOR_LOOP, For, &[
-    Optional(&[TYPE_GLOB]),
-    OfType(&[TokenData::TextCluster(None)]),
-    Of(&[TokenData::Semicolon]),
-    Glob,
-]);
+basic_control_flow!(IF_STATEMENT, If, ..);
+basic_control_flow!(ELSE_IF_STATEMENT, ElseIf, ..);
 
+// `else` is the only branch without a condition
+pub const ELSE_STATEMENT: &[Match] = gurn_grammar!(
+    Else ~
+    (
+        .. ";"
+        | brace{..}
+    )
+);
 
 
-pub const DO_WHILE_LOOP: &[Match] = &[
-    Of(&[TokenData::Keyword(Keyword::Do)]),
-    Bracket(b'{', &[Glob]),
-    Of(&[TokenData::Keyword(Keyword::While)]),
-    IgnoreWhitespace,
-    Bracket(b'(', &[Glob]),
-    IgnoreWhitespace,
-    Of(&[TokenData::Semicolon]),
-];
 
-pub const FUNCTION_DECLARATION: &[Match] = &[
-    // Function decorators
-    PossibleWhitespaceSeparated(&[
-        Of(&[TokenData::AtSign]),
-        OfType(&[TokenData::TextCluster(None)]),
-        IgnoreWhitespace,
-        Optional(&[Bracket(b'(', &[Glob])]),
-    ]),
-    IgnoreWhitespace,
-    PossibleWhitespaceSeparated(&[OfType(&[TokenData::Keyword(Keyword::DUMMY)])]),
-    IgnoreWhitespace,
+// Loops can carry a label for `break 'outer;`
+macro_rules! loop_control_flow {
+    ($name : ident, $keyword : ident, $($condition : tt)*) => {
+        pub const $name: &[Match] = gurn_grammar!(
+            ~ (<label> ~ ":" ~)?
+            Comptime? ~ $keyword ~ paren($($condition)*) ~
+            (
+                .. ";"
+                | brace{..}
+            )
+        );
+    };
+}
+loop_control_flow!(WHILE_LOOP, While, ..);
+// `for (u32 i = 0; i < n; i += 1)`, every clause can be left empty
+loop_control_flow!(FOR_LOOP, For, .. ";" .. ";" ..);
+// `for (x in iterable)`
+loop_control_flow!(FOR_IN_LOOP, For, ~ <ident> ~ In ~ ..);
+
+pub const DO_WHILE_LOOP: &[Match] = gurn_grammar!(
+    Do brace{..} While ~ paren(..) ~ ";"
+);
+
+pub const FUNCTION_DECLARATION: &[Match] = gurn_grammar!(
+    // Function decorators, `@inline` is named by a keyword
+    (~ "@" (<ident> | <keyword>) paren(..)?){0,}
+    // Modifiers, `fn` starts a function type instead
+    ~ (!Fn <keyword>)*
     // Return type
-    TYPE_GLOB,
-    IgnoreWhitespace,
+    ~ <type>
     // Function Name
-    OfType(&[TokenData::TextCluster(None)]),
-    IgnoreWhitespace,
+    ~ <ident>
     // Generics
-    Optional(&[
-        Of(&[TokenData::Operator(
-            crate::compiler::operators::Operator::LesserThan,
-        )]),
-        Glob,
-        Of(&[TokenData::Operator(
-            crate::compiler::operators::Operator::GreaterThan,
-        )]),
-        IgnoreWhitespace,
-    ]),
+    ~ ("<" .. ">" ~)?
     // Args
-    Bracket(
-        b'(',
-        &[PossibleCommaSeparated(&[
-            IgnoreWhitespace,
-            PossibleWhitespaceSeparated(&[OfType(&[TokenData::Keyword(Keyword::DUMMY)])]),
-            IgnoreWhitespace,
-            TYPE_GLOB,
-            Whitespace,
-            OfType(&[TokenData::TextCluster(None)]),
-            IgnoreWhitespace,
-        ])],
-    ),
-    Optional(&[
-        IgnoreWhitespace,
-        Of(&[TokenData::Keyword(Keyword::Where)]),
-        Glob,
-    ]),
-    IgnoreWhitespace,
-    Either(
-        &[Of(&[TokenData::Bracket(b'{', None)])],
-        &[
-            Of(&[TokenData::Operator(
-                crate::compiler::operators::Operator::EqualsArrow,
-            )]),
-            Glob,
-            Of(&[TokenData::Semicolon]),
-        ],
-    ),
-];
+    paren((~ (!Fn <keyword>)* ~ <type> <ws> <ident> ~),*)
+    (~ Where ..)?
+    // A signature alone declares a function defined elsewhere, like an `extern` one
+    ~ ("{" | "=>" .. ";" | ";")
+);
+
+pub const STRUCT_DECLARATION: &[Match] = gurn_grammar!(
+    (~ "@" (<ident> | <keyword>) paren(..)?){0,}
+    ~ (Pub | Private)?
+    ~ Struct ~ <ident>
+    // Generics
+    ~ ("<" .. ">" ~)?
+    (Implements ~ <ident> ~ ("," ~ <ident> ~)*)?
+    (Where .. ~)?
+    // Fields
+    brace{..}
+);
+
+pub const ENUM_DECLARATION: &[Match] = gurn_grammar!(
+    (~ "@" (<ident> | <keyword>) paren(..)?){0,}
+    ~ (Pub | Private)?
+    ~ Enum ~
+    // Backing type
+    (<type> ~)?
+    <ident>
+    ~ ("<" .. ">" ~)?
+    (Where .. ~)?
+    // Variants
+    brace{..}
+);
+
+pub const TRAIT_DECLARATION: &[Match] = gurn_grammar!(
+    (~ "@" (<ident> | <keyword>) paren(..)?){0,}
+    ~ (Pub | Private)?
+    ~ Trait ~ <ident>
+    ~ ("<" .. ">" ~)?
+    (Where .. ~)?
+    brace{..}
+);
+
+pub const IMPL_BLOCK: &[Match] = gurn_grammar!(
+    (~ "@" (<ident> | <keyword>) paren(..)?){0,}
+    ~ Impl ~ ("<" .. ">" ~)?
+    <type> ~
+    // `impl Trait for Type`
+    (For ~ <type> ~)?
+    (Where .. ~)?
+    brace{..}
+);
+
+pub const VARIABLE_DECLARATION: &[Match] = gurn_grammar!(
+    ~ (
+        // `static COUNTER u64 = 0;`, the name can come first after a keyword
+        (Static ~ ((Var | Const) ~)? | (Var | Const) ~) <ident> ~ <type>
+        | (Static ~)?
+        (
+            // The type can only be left out after `var` or `const`
+            (Var | Const) ~ (<type> ~)? <ident>
+            | <type> ~ <ident>
+        )
+    )
+    ~ ("=" ..)? ";"
+);
 
 pub const TEST: &[Match] = &[
     Of(&[TokenData::Keyword(Keyword::If)]),
@@ -114,3 +141,21 @@ pub const TEST: &[Match] = &[
     Optional(&[Of(&[TokenData::Keyword(Keyword::Where)]), Glob]),
     Of(&[TokenData::Semicolon]),
 ];
+
+// Every pattern above, by name, for tooling that needs to walk the whole grammar
+pub const ALL_PATTERNS: &[(&str, &[Match])] = &[
+    ("IF_STATEMENT", IF_STATEMENT),
+    ("ELSE_STATEMENT", ELSE_STATEMENT),
+    ("ELSE_IF_STATEMENT", ELSE_IF_STATEMENT),
+    ("FOR_LOOP", FOR_LOOP),
+    ("FOR_IN_LOOP", FOR_IN_LOOP),
+    ("WHILE_LOOP", WHILE_LOOP),
+    ("DO_WHILE_LOOP", DO_WHILE_LOOP),
+    ("FUNCTION_DECLARATION", FUNCTION_DECLARATION),
+    ("STRUCT_DECLARATION", STRUCT_DECLARATION),
+    ("ENUM_DECLARATION", ENUM_DECLARATION),
+    ("TRAIT_DECLARATION", TRAIT_DECLARATION),
+    ("IMPL_BLOCK", IMPL_BLOCK),
+    ("VARIABLE_DECLARATION", VARIABLE_DECLARATION),
+    ("TEST", TEST),
+];
@@ -1,5 +1,6 @@
 use std::{alloc::System, iter::Enumerate, vec};
 
+use super::match_trace::MatchTrace;
 use super::tokenizer::{Token, TokenData};
 
 
@@ -27,6 +28,44 @@ pub enum Match<'a> {
 
     // A glob with a custom verification / size determination function
     GlobWithSizer(fn(&[Token]) -> Option<&[Token]>),
+
+    // Greedy, between `min` and `max` (inclusive) repetitions, `None` is unbounded
+    Repeat {
+        min: usize,
+        max: Option<usize>,
+        pat: &'a [Match<'a>],
+    },
+
+    // Negative lookahead, succeeds without consuming if the pattern does NOT match here
+    Not(&'a [Match<'a>]),
+
+    // Any single token
+    Any,
+
+    // Separator, pattern, trailing separator allowed
+    SeparatedBy(&'a [Match<'a>], &'a [Match<'a>], bool),
+}
+
+impl Match<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Match::IgnoreWhitespace => "IgnoreWhitespace",
+            Match::Whitespace => "Whitespace",
+            Match::Of(_) => "Of",
+            Match::OfType(_) => "OfType",
+            Match::Optional(_) => "Optional",
+            Match::PossibleCommaSeparated(_) => "PossibleCommaSeparated",
+            Match::PossibleWhitespaceSeparated(_) => "PossibleWhitespaceSeparated",
+            Match::Bracket(..) => "Bracket",
+            Match::Either(..) => "Either",
+            Match::Glob => "Glob",
+            Match::GlobWithSizer(_) => "GlobWithSizer",
+            Match::Repeat { .. } => "Repeat",
+            Match::Not(_) => "Not",
+            Match::Any => "Any",
+            Match::SeparatedBy(..) => "SeparatedBy",
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -42,6 +81,11 @@ pub enum MatchResult {
     Either(EitherSide<Vec<MatchResult>, Vec<MatchResult>>),
 
     Glob(Vec<Token>),
+
+    Repeat(Vec<Vec<MatchResult>>),
+    Not,
+    Any(Token),
+    SeparatedBy(Vec<Vec<MatchResult>>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -63,7 +107,7 @@ impl<A, B> EitherSide<A, B> {
 pub fn consume_whitespace<'a>(mut tokens: &'a [Token]) -> (bool, &'a [Token]) {
     let mut hasSeenWhitespace = false;
     while let Some(token) = tokens.get(0) {
-        if !matches!(token.data, TokenData::Whitespace(_)) {
+        if !matches!(token.data, TokenData::Whitespace(_) | TokenData::DocComment(_)) {
             break;
         }
         hasSeenWhitespace = true;
@@ -72,192 +116,277 @@ pub fn consume_whitespace<'a>(mut tokens: &'a [Token]) -> (bool, &'a [Token]) {
     (hasSeenWhitespace, tokens)
 }
 use std::mem::discriminant;
+
+// Matches `pat (sep pat)*`, zero occurrences is a successful empty match.
+// A dangling separator is only consumed if `trailing_allowed` is set.
+fn match_separated<'a>(
+    sep: &[Match<'a>],
+    pat: &[Match<'a>],
+    trailing_allowed: bool,
+    mut tokens: &'a [Token],
+    trace: &mut Option<&mut MatchTrace>,
+) -> (&'a [Token], Vec<Vec<MatchResult>>) {
+    let mut values = Vec::new();
+    let mut after_sep = tokens;
+    while let Some((new_tokens, res)) = match_sequence(pat, after_sep, trace) {
+        let made_progress = new_tokens.len() != tokens.len();
+        tokens = new_tokens;
+        values.push(res);
+        match match_sequence(sep, tokens, trace) {
+            Some((new_tokens, _)) if made_progress => after_sep = new_tokens,
+            _ => return (tokens, values),
+        }
+    }
+    if trailing_allowed && !values.is_empty() {
+        tokens = after_sep;
+    } else if !values.is_empty() {
+        trace_backtrack(trace, "dropping trailing separator", tokens);
+    }
+    (tokens, values)
+}
+
 pub fn test_tokens_against<'a>(
+    test: &[Match<'a>],
+    tokens: &'a [Token],
+) -> Option<(&'a [Token], Vec<MatchResult>)> {
+    match_sequence(test, tokens, &mut None)
+}
+
+// Same as `test_tokens_against`, but records every combinator attempt into `trace`
+pub fn test_tokens_against_traced<'a>(
+    test: &[Match<'a>],
+    tokens: &'a [Token],
+    trace: &mut MatchTrace,
+) -> Option<(&'a [Token], Vec<MatchResult>)> {
+    match_sequence(test, tokens, &mut Some(trace))
+}
+
+// What is left after a single element matched
+enum Step<'a> {
+    // Continue with the next element
+    Next(&'a [Token]),
+    // The element already matched the rest of the sequence (`Optional`, `Either` and globs chain it)
+    Finished(&'a [Token]),
+}
+
+fn match_sequence<'a>(
     mut test: &[Match<'a>],
     mut tokens: &'a [Token],
+    trace: &mut Option<&mut MatchTrace>,
 ) -> Option<(&'a [Token], Vec<MatchResult>)> {
     let mut ret = Vec::new();
 
-    while let Some(method) = test.get(0) {
+    while let Some(method) = test.first() {
         test = &test[1..];
-        match method {
-            Match::IgnoreWhitespace => {
-                tokens = consume_whitespace(tokens).1;
-                ret.push(MatchResult::IgnoreWhitespace);
+        if let Some(trace) = trace {
+            trace.enter(method, tokens);
+        }
+        let step = match_element(method, test, tokens, &mut ret, trace);
+        if let Some(trace) = trace {
+            trace.exit(step.as_ref().map(|step| match step {
+                Step::Next(tokens) | Step::Finished(tokens) => *tokens,
+            }));
+        }
+        match step? {
+            Step::Next(new_tokens) => tokens = new_tokens,
+            Step::Finished(new_tokens) => {
+                tokens = new_tokens;
+                break;
+            }
+        }
+    }
+
+    Some((tokens, ret))
+}
+
+fn trace_backtrack(trace: &mut Option<&mut MatchTrace>, reason: &'static str, tokens: &[Token]) {
+    if let Some(trace) = trace {
+        trace.backtrack(reason, tokens);
+    }
+}
+
+// `test` is the rest of the sequence after `method`
+fn match_element<'a>(
+    method: &Match<'a>,
+    test: &[Match<'a>],
+    mut tokens: &'a [Token],
+    ret: &mut Vec<MatchResult>,
+    trace: &mut Option<&mut MatchTrace>,
+) -> Option<Step<'a>> {
+    match method {
+        Match::IgnoreWhitespace => {
+            tokens = consume_whitespace(tokens).1;
+            ret.push(MatchResult::IgnoreWhitespace);
+        }
+        Match::Whitespace => {
+            let (is_success, new_tokens) = consume_whitespace(tokens);
+            if !is_success {
+                return None;
             }
-            Match::Whitespace => {
-                let (is_success, new_tokens) = consume_whitespace(tokens);
-                if !is_success {
+            ret.push(MatchResult::IgnoreWhitespace);
+            tokens = new_tokens;
+        }
+        Match::Of(condition) => {
+            let mut found = Vec::with_capacity(condition.len());
+            for i in 0..condition.len() {
+                let tok = tokens.get(i)?;
+                if tok.data != condition[i] {
                     return None;
                 }
-                ret.push(MatchResult::IgnoreWhitespace);
-                tokens = new_tokens;
+                found.push(tok.clone());
             }
-            Match::Of(condition) => {
-                let mut found = Vec::with_capacity(condition.len());
-                for i in 0..condition.len() {
-                    let tok = tokens.get(i)?;
-                    if tok.data != condition[i] {
-                        return None;
-                    }
-                    found.push(tok.clone());
+            ret.push(MatchResult::Of(found));
+            tokens = &tokens[condition.len()..];
+        }
+        Match::OfType(condition) => {
+            let mut found = Vec::with_capacity(condition.len());
+            for i in 0..condition.len() {
+                // Test if the enum varieties are the same
+                let tok = tokens.get(i)?;
+                if discriminant(&tok.data) != discriminant(&condition[i]) {
+                    return None;
                 }
-                ret.push(MatchResult::Of(found));
-                tokens = &tokens[condition.len()..];
+                found.push(tok.clone());
             }
-            Match::OfType(condition) => {
-                let mut found = Vec::with_capacity(condition.len());
-                for i in 0..condition.len() {
-                    // Test if the enum varieties are the same
-                    let tok = tokens.get(i)?;
-                    if discriminant(&tok.data) != discriminant(&condition[i]) {
-                        return None;
-                    }
-                    found.push(tok.clone());
-                }
-                tokens = &tokens[condition.len()..];
-                ret.push(MatchResult::OfType(found))
+            tokens = &tokens[condition.len()..];
+            ret.push(MatchResult::OfType(found))
+        }
+        Match::Bracket(opener, inner_test) =>{
+            let tok = tokens.get(0)?;
+            tokens = &tokens[1..];
+            let inner_tokens = match &tok.data {
+                TokenData::Bracket(opener, Some(data)) => data,
+                _ => return None
+            };
+            
+            let (new_inner_tokens, inner_results) = match_sequence(inner_test, inner_tokens.as_slice(), trace)?;
+            
+            // We do not accept partial matches here
+            if new_inner_tokens.len() != 0{return None;}
+            ret.push(MatchResult::Bracket(inner_results));
+
+        },
+        Match::Optional(opt_test) => {
+            let rest_of_tests: Vec<_> = opt_test
+                .iter()
+                .chain(test.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            if let Some((new_tokens, mut res)) =
+                match_sequence(rest_of_tests.as_slice(), tokens, trace)
+            {
+                let mut after_option_results = res.split_off(opt_test.len());
+
+                ret.push(MatchResult::Optional(Some(res)));
+                ret.append(&mut after_option_results);
+                return Some(Step::Finished(new_tokens));
+            } else {
+                trace_backtrack(trace, "skipping optional", tokens);
+                ret.push(MatchResult::Optional(None));
             }
-            Match::Bracket(opener, inner_test) =>{
-                let tok = tokens.get(0)?;
-                tokens = &tokens[1..];
-                let inner_tokens = match &tok.data {
-                    TokenData::Bracket(opener, Some(data)) => data,
-                    _ => return None
+        }
+        Match::PossibleCommaSeparated(test) => {
+            let (new_tokens, values) =
+                match_separated(&[Match::Of(&[TokenData::Colon])], test, true, tokens, trace);
+            tokens = new_tokens;
+            ret.push(MatchResult::PossibleCommaSeparated(values));
+        }
+        Match::PossibleWhitespaceSeparated(test) => {
+            let (new_tokens, values) = match_separated(&[Match::Whitespace], test, true, tokens, trace);
+            tokens = new_tokens;
+            ret.push(MatchResult::PossibleWhitespaceSeparated(values));
+        }
+        Match::SeparatedBy(sep, test, trailing_allowed) => {
+            let (new_tokens, values) = match_separated(sep, test, *trailing_allowed, tokens, trace);
+            tokens = new_tokens;
+            ret.push(MatchResult::SeparatedBy(values));
+        }
+        Match::Repeat { min, max, pat } => {
+            let mut values = Vec::new();
+            while max.is_none_or(|max| values.len() < max) {
+                let Some((new_tokens, res)) = match_sequence(pat, tokens, trace) else {
+                    break;
                 };
-                
-                let (new_inner_tokens, inner_results) = test_tokens_against(inner_test, &inner_tokens.as_slice())?;
-                
-                // We do not accept partial matches here
-                if new_inner_tokens.len() != 0{return None;}
-                ret.push(MatchResult::Bracket(inner_results));
-
-            },
-            Match::Optional(opt_test) => {
-                let rest_of_tests: Vec<_> = opt_test
-                    .iter()
-                    .chain(test.iter())
-                    .cloned()
-                    .collect::<Vec<_>>();
-                if let Some((new_tokens, mut res)) =
-                    test_tokens_against(rest_of_tests.as_slice(), tokens)
-                {
-                    let mut after_option_results = res.split_off(opt_test.len());
-
-                    tokens = new_tokens;
-                    ret.push(MatchResult::Optional(Some(res)));
-                    ret.append(&mut after_option_results);
+                // A pattern that consumes nothing would repeat forever
+                let made_progress = new_tokens.len() != tokens.len();
+                tokens = new_tokens;
+                values.push(res);
+                if !made_progress {
                     break;
-                } else {
-                    ret.push(MatchResult::Optional(None));
                 }
             }
-            Match::PossibleCommaSeparated(test) | Match::PossibleWhitespaceSeparated(test) => {
-                let mut values = Vec::new();
-                loop {
-                    if let Some((new_tokens, res)) = test_tokens_against(test, tokens) {
-                        tokens = new_tokens;
-                        values.push(res);
-                    } else {
-                        break;
-                    }
-                    let next = tokens.get(0);
-                    if next.is_none() {
-                        break;
-                    }
-                    let next = next.unwrap();
-
-                    if matches!(method, Match::PossibleCommaSeparated(_))
-                        && !matches!(next.data, TokenData::Colon)
-                    {
-                        break;
-                    }
-                    if matches!(method, Match::PossibleWhitespaceSeparated(_))
-                        && !matches!(next.data, TokenData::Whitespace(_))
-                    {
-                        tokens = consume_whitespace(tokens).1;
-                        break;
-                    }
-                    tokens = &tokens[1..];
-                }
-                if matches!(method, Match::PossibleCommaSeparated(_)) {
-                    ret.push(MatchResult::PossibleCommaSeparated(values));
-                } else {
-                    ret.push(MatchResult::PossibleWhitespaceSeparated(values));
-                }
+            if values.len() < *min {
+                return None;
             }
-            Match::Either(this, that) => {
-                let this_chained = this.iter().chain(test.iter()).cloned().collect::<Vec<_>>();
-                let that_chained = that.iter().chain(test.iter()).cloned().collect::<Vec<_>>();
-                if let Some((new_tokens, mut res)) =
-                    test_tokens_against(this_chained.as_slice(), tokens)
-                {
-                    tokens = new_tokens;
-                    let mut after_either_results = res.split_off(this.len());
-                    ret.push(MatchResult::Either(EitherSide::Left(res)));
-                    ret.append(&mut after_either_results);
-                    break;
-                } else if let Some((new_tokens, mut res)) =
-                    test_tokens_against(that_chained.as_slice(), tokens)
-                {
-                    tokens = new_tokens;
-                    let mut after_either_results = res.split_off(that.len());
-                    ret.push(MatchResult::Either(EitherSide::Right(res)));
-                    ret.append(&mut after_either_results);
-                    break;
-                } else {
-                    return None;
-                }
+            ret.push(MatchResult::Repeat(values));
+        }
+        Match::Not(pat) => {
+            if match_sequence(pat, tokens, trace).is_some() {
+                return None;
+            }
+            ret.push(MatchResult::Not);
+        }
+        Match::Any => {
+            let tok = tokens.first()?;
+            ret.push(MatchResult::Any(tok.clone()));
+            tokens = &tokens[1..];
+        }
+        Match::Either(this, that) => {
+            let this_chained = this.iter().chain(test.iter()).cloned().collect::<Vec<_>>();
+            let that_chained = that.iter().chain(test.iter()).cloned().collect::<Vec<_>>();
+            if let Some((new_tokens, mut res)) =
+                match_sequence(this_chained.as_slice(), tokens, trace)
+            {
+                let mut after_either_results = res.split_off(this.len());
+                ret.push(MatchResult::Either(EitherSide::Left(res)));
+                ret.append(&mut after_either_results);
+                return Some(Step::Finished(new_tokens));
+            }
+            trace_backtrack(trace, "trying right branch", tokens);
+            let (new_tokens, mut res) = match_sequence(that_chained.as_slice(), tokens, trace)?;
+            let mut after_either_results = res.split_off(that.len());
+            ret.push(MatchResult::Either(EitherSide::Right(res)));
+            ret.append(&mut after_either_results);
+            return Some(Step::Finished(new_tokens));
+        }
+        Match::Glob => {
+            // The glob is trailing, we must CONSUME ALL
+            if test.len() == 0{
+                ret.push(MatchResult::Glob(tokens.to_vec()));
+                return Some(Step::Finished(&tokens[tokens.len()..]));
             }
-            Match::Glob => {
-                // The glob is trailing, we must CONSUME ALL
-                if test.len() == 0{
-                    ret.push(MatchResult::Glob(tokens.to_vec()));
-                    tokens = &tokens[tokens.len()..];
-                    break;
-                }
-
-                let mut itr = tokens;
-                let mut found_glob = false;
-
-                while 0 != itr.len() {
-                    if let Some((new_tokens, mut res)) = test_tokens_against(test, itr) {
-                        found_glob = true;
-                        ret.push(MatchResult::Glob(
-                            // The shrink in itr size is the amount of loop iterations
-                            tokens[..tokens.len() - itr.len()].to_vec(),
-                        ));
-                        tokens = new_tokens;
 
-                        ret.append(&mut res);
+            let mut itr = tokens;
 
-                        break;
-                    }
-                    itr = &itr[1..];
+            while 0 != itr.len() {
+                if let Some((new_tokens, mut res)) = match_sequence(test, itr, trace) {
+                    ret.push(MatchResult::Glob(
+                        // The shrink in itr size is the amount of loop iterations
+                        tokens[..tokens.len() - itr.len()].to_vec(),
+                    ));
+                    ret.append(&mut res);
+                    return Some(Step::Finished(new_tokens));
                 }
-                if !found_glob {
-                    return None;
-                }
-                break;
-            },
-            Match::GlobWithSizer(sizer) => {
-                let new_tokens = sizer(&tokens)?;
-                let glob_val = tokens[..tokens.len() - new_tokens.len()].to_vec();
+                itr = &itr[1..];
+                trace_backtrack(trace, "growing glob", itr);
+            }
+            return None;
+        },
+        Match::GlobWithSizer(sizer) => {
+            let new_tokens = sizer(&tokens)?;
+            let glob_val = tokens[..tokens.len() - new_tokens.len()].to_vec();
 
 
-                let (new_tokens, mut res) = test_tokens_against(test, &new_tokens)?;
-                ret.push(MatchResult::Glob(
-                    glob_val,
-                ));
-                tokens = new_tokens;
-                ret.append(&mut res);
-                break;
+            let (new_tokens, mut res) = match_sequence(test, &new_tokens, trace)?;
+            ret.push(MatchResult::Glob(
+                glob_val,
+            ));
+            ret.append(&mut res);
+            return Some(Step::Finished(new_tokens));
 
-            }
         }
     }
-
-    Some((tokens, ret))
+    Some(Step::Next(tokens))
 }
 
 // #[cfg(test)]
@@ -455,3 +584,100 @@ pub fn test_tokens_against<'a>(
 //         );
 //     }
 // }
+
+#[cfg(test)]
+mod combinator_tests {
+    use super::super::tokenizer::*;
+    use super::*;
+
+    fn run<'a>(pattern: &[Match<'a>], tokens: &'a [Token]) -> Option<(usize, Vec<MatchResult>)> {
+        test_tokens_against(pattern, tokens).map(|(rest, res)| (rest.len(), res))
+    }
+
+    #[test]
+    fn repeat_bounds() {
+        let tokens = tokenize_text("a b c d".to_string()).unwrap();
+        const WORD: &[Match] = gurn_grammar!(~ <ident>);
+
+        let (rest, res) = run(&[Match::Repeat { min: 1, max: None, pat: WORD }], &tokens).unwrap();
+        assert_eq!(rest, 0);
+        assert!(matches!(&res[0], MatchResult::Repeat(v) if v.len() == 4));
+
+        let (rest, _) = run(&[Match::Repeat { min: 2, max: Some(3), pat: WORD }], &tokens).unwrap();
+        assert_eq!(rest, 2);
+
+        assert!(run(&[Match::Repeat { min: 5, max: None, pat: WORD }], &tokens).is_none());
+
+        // Patterns that never consume do not loop forever
+        let (rest, _) = run(&[Match::Repeat { min: 0, max: None, pat: &[Match::IgnoreWhitespace] }], &tokens).unwrap();
+        assert_eq!(rest, tokens.len());
+    }
+
+    #[test]
+    fn not_and_any() {
+        let tokens = tokenize_text("a + b;".to_string()).unwrap();
+        const UNTIL_SEMICOLON: &[Match] = gurn_grammar!((!";" <any>)+ ";");
+        let (rest, res) = run(UNTIL_SEMICOLON, &tokens).unwrap();
+        assert_eq!(rest, 0);
+        assert!(matches!(&res[0], MatchResult::Repeat(v) if v.len() == 5));
+        assert!(matches!(&res[0], MatchResult::Repeat(v) if v[0] == vec![MatchResult::Not, MatchResult::Any(tokens[0].clone())]));
+
+        assert!(run(gurn_grammar!(!<ident> <any>), &tokens).is_none());
+        assert!(run(&[Match::Any], &[]).is_none());
+    }
+
+    #[test]
+    fn separated_by_trailing() {
+        let tokens = tokenize_text("a, b, c,".to_string()).unwrap();
+        const WITH_TRAILING: &[Match] = gurn_grammar!((~ <ident>) %% ",");
+        const WITHOUT_TRAILING: &[Match] = gurn_grammar!((~ <ident>) % ",");
+
+        let (rest, res) = run(WITH_TRAILING, &tokens).unwrap();
+        assert_eq!(rest, 0);
+        assert!(matches!(&res[0], MatchResult::SeparatedBy(v) if v.len() == 3));
+
+        // The dangling comma is left for whatever comes next
+        let (rest, _) = run(WITHOUT_TRAILING, &tokens).unwrap();
+        assert_eq!(rest, 1);
+
+        let (rest, res) = run(WITHOUT_TRAILING, &[]).unwrap();
+        assert_eq!(rest, 0);
+        assert_eq!(res, vec![MatchResult::SeparatedBy(vec![])]);
+    }
+
+    #[test]
+    fn legacy_separated_variants() {
+        let tokens = tokenize_text("if  else, var,defer,".to_string()).unwrap();
+        let (rest, res) = run(gurn_grammar!(If ~ (~ <keyword>),*), &tokens).unwrap();
+        assert_eq!(rest, 0);
+        assert!(matches!(&res[2], MatchResult::PossibleCommaSeparated(v) if v.len() == 3));
+
+        let tokens = tokenize_text("pub static inline u8".to_string()).unwrap();
+        let (rest, res) = run(gurn_grammar!(<keyword>* <ident>), &tokens).unwrap();
+        assert_eq!(rest, 0);
+        assert!(matches!(&res[0], MatchResult::PossibleWhitespaceSeparated(v) if v.len() == 3));
+    }
+
+    #[test]
+    fn while_loops() {
+        use super::super::pattern_constants::WHILE_LOOP;
+        for text in ["while (a) {}", " 'outer: while (a < b) { break 'outer; }", "comptime while (a) a += 1;", "'x : comptime while(a){}"] {
+            let tokens = tokenize_text(text.to_string()).unwrap();
+            assert_eq!(run(WHILE_LOOP, &tokens).map(|(rest, _)| rest), Some(0), "{}", text);
+        }
+        use super::super::pattern_constants::{FOR_IN_LOOP, FOR_LOOP};
+        for (text, pattern) in [("for (u32 i = 0; i < n; i += 1) {}", FOR_LOOP), ("for (;;) a;", FOR_LOOP), ("'a: for (x in 0..n) {}", FOR_IN_LOOP)] {
+            let tokens = tokenize_text(text.to_string()).unwrap();
+            assert_eq!(run(pattern, &tokens).map(|(rest, _)| rest), Some(0), "{}", text);
+        }
+        let tokens = tokenize_text("for (x in xs) {}".to_string()).unwrap();
+        assert!(run(FOR_LOOP, &tokens).is_none());
+
+        let tokens = tokenize_text("'outer while (a) {}".to_string()).unwrap();
+        assert!(run(WHILE_LOOP, &tokens).is_none());
+
+        let tokens = tokenize_text("'outer:".to_string()).unwrap();
+        assert_eq!(tokens[0].data, TokenData::Label("outer".to_string()));
+        assert_eq!((tokens[0].length, &tokens[1].data), (6, &TokenData::ColonSign));
+    }
+}
@@ -1,6 +1,6 @@
 
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum StringTypeBitmask{
     Char = 1, // Single quotes
     TemplatedString = 2, // backticks 
@@ -20,15 +20,73 @@ impl core::fmt::Debug for StringTypeBitmask{
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StringLiteral{
     pub string_type_bitmask : StringTypeBitmask, // Bitmask, no enum
     pub string_text_contents : String,
 }
 
 impl StringLiteral{
-    pub fn new(input : String) -> Self{
-        
-        todo!()
+    // A `"..."` string at the start of `text`, and how many bytes it takes. Only the double quoted form is read so
+    // far, with `\"`, `\\`, `\n`, `\t` and `\0` as its escapes. It ends on the same line it starts on
+    pub fn new(text: &str) -> Option<(usize, Self)> {
+        let rest = text.strip_prefix('"')?;
+        let mut contents = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((index, char)) = chars.next() {
+            match char {
+                '"' => {
+                    let literal = Self { string_type_bitmask: StringTypeBitmask::TypicalString, string_text_contents: contents };
+                    return Some((index + 2, literal));
+                }
+                '\\' => contents.push(match chars.next()?.1 {
+                    '"' => '"',
+                    '\\' => '\\',
+                    'n' => '\n',
+                    't' => '\t',
+                    '0' => '\0',
+                    _ => return None,
+                }),
+                '\n' => return None,
+                _ => contents.push(char),
+            }
+        }
+        None
     }
-}
\ No newline at end of file
+
+    // The literal as it is written, quotes and escapes included
+    pub fn source(&self) -> String {
+        let mut source = String::from('"');
+        for char in self.string_text_contents.chars() {
+            match char {
+                '"' => source.push_str("\\\""),
+                '\\' => source.push_str("\\\\"),
+                '\n' => source.push_str("\\n"),
+                '\t' => source.push_str("\\t"),
+                '\0' => source.push_str("\\0"),
+                _ => source.push(char),
+            }
+        }
+        source.push('"');
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_quoted() {
+        let (size, literal) = StringLiteral::new(r#""use \"sum\" instead\n" + 1"#).unwrap();
+        assert_eq!(size, 23);
+        assert_eq!(literal.string_text_contents, "use \"sum\" instead\n");
+        assert_eq!(literal.source(), r#""use \"sum\" instead\n""#);
+        assert_eq!(StringLiteral::new(r#""""#).unwrap().0, 2);
+
+        assert!(StringLiteral::new("sum").is_none());
+        assert!(StringLiteral::new(r#""unterminated"#).is_none());
+        assert!(StringLiteral::new("\"two\nlines\"").is_none());
+        assert!(StringLiteral::new(r#""\q""#).is_none());
+    }
+}
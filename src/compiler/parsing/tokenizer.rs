@@ -3,22 +3,25 @@ use std::error::Error;
 use std::str::Chars;
 use std::string;
 
-// TODO: Implement this shit
-pub struct StringLiteral;
 use super::super::operators::*;
 use super::number_parser::*;
+use super::string_parser::StringLiteral;
 
 #[derive(Debug, Clone)]
 pub enum TokenData {
     Keyword(Keyword),
     TextCluster(Option<String> /*Only None on pattern match constants*/),
     NumberLiteral(NumberLiteral),
-    // StringLiteral(StringLiteral),
+    StringLiteral(StringLiteral),
     Whitespace(String),
     Operator(Operator),
     Semicolon,
     Colon,
     AtSign, // @
+    Dot, // .
+    ColonSign, // :
+    Label(String), // `'outer`, without the quote
+    DocComment(String), // `///` up to the end of the line, without the slashes
 
     // Starting char, token contents
     Bracket(
@@ -44,6 +47,10 @@ impl PartialEq for TokenData{
                     _ => false
                 }
             },
+            TokenData::StringLiteral(sl) => match other {
+                TokenData::StringLiteral(sl2) => sl == sl2,
+                _ => false
+            },
             TokenData::Whitespace(_) => matches!(other, TokenData::Whitespace(_)),
             TokenData::Operator(opr) => match other {
                 TokenData::Operator(opr2) => opr == opr2,
@@ -51,7 +58,14 @@ impl PartialEq for TokenData{
             },
             TokenData::Semicolon => matches!(other, TokenData::Semicolon),
             TokenData::AtSign => matches!(other, TokenData::AtSign),
+            TokenData::Dot => matches!(other, TokenData::Dot),
             TokenData::Colon => matches!(other, TokenData::Colon),
+            TokenData::ColonSign => matches!(other, TokenData::ColonSign),
+            TokenData::Label(name) => match other {
+                TokenData::Label(name2) => name == name2,
+                _ => false
+            },
+            TokenData::DocComment(_) => matches!(other, TokenData::DocComment(_)),
             TokenData::Bracket(c, _) => match other {
                 TokenData::Bracket(c2, _) => *c == *c2,
                 _ => false
@@ -107,7 +121,7 @@ fn detect_text_cluster(s: &str) -> Option<usize> {
     let mut chars = s.chars();
     let first = chars.next().unwrap();
 
-    if (first.is_whitespace() || first.is_ascii_punctuation() || first.is_ascii_digit()) {
+    if (first.is_whitespace() || (first != '_' && first.is_ascii_punctuation()) || first.is_ascii_digit()) {
         return None;
     }
     let mut count = 1;
@@ -153,6 +167,25 @@ pub fn tokenize_text(text: String) -> Result<Vec<Token>, ParsingError> {
             continue;
         }
 
+        if text[index..].starts_with("//") {
+            let amount = text[index..].find('\n').unwrap_or(text.len() - index);
+            let comment = &text[index..index + amount];
+            // Plain comments are just whitespace to the grammar, `////...` banners are not doc comments
+            let data = match comment.strip_prefix("///") {
+                Some(doc) if !doc.starts_with('/') => TokenData::DocComment(doc.to_string()),
+                _ => TokenData::Whitespace(comment.to_string()),
+            };
+            tokenStack.push(Token {
+                index,
+                length: amount,
+                data,
+            });
+
+            index += amount;
+            isAfterWhitespace = true;
+            continue;
+        }
+
         if (is_opening_bracket(current)) {
             let closing = opening_to_closing(current);
 
@@ -161,15 +194,17 @@ pub fn tokenize_text(text: String) -> Result<Vec<Token>, ParsingError> {
             tokenStack = Vec::new();
             waiting_for_ending = closing;
 
-            canBePreUnary = true;
             index += 1;
+            // `(*p)`, what follows an opening bracket starts an expression
+            canBePreUnary = true;
+            continue;
         } else if (current == waiting_for_ending) {
             let (start, opening, mut oldStack, old_wait) =
                 bracketStack.pop().ok_or(ParsingError::BracketCountError)?;
 
             oldStack.push(Token {
                 index: start,
-                length: index - start,
+                length: index - start + 1,
                 data: TokenData::Bracket(opening, Some(tokenStack)),
             });
             tokenStack = oldStack;
@@ -202,6 +237,33 @@ pub fn tokenize_text(text: String) -> Result<Vec<Token>, ParsingError> {
             index += 1;
             canBePreUnary = true;
             continue;
+        } else if (current == b':') {
+            tokenStack.push(Token {
+                index,
+                length: 1,
+                data: TokenData::ColonSign,
+            });
+            index += 1;
+            canBePreUnary = true;
+            continue;
+        } else if (current == b'\'') {
+            // Loop labels, `'outer: while (...)`
+            let length = detect_text_cluster(&text[index + 1..]).ok_or(ParsingError::UnknownTokenizationError)?;
+            tokenStack.push(Token {
+                index,
+                length: length + 1,
+                data: TokenData::Label(text[index + 1..index + 1 + length].to_string()),
+            });
+            index += length + 1;
+        } else if (current == b'.' && !text[index..].starts_with("..")) {
+            tokenStack.push(Token {
+                index,
+                length: 1,
+                data: TokenData::Dot,
+            });
+            index += 1;
+            canBePreUnary = true;
+            continue;
         } else if let Some((length, numberLiteral)) = NumberLiteral::new(&text[index..]) {
             debug_assert_ne!(length, 0);
 
@@ -211,6 +273,14 @@ pub fn tokenize_text(text: String) -> Result<Vec<Token>, ParsingError> {
                 data: TokenData::NumberLiteral(numberLiteral),
             });
 
+            index += length;
+        } else if let Some((length, stringLiteral)) = StringLiteral::new(&text[index..]) {
+            tokenStack.push(Token {
+                index,
+                length,
+                data: TokenData::StringLiteral(stringLiteral),
+            });
+
             index += length;
         } else if let Some((length, opr)) = operator_test(
             &text[index..],
@@ -231,7 +301,29 @@ pub fn tokenize_text(text: String) -> Result<Vec<Token>, ParsingError> {
             debug_assert_ne!(length, 0);
             let cluster = &text[index..index + length].to_lowercase();
             let possible_keyword = Keyword::try_from_string(&cluster);
-            if let Some(keyword) = possible_keyword {
+            let starts_expression = matches!(
+                possible_keyword,
+                Some(Keyword::Return | Keyword::Break | Keyword::Defer | Keyword::Else | Keyword::Do | Keyword::In)
+            );
+            // `else if` is a single keyword with whitespace inside it
+            let else_if = match possible_keyword {
+                Some(Keyword::Else) => {
+                    let after = index + length;
+                    let gap = count_whitespace_indexes(&text[after..]);
+                    let next = detect_text_cluster(&text[after + gap..]);
+                    let is_if = text[after + gap..].get(..2).is_some_and(|word| word.eq_ignore_ascii_case("if"));
+                    (gap > 0 && next == Some(2) && is_if).then_some(gap + 2)
+                }
+                _ => None,
+            };
+            if let Some(extra) = else_if {
+                tokenStack.push(Token {
+                    index,
+                    length: length + extra,
+                    data: TokenData::Keyword(Keyword::ElseIf),
+                });
+                index += extra;
+            } else if let Some(keyword) = possible_keyword {
                 tokenStack.push(Token {
                     index,
                     length,
@@ -246,6 +338,12 @@ pub fn tokenize_text(text: String) -> Result<Vec<Token>, ParsingError> {
             }
 
             index += length;
+            // What follows these keywords is a new expression or statement, `return *p;` dereferences
+            if starts_expression {
+                isAfterWhitespace = false;
+                canBePreUnary = true;
+                continue;
+            }
         } else {
             println!("Unknown {} at {}", current, index);
             return Err(ParsingError::UnknownTokenizationError);
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use super::{table::const_eval, SemanticError, SemanticResult};
+use crate::compiler::treegen::ast::{AssocItem, Decorator, Expr, ExprKind, FnDecl, Item, TypeExprKind, VariantPayload};
+
+// What a decorator is written on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Function,
+    // A function in a trait or impl body
+    Method,
+    Struct,
+    Enum,
+    Variant,
+    Trait,
+    Impl,
+    Field,
+}
+
+impl Target {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::Function => "a function",
+            Target::Method => "a method",
+            Target::Struct => "a struct",
+            Target::Enum => "an enum",
+            Target::Variant => "a variant",
+            Target::Trait => "a trait",
+            Target::Impl => "an impl",
+            Target::Field => "a field",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    // `@name` or `@name()`
+    None,
+    // `@name(n)`, a constant integer
+    Integer,
+    // `@name` or `@name("text")`
+    OptionalMessage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoratorSpec {
+    pub name: &'static str,
+    pub targets: &'static [Target],
+    pub args: ArgKind,
+    // How the arguments are described when they are wrong, `@name takes ...`
+    pub usage: &'static str,
+}
+
+const FUNCTIONS: &[Target] = &[Target::Function, Target::Method];
+
+pub const BUILTIN_DECORATORS: &[DecoratorSpec] = &[
+    DecoratorSpec { name: "inline", targets: FUNCTIONS, args: ArgKind::None, usage: "no arguments" },
+    DecoratorSpec { name: "cold", targets: FUNCTIONS, args: ArgKind::None, usage: "no arguments" },
+    DecoratorSpec { name: "export", targets: &[Target::Function], args: ArgKind::None, usage: "no arguments" },
+    DecoratorSpec { name: "test", targets: &[Target::Function], args: ArgKind::None, usage: "no arguments" },
+    DecoratorSpec { name: "deprecated", targets: FUNCTIONS, args: ArgKind::OptionalMessage, usage: "at most one argument, a string saying why" },
+    DecoratorSpec { name: "packed", targets: &[Target::Struct], args: ArgKind::None, usage: "no arguments" },
+    DecoratorSpec { name: "align", targets: &[Target::Struct], args: ArgKind::Integer, usage: "one argument, the alignment in bytes" },
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    // Shown where it is used, usually what to use instead
+    pub message: Option<String>,
+}
+
+// What the decorators of one declaration ask of later stages
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attributes {
+    pub inline: bool,
+    // Rarely called, kept out of the hot path
+    pub cold: bool,
+    // Keeps its name in the output so other programs can call it
+    pub export: bool,
+    pub test: bool,
+    pub deprecated: Option<Deprecation>,
+    // No padding between fields
+    pub packed: bool,
+    pub align: Option<usize>,
+}
+
+impl Attributes {
+    // The decorators of a function that are set, in the order of the fields
+    pub fn function_names(&self) -> Vec<&'static str> {
+        [("inline", self.inline), ("cold", self.cold), ("export", self.export), ("test", self.test), ("deprecated", self.deprecated.is_some())]
+            .into_iter()
+            .filter_map(|(name, set)| set.then_some(name))
+            .collect()
+    }
+}
+
+// The decorators a program can use, the built in ones and any registered on top
+#[derive(Debug, Clone)]
+pub struct DecoratorRegistry {
+    specs: HashMap<&'static str, DecoratorSpec>,
+}
+
+impl Default for DecoratorRegistry {
+    fn default() -> Self {
+        Self { specs: BUILTIN_DECORATORS.iter().map(|spec| (spec.name, *spec)).collect() }
+    }
+}
+
+impl DecoratorRegistry {
+    // Replaces a decorator of the same name
+    pub fn register(&mut self, spec: DecoratorSpec) {
+        self.specs.insert(spec.name, spec);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DecoratorSpec> {
+        self.specs.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.specs.keys().copied()
+    }
+
+    // The effects of the decorators this registry knows, unknown ones are left to `check`
+    pub fn attributes(&self, decorators: &[Decorator]) -> SemanticResult<Attributes> {
+        let mut attributes = Attributes::default();
+        for decorator in decorators {
+            let Some(spec) = self.get(&decorator.name.name) else { continue };
+            let args = decorator.args.as_deref().unwrap_or_default();
+            let wrong_args = || SemanticError::new(format!("`@{}` takes {}", spec.name, spec.usage), decorator.span);
+            let (mut value, mut message) = (None, None);
+            match (spec.args, args) {
+                (ArgKind::None, []) | (ArgKind::OptionalMessage, []) => {}
+                (ArgKind::Integer, [arg]) => value = Some(const_eval(arg).map_err(|_| wrong_args())?),
+                (ArgKind::OptionalMessage, [Expr { kind: ExprKind::Str(arg), .. }]) => message = Some(arg.string_text_contents.clone()),
+                _ => return Err(wrong_args()),
+            }
+            match spec.name {
+                "inline" => attributes.inline = true,
+                "cold" => attributes.cold = true,
+                "export" => attributes.export = true,
+                "test" => attributes.test = true,
+                "deprecated" => attributes.deprecated = Some(Deprecation { message }),
+                "packed" => attributes.packed = true,
+                "align" => {
+                    let align = value.unwrap_or_default();
+                    if align <= 0 || (align & (align - 1)) != 0 {
+                        return Err(SemanticError::new(format!("alignment must be a power of two, not {}", align), decorator.span));
+                    }
+                    attributes.align = Some(align as usize);
+                }
+                // Registered on top, nothing in the compiler acts on it
+                _ => {}
+            }
+        }
+        Ok(attributes)
+    }
+
+    // Unknown decorators, decorators on the wrong kind of declaration and ones that contradict each other
+    pub fn check(&self, decorators: &[Decorator], target: Target) -> Vec<SemanticError> {
+        let mut errors = Vec::new();
+        for (i, decorator) in decorators.iter().enumerate() {
+            let name = &decorator.name.name;
+            let Some(spec) = self.get(name) else {
+                errors.push(SemanticError::new(format!("unknown decorator `@{}`", name), decorator.name.span));
+                continue;
+            };
+            if !spec.targets.contains(&target) {
+                let allowed = spec.targets.iter().map(Target::name).collect::<Vec<_>>().join(" or ");
+                let message = format!("`@{}` can't be used on {}, only on {}", name, target.name(), allowed);
+                errors.push(SemanticError::new(message, decorator.span));
+            }
+            if let Some(first) = decorators[..i].iter().find(|other| other.name.name == *name) {
+                errors.push(
+                    SemanticError::new(format!("`@{}` is written more than once", name), decorator.span).with_note(first.span, "first written here"),
+                );
+            }
+        }
+        match self.attributes(decorators) {
+            Ok(attributes) if attributes.inline && attributes.cold => {
+                let span = decorators.iter().find(|decorator| decorator.name.name == "cold").unwrap().span;
+                errors.push(SemanticError::new("a function can't be both `@inline` and `@cold`", span));
+            }
+            Ok(_) => {}
+            Err(error) => errors.push(error),
+        }
+        errors
+    }
+
+    // Every decorator in the program, with the extra rules for functions
+    pub fn check_items(&self, items: &[Item]) -> Result<(), Vec<SemanticError>> {
+        let mut errors = Vec::new();
+        for item in items {
+            match item {
+                Item::Struct(decl) => {
+                    errors.extend(self.check(&decl.decorators, Target::Struct));
+                    for field in &decl.fields {
+                        errors.extend(self.check(&field.decorators, Target::Field));
+                    }
+                }
+                Item::Enum(decl) => {
+                    errors.extend(self.check(&decl.decorators, Target::Enum));
+                    for variant in &decl.variants {
+                        errors.extend(self.check(&variant.decorators, Target::Variant));
+                        if let VariantPayload::Struct(fields) = &variant.payload {
+                            for field in fields {
+                                errors.extend(self.check(&field.decorators, Target::Field));
+                            }
+                        }
+                    }
+                }
+                Item::Trait(decl) => {
+                    errors.extend(self.check(&decl.decorators, Target::Trait));
+                    errors.extend(self.check_assoc(&decl.items));
+                }
+                Item::Impl(decl) => {
+                    errors.extend(self.check(&decl.decorators, Target::Impl));
+                    errors.extend(self.check_assoc(&decl.items));
+                }
+                Item::Function(decl) => errors.extend(self.check_fn(decl, Target::Function)),
+                Item::Var(_) | Item::Error(..) => {}
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check_assoc(&self, items: &[AssocItem]) -> Vec<SemanticError> {
+        items
+            .iter()
+            .flat_map(|item| match item {
+                AssocItem::Method(decl) => self.check_fn(decl, Target::Method),
+                AssocItem::Type(_) | AssocItem::Const(_) => Vec::new(),
+            })
+            .collect()
+    }
+
+    fn check_fn(&self, decl: &FnDecl, target: Target) -> Vec<SemanticError> {
+        let mut errors = self.check(&decl.decorators, target);
+        let Ok(attributes) = self.attributes(&decl.decorators) else { return errors };
+        let generic = !decl.generics.is_empty();
+        if attributes.export && generic {
+            errors.push(SemanticError::new("a generic function can't be `@export`, it has no single symbol to export", decl.name.span));
+        }
+        if attributes.test {
+            // The test runner calls it with nothing and expects nothing back
+            let returns_unit = matches!(&decl.return_type.kind, TypeExprKind::Tuple(items) if items.is_empty());
+            if !decl.params.is_empty() || generic || !returns_unit {
+                errors.push(SemanticError::new("a `@test` function must look like `() name()`", decl.name.span));
+            }
+            if decl.body.is_none() {
+                errors.push(SemanticError::new("a `@test` function needs a body", decl.name.span));
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::items::parse_items};
+
+    fn parse(text: &str) -> Vec<Item> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        parse_items(&tokens, text.len()).unwrap()
+    }
+
+    fn errors(text: &str) -> Vec<String> {
+        match DecoratorRegistry::default().check_items(&parse(text)) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.message).collect(),
+        }
+    }
+
+    #[test]
+    fn builtin_attributes() {
+        let items = parse("@inline @deprecated(\"use sum\") i32 add(i32 a, i32 b) => a + b; @cold @export @deprecated () fail() { }");
+        let [Item::Function(add), Item::Function(fail)] = &items[..] else { panic!() };
+        let registry = DecoratorRegistry::default();
+        let add = registry.attributes(&add.decorators).unwrap();
+        assert!(add.inline && !add.cold);
+        assert_eq!(add.deprecated, Some(Deprecation { message: Some("use sum".to_string()) }));
+        let fail = registry.attributes(&fail.decorators).unwrap();
+        assert!(fail.cold && fail.export && fail.deprecated.is_some());
+
+        assert!(errors("@test () runs() { } @packed @align(8) struct S { u8 a } impl S { @inline () get(Self* self) { } }").is_empty());
+    }
+
+    #[test]
+    fn decorator_errors() {
+        assert_eq!(errors("@fast () f() { }"), ["unknown decorator `@fast`"]);
+        assert_eq!(errors("@packed () f() { }"), ["`@packed` can't be used on a function, only on a struct"]);
+        assert_eq!(errors("struct S { @inline u8 a }"), ["`@inline` can't be used on a field, only on a function or a method"]);
+        assert_eq!(errors("impl S { @test () t() { } }"), ["`@test` can't be used on a method, only on a function"]);
+        assert_eq!(errors("@inline @inline () f() { }"), ["`@inline` is written more than once"]);
+        assert_eq!(errors("@inline @cold () f() { }"), ["a function can't be both `@inline` and `@cold`"]);
+        assert_eq!(errors("@inline(2) () f() { }"), ["`@inline` takes no arguments"]);
+        assert_eq!(errors("@deprecated(1 + 2) () f() { }"), ["`@deprecated` takes at most one argument, a string saying why"]);
+        assert_eq!(errors("@deprecated(sum) () f() { }"), ["`@deprecated` takes at most one argument, a string saying why"]);
+        assert_eq!(errors("@align(\"8\") struct S { u8 a }"), ["`@align` takes one argument, the alignment in bytes"]);
+        assert_eq!(errors("@align(3) struct S { u8 a }"), ["alignment must be a power of two, not 3"]);
+        assert_eq!(errors("@export T id<T>(T value) => value;"), ["a generic function can't be `@export`, it has no single symbol to export"]);
+        assert_eq!(errors("@test bool t(u8 a) => 1 < 2;"), ["a `@test` function must look like `() name()`"]);
+    }
+
+    #[test]
+    fn registered_decorators() {
+        let mut registry = DecoratorRegistry::default();
+        registry.register(DecoratorSpec { name: "serialize", targets: &[Target::Struct, Target::Field], args: ArgKind::None, usage: "no arguments" });
+        let items = parse("@serialize struct S { @serialize u8 a }");
+        assert!(registry.check_items(&items).is_ok());
+        let Item::Struct(decl) = &items[0] else { panic!() };
+        assert_eq!(registry.attributes(&decl.decorators), Ok(Attributes::default()));
+    }
+}
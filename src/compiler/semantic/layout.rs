@@ -0,0 +1,220 @@
+use super::{
+    table::{EnumInfo, PayloadInfo, StructInfo, TypeTable},
+    SemanticError, SemanticResult,
+};
+use crate::compiler::{
+    objects::{gurn_objects::Primitive, types::Ty},
+    treegen::ast::Span,
+};
+
+// Size of pointers, `isize` and `usize` on the target
+pub const POINTER_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    pub layout: Layout,
+    // Offset of every field, in declaration order
+    pub offsets: Vec<usize>,
+}
+
+// The tag comes first, followed by the largest payload of any variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumLayout {
+    pub layout: Layout,
+    pub tag: Primitive,
+    pub payload_offset: usize,
+    pub payload_size: usize,
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+fn primitive_layout(primitive: &Primitive) -> Layout {
+    let size = primitive.to_size().unwrap_or(POINTER_SIZE);
+    Layout { size, align: size }
+}
+
+// Lays out values one after another, `packed` leaves out the padding
+fn sequential(layouts: &[Layout], packed: bool) -> StructLayout {
+    let mut offsets = Vec::with_capacity(layouts.len());
+    let mut offset = 0;
+    let mut align = 1;
+    for layout in layouts {
+        if !packed {
+            offset = align_up(offset, layout.align);
+            align = align.max(layout.align);
+        }
+        offsets.push(offset);
+        offset += layout.size;
+    }
+    StructLayout { layout: Layout { size: align_up(offset, align), align }, offsets }
+}
+
+pub struct LayoutContext<'a> {
+    table: &'a TypeTable,
+    // Types being laid out, to catch types that contain themselves
+    in_progress: Vec<String>,
+}
+
+impl<'a> LayoutContext<'a> {
+    pub fn new(table: &'a TypeTable) -> Self {
+        Self { table, in_progress: Vec::new() }
+    }
+
+    pub fn layout_of(&mut self, ty: &Ty) -> SemanticResult<Layout> {
+        Ok(match ty {
+            Ty::Primitive(primitive) => primitive_layout(primitive),
+            Ty::Bool => Layout { size: 1, align: 1 },
+            Ty::Pointer(_) | Ty::FnPointer(..) => Layout { size: POINTER_SIZE, align: POINTER_SIZE },
+            // The function and a pointer to the captured values
+            Ty::Function(..) => Layout { size: 2 * POINTER_SIZE, align: POINTER_SIZE },
+            Ty::Tuple(items) => {
+                let layouts = items.iter().map(|item| self.layout_of(item)).collect::<SemanticResult<Vec<_>>>()?;
+                sequential(&layouts, false).layout
+            }
+            Ty::Param(name) => {
+                return Err(SemanticError::new(format!("`{}` has no layout until it is instantiated", name), Default::default()));
+            }
+            Ty::Named(name, args) => {
+                if let Some(info) = self.table.structs.get(name) {
+                    self.struct_layout(info, args)?.layout
+                } else if let Some(info) = self.table.enums.get(name) {
+                    self.enum_layout(info, args)?.layout
+                } else {
+                    return Err(SemanticError::new(format!("unknown type `{}`", name), Default::default()));
+                }
+            }
+        })
+    }
+
+    fn enter(&mut self, name: &str, span: Span) -> SemanticResult<()> {
+        if self.in_progress.iter().any(|other| other == name) {
+            return Err(SemanticError::new(format!("`{}` contains itself, put it behind a pointer", name), span));
+        }
+        self.in_progress.push(name.to_string());
+        Ok(())
+    }
+
+    pub fn struct_layout(&mut self, info: &StructInfo, args: &[Ty]) -> SemanticResult<StructLayout> {
+        self.enter(&info.name, info.span)?;
+        let layouts = info
+            .fields
+            .iter()
+            .map(|field| self.layout_of(&field.ty.substitute(&info.generics, args)).map_err(|error| with_span(error, field.span)))
+            .collect::<SemanticResult<Vec<_>>>();
+        self.in_progress.pop();
+
+        let mut layout = sequential(&layouts?, info.packed);
+        if info.packed {
+            layout.layout.align = 1;
+        }
+        if let Some(align) = info.align {
+            layout.layout.align = layout.layout.align.max(align);
+            layout.layout.size = align_up(layout.layout.size, layout.layout.align);
+        }
+        Ok(layout)
+    }
+
+    pub fn enum_layout(&mut self, info: &EnumInfo, args: &[Ty]) -> SemanticResult<EnumLayout> {
+        self.enter(&info.name, info.span)?;
+        let payloads = info
+            .variants
+            .iter()
+            .map(|variant| {
+                let layouts = match &variant.payload {
+                    PayloadInfo::Unit => Vec::new(),
+                    PayloadInfo::Tuple(types) => types
+                        .iter()
+                        .map(|ty| self.layout_of(&ty.substitute(&info.generics, args)))
+                        .collect::<SemanticResult<_>>()?,
+                    PayloadInfo::Struct(fields) => fields
+                        .iter()
+                        .map(|field| self.layout_of(&field.ty.substitute(&info.generics, args)))
+                        .collect::<SemanticResult<_>>()?,
+                };
+                Ok(sequential(&layouts, false).layout)
+            })
+            .collect::<SemanticResult<Vec<_>>>();
+        self.in_progress.pop();
+        let payloads = payloads.map_err(|error| with_span(error, info.span))?;
+
+        let tag = primitive_layout(&info.tag);
+        let payload_size = payloads.iter().map(|payload| payload.size).max().unwrap_or(0);
+        let payload_align = payloads.iter().map(|payload| payload.align).max().unwrap_or(1);
+        let payload_offset = if payload_size == 0 { tag.size } else { align_up(tag.size, payload_align) };
+        let align = tag.align.max(payload_align);
+        Ok(EnumLayout {
+            layout: Layout { size: align_up(payload_offset + payload_size, align), align },
+            tag: info.tag.clone(),
+            payload_offset,
+            payload_size,
+        })
+    }
+}
+
+// Errors from nested types have no span of their own, point them at what contained them
+fn with_span(mut error: SemanticError, span: Span) -> SemanticError {
+    if error.span == Default::default() {
+        error.span = span;
+    }
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::items::parse_items};
+
+    fn table_of(text: &str) -> TypeTable {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        TypeTable::from_items(&parse_items(&tokens, text.len()).unwrap()).unwrap()
+    }
+
+    fn named(name: &str) -> Ty {
+        Ty::Named(name.to_string(), Vec::new())
+    }
+
+    #[test]
+    fn struct_layouts() {
+        let table = table_of("struct A { u8 a, u32 b, u8 c } @packed struct P { u8 a, u32 b } @align(16) struct W { u8 a }");
+        let mut context = LayoutContext::new(&table);
+        let a = context.struct_layout(&table.structs["A"], &[]).unwrap();
+        assert_eq!(a.offsets, [0, 4, 8]);
+        assert_eq!(a.layout, Layout { size: 12, align: 4 });
+        assert_eq!(context.struct_layout(&table.structs["P"], &[]).unwrap().layout, Layout { size: 5, align: 1 });
+        assert_eq!(context.layout_of(&named("W")).unwrap(), Layout { size: 16, align: 16 });
+    }
+
+    #[test]
+    fn enum_layouts() {
+        let table = table_of(
+            "enum u16 Color { Red, Green }
+             enum Shape { Circle(f64), Rect { u8 w, u8 h }, Empty }
+             enum Small { A(u8), B }",
+        );
+        let mut context = LayoutContext::new(&table);
+        assert_eq!(context.layout_of(&named("Color")).unwrap(), Layout { size: 2, align: 2 });
+
+        let shape = context.enum_layout(&table.enums["Shape"], &[]).unwrap();
+        assert_eq!(shape.tag, Primitive::U8);
+        assert_eq!((shape.payload_offset, shape.payload_size), (8, 8));
+        assert_eq!(shape.layout, Layout { size: 16, align: 8 });
+
+        assert_eq!(context.layout_of(&named("Small")).unwrap(), Layout { size: 2, align: 1 });
+    }
+
+    #[test]
+    fn recursive_types() {
+        let table = table_of("struct List { u8 value, List next } struct Ok { u8 value, Ok* next }");
+        let mut context = LayoutContext::new(&table);
+        assert_eq!(context.layout_of(&named("List")).unwrap_err().message, "`List` contains itself, put it behind a pointer");
+        assert_eq!(context.layout_of(&named("Ok")).unwrap(), Layout { size: 16, align: 8 });
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+use super::treegen::ast::Span;
+
+pub mod table;
+pub mod layout;
+pub mod typeck;
+pub mod traits;
+pub mod decorators;
+pub mod resolve;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticError {
+    pub message: String,
+    pub span: Span,
+    // Other places that explain the error
+    pub notes: Vec<(Span, String)>,
+}
+
+impl SemanticError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self { message: message.into(), span, notes: Vec::new() }
+    }
+
+    pub fn with_note(mut self, span: Span, note: impl Into<String>) -> Self {
+        self.notes.push((span, note.into()));
+        self
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}: {}", self.span.start, self.span.end, self.message)?;
+        for (span, note) in &self.notes {
+            write!(f, "\n    {}..{}: note: {}", span.start, span.end, note)?;
+        }
+        Ok(())
+    }
+}
+
+pub type SemanticResult<T> = Result<T, SemanticError>;
@@ -0,0 +1,537 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{decorators::DecoratorRegistry, SemanticError};
+use crate::compiler::{
+    objects::{
+        gurn_objects::Primitive,
+        symbols::{KnownSymbolUsage, ResolvedSymbol, SymbolContext, UnresolvedSymbol},
+    },
+    state::{Definition, ScopeData, ScopeDataVariety},
+    treegen::{
+        ast::*,
+        visit::{walk, Visit},
+    },
+};
+
+const SELF: &str = "Self";
+
+// Types that are always there, besides the primitives
+const BUILTIN_TYPES: [&str; 2] = ["bool", "void"];
+
+// The scope tree of a file and what every name in it refers to
+#[derive(Debug)]
+pub struct Resolution {
+    // The built in scope, the file's scope is nested in it
+    pub root: Rc<RefCell<ScopeData>>,
+    // Every name that was found, in the order they were looked up
+    pub symbols: Vec<ResolvedSymbol>,
+    // The index in `symbols` of the name an expression or pattern uses, by the id of that node
+    pub nodes: HashMap<NodeId, usize>,
+    pub errors: Vec<SemanticError>,
+}
+
+impl Resolution {
+    // The name used by the expression or pattern `id`
+    pub fn symbol_at(&self, id: NodeId) -> Option<&ResolvedSymbol> {
+        self.nodes.get(&id).map(|&index| &self.symbols[index])
+    }
+}
+
+// Builds the scopes of `items` and resolves every name used in them. Declarations in the file, and in a trait
+// or impl, can be used before they are written, variables only after. Fields and methods depend on types, so
+// `value.name` is left to the type checker
+pub fn resolve_items(items: &[Item], decorators: &DecoratorRegistry) -> Resolution {
+    let root = ScopeData::new_scope(ScopeDataVariety::Builtin, None, Span::default());
+    {
+        let mut root = root.borrow_mut();
+        let types = Primitive::all().map(|primitive| primitive.name()).chain(BUILTIN_TYPES);
+        for name in types {
+            root.declare(KnownSymbolUsage::Type, name, Rc::new(RefCell::new(ScopeData::Builtin(name.to_string()))));
+        }
+        for name in decorators.names() {
+            root.declare(KnownSymbolUsage::Macro, name, Rc::new(RefCell::new(ScopeData::Builtin(name.to_string()))));
+        }
+    }
+    let span = items.iter().map(item_span).reduce(Span::to).unwrap_or_default();
+    let file = ScopeData::new_scope(ScopeDataVariety::File, Some(&root), span);
+
+    let mut resolver = Resolver { scope: file, symbols: Vec::new(), nodes: HashMap::new(), errors: Vec::new() };
+    for item in items {
+        resolver.declare_item(item);
+    }
+    for item in items {
+        resolver.visit_item(item);
+    }
+    Resolution { root, symbols: resolver.symbols, nodes: resolver.nodes, errors: resolver.errors }
+}
+
+fn item_span(item: &Item) -> Span {
+    match item {
+        Item::Struct(decl) => decl.span,
+        Item::Enum(decl) => decl.span,
+        Item::Trait(decl) => decl.span,
+        Item::Impl(decl) => decl.span,
+        Item::Function(decl) => decl.span,
+        Item::Var(decl) => decl.span,
+        Item::Error(_, span) => *span,
+    }
+}
+
+fn definition(name: &Ident) -> Definition {
+    Definition { name: name.name.clone(), span: name.span }
+}
+
+// A `const` or `static` is a constant, anything else a variable
+fn var_symbol(decl: &VarDecl) -> ScopeData {
+    match (decl.storage, decl.mutability) {
+        (Storage::Local, Mutability::Var) => ScopeData::Variable(definition(&decl.name)),
+        _ => ScopeData::Constant(definition(&decl.name)),
+    }
+}
+
+struct Resolver {
+    scope: Rc<RefCell<ScopeData>>,
+    symbols: Vec<ResolvedSymbol>,
+    nodes: HashMap<NodeId, usize>,
+    errors: Vec<SemanticError>,
+}
+
+impl Resolver {
+    fn enter(&mut self, variety: ScopeDataVariety, span: Span) {
+        self.scope = ScopeData::new_scope(variety, Some(&self.scope), span);
+    }
+
+    fn leave(&mut self) {
+        let parent = self.scope.borrow().parent().unwrap();
+        self.scope = parent;
+    }
+
+    // Variables can hide one declared before them in the same scope, nothing else can
+    fn declare(&mut self, usage: KnownSymbolUsage, symbol: ScopeData) {
+        let definition = symbol.definition().unwrap().clone();
+        let shadows = matches!(symbol, ScopeData::Variable(_));
+        let replaced = self.scope.borrow_mut().declare(usage, &definition.name, Rc::new(RefCell::new(symbol)));
+        if let Some(replaced) = replaced {
+            let replaced = replaced.borrow();
+            let previous = replaced.definition().unwrap();
+            if !(shadows && matches!(*replaced, ScopeData::Variable(_))) {
+                self.errors.push(
+                    SemanticError::new(format!("`{}` is defined more than once", definition.name), definition.span)
+                        .with_note(previous.span, "first defined here"),
+                );
+            }
+        }
+    }
+
+    // Names in types and decorators have no node of their own
+    fn use_name(&mut self, name: &str, span: Span, node: Option<NodeId>, usage: Option<KnownSymbolUsage>) {
+        let symbol = UnresolvedSymbol { name: name.to_string(), span, context: SymbolContext { usage } };
+        match symbol.resolve(&self.scope) {
+            Ok(resolved) => {
+                if let Some(node) = node {
+                    self.nodes.insert(node, self.symbols.len());
+                }
+                self.symbols.push(resolved);
+            }
+            Err(symbol) => {
+                let what = match symbol.context.usage {
+                    Some(KnownSymbolUsage::Type) => "type",
+                    Some(KnownSymbolUsage::Function) => "function",
+                    Some(KnownSymbolUsage::Macro) => "decorator",
+                    Some(KnownSymbolUsage::Variable) | None => "name",
+                };
+                self.errors.push(SemanticError::new(format!("unknown {} `{}`", what, symbol.name), symbol.span));
+            }
+        }
+    }
+
+    fn use_ident(&mut self, ident: &Ident, usage: KnownSymbolUsage) {
+        self.use_name(&ident.name, ident.span, None, Some(usage));
+    }
+
+    // What the file declares, before any of it is resolved
+    fn declare_item(&mut self, item: &Item) {
+        match item {
+            Item::Struct(decl) => self.declare(KnownSymbolUsage::Type, ScopeData::Struct(definition(&decl.name))),
+            Item::Enum(decl) => self.declare(KnownSymbolUsage::Type, ScopeData::Enum(definition(&decl.name))),
+            Item::Trait(decl) => self.declare(KnownSymbolUsage::Type, ScopeData::Trait(definition(&decl.name))),
+            Item::Function(decl) => self.declare(KnownSymbolUsage::Function, ScopeData::Function(definition(&decl.name))),
+            Item::Var(decl) => self.declare(KnownSymbolUsage::Variable, var_symbol(decl)),
+            Item::Impl(_) | Item::Error(..) => {}
+        }
+    }
+
+    // The scope of a struct, enum, trait or impl, `Self` is the type being declared
+    fn enter_declaration(&mut self, span: Span, self_span: Span, generics: &[GenericParam]) {
+        self.enter(ScopeDataVariety::Declaration, span);
+        self.declare(KnownSymbolUsage::Type, ScopeData::TypeParam(Definition { name: SELF.to_string(), span: self_span }));
+        generics.iter().for_each(|param| self.visit_generic_param(param));
+    }
+
+    // Associated types can be used anywhere in their trait or impl
+    fn assoc_items(&mut self, items: &[AssocItem]) {
+        for item in items {
+            if let AssocItem::Type(decl) = item {
+                self.declare(KnownSymbolUsage::Type, ScopeData::TypeAlias(definition(&decl.name)));
+            }
+        }
+        items.iter().for_each(|item| self.visit_assoc_item(item));
+    }
+}
+
+impl Visit for Resolver {
+    fn visit_struct(&mut self, decl: &StructDecl) {
+        decl.decorators.iter().for_each(|decorator| self.visit_decorator(decorator));
+        self.enter_declaration(decl.span, decl.name.span, &decl.generics);
+        decl.implements.iter().for_each(|name| self.use_ident(name, KnownSymbolUsage::Type));
+        if let Some(clause) = &decl.where_clause {
+            self.visit_where_clause(clause);
+        }
+        decl.fields.iter().for_each(|field| self.visit_field(field));
+        self.leave();
+    }
+
+    fn visit_enum(&mut self, decl: &EnumDecl) {
+        decl.decorators.iter().for_each(|decorator| self.visit_decorator(decorator));
+        if let Some(backing) = &decl.backing {
+            self.visit_type(backing);
+        }
+        self.enter_declaration(decl.span, decl.name.span, &decl.generics);
+        if let Some(clause) = &decl.where_clause {
+            self.visit_where_clause(clause);
+        }
+        decl.variants.iter().for_each(|variant| self.visit_variant(variant));
+        self.leave();
+    }
+
+    fn visit_trait(&mut self, decl: &TraitDecl) {
+        decl.decorators.iter().for_each(|decorator| self.visit_decorator(decorator));
+        self.enter_declaration(decl.span, decl.name.span, &decl.generics);
+        if let Some(clause) = &decl.where_clause {
+            self.visit_where_clause(clause);
+        }
+        self.assoc_items(&decl.items);
+        self.leave();
+    }
+
+    fn visit_impl(&mut self, decl: &ImplDecl) {
+        decl.decorators.iter().for_each(|decorator| self.visit_decorator(decorator));
+        self.enter_declaration(decl.span, decl.self_ty.span, &decl.generics);
+        if let Some(name) = &decl.trait_name {
+            self.use_ident(name, KnownSymbolUsage::Type);
+        }
+        self.visit_type(&decl.self_ty);
+        if let Some(clause) = &decl.where_clause {
+            self.visit_where_clause(clause);
+        }
+        self.assoc_items(&decl.items);
+        self.leave();
+    }
+
+    fn visit_fn(&mut self, decl: &FnDecl) {
+        decl.decorators.iter().for_each(|decorator| self.visit_decorator(decorator));
+        self.enter(ScopeDataVariety::Function, decl.span);
+        // Generics are written after the return type, but it can use them
+        decl.generics.iter().for_each(|param| self.visit_generic_param(param));
+        self.visit_type(&decl.return_type);
+        decl.params.iter().for_each(|param| self.visit_param(param));
+        if let Some(clause) = &decl.where_clause {
+            self.visit_where_clause(clause);
+        }
+        match &decl.body {
+            Some(FnBody::Block(block)) => self.visit_block(block),
+            Some(FnBody::Expr(expr)) => self.visit_expr(expr),
+            None => {}
+        }
+        self.leave();
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        self.visit_type(&param.ty);
+        self.declare(KnownSymbolUsage::Variable, ScopeData::Variable(definition(&param.name)));
+    }
+
+    fn visit_generic_param(&mut self, param: &GenericParam) {
+        match &param.kind {
+            GenericParamKind::Type(bounds) => {
+                self.declare(KnownSymbolUsage::Type, ScopeData::TypeParam(definition(&param.name)));
+                bounds.iter().for_each(|bound| self.use_ident(bound, KnownSymbolUsage::Type));
+            }
+            GenericParamKind::Const(ty) => {
+                self.visit_type(ty);
+                self.declare(KnownSymbolUsage::Variable, ScopeData::Constant(definition(&param.name)));
+            }
+        }
+    }
+
+    fn visit_where_predicate(&mut self, predicate: &WherePredicate) {
+        self.visit_type(&predicate.ty);
+        predicate.bounds.iter().for_each(|bound| self.use_ident(bound, KnownSymbolUsage::Type));
+    }
+
+    fn visit_decorator(&mut self, decorator: &Decorator) {
+        self.use_ident(&decorator.name, KnownSymbolUsage::Macro);
+        walk::walk_decorator(self, decorator);
+    }
+
+    fn visit_var(&mut self, decl: &VarDecl) {
+        walk::walk_var(self, decl);
+        // The file's variables were declared up front
+        if self.scope.borrow().variety() != Some(ScopeDataVariety::File) {
+            self.declare(KnownSymbolUsage::Variable, var_symbol(decl));
+        }
+    }
+
+    fn visit_type(&mut self, ty: &TypeExpr) {
+        match &ty.kind {
+            // There are no modules yet, a longer path fails on its first name
+            TypeExprKind::Path(segments, _) => self.use_ident(&segments[0], KnownSymbolUsage::Type),
+            TypeExprKind::Impl(name) => self.use_ident(name, KnownSymbolUsage::Type),
+            TypeExprKind::Array(_, ArrayLen::Param(name)) => self.use_ident(name, KnownSymbolUsage::Variable),
+            _ => {}
+        }
+        walk::walk_type(self, ty);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.enter(ScopeDataVariety::Block, block.span);
+        walk::walk_block(self, block);
+        self.leave();
+    }
+
+    fn visit_for(&mut self, lp: &ForLoop) {
+        self.enter(ScopeDataVariety::Loop, lp.span);
+        match &lp.kind {
+            ForKind::Clauses { init, cond, step } => {
+                if let Some(init) = init {
+                    self.visit_stmt(init);
+                }
+                if let Some(cond) = cond {
+                    self.visit_expr(cond);
+                }
+                if let Some(step) = step {
+                    self.visit_expr(step);
+                }
+            }
+            ForKind::In { binding, iterable } => {
+                self.visit_expr(iterable);
+                self.declare(KnownSymbolUsage::Variable, ScopeData::Variable(definition(binding)));
+            }
+        }
+        self.visit_block(&lp.body);
+        self.leave();
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Name(name) => self.use_name(name, expr.span, Some(expr.id), Some(KnownSymbolUsage::Variable)),
+            ExprKind::Call(callee, args) if matches!(callee.kind, ExprKind::Name(_)) => {
+                let ExprKind::Name(name) = &callee.kind else { unreachable!() };
+                self.use_name(name, callee.span, Some(callee.id), Some(KnownSymbolUsage::Function));
+                args.iter().for_each(|arg| self.visit_expr(arg));
+            }
+            // `Shape.Circle` names a type, `point.x` a value
+            ExprKind::Field(value, _) if matches!(value.kind, ExprKind::Name(_)) => {
+                let ExprKind::Name(name) = &value.kind else { unreachable!() };
+                self.use_name(name, value.span, Some(value.id), None);
+            }
+            _ => walk::walk_expr(self, expr),
+        }
+    }
+
+    fn visit_closure(&mut self, closure: &Closure) {
+        self.enter(ScopeDataVariety::Closure, closure.span);
+        walk::walk_closure(self, closure);
+        self.leave();
+    }
+
+    fn visit_closure_param(&mut self, param: &ClosureParam) {
+        if let Some(ty) = &param.ty {
+            self.visit_type(ty);
+        }
+        self.declare(KnownSymbolUsage::Variable, ScopeData::Variable(definition(&param.name)));
+    }
+
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        self.enter(ScopeDataVariety::Arm, arm.span);
+        walk::walk_match_arm(self, arm);
+        self.leave();
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Binding(name) => self.declare(KnownSymbolUsage::Variable, ScopeData::Variable(definition(name))),
+            PatternKind::Struct(name, _) | PatternKind::Variant(name, _, _) => {
+                self.use_name(&name.name, name.span, Some(pattern.id), Some(KnownSymbolUsage::Type))
+            }
+            _ => {}
+        }
+        walk::walk_pattern(self, pattern);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::items::parse_items};
+
+    fn resolve(text: &str) -> Resolution {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap_or_else(|error| panic!("{}\n{}", error, text));
+        resolve_items(&items, &DecoratorRegistry::default())
+    }
+
+    // Every use as `name@offset -> offset of its definition`, or `builtin`
+    fn uses(text: &str) -> Vec<String> {
+        let resolution = resolve(text);
+        assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+        resolution
+            .symbols
+            .iter()
+            .map(|symbol| {
+                let definition = symbol.definition.borrow();
+                let target = definition.definition().map_or("builtin".to_string(), |definition| definition.span.start.to_string());
+                format!("{}@{} -> {}", symbol.name, symbol.span.start, target)
+            })
+            .collect()
+    }
+
+    fn errors(text: &str) -> Vec<String> {
+        resolve(text).errors.iter().map(|error| error.message.clone()).collect()
+    }
+
+    #[test]
+    fn variables() {
+        //         0         1         2         3         4         5         6
+        //         0123456789012345678901234567890123456789012345678901234567890123456789
+        let text = "void f(u8 a) { var b = a; { var a = b; g(a); } a; } void g(u8 x) {}";
+        assert_eq!(
+            uses(text),
+            [
+                "void@0 -> builtin",
+                "u8@7 -> builtin",
+                "a@23 -> 10",
+                "b@36 -> 19",
+                "g@39 -> 57",
+                "a@41 -> 32",
+                "a@47 -> 10",
+                "void@52 -> builtin",
+                "u8@59 -> builtin",
+            ]
+        );
+        // Shadowing in the same scope, the value is resolved before the new variable exists
+        assert_eq!(uses("void f() { var x = 1; var x = x; x; }")[1..], ["x@30 -> 15", "x@33 -> 26"]);
+    }
+
+    #[test]
+    fn namespaces() {
+        //         0         1         2         3         4         5
+        //         0123456789012345678901234567890123456789012345678901234567
+        let text = "struct Point { u8 x } Point Point() { var Point = Point(); Point }";
+        assert_eq!(
+            uses(text),
+            ["u8@15 -> builtin", "Point@22 -> 7", "Point@50 -> 28", "Point@59 -> 42"]
+        );
+        let text = "enum Shape { Circle } u8 Shape() => 0; Shape f() => Shape.Circle;";
+        assert_eq!(uses(text)[1..], ["Shape@39 -> 5", "Shape@52 -> 5"]);
+        // A closure in a variable is called like a function, and hides the one outside
+        let text = "void g() {} void f() { var g = fn(x) => x; g(1); }";
+        assert_eq!(uses(text)[2..], ["x@40 -> 34", "g@43 -> 27"]);
+    }
+
+    #[test]
+    fn declarations() {
+        let text = "
+            @packed struct Pair<T: Copy> implements Copy where T: Copy { T a, Self b }
+            trait Copy { typedef Item; Item get(Self value); }
+            impl<T> Copy for Pair<T> { typedef T Item; Item get(Self value) => value.a; }
+            u8 first<const usize N>([u8; N] items) => match (items) { (x, _) => x };
+        ";
+        let resolution = resolve(text);
+        assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+        let found: Vec<String> = resolution
+            .symbols
+            .iter()
+            .map(|symbol| format!("{}:{}", symbol.name, match &*symbol.definition.borrow() {
+                ScopeData::Builtin(_) => "builtin",
+                ScopeData::Struct(_) => "struct",
+                ScopeData::Trait(_) => "trait",
+                ScopeData::TypeParam(_) => "param",
+                ScopeData::TypeAlias(_) => "alias",
+                ScopeData::Constant(_) => "const",
+                ScopeData::Variable(_) => "var",
+                _ => "other",
+            }))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "packed:builtin", "Copy:trait", "Copy:trait", "T:param", "Copy:trait", "T:param", "Self:param",
+                "Item:alias", "Self:param",
+                "Copy:trait", "Pair:struct", "T:param", "T:param", "Item:alias", "Self:param", "value:var",
+                "usize:builtin", "u8:builtin", "N:const", "u8:builtin", "items:var", "x:var",
+            ]
+        );
+    }
+
+    // The names written as expressions and patterns, with the node that writes them
+    struct Uses(Vec<(NodeId, String)>);
+
+    impl Visit for Uses {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Name(name) = &expr.kind {
+                self.0.push((expr.id, name.clone()));
+            }
+            walk::walk_expr(self, expr);
+        }
+
+        fn visit_pattern(&mut self, pattern: &Pattern) {
+            if let PatternKind::Struct(name, _) | PatternKind::Variant(name, _, _) = &pattern.kind {
+                self.0.push((pattern.id, name.name.clone()));
+            }
+            walk::walk_pattern(self, pattern);
+        }
+    }
+
+    #[test]
+    fn symbols_by_node() {
+        let text = "enum E { A(u8) } u8 g(u8 a) => a; u8 f(E e) { var x = g(1); match (e) { E.A(v) => v + x } }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap();
+        let resolution = resolve_items(&items, &DecoratorRegistry::default());
+        assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+        let mut uses = Uses(Vec::new());
+        items.iter().for_each(|item| uses.visit_item(item));
+        assert_eq!(uses.0.len(), 6);
+        for (id, name) in uses.0 {
+            assert_eq!(resolution.symbol_at(id).map(|symbol| symbol.name.as_str()), Some(name.as_str()));
+        }
+        assert!(resolution.symbol_at(NodeId::UNSET).is_none());
+    }
+
+    #[test]
+    fn scope_tree() {
+        let resolution = resolve("void f(u8 a) { for (x in a) { } } struct S {}");
+        let file = resolution.root.borrow().nested()[0].clone();
+        let varieties = |scope: &Rc<RefCell<ScopeData>>| scope.borrow().nested().iter().map(|scope| scope.borrow().variety().unwrap()).collect::<Vec<_>>();
+        assert_eq!(varieties(&file), [ScopeDataVariety::Function, ScopeDataVariety::Declaration]);
+        let function = file.borrow().nested()[0].clone();
+        let body = function.borrow().nested()[0].clone();
+        assert_eq!(varieties(&body), [ScopeDataVariety::Loop]);
+        // Parents are reachable from below
+        let parent = body.borrow().parent().unwrap();
+        assert!(Rc::ptr_eq(&parent, &function));
+    }
+
+    #[test]
+    fn resolution_errors() {
+        assert_eq!(errors("void f() { var x = y; var y = 1; }"), ["unknown name `y`"]);
+        assert_eq!(errors("Thing f() => g();"), ["unknown type `Thing`", "unknown function `g`"]);
+        assert_eq!(errors("@nope void f() {}"), ["unknown decorator `nope`"]);
+        assert_eq!(errors("void f() {} void f() {}"), ["`f` is defined more than once"]);
+        assert_eq!(errors("void f(u8 a, u8 a) {}"), Vec::<String>::new());
+        assert_eq!(errors("struct P<T, T> {}"), ["`T` is defined more than once"]);
+        // Out of its scope
+        assert_eq!(errors("u8 f() { { var x = 1; } x }"), ["unknown name `x`"]);
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{decorators::DecoratorRegistry, SemanticError, SemanticResult};
+use crate::compiler::{
+    objects::{gurn_objects::Primitive, types::{Abi, Ty}},
+    operators::Operator,
+    parsing::tokenizer::{Token, TokenData},
+    keywords::Keyword,
+    treegen::ast::{EnumDecl, Expr, ExprKind, FieldDecl, GenericParam, Ident, Item, Span, StructDecl, TypeExpr, TypeExprKind, VariantPayload},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInfo {
+    pub name: String,
+    pub ty: Ty,
+    pub has_default: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructInfo {
+    pub name: String,
+    pub generics: Vec<String>,
+    pub fields: Vec<FieldInfo>,
+    // `@packed`, no padding between fields
+    pub packed: bool,
+    // `@align(n)`
+    pub align: Option<usize>,
+    pub span: Span,
+}
+
+impl StructInfo {
+    pub fn field(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadInfo {
+    Unit,
+    Tuple(Vec<Ty>),
+    Struct(Vec<FieldInfo>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantInfo {
+    pub name: String,
+    pub discriminant: i128,
+    pub payload: PayloadInfo,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumInfo {
+    pub name: String,
+    pub generics: Vec<String>,
+    // The integer type the tag is stored as, picked from the discriminants if not written
+    pub tag: Primitive,
+    pub variants: Vec<VariantInfo>,
+    pub span: Span,
+}
+
+impl EnumInfo {
+    pub fn variant(&self, name: &str) -> Option<&VariantInfo> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+
+    // Only unit variants, so it is nothing but the tag
+    pub fn is_c_style(&self) -> bool {
+        self.variants.iter().all(|variant| variant.payload == PayloadInfo::Unit)
+    }
+}
+
+// Every struct and enum of a program, with field and payload types resolved
+#[derive(Debug, Default)]
+pub struct TypeTable {
+    pub structs: HashMap<String, StructInfo>,
+    pub enums: HashMap<String, EnumInfo>,
+    // How many generic parameters each one takes
+    declared: HashMap<String, usize>,
+}
+
+impl TypeTable {
+    pub fn from_items(items: &[Item]) -> Result<Self, Vec<SemanticError>> {
+        let mut table = TypeTable::default();
+        let mut errors = Vec::new();
+
+        // Names first, so declarations can use each other in any order
+        for item in items {
+            let (name, generics) = match item {
+                Item::Struct(decl) => (&decl.name, &decl.generics),
+                Item::Enum(decl) => (&decl.name, &decl.generics),
+                _ => continue,
+            };
+            if table.declared.insert(name.name.clone(), generics.len()).is_some() {
+                errors.push(SemanticError::new(format!("`{}` is declared more than once", name.name), name.span));
+            }
+        }
+
+        for item in items {
+            let result = match item {
+                Item::Struct(decl) => table.struct_info(decl).map(|info| {
+                    table.structs.insert(info.name.clone(), info);
+                }),
+                Item::Enum(decl) => table.enum_info(decl).map(|info| {
+                    table.enums.insert(info.name.clone(), info);
+                }),
+                _ => continue,
+            };
+            if let Err(error) = result {
+                errors.push(error);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(table)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.declared.contains_key(name)
+    }
+
+    pub fn resolve_type_ref(&self, ty: &TypeExpr, generics: &[String]) -> SemanticResult<Ty> {
+        Ok(match &ty.kind {
+            TypeExprKind::Path(segments, args) => {
+                let [name] = &segments[..] else {
+                    let path = segments.iter().map(|segment| segment.name.as_str()).collect::<Vec<_>>().join(".");
+                    return Err(SemanticError::new(format!("unknown type `{}`", path), ty.span));
+                };
+                let resolved = self.resolve_name(&name.name, name.span, generics)?;
+                if args.is_empty() {
+                    return Ok(resolved);
+                }
+                // A bare name leaves the arguments to inference, written ones must all be there
+                let expected = self.declared.get(&name.name).copied().unwrap_or(0);
+                if args.len() != expected {
+                    return Err(SemanticError::new(
+                        format!("`{}` takes {} generic arguments, found {}", name.name, expected, args.len()),
+                        ty.span,
+                    ));
+                }
+                let args = args.iter().map(|arg| self.resolve_type_ref(arg, generics)).collect::<SemanticResult<_>>()?;
+                Ty::Named(name.name.clone(), args)
+            }
+            TypeExprKind::Pointer(inner) | TypeExprKind::Reference(inner) => Ty::Pointer(Box::new(self.resolve_type_ref(inner, generics)?)),
+            TypeExprKind::Const(inner) => self.resolve_type_ref(inner, generics)?,
+            TypeExprKind::Tuple(items) => {
+                Ty::Tuple(items.iter().map(|item| self.resolve_type_ref(item, generics)).collect::<SemanticResult<_>>()?)
+            }
+            TypeExprKind::Function(abi, params, ret) => {
+                let params = params.iter().map(|param| self.resolve_type_ref(param, generics)).collect::<SemanticResult<Vec<_>>>()?;
+                let ret = match ret {
+                    Some(ret) => self.resolve_type_ref(ret, generics)?,
+                    None => Ty::unit(),
+                };
+                if *abi == Abi::C {
+                    check_c_abi(params.iter().chain([&ret]), ty.span)?;
+                }
+                Ty::FnPointer(*abi, params, Box::new(ret))
+            }
+            TypeExprKind::Impl(_) => return Err(SemanticError::new("`impl` types are not supported here yet", ty.span)),
+            TypeExprKind::Array(..) | TypeExprKind::Slice(_) => {
+                return Err(SemanticError::new("arrays are not supported by the type checker yet", ty.span));
+            }
+        })
+    }
+
+    fn resolve_name(&self, name: &str, span: Span, generics: &[String]) -> SemanticResult<Ty> {
+        if let Some(primitive) = Primitive::from_name(name) {
+            return Ok(Ty::Primitive(primitive));
+        }
+        if name == "bool" {
+            return Ok(Ty::Bool);
+        }
+        if generics.iter().any(|generic| generic == name) {
+            return Ok(Ty::Param(name.to_string()));
+        }
+        if self.is_declared(name) {
+            return Ok(Ty::Named(name.to_string(), Vec::new()));
+        }
+        Err(SemanticError::new(format!("unknown type `{}`", name), span))
+    }
+
+    fn fields(&self, fields: &[FieldDecl], generics: &[String]) -> SemanticResult<Vec<FieldInfo>> {
+        let mut infos: Vec<FieldInfo> = Vec::new();
+        for field in fields {
+            if infos.iter().any(|info| info.name == field.name.name) {
+                return Err(SemanticError::new(format!("field `{}` is declared more than once", field.name.name), field.name.span));
+            }
+            infos.push(FieldInfo {
+                name: field.name.name.clone(),
+                ty: self.resolve_type_ref(&field.ty, generics)?,
+                has_default: field.default.is_some(),
+                span: field.span,
+            });
+        }
+        Ok(infos)
+    }
+
+    fn struct_info(&self, decl: &StructDecl) -> SemanticResult<StructInfo> {
+        let generics = generic_names(&decl.generics);
+        let attributes = DecoratorRegistry::default().attributes(&decl.decorators)?;
+        Ok(StructInfo {
+            name: decl.name.name.clone(),
+            fields: self.fields(&decl.fields, &generics)?,
+            generics,
+            packed: attributes.packed,
+            align: attributes.align,
+            span: decl.span,
+        })
+    }
+
+    fn enum_info(&self, decl: &EnumDecl) -> SemanticResult<EnumInfo> {
+        let generics = generic_names(&decl.generics);
+        let backing = match &decl.backing {
+            Some(backing) => match self.resolve_type_ref(backing, &[])? {
+                Ty::Primitive(primitive) if !primitive.is_float() => Some(primitive),
+                other => return Err(SemanticError::new(format!("an enum must be backed by an integer type, not `{}`", other), backing.span)),
+            },
+            None => None,
+        };
+
+        let mut variants: Vec<VariantInfo> = Vec::new();
+        let mut next_discriminant = 0;
+        for variant in &decl.variants {
+            if variants.iter().any(|info| info.name == variant.name.name) {
+                return Err(SemanticError::new(format!("variant `{}` is declared more than once", variant.name.name), variant.name.span));
+            }
+            let discriminant = match &variant.discriminant {
+                Some(expr) => const_eval(expr)?,
+                None => next_discriminant,
+            };
+            if let Some(other) = variants.iter().find(|info| info.discriminant == discriminant) {
+                return Err(SemanticError::new(
+                    format!("`{}` has the same discriminant as `{}`, {}", variant.name.name, other.name, discriminant),
+                    variant.span,
+                ));
+            }
+            if let Some(backing) = &backing {
+                if !fits(discriminant, backing) {
+                    return Err(SemanticError::new(format!("discriminant {} does not fit in `{}`", discriminant, backing.name()), variant.span));
+                }
+            }
+            let payload = match &variant.payload {
+                VariantPayload::Unit => PayloadInfo::Unit,
+                VariantPayload::Tuple(types) => PayloadInfo::Tuple(
+                    types.iter().map(|ty| self.resolve_type_ref(ty, &generics)).collect::<SemanticResult<_>>()?,
+                ),
+                VariantPayload::Struct(fields) => PayloadInfo::Struct(self.fields(fields, &generics)?),
+            };
+            variants.push(VariantInfo { name: variant.name.name.clone(), discriminant, payload, span: variant.span });
+            next_discriminant = discriminant + 1;
+        }
+
+        let tag = backing.unwrap_or_else(|| smallest_tag(&variants));
+        Ok(EnumInfo { name: decl.name.name.clone(), generics, tag, variants, span: decl.span })
+    }
+}
+
+// Const parameters are in the list too, so generic arguments line up by position
+pub fn generic_names(generics: &[GenericParam]) -> Vec<String> {
+    generics.iter().map(|generic| generic.name.name.clone()).collect()
+}
+
+// Tuples and closures have no C equivalent, `()` is `void`
+pub fn check_c_abi<'t>(types: impl IntoIterator<Item = &'t Ty>, span: Span) -> SemanticResult<()> {
+    for ty in types {
+        if matches!(ty, Ty::Function(..)) || matches!(ty, Ty::Tuple(items) if !items.is_empty()) {
+            return Err(SemanticError::new(format!("`{}` can't be passed to or from an `extern` function", ty), span));
+        }
+    }
+    Ok(())
+}
+
+// Integer arithmetic on literals, for discriminants and decorator arguments
+pub fn const_eval(expr: &Expr) -> SemanticResult<i128> {
+    let error = || SemanticError::new("expected a constant integer", expr.span);
+    match &expr.kind {
+        ExprKind::Number(number) if !number.has_decimal => number.parse_int::<i128>().map_err(|_| error()),
+        ExprKind::Binary(operator, lhs, rhs) => {
+            let (lhs, rhs) = (const_eval(lhs)?, const_eval(rhs)?);
+            let value = match operator {
+                Operator::Add => lhs.checked_add(rhs),
+                Operator::Sub => lhs.checked_sub(rhs),
+                Operator::Mult => lhs.checked_mul(rhs),
+                Operator::Div => lhs.checked_div(rhs),
+                Operator::Mod => lhs.checked_rem(rhs),
+                Operator::BitwiseShiftLeft => u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shl(rhs)),
+                Operator::BitwiseShiftRight => u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shr(rhs)),
+                Operator::BitwiseOr => Some(lhs | rhs),
+                Operator::BitwiseAnd => Some(lhs & rhs),
+                Operator::Xor => Some(lhs ^ rhs),
+                _ => None,
+            };
+            value.ok_or_else(error)
+        }
+        _ => Err(error()),
+    }
+}
+
+pub fn integer_range(primitive: &Primitive) -> (i128, i128) {
+    let bits = primitive.to_size().unwrap_or(8) as u32 * 8;
+    if primitive.is_unsigned() {
+        (0, if bits >= 128 { i128::MAX } else { (1i128 << bits) - 1 })
+    } else {
+        (if bits >= 128 { i128::MIN } else { -(1i128 << (bits - 1)) }, if bits >= 128 { i128::MAX } else { (1i128 << (bits - 1)) - 1 })
+    }
+}
+
+pub fn fits(value: i128, primitive: &Primitive) -> bool {
+    let (min, max) = integer_range(primitive);
+    min <= value && value <= max
+}
+
+fn smallest_tag(variants: &[VariantInfo]) -> Primitive {
+    let candidates = if variants.iter().any(|variant| variant.discriminant < 0) {
+        [Primitive::I8, Primitive::I16, Primitive::I32, Primitive::I64]
+    } else {
+        [Primitive::U8, Primitive::U16, Primitive::U32, Primitive::U64]
+    };
+    candidates
+        .into_iter()
+        .find(|candidate| variants.iter().all(|variant| fits(variant.discriminant, candidate)))
+        .unwrap_or(Primitive::I128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::items::parse_items};
+
+    fn table_of(text: &str) -> Result<TypeTable, Vec<SemanticError>> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        TypeTable::from_items(&parse_items(&tokens, text.len()).unwrap())
+    }
+
+    fn first_error(text: &str) -> String {
+        table_of(text).unwrap_err()[0].message.clone()
+    }
+
+    #[test]
+    fn discriminants_and_tags() {
+        let table = table_of("enum u16 Color { Red = 1, Green, Blue = 1 << 4 } enum Sign { Minus = -1, Zero, Plus }").unwrap();
+        let color = &table.enums["Color"];
+        assert_eq!(color.variants.iter().map(|v| v.discriminant).collect::<Vec<_>>(), [1, 2, 16]);
+        assert_eq!(color.tag, Primitive::U16);
+        assert!(color.is_c_style());
+        assert_eq!(table.enums["Sign"].tag, Primitive::I8);
+    }
+
+    #[test]
+    fn payload_types() {
+        let table = table_of("struct Point { i32 x, i32 y } enum Shape { Circle(Point, f32*), Rect { Point corner, u8 w = 1 } }").unwrap();
+        let shape = &table.enums["Shape"];
+        assert_eq!(
+            shape.variant("Circle").unwrap().payload,
+            PayloadInfo::Tuple(vec![Ty::Named("Point".to_string(), vec![]), Ty::Pointer(Box::new(Ty::Primitive(Primitive::F32)))])
+        );
+        let PayloadInfo::Struct(fields) = &shape.variant("Rect").unwrap().payload else { panic!() };
+        assert!(!fields[0].has_default && fields[1].has_default);
+        assert!(!shape.is_c_style());
+    }
+
+    #[test]
+    fn declaration_errors() {
+        assert_eq!(first_error("enum u8 E { A = 255, B }"), "discriminant 256 does not fit in `u8`");
+        assert_eq!(first_error("enum E { A = 2, B = 2 }"), "`B` has the same discriminant as `A`, 2");
+        assert_eq!(first_error("enum f32 E { A }"), "an enum must be backed by an integer type, not `f32`");
+        assert_eq!(first_error("enum E { A(Missing) }"), "unknown type `Missing`");
+        assert_eq!(first_error("struct P<T> { u8 a } struct S { P<u8, u8> p }"), "`P` takes 1 generic arguments, found 2");
+        assert_eq!(first_error("struct S { [u8; 4] a }"), "arrays are not supported by the type checker yet");
+        assert_eq!(first_error("struct S { io.File a }"), "unknown type `io.File`");
+        assert_eq!(first_error("struct S { u8 a } enum S { A }"), "`S` is declared more than once");
+        assert_eq!(first_error("@align(3) struct S { u8 a }"), "alignment must be a power of two, not 3");
+    }
+}
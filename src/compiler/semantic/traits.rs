@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+
+use super::{
+    table::{generic_names, TypeTable},
+    SemanticError, SemanticResult,
+};
+use crate::compiler::{
+    objects::types::Ty,
+    parsing::tokenizer::tokenize_text,
+    treegen::{
+        ast::{AssocItem, FnDecl, GenericParam, GenericParamKind, Ident, ImplDecl, Item, Span, StructDecl, TraitDecl, WhereClause},
+        items::parse_items,
+    },
+};
+
+// Traits every program has without declaring them, a declaration with the same name replaces them.
+// `for (x in value)` calls `next` until it returns false, reading each item from `item`
+const PRELUDE: &str = "
+    trait Iterator {
+        typedef Item;
+        bool next(Self* self, Item* item);
+    }
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSig {
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    pub has_body: bool,
+    pub span: Span,
+}
+
+impl MethodSig {
+    fn describe(&self) -> String {
+        format!("{} {}({})", self.ret, self.name, self.params.iter().map(Ty::to_string).collect::<Vec<_>>().join(", "))
+    }
+
+    fn substitute(&self, params: &[String], args: &[Ty]) -> MethodSig {
+        MethodSig {
+            params: self.params.iter().map(|ty| ty.substitute(params, args)).collect(),
+            ret: self.ret.substitute(params, args),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssocTypeInfo {
+    pub name: String,
+    // The default in a trait, the chosen type in an impl
+    pub ty: Option<Ty>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssocConstInfo {
+    pub name: String,
+    pub ty: Ty,
+    pub has_value: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitInfo {
+    pub name: String,
+    pub methods: Vec<MethodSig>,
+    pub types: Vec<AssocTypeInfo>,
+    pub consts: Vec<AssocConstInfo>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplInfo {
+    // `None` for `impl Type { }`
+    pub trait_name: Option<String>,
+    pub generics: Vec<String>,
+    pub self_ty: Ty,
+    pub methods: Vec<MethodSig>,
+    pub types: Vec<AssocTypeInfo>,
+    pub consts: Vec<AssocConstInfo>,
+    // Came from `struct X implements Trait` rather than an `impl` block
+    pub from_implements: bool,
+    pub span: Span,
+}
+
+impl ImplInfo {
+    fn member_names(&self) -> impl Iterator<Item = (&str, Span)> {
+        self.methods
+            .iter()
+            .map(|method| (method.name.as_str(), method.span))
+            .chain(self.types.iter().map(|ty| (ty.name.as_str(), ty.span)))
+            .chain(self.consts.iter().map(|constant| (constant.name.as_str(), constant.span)))
+    }
+}
+
+// Could one type be the other once generic parameters are filled in
+fn types_overlap(a: &Ty, b: &Ty) -> bool {
+    match (a, b) {
+        (Ty::Param(_), _) | (_, Ty::Param(_)) => true,
+        (Ty::Pointer(a), Ty::Pointer(b)) => types_overlap(a, b),
+        (Ty::Named(a_name, a_args), Ty::Named(b_name, b_args)) => {
+            a_name == b_name && a_args.len() == b_args.len() && a_args.iter().zip(b_args).all(|(a, b)| types_overlap(a, b))
+        }
+        (Ty::Tuple(a), Ty::Tuple(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| types_overlap(a, b)),
+        _ => a == b,
+    }
+}
+
+const SELF: &str = "Self";
+
+// Traits and every implementation of them, checked for coherence
+#[derive(Debug, Default)]
+pub struct TraitTable {
+    pub traits: HashMap<String, TraitInfo>,
+    pub impls: Vec<ImplInfo>,
+}
+
+impl TraitTable {
+    pub fn from_items(items: &[Item], types: &TypeTable) -> Result<Self, Vec<SemanticError>> {
+        let mut table = TraitTable::default();
+        let mut errors = Vec::new();
+
+        for item in items {
+            if let Item::Trait(decl) = item {
+                if types.is_declared(&decl.name.name) || table.traits.contains_key(&decl.name.name) {
+                    errors.push(SemanticError::new(format!("`{}` is declared more than once", decl.name.name), decl.name.span));
+                    continue;
+                }
+                match trait_info(decl, types) {
+                    Ok(info) => {
+                        table.traits.insert(info.name.clone(), info);
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+        let tokens = tokenize_text(PRELUDE.to_string()).unwrap();
+        for item in parse_items(&tokens, PRELUDE.len()).unwrap() {
+            if let Item::Trait(decl) = item {
+                if !types.is_declared(&decl.name.name) && !table.traits.contains_key(&decl.name.name) {
+                    let info = trait_info(&decl, types).unwrap();
+                    table.traits.insert(info.name.clone(), info);
+                }
+            }
+        }
+        for item in items {
+            if let Item::Impl(decl) = item {
+                match table.impl_info(decl, types) {
+                    Ok(info) => table.impls.push(info),
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+        for item in items {
+            if let Item::Struct(decl) = item {
+                for trait_name in &decl.implements {
+                    match table.implements_sugar(decl, trait_name) {
+                        Ok(info) => table.impls.push(info),
+                        Err(error) => errors.push(error),
+                    }
+                }
+            }
+        }
+
+        errors.extend(table.check_all_generics(items, types));
+        errors.extend(table.check_inherent_duplicates());
+        for info in &table.impls {
+            if let Some(trait_name) = &info.trait_name {
+                errors.extend(table.check_impl(&table.traits[trait_name], info));
+            }
+        }
+        errors.extend(table.check_overlap());
+
+        if errors.is_empty() {
+            Ok(table)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // The implementation of `trait_name` that applies to `ty`
+    pub fn find_impl(&self, trait_name: &str, ty: &Ty) -> Option<&ImplInfo> {
+        self.impls
+            .iter()
+            .find(|info| info.trait_name.as_deref() == Some(trait_name) && types_overlap(&info.self_ty, ty))
+    }
+
+    pub fn implements(&self, ty: &Ty, trait_name: &str) -> bool {
+        self.find_impl(trait_name, ty).is_some()
+    }
+
+    fn impl_info(&self, decl: &ImplDecl, types: &TypeTable) -> SemanticResult<ImplInfo> {
+        let generics = generic_names(&decl.generics);
+        let self_ty = types.resolve_type_ref(&decl.self_ty, &generics)?;
+        if let Some(trait_name) = &decl.trait_name {
+            if !self.traits.contains_key(&trait_name.name) {
+                return Err(SemanticError::new(format!("unknown trait `{}`", trait_name.name), trait_name.span));
+            }
+        } else if !matches!(self_ty, Ty::Named(..)) {
+            return Err(SemanticError::new(
+                format!("`impl {}` needs a struct or enum declared in this program, implement a trait instead", self_ty),
+                decl.self_ty.span,
+            ));
+        }
+
+        let (methods, types, consts) = members(&decl.items, &generics, types)?;
+        if let Some(missing) = types.iter().find(|ty| ty.ty.is_none()) {
+            return Err(SemanticError::new(format!("`typedef {};` needs a type in an impl", missing.name), missing.span));
+        }
+        let (params, args) = impl_args(&self_ty, &types);
+        Ok(ImplInfo {
+            trait_name: decl.trait_name.as_ref().map(|name| name.name.clone()),
+            generics,
+            methods: methods.iter().map(|method| method.substitute(&params, &args)).collect(),
+            consts: consts.into_iter().map(|constant| AssocConstInfo { ty: constant.ty.substitute(&params, &args), ..constant }).collect(),
+            types,
+            self_ty,
+            from_implements: false,
+            span: decl.span,
+        })
+    }
+
+    // `struct X implements Trait` takes the trait's members from the inherent impls of `X`
+    fn implements_sugar(&self, decl: &StructDecl, trait_name: &Ident) -> SemanticResult<ImplInfo> {
+        let info = self
+            .traits
+            .get(&trait_name.name)
+            .ok_or_else(|| SemanticError::new(format!("unknown trait `{}`", trait_name.name), trait_name.span))?;
+        let self_ty = Ty::Named(decl.name.name.clone(), Vec::new());
+        let inherent: Vec<&ImplInfo> = self.impls.iter().filter(|other| other.trait_name.is_none() && other.self_ty == self_ty).collect();
+
+        let in_trait = |name: &str| {
+            info.methods.iter().any(|method| method.name == name)
+                || info.types.iter().any(|ty| ty.name == name)
+                || info.consts.iter().any(|constant| constant.name == name)
+        };
+        Ok(ImplInfo {
+            trait_name: Some(info.name.clone()),
+            generics: Vec::new(),
+            methods: inherent.iter().flat_map(|other| &other.methods).filter(|method| in_trait(&method.name)).cloned().collect(),
+            types: inherent.iter().flat_map(|other| &other.types).filter(|ty| in_trait(&ty.name)).cloned().collect(),
+            consts: inherent.iter().flat_map(|other| &other.consts).filter(|constant| in_trait(&constant.name)).cloned().collect(),
+            self_ty,
+            from_implements: true,
+            span: trait_name.span,
+        })
+    }
+
+    fn check_all_generics(&self, items: &[Item], types: &TypeTable) -> Vec<SemanticError> {
+        let mut errors = Vec::new();
+        for item in items {
+            let (generics, where_clause, members, has_self) = match item {
+                Item::Struct(decl) => (&decl.generics, &decl.where_clause, &[][..], false),
+                Item::Enum(decl) => (&decl.generics, &decl.where_clause, &[][..], false),
+                Item::Function(decl) => (&decl.generics, &decl.where_clause, &[][..], false),
+                Item::Trait(decl) => (&decl.generics, &decl.where_clause, &decl.items[..], true),
+                Item::Impl(decl) => (&decl.generics, &decl.where_clause, &decl.items[..], true),
+                Item::Var(_) | Item::Error(..) => continue,
+            };
+            let outer: Vec<String> = if has_self { vec![SELF.to_string()] } else { Vec::new() };
+            errors.extend(self.check_generics(generics, where_clause.as_ref(), &outer, types));
+
+            // Methods also see the parameters around them and the associated types
+            let mut outer = outer;
+            outer.extend(generic_names(generics));
+            outer.extend(members.iter().filter_map(|member| match member {
+                AssocItem::Type(ty) => Some(ty.name.name.clone()),
+                _ => None,
+            }));
+            for member in members {
+                if let AssocItem::Method(method) = member {
+                    errors.extend(self.check_generics(&method.generics, method.where_clause.as_ref(), &outer, types));
+                }
+            }
+        }
+        errors
+    }
+
+    // The generic parameters and `where` predicates of one declaration, `outer` are the names already in scope
+    fn check_generics(&self, generics: &[GenericParam], where_clause: Option<&WhereClause>, outer: &[String], types: &TypeTable) -> Vec<SemanticError> {
+        let mut errors = Vec::new();
+        for (i, param) in generics.iter().enumerate() {
+            if let Some(first) = generics[..i].iter().find(|other| other.name.name == param.name.name) {
+                errors.push(
+                    SemanticError::new(format!("the generic parameter `{}` is declared more than once", param.name.name), param.name.span)
+                        .with_note(first.name.span, "first declared here"),
+                );
+            }
+        }
+        let mut in_scope = outer.to_vec();
+        in_scope.extend(generic_names(generics));
+
+        for param in generics {
+            match &param.kind {
+                GenericParamKind::Type(bounds) => errors.extend(bounds.iter().filter_map(|bound| self.check_bound(bound, types))),
+                GenericParamKind::Const(ty) => match types.resolve_type_ref(ty, &in_scope) {
+                    Ok(resolved) if resolved.is_integer() || resolved == Ty::Bool => {}
+                    Ok(resolved) => errors.push(SemanticError::new(
+                        format!("a `const` parameter must be an integer or `bool`, found `{}`", resolved),
+                        ty.span,
+                    )),
+                    Err(error) => errors.push(error),
+                },
+            }
+        }
+        for predicate in where_clause.iter().flat_map(|clause| &clause.predicates) {
+            if let Err(error) = types.resolve_type_ref(&predicate.ty, &in_scope) {
+                errors.push(error);
+            }
+            errors.extend(predicate.bounds.iter().filter_map(|bound| self.check_bound(bound, types)));
+        }
+        errors
+    }
+
+    fn check_bound(&self, bound: &Ident, types: &TypeTable) -> Option<SemanticError> {
+        if self.traits.contains_key(&bound.name) {
+            return None;
+        }
+        let message = match types.is_declared(&bound.name) {
+            true => format!("`{}` is a type, only traits can be bounds", bound.name),
+            false => format!("unknown trait `{}`", bound.name),
+        };
+        Some(SemanticError::new(message, bound.span))
+    }
+
+    fn check_inherent_duplicates(&self) -> Vec<SemanticError> {
+        let mut errors = Vec::new();
+        let mut seen: Vec<(&Ty, &str, Span)> = Vec::new();
+        for info in self.impls.iter().filter(|info| info.trait_name.is_none()) {
+            for (name, span) in info.member_names() {
+                match seen.iter().find(|(ty, other, _)| **ty == info.self_ty && *other == name) {
+                    Some((_, _, first)) => errors.push(
+                        SemanticError::new(format!("`{}` is defined more than once for `{}`", name, info.self_ty), span)
+                            .with_note(*first, "the first definition is here"),
+                    ),
+                    None => seen.push((&info.self_ty, name, span)),
+                }
+            }
+        }
+        errors
+    }
+
+    fn check_impl(&self, info: &TraitInfo, implementation: &ImplInfo) -> Vec<SemanticError> {
+        let mut errors = Vec::new();
+        let for_what = format!("`{}` for `{}`", info.name, implementation.self_ty);
+
+        for (name, span) in implementation.member_names() {
+            let known = info.methods.iter().any(|method| method.name == name)
+                || info.types.iter().any(|ty| ty.name == name)
+                || info.consts.iter().any(|constant| constant.name == name);
+            if !known {
+                errors.push(SemanticError::new(format!("`{}` is not a member of trait `{}`", name, info.name), span).with_note(info.span, "the trait is declared here"));
+            }
+        }
+
+        let mut missing = Vec::new();
+        // The impl's choice for each associated type, or the trait's default
+        let chosen: Vec<AssocTypeInfo> = info
+            .types
+            .iter()
+            .map(|ty| implementation.types.iter().find(|other| other.name == ty.name).unwrap_or(ty).clone())
+            .collect();
+        let (params, args) = impl_args(&implementation.self_ty, &chosen);
+        for method in &info.methods {
+            let expected = method.substitute(&params, &args);
+            match implementation.methods.iter().find(|other| other.name == method.name) {
+                Some(found) if found.params != expected.params || found.ret != expected.ret => errors.push(
+                    SemanticError::new(
+                        format!("`{}` does not match its declaration in the trait, expected `{}`, found `{}`", method.name, expected.describe(), found.describe()),
+                        found.span,
+                    )
+                    .with_note(method.span, "declared here"),
+                ),
+                Some(_) => {}
+                None if !method.has_body => missing.push((method.name.as_str(), method.span)),
+                None => {}
+            }
+        }
+        for ty in &info.types {
+            if ty.ty.is_none() && !implementation.types.iter().any(|other| other.name == ty.name) {
+                missing.push((ty.name.as_str(), ty.span));
+            }
+        }
+        for constant in &info.consts {
+            match implementation.consts.iter().find(|other| other.name == constant.name) {
+                Some(found) if found.ty != constant.ty.substitute(&params, &args) => errors.push(
+                    SemanticError::new(format!("`{}` should be `{}`, found `{}`", constant.name, constant.ty.substitute(&params, &args), found.ty), found.span)
+                        .with_note(constant.span, "declared here"),
+                ),
+                Some(_) => {}
+                None if !constant.has_value => missing.push((constant.name.as_str(), constant.span)),
+                None => {}
+            }
+        }
+
+        if !missing.is_empty() {
+            let names = missing.iter().map(|(name, _)| format!("`{}`", name)).collect::<Vec<_>>().join(", ");
+            let mut error = SemanticError::new(format!("the implementation of {} is missing {}", for_what, names), implementation.span);
+            for (name, span) in missing {
+                error = error.with_note(span, format!("`{}` is declared here", name));
+            }
+            errors.push(error);
+        }
+        errors
+    }
+
+    fn check_overlap(&self) -> Vec<SemanticError> {
+        let mut errors = Vec::new();
+        for (i, later) in self.impls.iter().enumerate() {
+            let Some(trait_name) = &later.trait_name else {
+                continue;
+            };
+            if let Some(earlier) = self.impls[..i]
+                .iter()
+                .find(|earlier| earlier.trait_name.as_ref() == Some(trait_name) && types_overlap(&earlier.self_ty, &later.self_ty))
+            {
+                errors.push(
+                    SemanticError::new(format!("conflicting implementations of `{}` for `{}`", trait_name, later.self_ty), later.span)
+                        .with_note(earlier.span, format!("`{}` is already implemented for `{}` here", trait_name, earlier.self_ty)),
+                );
+            }
+        }
+        errors
+    }
+}
+
+// `Self` and the associated types that have a type, with what they stand for in an impl
+fn impl_args(self_ty: &Ty, types: &[AssocTypeInfo]) -> (Vec<String>, Vec<Ty>) {
+    let mut params = vec![SELF.to_string()];
+    let mut args = vec![self_ty.clone()];
+    for assoc in types {
+        if let Some(ty) = &assoc.ty {
+            params.push(assoc.name.clone());
+            args.push(ty.clone());
+        }
+    }
+    (params, args)
+}
+
+fn method_sig(decl: &FnDecl, generics: &[String], types: &TypeTable) -> SemanticResult<MethodSig> {
+    let mut generics = generics.to_vec();
+    generics.extend(generic_names(&decl.generics));
+    Ok(MethodSig {
+        name: decl.name.name.clone(),
+        params: decl.params.iter().map(|param| types.resolve_type_ref(&param.ty, &generics)).collect::<SemanticResult<_>>()?,
+        ret: types.resolve_type_ref(&decl.return_type, &generics)?,
+        has_body: decl.body.is_some(),
+        span: decl.span,
+    })
+}
+
+type Members = (Vec<MethodSig>, Vec<AssocTypeInfo>, Vec<AssocConstInfo>);
+
+fn members(items: &[AssocItem], generics: &[String], types: &TypeTable) -> SemanticResult<Members> {
+    let mut generics = generics.to_vec();
+    generics.push(SELF.to_string());
+    // Signatures can name the associated types, each impl fills them in
+    generics.extend(items.iter().filter_map(|item| match item {
+        AssocItem::Type(decl) => Some(decl.name.name.clone()),
+        _ => None,
+    }));
+
+    let (mut methods, mut assoc_types, mut consts) = (Vec::new(), Vec::new(), Vec::new());
+    let mut names: Vec<(&str, Span)> = Vec::new();
+    for item in items {
+        let name = match item {
+            AssocItem::Method(decl) => {
+                methods.push(method_sig(decl, &generics, types)?);
+                &decl.name
+            }
+            AssocItem::Type(decl) => {
+                let ty = decl.ty.as_ref().map(|ty| types.resolve_type_ref(ty, &generics)).transpose()?;
+                assoc_types.push(AssocTypeInfo { name: decl.name.name.clone(), ty, span: decl.span });
+                &decl.name
+            }
+            AssocItem::Const(decl) => {
+                let ty = types.resolve_type_ref(&decl.ty, &generics)?;
+                consts.push(AssocConstInfo { name: decl.name.name.clone(), ty, has_value: decl.value.is_some(), span: decl.span });
+                &decl.name
+            }
+        };
+        if let Some((_, first)) = names.iter().find(|(other, _)| *other == name.name) {
+            return Err(SemanticError::new(format!("`{}` is declared more than once", name.name), name.span).with_note(*first, "the first declaration is here"));
+        }
+        names.push((&name.name, name.span));
+    }
+    Ok((methods, assoc_types, consts))
+}
+
+fn trait_info(decl: &TraitDecl, types: &TypeTable) -> SemanticResult<TraitInfo> {
+    let generics = generic_names(&decl.generics);
+    let (methods, types, consts) = members(&decl.items, &generics, types)?;
+    Ok(TraitInfo { name: decl.name.name.clone(), methods, types, consts, span: decl.span })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::items::parse_items};
+
+    const SHAPE: &str = "
+        struct Circle { f32 r }
+        struct Square { f32 side }
+        trait Shape {
+            typedef Unit;
+            const u32 SIDES;
+            f32 area(Self* self);
+            f32 double_area(Self* self) => 2.0;
+        }
+    ";
+
+    fn check(text: &str) -> Result<TraitTable, Vec<SemanticError>> {
+        let text = format!("{}{}", SHAPE, text);
+        let tokens = tokenize_text(text.clone()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap();
+        TraitTable::from_items(&items, &TypeTable::from_items(&items).unwrap())
+    }
+
+    fn messages(text: &str) -> Vec<String> {
+        check(text).unwrap_err().into_iter().map(|error| error.message).collect()
+    }
+
+    #[test]
+    fn complete_implementations() {
+        let table = check(
+            "impl Shape for Circle {
+                typedef f32 Unit;
+                const u32 SIDES = 0;
+                f32 area(Circle* self) => 3.0;
+            }
+            struct Triangle implements Shape { f32 base }
+            impl Triangle {
+                typedef f32 Unit;
+                const u32 SIDES = 3;
+                f32 area(Self* self) => 1.0;
+                u8 unrelated() => 0;
+            }",
+        )
+        .unwrap();
+        let circle = Ty::Named("Circle".to_string(), vec![]);
+        assert!(table.implements(&circle, "Shape"));
+        assert!(table.implements(&Ty::Named("Triangle".to_string(), vec![]), "Shape"));
+        assert!(!table.implements(&Ty::Named("Square".to_string(), vec![]), "Shape"));
+        assert_eq!(table.traits["Shape"].methods.iter().filter(|method| method.has_body).count(), 1);
+    }
+
+    #[test]
+    fn missing_and_extra_members() {
+        assert_eq!(
+            messages("impl Shape for Circle { const u32 SIDES = 0; u8 radius() => 1; }"),
+            [
+                "`radius` is not a member of trait `Shape`",
+                "the implementation of `Shape` for `Circle` is missing `area`, `Unit`"
+            ]
+        );
+        assert_eq!(messages("struct Hexagon implements Shape { u8 x }"), ["the implementation of `Shape` for `Hexagon` is missing `area`, `Unit`, `SIDES`"]);
+        let errors = check("impl Shape for Circle { typedef u8 Unit; const u32 SIDES = 0; }").unwrap_err();
+        assert_eq!(errors[0].notes.len(), 1);
+    }
+
+    #[test]
+    fn mismatches_and_overlap() {
+        let body = "typedef u8 Unit; const u32 SIDES = 0;";
+        assert_eq!(
+            messages(&format!("impl Shape for Circle {{ {} f32 area(Circle self) => 1.0; }}", body)),
+            ["`area` does not match its declaration in the trait, expected `f32 area(*Circle)`, found `f32 area(Circle)`"]
+        );
+        assert_eq!(
+            messages(&format!("impl Shape for Circle {{ {0} f32 area(Self* self) => 1.0; }} impl<T> Shape for T {{ {0} f32 area(T* self) => 1.0; }}", body)),
+            ["conflicting implementations of `Shape` for `T`"]
+        );
+        assert_eq!(messages("impl Missing for Circle { }"), ["unknown trait `Missing`"]);
+        assert_eq!(messages("impl Circle { u8 a() => 1; } impl Circle { u8 a() => 2; }"), ["`a` is defined more than once for `Circle`"]);
+        assert_eq!(messages("impl u8 { }"), ["`impl u8` needs a struct or enum declared in this program, implement a trait instead"]);
+    }
+
+    #[test]
+    fn generic_bounds() {
+        assert!(check(
+            "struct Pair<T: Shape, const u8 N> where *T implements Shape { T first }
+            impl<T: Shape> Pair { u8 get<U: Shape>(Self* self) where U: Shape + Iterator => 1; }
+            trait Measure where Self: Shape { f32 size(Self* self) where Unit: Shape; typedef Unit; }"
+        )
+        .is_ok());
+
+        assert_eq!(messages("struct A<T: Missing> { u8 x }"), ["unknown trait `Missing`"]);
+        assert_eq!(messages("struct A<T> where T implements Circle { u8 x }"), ["`Circle` is a type, only traits can be bounds"]);
+        assert_eq!(messages("struct A<const f32 N> { u8 x }"), ["a `const` parameter must be an integer or `bool`, found `f32`"]);
+        assert_eq!(messages("struct A<T> where U: Shape { u8 x }"), ["unknown type `U`"]);
+        assert_eq!(messages("u8 f<T>() where Self: Shape => 1;"), ["unknown type `Self`"]);
+        let errors = check("enum E<T, U, T> { A }").unwrap_err();
+        assert_eq!(errors[0].message, "the generic parameter `T` is declared more than once");
+        assert_eq!(errors[0].notes[0].1, "first declared here");
+    }
+}
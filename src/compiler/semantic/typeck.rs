@@ -0,0 +1,1211 @@
+use std::collections::HashMap;
+
+use super::{
+    decorators::{Attributes, DecoratorRegistry},
+    table::{check_c_abi, fits, generic_names, EnumInfo, PayloadInfo, TypeTable},
+    traits::TraitTable,
+    SemanticError, SemanticResult,
+};
+use crate::compiler::{
+    lowering::{
+        closures::{analyze_captures, ClosureInfo},
+        loops::{lower_iterator, lower_range},
+        matching::lower_match,
+    },
+    keywords::Keyword,
+    objects::{
+        gurn_objects::Primitive,
+        types::{Abi, Ty},
+    },
+    operators::Operator,
+    parsing::number_parser::NumberLiteral,
+    treegen::ast::{
+        Block, Closure, Conditional, Expr, ExprKind, FnDecl, ForKind, Ident, MatchArm, Mutability, NodeId, Pattern, PatternArg, PatternKind, Span, Stmt, StmtKind, Storage,
+        VarDecl,
+    },
+};
+
+// What a variant pattern binds, `Shape.Empty`, `Shape.Circle(r)` or `Shape.Rect(w, h)` by field name
+#[derive(Debug, Clone, Copy)]
+pub enum VariantBindings<'p> {
+    Unit,
+    Positional(usize),
+    Named(&'p [Ident]),
+}
+
+fn mismatch(expected: &Ty, found: &Ty, span: Span) -> SemanticError {
+    SemanticError::new(format!("expected `{}`, found `{}`", expected, found), span)
+}
+
+fn expect(found: Ty, expected: Option<&Ty>, span: Span) -> SemanticResult<Ty> {
+    match expected {
+        Some(expected) if *expected != found => Err(mismatch(expected, &found, span)),
+        _ => Ok(found),
+    }
+}
+
+// Fills in `solutions` for the generic parameters `params` used in `pattern`, false if `actual` can't match it
+fn unify(pattern: &Ty, actual: &Ty, params: &[String], solutions: &mut [Option<Ty>]) -> bool {
+    match (pattern, actual) {
+        (Ty::Param(name), _) => match params.iter().position(|param| param == name) {
+            Some(i) => match &solutions[i] {
+                Some(solved) => solved == actual,
+                None => {
+                    solutions[i] = Some(actual.clone());
+                    true
+                }
+            },
+            None => pattern == actual,
+        },
+        (Ty::Pointer(pattern), Ty::Pointer(actual)) => unify(pattern, actual, params, solutions),
+        (Ty::FnPointer(abi, patterns, pattern_ret), Ty::FnPointer(actual_abi, actuals, actual_ret)) => {
+            abi == actual_abi
+                && patterns.len() == actuals.len()
+                && patterns.iter().zip(actuals).all(|(pattern, actual)| unify(pattern, actual, params, solutions))
+                && unify(pattern_ret, actual_ret, params, solutions)
+        }
+        (Ty::Tuple(patterns), Ty::Tuple(actuals)) | (Ty::Named(_, patterns), Ty::Named(_, actuals)) => {
+            let same_name = !matches!((pattern, actual), (Ty::Named(a, _), Ty::Named(b, _)) if a != b);
+            same_name
+                && patterns.len() == actuals.len()
+                && patterns.iter().zip(actuals).all(|(pattern, actual)| unify(pattern, actual, params, solutions))
+        }
+        _ => pattern == actual,
+    }
+}
+
+// A variable in scope
+#[derive(Debug, Clone)]
+struct Local {
+    ty: Ty,
+    mutable: bool,
+    // A `const` whose value is known at compile time, so `static` values can use it
+    constant: bool,
+    span: Span,
+}
+
+pub struct Checker<'a> {
+    table: &'a TypeTable,
+    // Needed for `for (x in value)` over anything but a range
+    traits: Option<&'a TraitTable>,
+    scopes: Vec<HashMap<String, Local>>,
+    // The labels of the loops around the statement being checked, innermost last
+    loops: Vec<Option<Ident>>,
+    // Inside `defer`, which runs while a scope is left and can't leave anything itself
+    in_defer: bool,
+    // Every closure checked so far, and the ids of calls made through closure values, for closure conversion
+    closures: Vec<ClosureInfo>,
+    closure_calls: Vec<NodeId>,
+    // For lowering, the ids of names that take the address of a function, and of calls through fn pointer values
+    function_values: Vec<NodeId>,
+    indirect_calls: Vec<(NodeId, Abi)>,
+    // Declared functions, below every scope so variables can shadow them and closures don't capture them
+    functions: HashMap<String, (Ty, Attributes)>,
+    // Uses of `@deprecated` functions, they don't stop the program from compiling
+    warnings: Vec<SemanticError>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(table: &'a TypeTable) -> Self {
+        Self {
+            table,
+            traits: None,
+            scopes: vec![HashMap::new()],
+            loops: Vec::new(),
+            in_defer: false,
+            closures: Vec::new(),
+            closure_calls: Vec::new(),
+            function_values: Vec::new(),
+            indirect_calls: Vec::new(),
+            functions: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_traits(mut self, traits: &'a TraitTable) -> Self {
+        self.traits = Some(traits);
+        self
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    pub fn declare(&mut self, name: &str, ty: Ty) {
+        let local = Local { ty, mutable: true, constant: false, span: Span::default() };
+        self.scopes.last_mut().unwrap().insert(name.to_string(), local);
+    }
+
+    // A declared function is a constant `fn` pointer, calling it by name is a call through that pointer
+    pub fn declare_function(&mut self, decl: &FnDecl) -> SemanticResult<Ty> {
+        let generics = generic_names(&decl.generics);
+        let abi = if decl.modifiers.contains(&Keyword::Extern) { Abi::C } else { Abi::Gurn };
+        let params = decl.params.iter().map(|param| self.table.resolve_type_ref(&param.ty, &generics)).collect::<SemanticResult<Vec<_>>>()?;
+        let ret = self.table.resolve_type_ref(&decl.return_type, &generics)?;
+        if abi == Abi::C {
+            check_c_abi(params.iter().chain([&ret]), decl.span)?;
+        }
+        let ty = Ty::FnPointer(abi, params, Box::new(ret));
+        let attributes = DecoratorRegistry::default().attributes(&decl.decorators)?;
+        self.functions.insert(decl.name.name.clone(), (ty.clone(), attributes));
+        Ok(ty)
+    }
+
+    pub fn function_attributes(&self, name: &str) -> Option<&Attributes> {
+        self.functions.get(name).map(|(_, attributes)| attributes)
+    }
+
+    pub fn warnings(&self) -> &[SemanticError] {
+        &self.warnings
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Ty> {
+        self.local(name).map(|local| &local.ty)
+    }
+
+    fn local(&self, name: &str) -> Option<&Local> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub fn closures(&self) -> &[ClosureInfo] {
+        &self.closures
+    }
+
+    pub fn closure_calls(&self) -> &[NodeId] {
+        &self.closure_calls
+    }
+
+    pub fn function_values(&self) -> &[NodeId] {
+        &self.function_values
+    }
+
+    pub fn indirect_calls(&self) -> &[(NodeId, Abi)] {
+        &self.indirect_calls
+    }
+
+    // The declared function `name` refers to, unless a variable hides it
+    fn declared_function<'e>(&self, expr: &'e Expr) -> Option<&'e str> {
+        match &expr.kind {
+            ExprKind::Name(name) if self.lookup(name).is_none() && self.functions.contains_key(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn use_function(&mut self, name: &str, span: Span) -> Ty {
+        let (ty, attributes) = &self.functions[name];
+        if let Some(deprecation) = &attributes.deprecated {
+            let message = match &deprecation.message {
+                Some(message) => format!("`{}` is deprecated: {}", name, message),
+                None => format!("`{}` is deprecated", name),
+            };
+            self.warnings.push(SemanticError::new(message, span));
+        }
+        ty.clone()
+    }
+
+    // A block run for its effects, it can't have a value
+    pub fn check_block(&mut self, block: &Block) -> SemanticResult<()> {
+        self.check_block_value(block, Some(&Ty::unit())).map(drop)
+    }
+
+    // The type of the block's value, `()` without one
+    pub fn check_block_value(&mut self, block: &Block, expected: Option<&Ty>) -> SemanticResult<Ty> {
+        self.push_scope();
+        let result = block.stmts.iter().try_for_each(|stmt| self.check_stmt(stmt)).and_then(|_| match &block.value {
+            Some(value) => self.check_expr(value, expected),
+            None => expect(Ty::unit(), expected, block.span),
+        });
+        self.pop_scope();
+        result
+    }
+
+    fn check_conditional(&mut self, conditional: &Conditional, expected: Option<&Ty>) -> SemanticResult<Ty> {
+        let unit = Ty::unit();
+        // Without an `else` nothing is produced when every condition is false
+        if conditional.else_branch.is_none() {
+            if let Some(expected) = expected.filter(|expected| **expected != unit) {
+                return Err(SemanticError::new(format!("this `if` needs an `else` to produce a `{}`", expected), conditional.span));
+            }
+        }
+        let mut result: Option<Ty> = None;
+        for branch in &conditional.branches {
+            self.check_expr(&branch.cond, Some(&Ty::Bool))?;
+            if conditional.comptime && !self.is_constant(&branch.cond) {
+                return Err(SemanticError::new("the condition of a `comptime if` must be known at compile time", branch.cond.span));
+            }
+            let hint = result.clone().or(expected.cloned()).or(conditional.else_branch.is_none().then(Ty::unit));
+            let ty = self.check_block_value(&branch.body, hint.as_ref())?;
+            result.get_or_insert(ty);
+        }
+        if let Some(body) = &conditional.else_branch {
+            self.check_block_value(body, result.as_ref())?;
+        }
+        Ok(result.unwrap_or(unit))
+    }
+
+    // Parameter types are written, or taken from the function type expected here
+    fn check_closure(&mut self, id: NodeId, closure: &Closure, expected: Option<&Ty>) -> SemanticResult<Ty> {
+        let (expected_params, expected_ret) = match expected {
+            Some(Ty::Function(params, ret)) if params.len() == closure.params.len() => (Some(params), Some(&**ret)),
+            Some(Ty::Function(params, _)) => {
+                let message = format!("expected a closure taking {} values, this one takes {}", params.len(), closure.params.len());
+                return Err(SemanticError::new(message, closure.span));
+            }
+            Some(ty @ Ty::FnPointer(..)) => {
+                let message = format!("a closure can't be stored as `{}`, it carries its captures, declare a function instead", ty);
+                return Err(SemanticError::new(message, closure.span));
+            }
+            _ => (None, None),
+        };
+        let mut params = Vec::new();
+        for (i, param) in closure.params.iter().enumerate() {
+            let hint = expected_params.map(|types| &types[i]);
+            let ty = match (&param.ty, hint) {
+                (Some(written), hint) => {
+                    let ty = self.table.resolve_type_ref(written, &[])?;
+                    if let Some(hint) = hint.filter(|hint| **hint != ty) {
+                        return Err(mismatch(hint, &ty, written.span));
+                    }
+                    ty
+                }
+                (None, Some(hint)) => hint.clone(),
+                (None, None) => {
+                    let message = format!("can't infer the type of `{}`, write it before the name", param.name.name);
+                    return Err(SemanticError::new(message, param.name.span));
+                }
+            };
+            params.push(ty);
+        }
+
+        self.push_scope();
+        for (param, ty) in closure.params.iter().zip(&params) {
+            let local = Local { ty: ty.clone(), mutable: true, constant: false, span: param.name.span };
+            self.scopes.last_mut().unwrap().insert(param.name.name.clone(), local);
+        }
+        // The body is a function of its own, loops and defers around it don't reach in
+        let loops = std::mem::take(&mut self.loops);
+        let in_defer = std::mem::replace(&mut self.in_defer, false);
+        let ret = self.check_block_value(&closure.body, expected_ret);
+        self.loops = loops;
+        self.in_defer = in_defer;
+        self.pop_scope();
+        let ret = ret?;
+
+        let captures = analyze_captures(closure, &|name| self.lookup(name).cloned());
+        self.closures.push(ClosureInfo { id, params: params.clone(), ret: ret.clone(), captures });
+        expect(Ty::Function(params, Box::new(ret)), expected, closure.span)
+    }
+
+    pub fn check_stmt(&mut self, stmt: &Stmt) -> SemanticResult<()> {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => self.check_expr(expr, None).map(drop),
+            StmtKind::Var(decl) => self.check_var(decl).map(drop),
+            StmtKind::While(lp) => {
+                self.check_expr(&lp.cond, Some(&Ty::Bool))?;
+                self.check_loop_body(lp.label.as_ref(), &lp.body)
+            }
+            StmtKind::For(lp) => {
+                self.push_scope();
+                let result = self.check_for(lp.label.as_ref(), &lp.kind, &lp.body);
+                self.pop_scope();
+                result
+            }
+            StmtKind::Block(block) => self.check_block(block),
+            StmtKind::Defer(body) => {
+                let loops = std::mem::take(&mut self.loops);
+                let in_defer = std::mem::replace(&mut self.in_defer, true);
+                let result = self.check_block(body);
+                self.loops = loops;
+                self.in_defer = in_defer;
+                result
+            }
+            StmtKind::Return(_) if self.in_defer => Err(SemanticError::new("`return` can't leave a `defer` block", stmt.span)),
+            StmtKind::Return(value) => value.iter().try_for_each(|value| self.check_expr(value, None).map(drop)),
+            StmtKind::Break(label) => self.check_jump("break", label.as_ref(), stmt.span),
+            StmtKind::Continue(label) => self.check_jump("continue", label.as_ref(), stmt.span),
+            // Already reported by the parser
+            StmtKind::Error(_) => Ok(()),
+        }
+    }
+
+    // Everything but the body is checked in the scope the loop opened
+    fn check_for(&mut self, label: Option<&Ident>, kind: &ForKind, body: &Block) -> SemanticResult<()> {
+        match kind {
+            ForKind::Clauses { init, cond, step } => {
+                if let Some(init) = init {
+                    self.check_stmt(init)?;
+                }
+                if let Some(cond) = cond {
+                    self.check_expr(cond, Some(&Ty::Bool))?;
+                }
+                if let Some(step) = step {
+                    self.check_expr(step, None)?;
+                }
+            }
+            ForKind::In { binding, iterable } => {
+                let lowered = match &iterable.kind {
+                    ExprKind::Binary(operator @ (Operator::Range | Operator::RangeInclusive), start, end) => {
+                        // `0..n` takes its type from `n`
+                        let (first, second) = match start.kind {
+                            ExprKind::Number(_) => (end, start),
+                            _ => (start, end),
+                        };
+                        let ty = self.check_expr(first, None)?;
+                        if !ty.is_integer() {
+                            return Err(SemanticError::new(format!("a range needs integers, found `{}`", ty), first.span));
+                        }
+                        self.check_expr(second, Some(&ty))?;
+                        lower_range(ty, start, end, *operator == Operator::RangeInclusive)
+                    }
+                    _ => {
+                        let ty = self.check_expr(iterable, None)?;
+                        match self.traits {
+                            Some(traits) => lower_iterator(&ty, traits, iterable.span)?,
+                            None => return Err(SemanticError::new(format!("`{}` can't be looped over, only ranges can here", ty), iterable.span)),
+                        }
+                    }
+                };
+                let local = Local { ty: lowered.item().clone(), mutable: true, constant: false, span: binding.span };
+                self.scopes.last_mut().unwrap().insert(binding.name.clone(), local);
+            }
+        }
+        self.check_loop_body(label, body)
+    }
+
+    fn check_loop_body(&mut self, label: Option<&Ident>, body: &Block) -> SemanticResult<()> {
+        if let Some(label) = label {
+            let enclosing = self.loops.iter().flatten().find(|outer| outer.name == label.name);
+            if let Some(outer) = enclosing {
+                return Err(SemanticError::new(format!("the label `'{}` is already used by an enclosing loop", label.name), label.span)
+                    .with_note(outer.span, "first used here"));
+            }
+        }
+        self.loops.push(label.cloned());
+        let result = self.check_block(body);
+        self.loops.pop();
+        result
+    }
+
+    // `break` and `continue` need a loop around them, and a label names one of those loops
+    fn check_jump(&self, keyword: &str, label: Option<&Ident>, span: Span) -> SemanticResult<()> {
+        if self.loops.is_empty() {
+            return Err(SemanticError::new(format!("`{}` outside of a loop", keyword), span));
+        }
+        match label {
+            Some(label) if !self.loops.iter().flatten().any(|outer| outer.name == label.name) => {
+                Err(SemanticError::new(format!("no loop around this `{}` is labeled `'{}`", keyword, label.name), label.span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Declares the variable and returns its type, taken from the value when none is written
+    pub fn check_var(&mut self, decl: &VarDecl) -> SemanticResult<Ty> {
+        let declared = decl.ty.as_ref().map(|ty| self.table.resolve_type_ref(ty, &[])).transpose()?;
+        let ty = match (declared, &decl.value) {
+            (declared, Some(value)) => self.check_expr(value, declared.as_ref())?,
+            (Some(declared), None) => declared,
+            (None, None) => return Err(SemanticError::new(format!("`{}` needs a type or a value to infer it from", decl.name.name), decl.span)),
+        };
+        let constant = decl.value.as_ref().is_some_and(|value| self.is_constant(value));
+        if decl.storage == Storage::Static && !constant {
+            let span = decl.value.as_ref().map_or(decl.span, |value| value.span);
+            return Err(SemanticError::new(format!("`{}` is `static`, its value must be known at compile time", decl.name.name), span));
+        }
+        let local = Local {
+            ty: ty.clone(),
+            mutable: decl.mutability == Mutability::Var,
+            constant: constant && decl.mutability == Mutability::Const,
+            span: decl.name.span,
+        };
+        self.scopes.last_mut().unwrap().insert(decl.name.name.clone(), local);
+        Ok(ty)
+    }
+
+    // Whether the value of `expr` can be worked out while compiling
+    fn is_constant(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Number(_) => true,
+            ExprKind::Name(name) => match self.local(name) {
+                Some(local) => local.constant,
+                None => self.functions.contains_key(name),
+            },
+            ExprKind::Unary(Operator::Not, operand) => self.is_constant(operand),
+            ExprKind::Binary(operator, lhs, rhs) => !operator.is_assignment() && self.is_constant(lhs) && self.is_constant(rhs),
+            ExprKind::Tuple(items) | ExprKind::Array(items) => items.iter().all(|item| self.is_constant(item)),
+            // Enum constructors
+            ExprKind::Field(base, _) => self.named_enum(base).is_some(),
+            ExprKind::Call(callee, args) => self.is_constant(callee) && args.iter().all(|arg| self.is_constant(arg)),
+            _ => false,
+        }
+    }
+
+    // The variable an assignment writes to, unless it writes through a pointer
+    fn assigned_local(&mut self, place: &Expr) -> Option<String> {
+        match &place.kind {
+            ExprKind::Name(name) => Some(name.clone()),
+            ExprKind::Index(base, _) => self.assigned_local(base),
+            ExprKind::Field(base, _) => match self.check_expr(base, None) {
+                Ok(Ty::Pointer(_)) => None,
+                _ => self.assigned_local(base),
+            },
+            _ => None,
+        }
+    }
+
+    // The enum an expression names, `Shape` in `Shape.Circle`, unless a variable shadows it
+    fn named_enum(&self, expr: &Expr) -> Option<&'a EnumInfo> {
+        match &expr.kind {
+            ExprKind::Name(name) if self.lookup(name).is_none() => self.table.enums.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn check_expr(&mut self, expr: &Expr, expected: Option<&Ty>) -> SemanticResult<Ty> {
+        match &expr.kind {
+            ExprKind::Number(number) => check_number(number, expected, expr.span),
+            // There is no string type yet
+            ExprKind::Str(_) => Err(SemanticError::new("a string can only be a decorator argument", expr.span)),
+            ExprKind::If(conditional) => self.check_conditional(conditional, expected),
+            ExprKind::Name(name) if self.declared_function(expr).is_some() => {
+                let ty = self.use_function(name, expr.span);
+                self.function_values.push(expr.id);
+                expect(ty, expected, expr.span)
+            }
+            ExprKind::Name(name) => match self.lookup(name) {
+                Some(ty) => expect(ty.clone(), expected, expr.span),
+                None if self.table.is_declared(name) => Err(SemanticError::new(format!("`{}` is a type, not a value", name), expr.span)),
+                None => Err(SemanticError::new(format!("unknown name `{}`", name), expr.span)),
+            },
+            ExprKind::Unary(operator, operand) => self.check_unary(*operator, operand, expected, expr.span),
+            ExprKind::Binary(operator, lhs, rhs) => self.check_binary(*operator, lhs, rhs, expected, expr.span),
+            ExprKind::Call(callee, args) => match &callee.kind {
+                ExprKind::Field(base, variant) if self.named_enum(base).is_some() => {
+                    self.check_constructor(self.named_enum(base).unwrap(), variant, Some(args), expected, expr.span)
+                }
+                _ => {
+                    // Calling a function by its name is a direct call, not a use of its address
+                    let direct = self.declared_function(callee);
+                    let callee_ty = match direct {
+                        Some(name) => self.use_function(name, callee.span),
+                        None => self.check_expr(callee, None)?,
+                    };
+                    match callee_ty {
+                        Ty::Function(params, ret) => {
+                            if params.len() != args.len() {
+                                return Err(SemanticError::new(format!("this closure takes {} values, found {}", params.len(), args.len()), expr.span));
+                            }
+                            for (arg, ty) in args.iter().zip(&params) {
+                                self.check_expr(arg, Some(ty))?;
+                            }
+                            self.closure_calls.push(expr.id);
+                            expect(*ret, expected, expr.span)
+                        }
+                        Ty::FnPointer(abi, params, ret) => {
+                            if params.len() != args.len() {
+                                return Err(SemanticError::new(format!("this function takes {} values, found {}", params.len(), args.len()), expr.span));
+                            }
+                            for (arg, ty) in args.iter().zip(&params) {
+                                self.check_expr(arg, Some(ty))?;
+                            }
+                            if direct.is_none() {
+                                self.indirect_calls.push((expr.id, abi));
+                            }
+                            expect(*ret, expected, expr.span)
+                        }
+                        ty => Err(SemanticError::new(
+                            format!("`{}` can't be called, only enum variants, functions and closures can", ty),
+                            callee.span,
+                        )),
+                    }
+                }
+            },
+            ExprKind::Closure(closure) => self.check_closure(expr.id, closure, expected),
+            ExprKind::Field(base, name) => {
+                if let Some(info) = self.named_enum(base) {
+                    return self.check_constructor(info, name, None, expected, expr.span);
+                }
+                let base_ty = match self.check_expr(base, None)? {
+                    // Fields are reached through one pointer without writing `*`
+                    Ty::Pointer(inner) => *inner,
+                    ty => ty,
+                };
+                let field = match &base_ty {
+                    Ty::Named(struct_name, args) => self.table.structs.get(struct_name).and_then(|info| {
+                        info.field(&name.name).map(|field| field.ty.substitute(&info.generics, args))
+                    }),
+                    _ => None,
+                };
+                let field = field.ok_or_else(|| SemanticError::new(format!("`{}` has no field `{}`", base_ty, name.name), name.span))?;
+                expect(field, expected, expr.span)
+            }
+            ExprKind::Tuple(items) => {
+                let expected_items = match expected {
+                    Some(Ty::Tuple(types)) if types.len() == items.len() => Some(types),
+                    _ => None,
+                };
+                let types = items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| self.check_expr(item, expected_items.map(|types| &types[i])))
+                    .collect::<SemanticResult<Vec<_>>>()?;
+                expect(Ty::Tuple(types), expected, expr.span)
+            }
+            ExprKind::Postfix(Operator::ErrorOperator, _) if self.in_defer => {
+                Err(SemanticError::new("`!` can't pass an error out of a `defer` block", expr.span))
+            }
+            ExprKind::Postfix(operator, _) => {
+                Err(SemanticError::new(format!("`{}` is not supported by the type checker yet", operator.text()), expr.span))
+            }
+            // Only made by lowering, after checking
+            ExprKind::Propagate(..) => Err(SemanticError::new("`!` is not supported by the type checker yet", expr.span)),
+            ExprKind::Index(..) | ExprKind::Array(_) => Err(SemanticError::new("arrays are not supported by the type checker yet", expr.span)),
+            ExprKind::Match(value, arms) => {
+                let scrutinee = self.check_expr(value, None)?;
+                // The first arm decides the type when nothing is expected
+                let mut result = expected.cloned();
+                for arm in arms {
+                    self.push_scope();
+                    let ty = self.check_arm(arm, &scrutinee, result.as_ref());
+                    self.pop_scope();
+                    result.get_or_insert(ty?);
+                }
+                lower_match(&scrutinee, arms, self.table, expr.span)?;
+                Ok(result.unwrap_or_else(Ty::unit))
+            }
+        }
+    }
+
+    fn check_arm(&mut self, arm: &MatchArm, scrutinee: &Ty, expected: Option<&Ty>) -> SemanticResult<Ty> {
+        self.check_pattern(&arm.pattern, scrutinee)?;
+        if let Some(guard) = &arm.guard {
+            self.check_expr(guard, Some(&Ty::Bool))?;
+        }
+        self.check_expr(&arm.body, expected)
+    }
+
+    // Declares what `pattern` binds in the current scope
+    pub fn check_pattern(&mut self, pattern: &Pattern, scrutinee: &Ty) -> SemanticResult<()> {
+        let mut bound = Vec::new();
+        self.check_subpattern(pattern, scrutinee, &mut bound)
+    }
+
+    fn bind(&mut self, name: &Ident, ty: Ty, bound: &mut Vec<String>) -> SemanticResult<()> {
+        if bound.contains(&name.name) {
+            return Err(SemanticError::new(format!("`{}` is bound more than once in this pattern", name.name), name.span));
+        }
+        bound.push(name.name.clone());
+        self.declare(&name.name, ty);
+        Ok(())
+    }
+
+    fn check_subpattern(&mut self, pattern: &Pattern, scrutinee: &Ty, bound: &mut Vec<String>) -> SemanticResult<()> {
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(()),
+            PatternKind::Binding(name) => self.bind(name, scrutinee.clone(), bound),
+            PatternKind::Literal(number) => check_number_pattern(number, scrutinee, pattern.span).map(|_| ()),
+            PatternKind::Range(start, end, inclusive) => {
+                let start = check_number_pattern(start, scrutinee, pattern.span)?;
+                let end = check_number_pattern(end, scrutinee, pattern.span)?;
+                if start > end || (start == end && !inclusive) {
+                    return Err(SemanticError::new("this range is empty", pattern.span));
+                }
+                Ok(())
+            }
+            PatternKind::Tuple(items) => match scrutinee {
+                Ty::Tuple(types) if types.len() == items.len() => {
+                    items.iter().zip(types).try_for_each(|(item, ty)| self.check_subpattern(item, ty, bound))
+                }
+                _ => Err(SemanticError::new(format!("a tuple of {} values can't match `{}`", items.len(), scrutinee), pattern.span)),
+            },
+            PatternKind::Struct(name, args) => {
+                let info = self
+                    .table
+                    .structs
+                    .get(&name.name)
+                    .ok_or_else(|| SemanticError::new(format!("`{}` is not a struct", name.name), name.span))?;
+                let generic_args = match scrutinee {
+                    Ty::Named(found, generic_args) if *found == info.name => generic_args,
+                    _ => return Err(SemanticError::new(format!("a `{}` pattern can't match `{}`", info.name, scrutinee), pattern.span)),
+                };
+                let mut seen: Vec<&str> = Vec::new();
+                for arg in args {
+                    let field_name = arg_field_name(arg)?;
+                    if seen.contains(&field_name.name.as_str()) {
+                        return Err(SemanticError::new(format!("field `{}` is matched more than once", field_name.name), arg.span));
+                    }
+                    seen.push(&field_name.name);
+                    let field = info
+                        .field(&field_name.name)
+                        .ok_or_else(|| SemanticError::new(format!("`{}` has no field `{}`", info.name, field_name.name), field_name.span))?;
+                    self.check_subpattern(&arg.pattern, &field.ty.substitute(&info.generics, generic_args), bound)?;
+                }
+                Ok(())
+            }
+            PatternKind::Variant(enum_name, variant, args) => {
+                let by_name = self
+                    .table
+                    .enums
+                    .get(&enum_name.name)
+                    .and_then(|info| info.variant(&variant.name))
+                    .is_some_and(|found| matches!(found.payload, PayloadInfo::Struct(_)));
+                let names: Vec<Ident>;
+                let bindings = match args {
+                    None => VariantBindings::Unit,
+                    Some(args) if by_name => {
+                        names = args.iter().map(|arg| arg_field_name(arg).cloned()).collect::<SemanticResult<_>>()?;
+                        VariantBindings::Named(&names)
+                    }
+                    Some(args) => {
+                        if let Some(named) = args.iter().find(|arg| arg.name.is_some()) {
+                            return Err(SemanticError::new(
+                                format!("`{}.{}` has a tuple payload, match it by position", enum_name.name, variant.name),
+                                named.span,
+                            ));
+                        }
+                        VariantBindings::Positional(args.len())
+                    }
+                };
+                let types = self.check_variant_pattern(scrutinee, enum_name, variant, bindings, pattern.span)?;
+                for (arg, ty) in args.iter().flatten().zip(&types) {
+                    self.check_subpattern(&arg.pattern, ty, bound)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_unary(&mut self, operator: Operator, operand: &Expr, expected: Option<&Ty>, span: Span) -> SemanticResult<Ty> {
+        match operator {
+            Operator::Reference => {
+                let inner = match expected {
+                    Some(Ty::Pointer(inner)) => Some(&**inner),
+                    _ => None,
+                };
+                let ty = Ty::Pointer(Box::new(self.check_expr(operand, inner)?));
+                expect(ty, expected, span)
+            }
+            Operator::Dereference => match self.check_expr(operand, None)? {
+                Ty::Pointer(inner) => expect(*inner, expected, span),
+                other => Err(SemanticError::new(format!("can't dereference `{}`, it is not a pointer", other), operand.span)),
+            },
+            _ => {
+                let ty = self.check_expr(operand, expected)?;
+                if !ty.is_integer() {
+                    return Err(SemanticError::new(format!("`{}` needs an integer, found `{}`", operator.text(), ty), operand.span));
+                }
+                Ok(ty)
+            }
+        }
+    }
+
+    fn check_binary(&mut self, operator: Operator, lhs: &Expr, rhs: &Expr, expected: Option<&Ty>, span: Span) -> SemanticResult<Ty> {
+        use Operator::*;
+        match operator {
+            Assign | AddEq | SubEq | MultEq | DivEq | ModEq | BitwiseAndEq | XorEq | BitwiseOrEq | BitwiseShiftRightEq
+            | BitwiseShiftLeftEq | BitwiseUnsignedShiftRightEq | BitwiseUnsignedShiftLeftEq | LogicalAndEq | LogicalOrEq => {
+                if !is_place(lhs) {
+                    return Err(SemanticError::new("can't assign to this expression", lhs.span));
+                }
+                if let Some(name) = self.assigned_local(lhs) {
+                    match self.local(&name) {
+                        Some(local) if !local.mutable => {
+                            return Err(SemanticError::new(format!("can't assign to `{}`, it is `const`", name), lhs.span)
+                                .with_note(local.span, "declared `const` here"));
+                        }
+                        None if self.functions.contains_key(&name) => {
+                            return Err(SemanticError::new(format!("can't assign to `{}`, it is a function", name), lhs.span));
+                        }
+                        _ => {}
+                    }
+                }
+                let ty = self.check_expr(lhs, None)?;
+                if operator != Assign && !ty.is_numeric() && ty != Ty::Bool {
+                    return Err(SemanticError::new(format!("`{}` needs a number, found `{}`", operator.text(), ty), lhs.span));
+                }
+                self.check_expr(rhs, Some(&ty))?;
+                expect(Ty::unit(), expected, span)
+            }
+            Add | Sub | Mult | Div | Mod | BitwiseAnd | BitwiseOr | Xor | BitwiseShiftLeft | BitwiseShiftRight
+            | BitwiseUnsignedShiftLeft | BitwiseUnsignedShiftRight => {
+                let hint = expected.filter(|ty| ty.is_numeric());
+                let ty = self.check_expr(lhs, hint)?;
+                let needs_integer = !matches!(operator, Add | Sub | Mult | Div | Mod);
+                if !ty.is_numeric() || (needs_integer && !ty.is_integer()) {
+                    return Err(SemanticError::new(format!("`{}` can't be used on `{}`", operator.text(), ty), lhs.span));
+                }
+                self.check_expr(rhs, Some(&ty))?;
+                expect(ty, expected, span)
+            }
+            EqualityCheck | NotEqualityCheck | GreaterThan | GreaterThanEq | LesserThan | LesserThanEq => {
+                let ty = self.check_expr(lhs, None)?;
+                if !matches!(operator, EqualityCheck | NotEqualityCheck) && !ty.is_numeric() {
+                    return Err(SemanticError::new(format!("`{}` can only be ordered if it is a number", ty), lhs.span));
+                }
+                self.check_expr(rhs, Some(&ty))?;
+                expect(Ty::Bool, expected, span)
+            }
+            LogicalAnd | LogicalOr => {
+                self.check_expr(lhs, Some(&Ty::Bool))?;
+                self.check_expr(rhs, Some(&Ty::Bool))?;
+                expect(Ty::Bool, expected, span)
+            }
+            Range | RangeInclusive => Err(SemanticError::new("ranges can only be looped over or matched for now", span)),
+            _ => Err(SemanticError::new(format!("`{}` is not supported by the type checker yet", operator.text()), span)),
+        }
+    }
+
+    // `Enum.Variant` when `args` is `None`, otherwise `Enum.Variant(args)`
+    fn check_constructor(&mut self, info: &EnumInfo, variant: &Ident, args: Option<&[Expr]>, expected: Option<&Ty>, span: Span) -> SemanticResult<Ty> {
+        let found = info
+            .variant(&variant.name)
+            .ok_or_else(|| SemanticError::new(format!("`{}` has no variant `{}`", info.name, variant.name), variant.span))?;
+        let full_name = format!("{}.{}", info.name, variant.name);
+
+        let mut solutions: Vec<Option<Ty>> = vec![None; info.generics.len()];
+        if let Some(Ty::Named(name, args)) = expected {
+            if *name == info.name && args.len() == solutions.len() {
+                solutions = args.iter().cloned().map(Some).collect();
+            }
+        }
+
+        // Each argument with the payload type it must have
+        let mut pairs: Vec<(&Expr, &Ty)> = Vec::new();
+        match (&found.payload, args) {
+            (PayloadInfo::Unit, None) => {}
+            (PayloadInfo::Unit, Some(_)) => return Err(SemanticError::new(format!("`{}` has no payload", full_name), span)),
+            (_, None) => return Err(SemanticError::new(format!("`{}` needs a payload", full_name), span)),
+            (PayloadInfo::Tuple(types), Some(args)) => {
+                if types.len() != args.len() {
+                    return Err(SemanticError::new(format!("`{}` takes {} values, found {}", full_name, types.len(), args.len()), span));
+                }
+                pairs.extend(args.iter().zip(types));
+            }
+            (PayloadInfo::Struct(fields), Some(args)) => {
+                let mut given: Vec<&str> = Vec::new();
+                for arg in args {
+                    let (name, value) = match &arg.kind {
+                        ExprKind::Binary(Operator::Assign, name, value) => match &name.kind {
+                            ExprKind::Name(name) => (name, value),
+                            _ => return Err(SemanticError::new("expected a field name", name.span)),
+                        },
+                        _ => return Err(SemanticError::new(format!("`{}` takes its fields by name, `field = value`", full_name), arg.span)),
+                    };
+                    let field = fields
+                        .iter()
+                        .find(|field| field.name == *name)
+                        .ok_or_else(|| SemanticError::new(format!("`{}` has no field `{}`", full_name, name), arg.span))?;
+                    if given.contains(&name.as_str()) {
+                        return Err(SemanticError::new(format!("field `{}` is given more than once", name), arg.span));
+                    }
+                    given.push(name);
+                    pairs.push((value, &field.ty));
+                }
+                if let Some(missing) = fields.iter().find(|field| !field.has_default && !given.contains(&field.name.as_str())) {
+                    return Err(SemanticError::new(format!("missing field `{}` of `{}`", missing.name, full_name), span));
+                }
+            }
+        }
+
+        for (arg, ty) in pairs {
+            let known: Vec<Ty> = solutions.iter().map(|solution| solution.clone().unwrap_or(Ty::unit())).collect();
+            let hint = if solutions.iter().all(Option::is_some) { Some(ty.substitute(&info.generics, &known)) } else { None };
+            let found = self.check_expr(arg, hint.as_ref())?;
+            if !unify(ty, &found, &info.generics, &mut solutions) {
+                let known: Vec<Ty> = solutions.iter().map(|solution| solution.clone().unwrap_or(Ty::unit())).collect();
+                return Err(mismatch(&ty.substitute(&info.generics, &known), &found, arg.span));
+            }
+        }
+
+        let args = solutions
+            .into_iter()
+            .zip(&info.generics)
+            .map(|(solution, param)| {
+                solution.ok_or_else(|| SemanticError::new(format!("can't infer `{}` for `{}`", param, full_name), span))
+            })
+            .collect::<SemanticResult<Vec<_>>>()?;
+        expect(Ty::Named(info.name.clone(), args), expected, span)
+    }
+
+    // The types of the bindings in `bindings` order, when matching `scrutinee` against `enum_name.variant`
+    pub fn check_variant_pattern(&self, scrutinee: &Ty, enum_name: &Ident, variant: &Ident, bindings: VariantBindings, span: Span) -> SemanticResult<Vec<Ty>> {
+        let info = self
+            .table
+            .enums
+            .get(&enum_name.name)
+            .ok_or_else(|| SemanticError::new(format!("`{}` is not an enum", enum_name.name), enum_name.span))?;
+        let args = match scrutinee {
+            Ty::Named(name, args) if *name == info.name => args,
+            _ => return Err(SemanticError::new(format!("a `{}` pattern can't match `{}`", info.name, scrutinee), span)),
+        };
+        let found = info
+            .variant(&variant.name)
+            .ok_or_else(|| SemanticError::new(format!("`{}` has no variant `{}`", info.name, variant.name), variant.span))?;
+        let full_name = format!("{}.{}", info.name, variant.name);
+
+        let types = match (&found.payload, bindings) {
+            (PayloadInfo::Unit, VariantBindings::Unit) => Vec::new(),
+            (PayloadInfo::Tuple(types), VariantBindings::Positional(count)) if types.len() == count => types.clone(),
+            (PayloadInfo::Tuple(types), VariantBindings::Positional(count)) => {
+                return Err(SemanticError::new(format!("`{}` has {} values, the pattern binds {}", full_name, types.len(), count), span));
+            }
+            (PayloadInfo::Struct(fields), VariantBindings::Named(names)) => names
+                .iter()
+                .map(|name| {
+                    fields
+                        .iter()
+                        .find(|field| field.name == name.name)
+                        .map(|field| field.ty.clone())
+                        .ok_or_else(|| SemanticError::new(format!("`{}` has no field `{}`", full_name, name.name), name.span))
+                })
+                .collect::<SemanticResult<_>>()?,
+            (PayloadInfo::Unit, _) => return Err(SemanticError::new(format!("`{}` has no payload to bind", full_name), span)),
+            (PayloadInfo::Tuple(_), _) => return Err(SemanticError::new(format!("`{}` has a tuple payload, bind it by position", full_name), span)),
+            (PayloadInfo::Struct(_), _) => return Err(SemanticError::new(format!("`{}` has named fields, bind them by name", full_name), span)),
+        };
+        Ok(types.iter().map(|ty| ty.substitute(&info.generics, args)).collect())
+    }
+}
+
+// The field a struct pattern argument matches, `field = pattern` or just `field`
+fn arg_field_name(arg: &PatternArg) -> SemanticResult<&Ident> {
+    match (&arg.name, &arg.pattern.kind) {
+        (Some(name), _) | (None, PatternKind::Binding(name)) => Ok(name),
+        _ => Err(SemanticError::new("name the field this pattern matches, `field = pattern`", arg.span)),
+    }
+}
+
+// The value of a number pattern, which must be an integer of the matched type
+fn check_number_pattern(number: &NumberLiteral, scrutinee: &Ty, span: Span) -> SemanticResult<i128> {
+    if number.has_decimal {
+        return Err(SemanticError::new(format!("`{}` can't be matched exactly, compare it in a `where` guard", number.text_content), span));
+    }
+    if !scrutinee.is_integer() {
+        return Err(SemanticError::new(format!("a number can't match `{}`", scrutinee), span));
+    }
+    check_number(number, Some(scrutinee), span)?;
+    number.parse_int::<i128>().map_err(|_| SemanticError::new(format!("{} is not a valid integer", number.text_content), span))
+}
+
+// Something that can be assigned to
+fn is_place(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Name(_) | ExprKind::Field(..) | ExprKind::Index(..) | ExprKind::Unary(Operator::Dereference, _))
+}
+
+fn check_number(number: &NumberLiteral, expected: Option<&Ty>, span: Span) -> SemanticResult<Ty> {
+    let is_float = number.has_decimal;
+    let ty = match (&number.number_type, expected) {
+        (Some(suffix), _) => Ty::Primitive(suffix.clone()),
+        (None, Some(Ty::Primitive(primitive))) if primitive.is_float() || !is_float => Ty::Primitive(primitive.clone()),
+        (None, _) if is_float => Ty::Primitive(Primitive::F64),
+        (None, _) => Ty::Primitive(Primitive::I32),
+    };
+    let ty = expect(ty, expected, span)?;
+    if let Ty::Primitive(primitive) = &ty {
+        if is_float && !primitive.is_float() {
+            return Err(SemanticError::new(format!("`{}` is not an integer", number.text_content), span));
+        }
+        if !primitive.is_float() && !number.parse_int::<i128>().is_ok_and(|value| fits(value, primitive)) {
+            return Err(SemanticError::new(format!("{} does not fit in `{}`", number.text_content, primitive.name()), span));
+        }
+    }
+    Ok(ty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        parsing::tokenizer::tokenize_text,
+        treegen::{ast::Item, expr::parse_expr, items::parse_items, parser::Cursor, stmt::parse_block},
+    };
+
+    const DECLS: &str = "
+        struct Point { i32 x, i32 y }
+        enum Shape { Circle(Point, f32), Rect { f32 w, f32 h = 1.0 }, Empty }
+        enum Maybe<T> { Some(T), Nothing }
+        struct Countdown { u32 left }
+        impl Iterator for Countdown {
+            typedef u32 Item;
+            bool next(Self* self, Item* item) => 1 < 2;
+        }";
+
+    fn table() -> TypeTable {
+        let tokens = tokenize_text(DECLS.to_string()).unwrap();
+        TypeTable::from_items(&parse_items(&tokens, DECLS.len()).unwrap()).unwrap()
+    }
+
+    fn check(table: &TypeTable, text: &str, expected: Option<&Ty>) -> SemanticResult<Ty> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let expr = parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap();
+        let mut checker = Checker::new(table);
+        checker.declare("p", Ty::Named("Point".to_string(), vec![]));
+        checker.declare("shape", Ty::Named("Shape".to_string(), vec![]));
+        checker.declare("byte", Ty::Primitive(Primitive::U8));
+        checker.check_expr(&expr, expected)
+    }
+
+    fn named(name: &str, args: Vec<Ty>) -> Ty {
+        Ty::Named(name.to_string(), args)
+    }
+
+    fn ident(name: &str) -> Ident {
+        Ident { name: name.to_string(), span: Span::default() }
+    }
+
+    #[test]
+    fn constructors() {
+        let table = table();
+        assert_eq!(check(&table, "Shape.Circle(p, 2.5)", None), Ok(named("Shape", vec![])));
+        assert_eq!(check(&table, "Shape.Rect(w = 1.0)", None), Ok(named("Shape", vec![])));
+        assert_eq!(check(&table, "Shape.Empty", None), Ok(named("Shape", vec![])));
+        assert_eq!(check(&table, "Maybe.Some(byte)", None), Ok(named("Maybe", vec![Ty::Primitive(Primitive::U8)])));
+        assert_eq!(
+            check(&table, "Maybe.Nothing", Some(&named("Maybe", vec![Ty::Primitive(Primitive::I64)]))),
+            Ok(named("Maybe", vec![Ty::Primitive(Primitive::I64)]))
+        );
+        assert_eq!(check(&table, "p.x + 1 == 3", None), Ok(Ty::Bool));
+    }
+
+    #[test]
+    fn constructor_errors() {
+        let table = table();
+        let message = |text: &str| check(&table, text, None).unwrap_err().message;
+        assert_eq!(message("Shape.Circle(p)"), "`Shape.Circle` takes 2 values, found 1");
+        assert_eq!(message("Shape.Circle(p, p)"), "expected `f32`, found `Point`");
+        assert_eq!(message("Shape.Rect(h = 2.0)"), "missing field `w` of `Shape.Rect`");
+        assert_eq!(message("Shape.Rect(w = 1.0, d = 2.0)"), "`Shape.Rect` has no field `d`");
+        assert_eq!(message("Shape.Empty(1)"), "`Shape.Empty` has no payload");
+        assert_eq!(message("Shape.Circle"), "`Shape.Circle` needs a payload");
+        assert_eq!(message("Shape.Square"), "`Shape` has no variant `Square`");
+        assert_eq!(message("Maybe.Nothing"), "can't infer `T` for `Maybe.Nothing`");
+        assert_eq!(message("byte + 300"), "300 does not fit in `u8`");
+        assert_eq!(message("byte + \"300\""), "a string can only be a decorator argument");
+    }
+
+    #[test]
+    fn variant_patterns() {
+        let table = table();
+        let checker = Checker::new(&table);
+        let shape = named("Shape", vec![]);
+        let circle = checker.check_variant_pattern(&shape, &ident("Shape"), &ident("Circle"), VariantBindings::Positional(2), Span::default());
+        assert_eq!(circle, Ok(vec![named("Point", vec![]), Ty::Primitive(Primitive::F32)]));
+
+        let names = [ident("h")];
+        let rect = checker.check_variant_pattern(&shape, &ident("Shape"), &ident("Rect"), VariantBindings::Named(&names), Span::default());
+        assert_eq!(rect, Ok(vec![Ty::Primitive(Primitive::F32)]));
+
+        let maybe = named("Maybe", vec![Ty::Bool]);
+        let some = checker.check_variant_pattern(&maybe, &ident("Maybe"), &ident("Some"), VariantBindings::Positional(1), Span::default());
+        assert_eq!(some, Ok(vec![Ty::Bool]));
+
+        let wrong_enum = checker.check_variant_pattern(&maybe, &ident("Shape"), &ident("Empty"), VariantBindings::Unit, Span::default());
+        assert_eq!(wrong_enum.unwrap_err().message, "a `Shape` pattern can't match `Maybe<bool>`");
+        let wrong_shape = checker.check_variant_pattern(&shape, &ident("Shape"), &ident("Rect"), VariantBindings::Positional(2), Span::default());
+        assert_eq!(wrong_shape.unwrap_err().message, "`Shape.Rect` has named fields, bind them by name");
+    }
+
+    fn check_block<'a>(table: &'a TypeTable, text: &str) -> SemanticResult<Checker<'a>> {
+        check_block_with(Checker::new(table), text)
+    }
+
+    fn check_block_with<'a>(mut checker: Checker<'a>, text: &str) -> SemanticResult<Checker<'a>> {
+        let text = format!("{{{}}}", text);
+        let tokens = tokenize_text(text).unwrap();
+        let block = parse_block(&tokens[0]).unwrap();
+        checker.push_scope();
+        block.stmts.iter().try_for_each(|stmt| checker.check_stmt(stmt))?;
+        if let Some(value) = &block.value {
+            checker.check_expr(value, Some(&Ty::unit()))?;
+        }
+        Ok(checker)
+    }
+
+    #[test]
+    fn variables() {
+        let table = table();
+        let checker = check_block(&table, "var a = 4; var b = 4u8; u16 c = 7; const d = Shape.Empty; i64 e; var f = (a, c); a = 5; e = 9;").unwrap();
+        assert_eq!(checker.lookup("a"), Some(&Ty::Primitive(Primitive::I32)));
+        assert_eq!(checker.lookup("b"), Some(&Ty::Primitive(Primitive::U8)));
+        assert_eq!(checker.lookup("c"), Some(&Ty::Primitive(Primitive::U16)));
+        assert_eq!(checker.lookup("d"), Some(&named("Shape", vec![])));
+        assert_eq!(checker.lookup("f"), Some(&Ty::Tuple(vec![Ty::Primitive(Primitive::I32), Ty::Primitive(Primitive::U16)])));
+
+        assert!(check_block(&table, "const u64 N = 4; static u64 total = N * 2; static var count = 0; count = count + 1;").is_ok());
+        assert!(check_block(&table, "const Point p = Point.Origin;").is_err());
+
+        let message = |text: &str| check_block(&table, text).err().unwrap().message;
+        assert_eq!(message("const n = 4; n = 5;"), "can't assign to `n`, it is `const`");
+        assert_eq!(message("static const u8 LIMIT = 4; LIMIT += 1;"), "can't assign to `LIMIT`, it is `const`");
+        assert_eq!(message("var x = 1; static var y = x;"), "`y` is `static`, its value must be known at compile time");
+        assert_eq!(message("u8 x = 256;"), "256 does not fit in `u8`");
+        assert_eq!(message("var x = x;"), "unknown name `x`");
+        let error = check_block(&table, "const n = 4; n = 5;").err().unwrap();
+        assert_eq!(error.notes, [(Span::new(7, 8), "declared `const` here".to_string())]);
+    }
+
+    #[test]
+    fn loop_control() {
+        let table = table();
+        assert!(check_block(&table, "var i = 0; while (i < 10) { i += 1; 'if_zero: while (i == 0) break; continue; }").is_ok());
+        assert!(check_block(&table, "'outer: while (1 < 2) { 'inner: while (2 < 3) { break 'outer; } continue 'outer; }").is_ok());
+        assert!(check_block(&table, "comptime while (1 < 2) break;").is_ok());
+
+        let message = |text: &str| check_block(&table, text).err().unwrap().message;
+        assert_eq!(message("break;"), "`break` outside of a loop");
+        assert_eq!(message("while (1 < 2) {} continue;"), "`continue` outside of a loop");
+        assert_eq!(message("'a: while (1 < 2) {} while (1 < 2) { break 'a; }"), "no loop around this `break` is labeled `'a`");
+        assert_eq!(message("var i = 0; while (i) {}"), "expected `bool`, found `i32`");
+        let error = check_block(&table, "'a: while (1 < 2) { 'a: while (1 < 2) {} }").err().unwrap();
+        assert_eq!(error.message, "the label `'a` is already used by an enclosing loop");
+        assert_eq!(error.notes, [(Span::new(1, 3), "first used here".to_string())]);
+    }
+
+    #[test]
+    fn for_loops() {
+        let tokens = tokenize_text(DECLS.to_string()).unwrap();
+        let items = parse_items(&tokens, DECLS.len()).unwrap();
+        let table = TypeTable::from_items(&items).unwrap();
+        let traits = TraitTable::from_items(&items, &table).unwrap();
+        let run = |text: &str| {
+            let text = format!("{{{}}}", text);
+            let tokens = tokenize_text(text).unwrap();
+            let block = parse_block(&tokens[0]).unwrap();
+            Checker::new(&table).with_traits(&traits).check_block(&block)
+        };
+
+        assert!(run("u8 n = 9; for (i in 0..n) { u8 copy = i; } for (j in n..=255) continue;").is_ok());
+        assert!(run("for (u32 i = 0; i < 10; i += 1) { u32 copy = i; } for (;;) break;").is_ok());
+        assert!(run("Countdown c; 'each: for (left in c) { u32 copy = left; break 'each; }").is_ok());
+
+        let message = |text: &str| run(text).err().unwrap().message;
+        assert_eq!(message("Point p; for (x in p) {}"), "`Point` can't be looped over, it does not implement `Iterator`");
+        assert_eq!(message("for (x in 0.0..1.0) {}"), "a range needs integers, found `f64`");
+        assert_eq!(message("for (var i = 0; i; i += 1) {}"), "expected `bool`, found `i32`");
+        assert_eq!(message("for (i in 0..4) {} i = 1;"), "unknown name `i`");
+        assert_eq!(message("var r = 0..4;"), "ranges can only be looped over or matched for now");
+        let without_traits = check_block(&table, "Countdown c; for (x in c) {}").err().unwrap();
+        assert_eq!(without_traits.message, "`Countdown` can't be looped over, only ranges can here");
+    }
+
+    #[test]
+    fn conditionals() {
+        let table = table();
+        let checker = check_block(&table, "var a = 1; u8 b = if (a < 2) 3 else if (a < 4) { 5 } else 6; var c = if (a == 1) { a } else 2; if (a == 2) { a = 3; }").unwrap();
+        assert_eq!(checker.lookup("b"), Some(&Ty::Primitive(Primitive::U8)));
+        assert_eq!(checker.lookup("c"), Some(&Ty::Primitive(Primitive::I32)));
+        assert!(check_block(&table, "const n = 4; comptime if (n > 2) { } else { }").is_ok());
+
+        let message = |text: &str| check_block(&table, text).err().unwrap().message;
+        assert_eq!(message("var a = 1; var b = if (a) 1 else 2;"), "expected `bool`, found `i32`");
+        assert_eq!(message("var a = 1; u8 b = if (a < 2) 3;"), "this `if` needs an `else` to produce a `u8`");
+        assert_eq!(message("var a = 1; if (a < 2) { a }"), "expected `()`, found `i32`");
+        assert_eq!(message("var a = 1; var b = if (a < 2) 1 else 2.0;"), "expected `i32`, found `f64`");
+        assert_eq!(message("var a = 1; comptime if (a < 2) {}"), "the condition of a `comptime if` must be known at compile time");
+        assert_eq!(message("while (1 < 2) { 4 }"), "expected `()`, found `i32`");
+    }
+
+    #[test]
+    fn defer_blocks() {
+        let table = table();
+        assert!(check_block(&table, "var a = 1; defer { a = 2; } { defer a += 1; } while (a < 3) { defer { while (a < 9) break; } break; } return a;").is_ok());
+
+        let message = |text: &str| check_block(&table, text).err().unwrap().message;
+        assert_eq!(message("defer return;"), "`return` can't leave a `defer` block");
+        assert_eq!(message("while (1 < 2) { defer break; }"), "`break` outside of a loop");
+        assert_eq!(message("var a = 1; defer { a!; }"), "`!` can't pass an error out of a `defer` block");
+        assert_eq!(message("{ var a = 1; } a = 2;"), "unknown name `a`");
+    }
+
+    #[test]
+    fn closures() {
+        let table = table();
+        let i32 = Ty::Primitive(Primitive::I32);
+        let checker = check_block(&table, "var total = 0; var add = fn(i32 n) { total += n; }; add(4); var twice = fn(i32 n) => n * 2; twice = fn(n) => n + n; i32 r = twice(3);").unwrap();
+        assert_eq!(checker.lookup("add"), Some(&Ty::Function(vec![i32.clone()], Box::new(Ty::unit()))));
+        assert_eq!(checker.lookup("twice"), Some(&Ty::Function(vec![i32.clone()], Box::new(i32.clone()))));
+        assert_eq!(checker.closures().len(), 3);
+        assert_eq!(checker.closure_calls().len(), 2);
+        // The `else` branch takes its parameter type from the first one
+        assert!(check_block(&table, "var f = if (1 < 2) fn(u8 x) => x else fn(x) => x + 1; u8 y = f(2);").is_ok());
+        assert!(check_block(&table, "while (1 < 2) { var f = fn() { while (2 < 3) break; }; break; } defer { var g = fn() { return; }; }").is_ok());
+
+        let message = |text: &str| check_block(&table, text).err().unwrap().message;
+        assert_eq!(message("var f = fn(x) => x;"), "can't infer the type of `x`, write it before the name");
+        assert_eq!(message("var f = fn(u8 x) => x; f = fn(a, b) => a;"), "expected a closure taking 1 values, this one takes 2");
+        assert_eq!(message("var f = fn(u8 x) => x; f = fn(u16 x) => x;"), "expected `u8`, found `u16`");
+        assert_eq!(message("var f = fn(u8 x) => x; f(1, 2);"), "this closure takes 1 values, found 2");
+        assert_eq!(message("var f = fn(u8 x) => x; u16 y = f(1);"), "expected `u16`, found `u8`");
+        assert_eq!(message("var n = 1; n(2);"), "`i32` can't be called, only enum variants, functions and closures can");
+        assert_eq!(message("while (1 < 2) { var f = fn() { break; }; }"), "`break` outside of a loop");
+        assert_eq!(message("const n = 1; var f = fn() { n = 2; };"), "can't assign to `n`, it is `const`");
+    }
+
+    #[test]
+    fn function_pointers() {
+        let table = table();
+        let fns = "bool less(i32 a, i32 b) => a < b;
+            extern i32 compare(*u8 a, *u8 b);
+            i32 count(fn(i32, i32) -> bool test, i32 x) => x;
+            extern () on_exit(extern fn() callback);
+            @inline @deprecated(\"use less\") bool smaller(i32 a, i32 b) => a < b;";
+        let tokens = tokenize_text(fns.to_string()).unwrap();
+        let items = parse_items(&tokens, fns.len()).unwrap();
+        let run = |text: &str| {
+            let mut checker = Checker::new(&table);
+            for item in &items {
+                let Item::Function(decl) = item else { panic!() };
+                checker.declare_function(decl)?;
+            }
+            check_block_with(checker, text)
+        };
+
+        let i32 = Ty::Primitive(Primitive::I32);
+        let checker = run("var f = less; bool b = f(1, 2); fn(i32, i32) -> bool g = less; var n = count(less, 3); extern fn(*u8, *u8) -> i32 c = compare;").unwrap();
+        assert_eq!(checker.lookup("f"), Some(&Ty::FnPointer(Abi::Gurn, vec![i32.clone(), i32.clone()], Box::new(Ty::Bool))));
+        assert_eq!(checker.lookup("c").map(Ty::to_string).as_deref(), Some("extern fn(*u8, *u8) -> i32"));
+        // Calls through function pointers are not closure calls
+        assert!(checker.closure_calls().is_empty());
+        // A local shadows the function, and closures don't capture functions
+        assert!(run("var less = 4; less += 1;").is_ok());
+        let checker = run("var g = fn(i32 x) => less(x, 2);").unwrap();
+        assert!(checker.closures()[0].captures.is_empty());
+        // Decorators reach the checker
+        let checker = run("bool b = smaller(1, 2); var less = 1;").unwrap();
+        assert!(checker.function_attributes("smaller").unwrap().inline);
+        assert_eq!(checker.warnings().iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>(), ["`smaller` is deprecated: use less"]);
+
+        let message = |text: &str| run(text).err().unwrap().message;
+        assert_eq!(message("fn(i32) -> bool f = less;"), "expected `fn(i32) -> bool`, found `fn(i32, i32) -> bool`");
+        assert_eq!(message("fn(*u8, *u8) -> i32 f = compare;"), "expected `fn(*u8, *u8) -> i32`, found `extern fn(*u8, *u8) -> i32`");
+        assert_eq!(message("less(1);"), "this function takes 2 values, found 1");
+        assert_eq!(message("u8 x = less(1, 2);"), "expected `u8`, found `bool`");
+        assert_eq!(message("less = less;"), "can't assign to `less`, it is a function");
+        assert_eq!(message("extern fn((u8, u8)) f;"), "`(u8, u8)` can't be passed to or from an `extern` function");
+        assert_eq!(message("on_exit(fn() {});"), "a closure can't be stored as `extern fn() -> ()`, it carries its captures, declare a function instead");
+    }
+
+    #[test]
+    fn match_expressions() {
+        let table = table();
+        let f32 = Ty::Primitive(Primitive::F32);
+        assert_eq!(check(&table, "match (shape) { Shape.Circle(Point(x, y = 0), r) where x > 0 => r, Shape.Rect(w, h = _) => w, _ => 0.0 }", None), Ok(f32.clone()));
+        assert_eq!(check(&table, "match ((byte, p)) { (0..10, Point(x)) => x, (n, _) => 1 }", None), Ok(Ty::Primitive(Primitive::I32)));
+        assert_eq!(check(&table, "match (byte) { 0 => 1.0, _ => 2.0 }", Some(&f32)), Ok(f32));
+
+        let message = |text: &str| check(&table, text, None).unwrap_err().message;
+        assert_eq!(message("match (byte) { 0 => p, _ => shape }"), "expected `Point`, found `Shape`");
+        assert_eq!(message("match (byte) { 300 => 1, _ => 2 }"), "300 does not fit in `u8`");
+        assert_eq!(message("match (byte) { 5..5 => 1, _ => 2 }"), "this range is empty");
+        assert_eq!(message("match (shape) { Shape.Rect(1.0) => 1, _ => 2 }"), "name the field this pattern matches, `field = pattern`");
+        assert_eq!(message("match (shape) { Shape.Rect(w = 1.0) => 1, _ => 2 }"), "`1.0` can't be matched exactly, compare it in a `where` guard");
+        assert_eq!(message("match (shape) { Shape.Circle(c = p, r) => 1, _ => 2 }"), "`Shape.Circle` has a tuple payload, match it by position");
+        assert_eq!(message("match ((byte, byte)) { (a, a) => a }"), "`a` is bound more than once in this pattern");
+        assert_eq!(message("match (p) { Point(x) where x => 1, _ => 2 }"), "expected `bool`, found `i32`");
+        assert_eq!(message("match (shape) { Shape.Empty => 1 }"), "this `match` does not cover `Shape.Circle(_, _)`");
+    }
+}
@@ -1,36 +1,132 @@
-use std::{borrow::Borrow, cell::{Ref, RefCell}, collections::HashMap, hash::Hash, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::{Rc, Weak}};
+
+use crate::compiler::{objects::symbols::KnownSymbolUsage, treegen::ast::Span};
+
+// What a scope belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ScopeDataVariety{
+    // Primitive types and the built in decorators, around every file
+    Builtin,
+    File,
+    // A struct, enum, trait or impl, with its generic parameters and `Self`
+    Declaration,
+    // A function's generic parameters and parameters
+    Function,
+    Block,
+    Closure,
+    // The variable of a `for` loop, or the one its first clause declares
+    Loop,
+    // The bindings of a match arm
+    Arm,
+}
 
+// Where a name was declared
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition{
+    pub name : String,
+    pub span : Span,
 }
 
+#[derive(Debug)]
 pub enum ScopeData{
     Scope{
         variety : ScopeDataVariety,
-        parent : Option<Rc<RefCell<ScopeData>>>,
-        children : HashMap<String, Rc<RefCell<ScopeData>>>
+        // Weak, the parent owns its nested scopes
+        parent : Option<Weak<RefCell<ScopeData>>>,
+        // Everything declared directly in this scope, each namespace on its own
+        children : HashMap<(KnownSymbolUsage, String), Rc<RefCell<ScopeData>>>,
+        // The scopes inside this one, in source order
+        nested : Vec<Rc<RefCell<ScopeData>>>,
+        span : Span,
     },
 
-    // Todo
-    Function(),
-    Constant(),
-    Struct(),
-    Enum(),
+    Function(Definition),
+    // `const` and `static` variables, and `const` generic parameters
+    Constant(Definition),
+    // Other variables, parameters and pattern bindings
+    Variable(Definition),
+    Struct(Definition),
+    Enum(Definition),
+    Trait(Definition),
+    // A generic type parameter or `Self`
+    TypeParam(Definition),
+    // `typedef` in a trait or impl
+    TypeAlias(Definition),
+    // A primitive type or decorator, declared by the compiler
+    Builtin(String),
 }
 
 impl ScopeData{
-    // Search until a node with a name is found
-    pub fn symbol_search(mut current : Rc<RefCell<Self>>, findme : &String) -> Option<Rc<RefCell<Self>>>{
+    // A new scope, added to the end of `parent`'s nested scopes
+    pub fn new_scope(variety : ScopeDataVariety, parent : Option<&Rc<RefCell<Self>>>, span : Span) -> Rc<RefCell<Self>>{
+        let scope = Rc::new(RefCell::new(Self::Scope{
+            variety,
+            parent : parent.map(Rc::downgrade),
+            children : HashMap::new(),
+            nested : Vec::new(),
+            span,
+        }));
+        if let Some(parent) = parent{
+            if let Self::Scope{nested, ..} = &mut *parent.borrow_mut(){
+                nested.push(scope.clone());
+            }
+        }
+        scope
+    }
+
+    pub fn variety(&self) -> Option<ScopeDataVariety>{
+        match self{
+            Self::Scope{variety, ..} => Some(*variety),
+            _ => None
+        }
+    }
+
+    pub fn parent(&self) -> Option<Rc<RefCell<Self>>>{
+        match self{
+            Self::Scope{parent, ..} => parent.as_ref()?.upgrade(),
+            _ => None
+        }
+    }
+
+    pub fn nested(&self) -> &[Rc<RefCell<Self>>]{
+        match self{
+            Self::Scope{nested, ..} => nested,
+            _ => &[]
+        }
+    }
+
+    // Where it was declared, `None` for scopes and built in names
+    pub fn definition(&self) -> Option<&Definition>{
+        match self{
+            Self::Function(definition) | Self::Constant(definition) | Self::Variable(definition) | Self::Struct(definition)
+            | Self::Enum(definition) | Self::Trait(definition) | Self::TypeParam(definition) | Self::TypeAlias(definition) => Some(definition),
+            Self::Scope{..} | Self::Builtin(_) => None
+        }
+    }
+
+    // Adds `symbol` to this scope, handing back what it replaced
+    pub fn declare(&mut self, usage : KnownSymbolUsage, name : &str, symbol : Rc<RefCell<Self>>) -> Option<Rc<RefCell<Self>>>{
+        match self{
+            Self::Scope{children, ..} => children.insert((usage, name.to_string()), symbol),
+            _ => panic!("only scopes can declare names")
+        }
+    }
+
+    // Search until a node with a name is found. Each scope is searched in every namespace of `usages` before
+    // its parent, so an inner variable hides an outer function. Hands back the namespace it was found in
+    pub fn symbol_search(mut current : Rc<RefCell<Self>>, findme : &str, usages : &[KnownSymbolUsage]) -> Option<(KnownSymbolUsage, Rc<RefCell<Self>>)>{
         loop{
             current = {
                 let current = current.as_ref().borrow();
-                let (parent, children) = match &*current{
-                    Self::Scope{variety : _, parent, children} => (parent, children),
-                    _ => return None
+                let Self::Scope{children, ..} = &*current else{
+                    return None
                 };
-                if let Some(found) = children.get(findme){
-                    return Some(found.clone());
+                for usage in usages{
+                    if let Some(found) = children.get(&(*usage, findme.to_string())){
+                        return Some((*usage, found.clone()));
+                    }
                 }
-                parent.clone()?
+                current.parent()?
             };
         }
     }
@@ -0,0 +1,534 @@
+use super::parser::SyntaxError;
+use crate::compiler::{
+    keywords::Keyword,
+    objects::types::Abi,
+    operators::Operator,
+    parsing::{number_parser::NumberLiteral, string_parser::StringLiteral, tokenizer::Token},
+};
+
+// Byte offsets into the source text, `end` is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn of_token(token: &Token) -> Self {
+        Self::new(token.index, token.index + token.length)
+    }
+
+    // The smallest span covering both
+    pub fn to(self, other: Span) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// Names a declaration, parameter, type, block, statement, expression or pattern, unique within one parse. The
+// parser numbers the tree once it is built, children before their parents, and nodes made by lowering take new
+// ids from `FreshIds`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    // What a node holds until the parser numbers it
+    pub const UNSET: NodeId = NodeId(u32::MAX);
+
+    pub fn new(index: usize) -> Self {
+        Self(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    // Nothing was written
+    #[default]
+    Default,
+    Pub,
+    Private,
+}
+
+// `@name` or `@name(args)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decorator {
+    pub name: Ident,
+    pub args: Option<Vec<Expr>>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+// A type as written, `*Maybe<u8>` or `[T; N]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr {
+    pub kind: TypeExprKind,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+impl TypeExpr {
+    pub fn new(kind: TypeExprKind, span: Span) -> Self {
+        Self { kind, span, id: NodeId::UNSET }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExprKind {
+    // `u8`, `io.File` or `Pair<T, u8>`
+    Path(Vec<Ident>, Vec<TypeExpr>),
+    // `*T` and `T*` are the same type
+    Pointer(Box<TypeExpr>),
+    // `&T`
+    Reference(Box<TypeExpr>),
+    // `const T`
+    Const(Box<TypeExpr>),
+    // `impl Iterator`
+    Impl(Ident),
+    // `[T; 4]` or `[T; N]`
+    Array(Box<TypeExpr>, ArrayLen),
+    // `[T]`
+    Slice(Box<TypeExpr>),
+    // `(A, B)`, `(A,)` or `()`
+    Tuple(Vec<TypeExpr>),
+    // `fn(u8, *T) -> bool` or `extern fn(i32)`, no `->` returns `()`
+    Function(Abi, Vec<TypeExpr>, Option<Box<TypeExpr>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayLen {
+    Number(NumberLiteral),
+    // A `const` generic parameter
+    Param(Ident),
+}
+
+// `T`, `T: Add + Copy` or `const usize N`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericParam {
+    pub name: Ident,
+    pub kind: GenericParamKind,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenericParamKind {
+    // The traits the type filled in must implement
+    Type(Vec<Ident>),
+    // A value known at compile time, of this type
+    Const(TypeExpr),
+}
+
+// `where T implements Copy, *U: Add + Copy`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhereClause {
+    pub predicates: Vec<WherePredicate>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WherePredicate {
+    pub ty: TypeExpr,
+    pub bounds: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub visibility: Visibility,
+    pub ty: TypeExpr,
+    pub name: Ident,
+    pub default: Option<Expr>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub visibility: Visibility,
+    pub name: Ident,
+    pub generics: Vec<GenericParam>,
+    // `struct Name implements A, B`
+    pub implements: Vec<Ident>,
+    pub where_clause: Option<WhereClause>,
+    pub fields: Vec<FieldDecl>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantPayload {
+    Unit,
+    // `Name(T, U)`
+    Tuple(Vec<TypeExpr>),
+    // `Name { T a, U b }`
+    Struct(Vec<FieldDecl>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    pub payload: VariantPayload,
+    // `Name = 4`
+    pub discriminant: Option<Expr>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+// `enum u8 Name { ... }`, the backing type is written where a type is written everywhere else
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub visibility: Visibility,
+    pub backing: Option<TypeExpr>,
+    pub name: Ident,
+    pub generics: Vec<GenericParam>,
+    pub where_clause: Option<WhereClause>,
+    pub variants: Vec<VariantDecl>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    // `comptime` and the like, type qualifiers are part of `ty`
+    pub modifiers: Vec<Keyword>,
+    pub ty: TypeExpr,
+    pub name: Ident,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FnBody {
+    Block(Block),
+    // `=> expr;`
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub visibility: Visibility,
+    // `static`, `inline`, `pure`, ... in the order they were written
+    pub modifiers: Vec<Keyword>,
+    pub return_type: TypeExpr,
+    pub name: Ident,
+    pub generics: Vec<GenericParam>,
+    pub params: Vec<Param>,
+    pub where_clause: Option<WhereClause>,
+    // `None` for a signature ending in `;`
+    pub body: Option<FnBody>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+// `typedef Name;` in a trait, `typedef u8 Name;` to give it a type
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssocType {
+    pub docs: Vec<String>,
+    pub ty: Option<TypeExpr>,
+    pub name: Ident,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+// `const u32 NAME;` or `const u32 NAME = 4;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssocConst {
+    pub docs: Vec<String>,
+    pub ty: TypeExpr,
+    pub name: Ident,
+    pub value: Option<Expr>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssocItem {
+    Method(FnDecl),
+    Type(AssocType),
+    Const(AssocConst),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub visibility: Visibility,
+    pub name: Ident,
+    pub generics: Vec<GenericParam>,
+    pub where_clause: Option<WhereClause>,
+    pub items: Vec<AssocItem>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+// `impl Type { }` or `impl Trait for Type { }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplDecl {
+    pub docs: Vec<String>,
+    pub decorators: Vec<Decorator>,
+    pub generics: Vec<GenericParam>,
+    pub trait_name: Option<Ident>,
+    pub self_ty: TypeExpr,
+    pub where_clause: Option<WhereClause>,
+    pub items: Vec<AssocItem>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutability {
+    // `var`, or just a type
+    Var,
+    Const,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage {
+    Local,
+    // `static`, one value for the whole program
+    Static,
+}
+
+// `static const u64 NAME = value;`, the type is left out when it is inferred
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDecl {
+    pub docs: Vec<String>,
+    pub storage: Storage,
+    pub mutability: Mutability,
+    pub ty: Option<TypeExpr>,
+    pub name: Ident,
+    pub value: Option<Expr>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Struct(StructDecl),
+    Enum(EnumDecl),
+    Trait(TraitDecl),
+    Impl(ImplDecl),
+    Function(FnDecl),
+    // Only `static` and `const` variables
+    Var(VarDecl),
+    // A declaration that failed to parse, spanning the tokens skipped over it
+    Error(SyntaxError, Span),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    // A last expression without `;` is what the block evaluates to
+    pub value: Option<Box<Expr>>,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+impl Block {
+    pub fn new(stmts: Vec<Stmt>, value: Option<Box<Expr>>, span: Span) -> Self {
+        Self { stmts, value, span, id: NodeId::UNSET }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span, id: NodeId::UNSET }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    // `expr;`
+    Expr(Expr),
+    Var(VarDecl),
+    While(WhileLoop),
+    For(ForLoop),
+    // `break;` or `break 'outer;`
+    Break(Option<Ident>),
+    Continue(Option<Ident>),
+    Return(Option<Expr>),
+    // A nested scope, `{ ... }`
+    Block(Block),
+    // `defer stmt;` or `defer { ... }`, runs when the enclosing scope is left
+    Defer(Block),
+    // A statement that failed to parse, the parser skipped to the next one
+    Error(SyntaxError),
+}
+
+// `'outer: comptime while (cond) { ... }`, a single statement body is wrapped in a block
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhileLoop {
+    // Without the quote
+    pub label: Option<Ident>,
+    pub comptime: bool,
+    pub cond: Expr,
+    pub body: Block,
+    pub span: Span,
+}
+
+// `'outer: comptime for (...) { ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForLoop {
+    pub label: Option<Ident>,
+    pub comptime: bool,
+    pub kind: ForKind,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForKind {
+    // `for (u32 i = 0; i < n; i += 1)`, with any clause left out
+    Clauses { init: Option<Box<Stmt>>, cond: Option<Expr>, step: Option<Expr> },
+    // `for (x in iterable)`, ranges included
+    In { binding: Ident, iterable: Expr },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span, id: NodeId::UNSET }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Number(NumberLiteral),
+    // Only decorators take one so far, `@deprecated("use sum")`
+    Str(StringLiteral),
+    Name(String),
+    // `*x`, `&x`, `~x`
+    Unary(Operator, Box<Expr>),
+    // `x?`, `x!`
+    Postfix(Operator, Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    // `value.name`, also how enum variants are named, `Shape.Circle`
+    Field(Box<Expr>, Ident),
+    // `()` and `(a, b)`, a single parenthesized expression is not a tuple
+    Tuple(Vec<Expr>),
+    Array(Vec<Expr>),
+    // `match (value) { pattern => expr, ... }`
+    Match(Box<Expr>, Vec<MatchArm>),
+    If(Conditional),
+    Closure(Box<Closure>),
+    // `value!` once defers are lowered, the block runs before the error leaves the function.
+    // The parser never produces it
+    Propagate(Box<Expr>, Block),
+}
+
+// `if (a) x else if (b) y else z`, the whole chain in one node.
+// Single statement branches are wrapped in a block, as values they become the block's value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conditional {
+    pub comptime: bool,
+    pub branches: Vec<CondBranch>,
+    pub else_branch: Option<Block>,
+    pub span: Span,
+}
+
+// `fn(u8 a, b) => a + b` or `fn(a) { ... }`, a `=>` body becomes the block's value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub params: Vec<ClosureParam>,
+    pub body: Block,
+    pub span: Span,
+}
+
+// The type is left out when the closure is written where a function type is expected
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosureParam {
+    pub ty: Option<TypeExpr>,
+    pub name: Ident,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CondBranch {
+    pub cond: Expr,
+    pub body: Block,
+    pub span: Span,
+}
+
+// `pattern where guard => body`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind, span: Span) -> Self {
+        Self { kind, span, id: NodeId::UNSET }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    // `_`
+    Wildcard,
+    Binding(Ident),
+    Literal(NumberLiteral),
+    // `1..5` leaves out the end, `1..=5` includes it
+    Range(NumberLiteral, NumberLiteral, bool),
+    Tuple(Vec<Pattern>),
+    // `Point(x, y = 0)`, fields that aren't written match anything
+    Struct(Ident, Vec<PatternArg>),
+    // `Shape.Empty`, `Shape.Circle(c, r)` by position or `Shape.Rect(w, h = 1.0)` by field name
+    Variant(Ident, Ident, Option<Vec<PatternArg>>),
+}
+
+// One value in a struct or variant pattern, `name = pattern` picks a field by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternArg {
+    pub name: Option<Ident>,
+    pub pattern: Pattern,
+    pub span: Span,
+}
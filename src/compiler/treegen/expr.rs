@@ -0,0 +1,281 @@
+use super::ast::{Block, Closure, ClosureParam, Expr, ExprKind, Ident, NodeId, Span};
+use super::items::parse_type_before_name;
+use super::parser::{Cursor, ParseResult};
+use super::pattern::parse_match_arms;
+use super::stmt::{parse_block_recovering, parse_conditional};
+use crate::compiler::{
+    keywords::Keyword,
+    operators::{Operator, PREFIX_PRECEDENCE},
+    parsing::tokenizer::{Token, TokenData},
+};
+
+pub fn parse_expr(cursor: &mut Cursor) -> ParseResult<Expr> {
+    parse_binary(cursor, 0)
+}
+
+// Comma separated expressions filling the whole cursor, a trailing comma is allowed
+pub fn parse_expr_list(cursor: &mut Cursor) -> ParseResult<Vec<Expr>> {
+    let mut exprs = Vec::new();
+    while !cursor.is_at_end() {
+        exprs.push(parse_expr(cursor)?);
+        if cursor.is_at_end() {
+            break;
+        }
+        cursor.expect(&TokenData::Colon, "`,` between expressions")?;
+    }
+    Ok(exprs)
+}
+
+fn parse_binary(cursor: &mut Cursor, min_precedence: u8) -> ParseResult<Expr> {
+    let mut lhs = parse_postfix(cursor)?;
+    loop {
+        let before = cursor.position();
+        let operator = match cursor.peek() {
+            Some(Token { data: TokenData::Operator(operator), .. }) => *operator,
+            _ => {
+                cursor.reset(before);
+                return Ok(lhs);
+            }
+        };
+        let precedence = match operator.binary_precedence() {
+            Some(precedence) if precedence > min_precedence || (precedence == min_precedence && operator.is_right_associative()) => precedence,
+            _ => {
+                cursor.reset(before);
+                return Ok(lhs);
+            }
+        };
+        cursor.bump();
+        let rhs = parse_binary(cursor, precedence)?;
+        let span = lhs.span.to(rhs.span);
+        lhs = Expr::new(ExprKind::Binary(operator, Box::new(lhs), Box::new(rhs)), span);
+    }
+}
+
+// Calls, indexing, fields, `?` and `!` must directly follow what they apply to
+fn parse_postfix(cursor: &mut Cursor) -> ParseResult<Expr> {
+    let mut expr = parse_prefix(cursor)?;
+    while let Some(token) = cursor.peek_raw() {
+        let span = expr.span.to(Span::of_token(token));
+        let kind = match &token.data {
+            TokenData::Bracket(b'(', _) => {
+                let args = parse_expr_list(&mut Cursor::enter(token))?;
+                ExprKind::Call(Box::new(expr), args)
+            }
+            TokenData::Bracket(b'[', _) => {
+                let mut inner = Cursor::enter(token);
+                let index = parse_expr(&mut inner)?;
+                if !inner.is_at_end() {
+                    return Err(inner.error("expected `]` after the index"));
+                }
+                ExprKind::Index(Box::new(expr), Box::new(index))
+            }
+            TokenData::Operator(operator @ (Operator::OptionalOperator | Operator::ErrorOperator)) => {
+                ExprKind::Postfix(*operator, Box::new(expr))
+            }
+            TokenData::Dot => {
+                cursor.bump();
+                let name = cursor.expect_ident("a name after `.`")?;
+                let span = expr.span.to(name.span);
+                expr = Expr::new(ExprKind::Field(Box::new(expr), name), span);
+                continue;
+            }
+            _ => break,
+        };
+        cursor.bump();
+        expr = Expr::new(kind, span);
+    }
+    Ok(expr)
+}
+
+fn parse_prefix(cursor: &mut Cursor) -> ParseResult<Expr> {
+    let Some(token) = cursor.peek() else {
+        return Err(cursor.error("expected an expression"));
+    };
+    let span = Span::of_token(token);
+    let kind = match &token.data {
+        TokenData::NumberLiteral(number) => ExprKind::Number(number.clone()),
+        TokenData::StringLiteral(string) => ExprKind::Str(string.clone()),
+        TokenData::TextCluster(Some(name)) => ExprKind::Name(name.clone()),
+        TokenData::Operator(operator @ (Operator::Dereference | Operator::Reference | Operator::Not)) => {
+            cursor.bump();
+            let operand = parse_binary(cursor, PREFIX_PRECEDENCE)?;
+            let span = span.to(operand.span);
+            return Ok(Expr::new(ExprKind::Unary(*operator, Box::new(operand)), span));
+        }
+        TokenData::Bracket(b'(', _) => {
+            let mut inner = Cursor::enter(token);
+            let mut exprs = parse_expr_list(&mut inner)?;
+            let is_tuple = exprs.len() != 1 || matches!(token.data, TokenData::Bracket(_, Some(ref tokens)) if tokens.iter().any(|t| t.data == TokenData::Colon));
+            cursor.bump();
+            if !is_tuple {
+                let mut expr = exprs.pop().unwrap();
+                expr.span = span;
+                return Ok(expr);
+            }
+            return Ok(Expr::new(ExprKind::Tuple(exprs), span));
+        }
+        TokenData::Bracket(b'[', _) => ExprKind::Array(parse_expr_list(&mut Cursor::enter(token))?),
+        TokenData::Keyword(Keyword::Match) => {
+            cursor.bump();
+            let paren = cursor.eat_bracket(b'(').ok_or_else(|| cursor.error("expected `(` after `match`"))?;
+            let mut inner = Cursor::enter(paren);
+            let scrutinee = parse_expr(&mut inner)?;
+            if !inner.is_at_end() {
+                return Err(inner.error("expected `)` after the matched value"));
+            }
+            let brace = cursor.eat_bracket(b'{').ok_or_else(|| cursor.error("expected `{` to start the match arms"))?;
+            let arms = parse_match_arms(brace)?;
+            return Ok(Expr::new(ExprKind::Match(Box::new(scrutinee), arms), span.to(Span::of_token(brace))));
+        }
+        TokenData::Keyword(Keyword::If | Keyword::Comptime) => return parse_conditional(cursor, false),
+        TokenData::Keyword(Keyword::Fn) => return parse_closure(cursor),
+        TokenData::Keyword(Keyword::Else | Keyword::ElseIf) => return Err(cursor.error("`else` without a preceding `if`")),
+        _ => return Err(cursor.error("expected an expression")),
+    };
+    cursor.bump();
+    Ok(Expr::new(kind, span))
+}
+
+// `fn(u8 a, b) => a + b` or `fn(a) { ... }`
+fn parse_closure(cursor: &mut Cursor) -> ParseResult<Expr> {
+    let start = cursor.start();
+    cursor.bump();
+    let paren = cursor.eat_bracket(b'(').ok_or_else(|| cursor.error("expected `(` after `fn`"))?;
+    let mut inner = Cursor::enter(paren);
+    let mut params = Vec::new();
+    while !inner.is_at_end() {
+        let ty = parse_type_before_name(&mut inner);
+        let name = inner.expect_ident("a parameter name")?;
+        params.push(ClosureParam { ty, name, id: NodeId::UNSET });
+        if inner.is_at_end() {
+            break;
+        }
+        inner.expect(&TokenData::Colon, "`,` between parameters")?;
+    }
+    let body = if let Some(brace) = cursor.eat_bracket(b'{') {
+        parse_block_recovering(brace)
+    } else if cursor.eat(&TokenData::Operator(Operator::EqualsArrow)).is_some() {
+        let value = parse_expr(cursor)?;
+        let span = value.span;
+        Block::new(Vec::new(), Some(Box::new(value)), span)
+    } else {
+        return Err(cursor.error("expected `=>` or `{` after the closure parameters"));
+    };
+    let span = cursor.span_from(start);
+    Ok(Expr::new(ExprKind::Closure(Box::new(Closure { params, body, span })), span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::ast::TypeExprKind};
+
+    fn parse(text: &str) -> Expr {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let mut cursor = Cursor::new(&tokens, text.len());
+        let expr = parse_expr(&mut cursor).unwrap();
+        assert!(cursor.is_at_end(), "{} was not fully parsed", text);
+        expr
+    }
+
+    // Fully parenthesized, to check grouping
+    fn shape(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(number) => number.text_content.clone(),
+            ExprKind::Str(string) => string.source(),
+            ExprKind::Name(name) => name.clone(),
+            ExprKind::Unary(operator, operand) => format!("({}{})", operator.text(), shape(operand)),
+            ExprKind::Postfix(operator, operand) => format!("({}{})", shape(operand), operator.text()),
+            ExprKind::Binary(operator, lhs, rhs) => format!("({} {} {})", shape(lhs), operator.text(), shape(rhs)),
+            ExprKind::Call(callee, args) => format!("{}({})", shape(callee), args.iter().map(shape).collect::<Vec<_>>().join(", ")),
+            ExprKind::Index(value, index) => format!("{}[{}]", shape(value), shape(index)),
+            ExprKind::Field(value, name) => format!("{}.{}", shape(value), name.name),
+            ExprKind::Tuple(items) => format!("tuple({})", items.iter().map(shape).collect::<Vec<_>>().join(", ")),
+            ExprKind::Array(items) => format!("[{}]", items.iter().map(shape).collect::<Vec<_>>().join(", ")),
+            ExprKind::Match(value, arms) => format!("match({}, {} arms)", shape(value), arms.len()),
+            ExprKind::If(conditional) => {
+                let body = |block: &Block| block.value.as_ref().map_or(format!("{} stmts", block.stmts.len()), |value| shape(value));
+                let mut parts: Vec<String> = conditional.branches.iter().map(|branch| format!("{} => {}", shape(&branch.cond), body(&branch.body))).collect();
+                parts.extend(conditional.else_branch.as_ref().map(|block| format!("else {}", body(block))));
+                format!("if({})", parts.join("; "))
+            }
+            ExprKind::Closure(closure) => {
+                let params: Vec<&str> = closure.params.iter().map(|param| param.name.name.as_str()).collect();
+                let body = closure.body.value.as_ref().map_or(format!("{} stmts", closure.body.stmts.len()), |value| shape(value));
+                format!("fn({}) => {}", params.join(", "), body)
+            }
+            ExprKind::Propagate(value, _) => format!("({}!)", shape(value)),
+        }
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(shape(&parse("1 + 2 * 3")), "(1 + (2 * 3))");
+        assert_eq!(shape(&parse("a - b - c")), "((a - b) - c)");
+        assert_eq!(shape(&parse("a = b = c")), "(a = (b = c))");
+        assert_eq!(shape(&parse("(1 + 2) * 3")), "((1 + 2) * 3)");
+        assert_eq!(shape(&parse("a == 1 && b < 2 || c")), "(((a == 1) && (b < 2)) || c)");
+        assert_eq!(shape(&parse("x = *ptr + 1")), "(x = ((*ptr) + 1))");
+        assert_eq!(shape(&parse("*ptr * 2")), "((*ptr) * 2)");
+    }
+
+    #[test]
+    fn postfix_and_groups() {
+        assert_eq!(shape(&parse("read(file, 4)!")), "(read(file, 4)!)");
+        assert_eq!(shape(&parse("items[i + 1]")), "items[(i + 1)]");
+        assert_eq!(shape(&parse("(a, b)")), "tuple(a, b)");
+        assert_eq!(shape(&parse("(a,)")), "tuple(a)");
+        assert_eq!(shape(&parse("[1, 2, 3]")), "[1, 2, 3]");
+        assert_eq!(shape(&parse("Shape.Circle(1.5) + a.b.c")), "(Shape.Circle(1.5) + a.b.c)");
+
+        assert_eq!(shape(&parse("match (x) { 1 => a, _ => b } + 1")), "(match(x, 2 arms) + 1)");
+
+        let call = parse("f(1)");
+        assert_eq!(call.span, Span::new(0, 4));
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        assert_eq!(shape(&parse("if (a) 1 else 2")), "if(a => 1; else 2)");
+        assert_eq!(shape(&parse("if (a) 1 else if (b < 2) { x; y } else { z }")), "if(a => 1; (b < 2) => y; else z)");
+        assert_eq!(shape(&parse("if (a) 1 else // why not\n if (b) 2 else 3")), "if(a => 1; b => 2; else 3)");
+        assert_eq!(shape(&parse("if (a) { f(); }")), "if(a => 1 stmts)");
+
+        let text = "comptime if (a) 1 else 2";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let ExprKind::If(conditional) = parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap().kind else { panic!() };
+        assert!(conditional.comptime);
+        assert_eq!(conditional.span, Span::new(0, text.len()));
+
+        let error = |text: &str| {
+            let tokens = tokenize_text(text.to_string()).unwrap();
+            parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap_err().message
+        };
+        assert_eq!(error("else 1"), "`else` without a preceding `if`");
+        assert_eq!(error("if a 1"), "expected `(` after `if`");
+        assert_eq!(error("comptime 1"), "expected `if` after `comptime`");
+    }
+
+    #[test]
+    fn closures() {
+        assert_eq!(shape(&parse("fn(u8 a, b) => a + b * 2")), "fn(a, b) => (a + (b * 2))");
+        assert_eq!(shape(&parse("fn() { f(); g() }")), "fn() => g()");
+        assert_eq!(shape(&parse("apply(fn(x) => x, 4)")), "apply(fn(x) => x, 4)");
+        assert_eq!(shape(&parse("fn(x) => fn(y) => x + y")), "fn(x) => fn(y) => (x + y)");
+
+        let text = "fn(*u8 p, count) => 1";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let ExprKind::Closure(closure) = parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap().kind else { panic!() };
+        assert!(matches!(closure.params[0].ty.as_ref().map(|ty| &ty.kind), Some(TypeExprKind::Pointer(_))));
+        assert!(closure.params[1].ty.is_none());
+        assert_eq!(closure.span, Span::new(0, text.len()));
+
+        let error = |text: &str| {
+            let tokens = tokenize_text(text.to_string()).unwrap();
+            parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap_err().message
+        };
+        assert_eq!(error("fn x => x"), "expected `(` after `fn`");
+        assert_eq!(error("fn(x) x"), "expected `=>` or `{` after the closure parameters");
+        assert_eq!(error("fn(u8 x y) => x"), "expected `,` between parameters");
+    }
+}
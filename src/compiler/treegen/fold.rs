@@ -0,0 +1,534 @@
+use super::ast::*;
+
+// Rebuilding the tree. Each method takes a node by value and returns what replaces it, by default the same node
+// with its children folded. A desugaring pass overrides the nodes it rewrites and calls `walk_*` for the rest
+pub trait Fold: Sized {
+    fn fold_item(&mut self, item: Item) -> Item {
+        walk_item(self, item)
+    }
+    fn fold_struct(&mut self, decl: StructDecl) -> StructDecl {
+        walk_struct(self, decl)
+    }
+    fn fold_field(&mut self, field: FieldDecl) -> FieldDecl {
+        walk_field(self, field)
+    }
+    fn fold_enum(&mut self, decl: EnumDecl) -> EnumDecl {
+        walk_enum(self, decl)
+    }
+    fn fold_variant(&mut self, variant: VariantDecl) -> VariantDecl {
+        walk_variant(self, variant)
+    }
+    fn fold_trait(&mut self, decl: TraitDecl) -> TraitDecl {
+        walk_trait(self, decl)
+    }
+    fn fold_impl(&mut self, decl: ImplDecl) -> ImplDecl {
+        walk_impl(self, decl)
+    }
+    fn fold_assoc_item(&mut self, item: AssocItem) -> AssocItem {
+        walk_assoc_item(self, item)
+    }
+    fn fold_assoc_type(&mut self, decl: AssocType) -> AssocType {
+        walk_assoc_type(self, decl)
+    }
+    fn fold_assoc_const(&mut self, decl: AssocConst) -> AssocConst {
+        walk_assoc_const(self, decl)
+    }
+    fn fold_fn(&mut self, decl: FnDecl) -> FnDecl {
+        walk_fn(self, decl)
+    }
+    fn fold_param(&mut self, param: Param) -> Param {
+        walk_param(self, param)
+    }
+    fn fold_generic_param(&mut self, param: GenericParam) -> GenericParam {
+        walk_generic_param(self, param)
+    }
+    fn fold_where_clause(&mut self, clause: WhereClause) -> WhereClause {
+        walk_where_clause(self, clause)
+    }
+    fn fold_where_predicate(&mut self, predicate: WherePredicate) -> WherePredicate {
+        walk_where_predicate(self, predicate)
+    }
+    fn fold_decorator(&mut self, decorator: Decorator) -> Decorator {
+        walk_decorator(self, decorator)
+    }
+    fn fold_var(&mut self, decl: VarDecl) -> VarDecl {
+        walk_var(self, decl)
+    }
+    fn fold_type(&mut self, ty: TypeExpr) -> TypeExpr {
+        walk_type(self, ty)
+    }
+    fn fold_block(&mut self, block: Block) -> Block {
+        walk_block(self, block)
+    }
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        walk_stmt(self, stmt)
+    }
+    fn fold_while(&mut self, lp: WhileLoop) -> WhileLoop {
+        walk_while(self, lp)
+    }
+    fn fold_for(&mut self, lp: ForLoop) -> ForLoop {
+        walk_for(self, lp)
+    }
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+    fn fold_conditional(&mut self, conditional: Conditional) -> Conditional {
+        walk_conditional(self, conditional)
+    }
+    fn fold_cond_branch(&mut self, branch: CondBranch) -> CondBranch {
+        walk_cond_branch(self, branch)
+    }
+    fn fold_closure(&mut self, closure: Closure) -> Closure {
+        walk_closure(self, closure)
+    }
+    fn fold_closure_param(&mut self, param: ClosureParam) -> ClosureParam {
+        walk_closure_param(self, param)
+    }
+    fn fold_match_arm(&mut self, arm: MatchArm) -> MatchArm {
+        walk_match_arm(self, arm)
+    }
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        walk_pattern(self, pattern)
+    }
+    fn fold_pattern_arg(&mut self, arg: PatternArg) -> PatternArg {
+        walk_pattern_arg(self, arg)
+    }
+    fn fold_ident(&mut self, ident: Ident) -> Ident {
+        Ident { span: self.fold_span(ident.span), ..ident }
+    }
+    fn fold_span(&mut self, span: Span) -> Span {
+        span
+    }
+    fn fold_node_id(&mut self, id: NodeId) -> NodeId {
+        id
+    }
+}
+
+// Folds every element, in order
+fn all<T>(items: Vec<T>, mut fold: impl FnMut(T) -> T) -> Vec<T> {
+    items.into_iter().map(&mut fold).collect()
+}
+
+fn boxed<F: Fold>(folder: &mut F, expr: Expr) -> Box<Expr> {
+    Box::new(folder.fold_expr(expr))
+}
+
+fn boxed_type<F: Fold>(folder: &mut F, ty: TypeExpr) -> Box<TypeExpr> {
+    Box::new(folder.fold_type(ty))
+}
+
+pub fn walk_item<F: Fold>(folder: &mut F, item: Item) -> Item {
+    match item {
+        Item::Struct(decl) => Item::Struct(folder.fold_struct(decl)),
+        Item::Enum(decl) => Item::Enum(folder.fold_enum(decl)),
+        Item::Trait(decl) => Item::Trait(folder.fold_trait(decl)),
+        Item::Impl(decl) => Item::Impl(folder.fold_impl(decl)),
+        Item::Function(decl) => Item::Function(folder.fold_fn(decl)),
+        Item::Var(decl) => Item::Var(folder.fold_var(decl)),
+        Item::Error(error, span) => Item::Error(error, folder.fold_span(span)),
+    }
+}
+
+pub fn walk_struct<F: Fold>(folder: &mut F, decl: StructDecl) -> StructDecl {
+    StructDecl {
+        docs: decl.docs,
+        decorators: all(decl.decorators, |decorator| folder.fold_decorator(decorator)),
+        visibility: decl.visibility,
+        name: folder.fold_ident(decl.name),
+        generics: all(decl.generics, |param| folder.fold_generic_param(param)),
+        implements: all(decl.implements, |name| folder.fold_ident(name)),
+        where_clause: decl.where_clause.map(|clause| folder.fold_where_clause(clause)),
+        fields: all(decl.fields, |field| folder.fold_field(field)),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_field<F: Fold>(folder: &mut F, field: FieldDecl) -> FieldDecl {
+    FieldDecl {
+        docs: field.docs,
+        decorators: all(field.decorators, |decorator| folder.fold_decorator(decorator)),
+        visibility: field.visibility,
+        ty: folder.fold_type(field.ty),
+        name: folder.fold_ident(field.name),
+        default: field.default.map(|default| folder.fold_expr(default)),
+        span: folder.fold_span(field.span),
+        id: folder.fold_node_id(field.id),
+    }
+}
+
+pub fn walk_enum<F: Fold>(folder: &mut F, decl: EnumDecl) -> EnumDecl {
+    EnumDecl {
+        docs: decl.docs,
+        decorators: all(decl.decorators, |decorator| folder.fold_decorator(decorator)),
+        visibility: decl.visibility,
+        backing: decl.backing.map(|backing| folder.fold_type(backing)),
+        name: folder.fold_ident(decl.name),
+        generics: all(decl.generics, |param| folder.fold_generic_param(param)),
+        where_clause: decl.where_clause.map(|clause| folder.fold_where_clause(clause)),
+        variants: all(decl.variants, |variant| folder.fold_variant(variant)),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_variant<F: Fold>(folder: &mut F, variant: VariantDecl) -> VariantDecl {
+    let decorators = all(variant.decorators, |decorator| folder.fold_decorator(decorator));
+    let name = folder.fold_ident(variant.name);
+    let payload = match variant.payload {
+        VariantPayload::Unit => VariantPayload::Unit,
+        VariantPayload::Tuple(types) => VariantPayload::Tuple(all(types, |ty| folder.fold_type(ty))),
+        VariantPayload::Struct(fields) => VariantPayload::Struct(all(fields, |field| folder.fold_field(field))),
+    };
+    let discriminant = variant.discriminant.map(|discriminant| folder.fold_expr(discriminant));
+    let (span, id) = (folder.fold_span(variant.span), folder.fold_node_id(variant.id));
+    VariantDecl { docs: variant.docs, decorators, name, payload, discriminant, span, id }
+}
+
+pub fn walk_trait<F: Fold>(folder: &mut F, decl: TraitDecl) -> TraitDecl {
+    TraitDecl {
+        docs: decl.docs,
+        decorators: all(decl.decorators, |decorator| folder.fold_decorator(decorator)),
+        visibility: decl.visibility,
+        name: folder.fold_ident(decl.name),
+        generics: all(decl.generics, |param| folder.fold_generic_param(param)),
+        where_clause: decl.where_clause.map(|clause| folder.fold_where_clause(clause)),
+        items: all(decl.items, |item| folder.fold_assoc_item(item)),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_impl<F: Fold>(folder: &mut F, decl: ImplDecl) -> ImplDecl {
+    ImplDecl {
+        docs: decl.docs,
+        decorators: all(decl.decorators, |decorator| folder.fold_decorator(decorator)),
+        generics: all(decl.generics, |param| folder.fold_generic_param(param)),
+        trait_name: decl.trait_name.map(|name| folder.fold_ident(name)),
+        self_ty: folder.fold_type(decl.self_ty),
+        where_clause: decl.where_clause.map(|clause| folder.fold_where_clause(clause)),
+        items: all(decl.items, |item| folder.fold_assoc_item(item)),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_assoc_item<F: Fold>(folder: &mut F, item: AssocItem) -> AssocItem {
+    match item {
+        AssocItem::Method(decl) => AssocItem::Method(folder.fold_fn(decl)),
+        AssocItem::Type(decl) => AssocItem::Type(folder.fold_assoc_type(decl)),
+        AssocItem::Const(decl) => AssocItem::Const(folder.fold_assoc_const(decl)),
+    }
+}
+
+pub fn walk_assoc_type<F: Fold>(folder: &mut F, decl: AssocType) -> AssocType {
+    AssocType {
+        docs: decl.docs,
+        ty: decl.ty.map(|ty| folder.fold_type(ty)),
+        name: folder.fold_ident(decl.name),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_assoc_const<F: Fold>(folder: &mut F, decl: AssocConst) -> AssocConst {
+    AssocConst {
+        docs: decl.docs,
+        ty: folder.fold_type(decl.ty),
+        name: folder.fold_ident(decl.name),
+        value: decl.value.map(|value| folder.fold_expr(value)),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_fn<F: Fold>(folder: &mut F, decl: FnDecl) -> FnDecl {
+    FnDecl {
+        docs: decl.docs,
+        decorators: all(decl.decorators, |decorator| folder.fold_decorator(decorator)),
+        visibility: decl.visibility,
+        modifiers: decl.modifiers,
+        return_type: folder.fold_type(decl.return_type),
+        name: folder.fold_ident(decl.name),
+        generics: all(decl.generics, |param| folder.fold_generic_param(param)),
+        params: all(decl.params, |param| folder.fold_param(param)),
+        where_clause: decl.where_clause.map(|clause| folder.fold_where_clause(clause)),
+        body: decl.body.map(|body| match body {
+            FnBody::Block(block) => FnBody::Block(folder.fold_block(block)),
+            FnBody::Expr(expr) => FnBody::Expr(folder.fold_expr(expr)),
+        }),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_param<F: Fold>(folder: &mut F, param: Param) -> Param {
+    Param {
+        modifiers: param.modifiers,
+        ty: folder.fold_type(param.ty),
+        name: folder.fold_ident(param.name),
+        span: folder.fold_span(param.span),
+        id: folder.fold_node_id(param.id),
+    }
+}
+
+pub fn walk_generic_param<F: Fold>(folder: &mut F, param: GenericParam) -> GenericParam {
+    let (name, kind) = match param.kind {
+        GenericParamKind::Type(bounds) => {
+            let name = folder.fold_ident(param.name);
+            (name, GenericParamKind::Type(all(bounds, |bound| folder.fold_ident(bound))))
+        }
+        GenericParamKind::Const(ty) => {
+            let ty = folder.fold_type(ty);
+            (folder.fold_ident(param.name), GenericParamKind::Const(ty))
+        }
+    };
+    GenericParam { name, kind, span: folder.fold_span(param.span), id: folder.fold_node_id(param.id) }
+}
+
+pub fn walk_where_clause<F: Fold>(folder: &mut F, clause: WhereClause) -> WhereClause {
+    WhereClause {
+        predicates: all(clause.predicates, |predicate| folder.fold_where_predicate(predicate)),
+        span: folder.fold_span(clause.span),
+    }
+}
+
+pub fn walk_where_predicate<F: Fold>(folder: &mut F, predicate: WherePredicate) -> WherePredicate {
+    WherePredicate {
+        ty: folder.fold_type(predicate.ty),
+        bounds: all(predicate.bounds, |bound| folder.fold_ident(bound)),
+        span: folder.fold_span(predicate.span),
+    }
+}
+
+pub fn walk_decorator<F: Fold>(folder: &mut F, decorator: Decorator) -> Decorator {
+    Decorator {
+        name: folder.fold_ident(decorator.name),
+        args: decorator.args.map(|args| all(args, |arg| folder.fold_expr(arg))),
+        span: folder.fold_span(decorator.span),
+        id: folder.fold_node_id(decorator.id),
+    }
+}
+
+pub fn walk_var<F: Fold>(folder: &mut F, decl: VarDecl) -> VarDecl {
+    VarDecl {
+        docs: decl.docs,
+        storage: decl.storage,
+        mutability: decl.mutability,
+        ty: decl.ty.map(|ty| folder.fold_type(ty)),
+        name: folder.fold_ident(decl.name),
+        value: decl.value.map(|value| folder.fold_expr(value)),
+        span: folder.fold_span(decl.span),
+        id: folder.fold_node_id(decl.id),
+    }
+}
+
+pub fn walk_type<F: Fold>(folder: &mut F, ty: TypeExpr) -> TypeExpr {
+    let kind = match ty.kind {
+        TypeExprKind::Path(segments, args) => {
+            let segments = all(segments, |segment| folder.fold_ident(segment));
+            TypeExprKind::Path(segments, all(args, |arg| folder.fold_type(arg)))
+        }
+        TypeExprKind::Pointer(pointee) => TypeExprKind::Pointer(boxed_type(folder, *pointee)),
+        TypeExprKind::Reference(referent) => TypeExprKind::Reference(boxed_type(folder, *referent)),
+        TypeExprKind::Const(value) => TypeExprKind::Const(boxed_type(folder, *value)),
+        TypeExprKind::Slice(element) => TypeExprKind::Slice(boxed_type(folder, *element)),
+        TypeExprKind::Impl(name) => TypeExprKind::Impl(folder.fold_ident(name)),
+        TypeExprKind::Array(element, len) => {
+            let element = boxed_type(folder, *element);
+            let len = match len {
+                ArrayLen::Number(number) => ArrayLen::Number(number),
+                ArrayLen::Param(name) => ArrayLen::Param(folder.fold_ident(name)),
+            };
+            TypeExprKind::Array(element, len)
+        }
+        TypeExprKind::Tuple(items) => TypeExprKind::Tuple(all(items, |item| folder.fold_type(item))),
+        TypeExprKind::Function(abi, params, ret) => {
+            let params = all(params, |param| folder.fold_type(param));
+            TypeExprKind::Function(abi, params, ret.map(|ret| boxed_type(folder, *ret)))
+        }
+    };
+    TypeExpr { kind, span: folder.fold_span(ty.span), id: folder.fold_node_id(ty.id) }
+}
+
+pub fn walk_block<F: Fold>(folder: &mut F, block: Block) -> Block {
+    Block {
+        stmts: all(block.stmts, |stmt| folder.fold_stmt(stmt)),
+        value: block.value.map(|value| boxed(folder, *value)),
+        span: folder.fold_span(block.span),
+        id: folder.fold_node_id(block.id),
+    }
+}
+
+pub fn walk_stmt<F: Fold>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let kind = match stmt.kind {
+        StmtKind::Expr(expr) => StmtKind::Expr(folder.fold_expr(expr)),
+        StmtKind::Var(decl) => StmtKind::Var(folder.fold_var(decl)),
+        StmtKind::While(lp) => StmtKind::While(folder.fold_while(lp)),
+        StmtKind::For(lp) => StmtKind::For(folder.fold_for(lp)),
+        StmtKind::Break(label) => StmtKind::Break(label.map(|label| folder.fold_ident(label))),
+        StmtKind::Continue(label) => StmtKind::Continue(label.map(|label| folder.fold_ident(label))),
+        StmtKind::Return(value) => StmtKind::Return(value.map(|value| folder.fold_expr(value))),
+        StmtKind::Block(block) => StmtKind::Block(folder.fold_block(block)),
+        StmtKind::Defer(block) => StmtKind::Defer(folder.fold_block(block)),
+        StmtKind::Error(error) => StmtKind::Error(error),
+    };
+    Stmt { kind, span: folder.fold_span(stmt.span), id: folder.fold_node_id(stmt.id) }
+}
+
+pub fn walk_while<F: Fold>(folder: &mut F, lp: WhileLoop) -> WhileLoop {
+    WhileLoop {
+        label: lp.label.map(|label| folder.fold_ident(label)),
+        comptime: lp.comptime,
+        cond: folder.fold_expr(lp.cond),
+        body: folder.fold_block(lp.body),
+        span: folder.fold_span(lp.span),
+    }
+}
+
+pub fn walk_for<F: Fold>(folder: &mut F, lp: ForLoop) -> ForLoop {
+    let label = lp.label.map(|label| folder.fold_ident(label));
+    let kind = match lp.kind {
+        ForKind::Clauses { init, cond, step } => ForKind::Clauses {
+            init: init.map(|init| Box::new(folder.fold_stmt(*init))),
+            cond: cond.map(|cond| folder.fold_expr(cond)),
+            step: step.map(|step| folder.fold_expr(step)),
+        },
+        ForKind::In { binding, iterable } => ForKind::In { binding: folder.fold_ident(binding), iterable: folder.fold_expr(iterable) },
+    };
+    ForLoop { label, comptime: lp.comptime, kind, body: folder.fold_block(lp.body), span: folder.fold_span(lp.span) }
+}
+
+pub fn walk_expr<F: Fold>(folder: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        kind @ (ExprKind::Number(_) | ExprKind::Str(_) | ExprKind::Name(_)) => kind,
+        ExprKind::Unary(operator, operand) => ExprKind::Unary(operator, boxed(folder, *operand)),
+        ExprKind::Postfix(operator, operand) => ExprKind::Postfix(operator, boxed(folder, *operand)),
+        ExprKind::Binary(operator, lhs, rhs) => {
+            let lhs = boxed(folder, *lhs);
+            ExprKind::Binary(operator, lhs, boxed(folder, *rhs))
+        }
+        ExprKind::Call(callee, args) => {
+            let callee = boxed(folder, *callee);
+            ExprKind::Call(callee, all(args, |arg| folder.fold_expr(arg)))
+        }
+        ExprKind::Index(value, index) => {
+            let value = boxed(folder, *value);
+            ExprKind::Index(value, boxed(folder, *index))
+        }
+        ExprKind::Field(value, name) => {
+            let value = boxed(folder, *value);
+            ExprKind::Field(value, folder.fold_ident(name))
+        }
+        ExprKind::Tuple(items) => ExprKind::Tuple(all(items, |item| folder.fold_expr(item))),
+        ExprKind::Array(items) => ExprKind::Array(all(items, |item| folder.fold_expr(item))),
+        ExprKind::Match(value, arms) => {
+            let value = boxed(folder, *value);
+            ExprKind::Match(value, all(arms, |arm| folder.fold_match_arm(arm)))
+        }
+        ExprKind::If(conditional) => ExprKind::If(folder.fold_conditional(conditional)),
+        ExprKind::Closure(closure) => ExprKind::Closure(Box::new(folder.fold_closure(*closure))),
+        ExprKind::Propagate(value, cleanup) => {
+            let value = boxed(folder, *value);
+            ExprKind::Propagate(value, folder.fold_block(cleanup))
+        }
+    };
+    Expr { kind, span: folder.fold_span(expr.span), id: folder.fold_node_id(expr.id) }
+}
+
+pub fn walk_conditional<F: Fold>(folder: &mut F, conditional: Conditional) -> Conditional {
+    Conditional {
+        comptime: conditional.comptime,
+        branches: all(conditional.branches, |branch| folder.fold_cond_branch(branch)),
+        else_branch: conditional.else_branch.map(|body| folder.fold_block(body)),
+        span: folder.fold_span(conditional.span),
+    }
+}
+
+pub fn walk_cond_branch<F: Fold>(folder: &mut F, branch: CondBranch) -> CondBranch {
+    CondBranch { cond: folder.fold_expr(branch.cond), body: folder.fold_block(branch.body), span: folder.fold_span(branch.span) }
+}
+
+pub fn walk_closure<F: Fold>(folder: &mut F, closure: Closure) -> Closure {
+    Closure {
+        params: all(closure.params, |param| folder.fold_closure_param(param)),
+        body: folder.fold_block(closure.body),
+        span: folder.fold_span(closure.span),
+    }
+}
+
+pub fn walk_closure_param<F: Fold>(folder: &mut F, param: ClosureParam) -> ClosureParam {
+    ClosureParam { ty: param.ty.map(|ty| folder.fold_type(ty)), name: folder.fold_ident(param.name), id: folder.fold_node_id(param.id) }
+}
+
+pub fn walk_match_arm<F: Fold>(folder: &mut F, arm: MatchArm) -> MatchArm {
+    MatchArm {
+        pattern: folder.fold_pattern(arm.pattern),
+        guard: arm.guard.map(|guard| folder.fold_expr(guard)),
+        body: folder.fold_expr(arm.body),
+        span: folder.fold_span(arm.span),
+        id: folder.fold_node_id(arm.id),
+    }
+}
+
+pub fn walk_pattern<F: Fold>(folder: &mut F, pattern: Pattern) -> Pattern {
+    let kind = match pattern.kind {
+        kind @ (PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range(..)) => kind,
+        PatternKind::Binding(name) => PatternKind::Binding(folder.fold_ident(name)),
+        PatternKind::Tuple(patterns) => PatternKind::Tuple(all(patterns, |pattern| folder.fold_pattern(pattern))),
+        PatternKind::Struct(name, args) => {
+            let name = folder.fold_ident(name);
+            PatternKind::Struct(name, all(args, |arg| folder.fold_pattern_arg(arg)))
+        }
+        PatternKind::Variant(ty, variant, args) => {
+            let ty = folder.fold_ident(ty);
+            let variant = folder.fold_ident(variant);
+            PatternKind::Variant(ty, variant, args.map(|args| all(args, |arg| folder.fold_pattern_arg(arg))))
+        }
+    };
+    Pattern { kind, span: folder.fold_span(pattern.span), id: folder.fold_node_id(pattern.id) }
+}
+
+pub fn walk_pattern_arg<F: Fold>(folder: &mut F, arg: PatternArg) -> PatternArg {
+    PatternArg {
+        name: arg.name.map(|name| folder.fold_ident(name)),
+        pattern: folder.fold_pattern(arg.pattern),
+        span: folder.fold_span(arg.span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        parsing::tokenizer::tokenize_text,
+        treegen::{items::parse_items, unparse::unparse},
+    };
+
+    // Renames a variable everywhere it is declared or used
+    struct Rename<'a>(&'a str, &'a str);
+
+    impl Fold for Rename<'_> {
+        fn fold_ident(&mut self, ident: Ident) -> Ident {
+            match ident.name == self.0 {
+                true => Ident { name: self.1.to_string(), ..ident },
+                false => ident,
+            }
+        }
+
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            match expr.kind {
+                ExprKind::Name(name) if name == self.0 => Expr { kind: ExprKind::Name(self.1.to_string()), ..expr },
+                _ => walk_expr(self, expr),
+            }
+        }
+    }
+
+    #[test]
+    fn rename() {
+        let text = "u8 f(u8 x) { var y = fn(x) => x; return x + g(x); }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items: Vec<Item> = parse_items(&tokens, text.len()).unwrap().into_iter().map(|item| Rename("x", "z").fold_item(item)).collect();
+        assert_eq!(unparse(&items), "u8 f(u8 z) {\n    var y = fn(z) => z;\n    return z + g(z);\n}\n");
+    }
+}
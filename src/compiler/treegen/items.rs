@@ -0,0 +1,653 @@
+use super::ast::{
+    AssocConst, AssocItem, AssocType, EnumDecl, FieldDecl, FnBody, FnDecl, GenericParam, GenericParamKind, Ident, ImplDecl, Item, Mutability, NodeId,
+    Param, Storage, StructDecl, TraitDecl, TypeExpr, TypeExprKind, VariantDecl, VariantPayload, Visibility, WhereClause, WherePredicate,
+};
+use super::expr::parse_expr;
+use super::parser::{number_items, parse_decorators, parse_visibility, Cursor, ParseResult, SyntaxError};
+use super::recovery::{starts_item, synchronize, syntax_errors};
+use super::stmt::{parse_block_recovering, parse_var, reorder_name_first};
+use super::types::parse_type;
+use crate::compiler::{
+    keywords::Keyword,
+    operators::Operator,
+    parsing::{
+        pattern_constants::{
+            ENUM_DECLARATION, FUNCTION_DECLARATION, IMPL_BLOCK, STRUCT_DECLARATION, TRAIT_DECLARATION, VARIABLE_DECLARATION,
+        },
+        test_tokens_against,
+        tokenizer::{Token, TokenData},
+    },
+};
+
+// Every declaration in a file, `end` is the length of the source text
+pub fn parse_items(tokens: &[Token], end: usize) -> ParseResult<Vec<Item>> {
+    let (items, errors) = parse_file(tokens, end);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(items),
+    }
+}
+
+// Every declaration in a file and every syntax error in it, what failed to parse is left as an error node
+pub fn parse_file(tokens: &[Token], end: usize) -> (Vec<Item>, Vec<SyntaxError>) {
+    let mut cursor = Cursor::new(tokens, end);
+    let mut items = Vec::new();
+    while !cursor.is_at_end() {
+        // Not `start`, the doc comments in front belong to the item
+        let before = cursor.position();
+        match parse_item(&mut cursor) {
+            Ok(item) => items.push(item),
+            Err(error) => {
+                cursor.reset(before);
+                let start = cursor.start();
+                synchronize(&mut cursor, starts_item, &error);
+                items.push(Item::Error(error, cursor.span_from(start)));
+            }
+        }
+    }
+    reorder_name_first(&mut items);
+    number_items(&mut items);
+    let errors = syntax_errors(&items);
+    (items, errors)
+}
+
+pub fn parse_item(cursor: &mut Cursor) -> ParseResult<Item> {
+    // The patterns pick the construct, the doc comments in front are read by its parser
+    let rest = cursor.rest();
+    if test_tokens_against(STRUCT_DECLARATION, rest).is_some() {
+        return Ok(Item::Struct(parse_struct(cursor)?));
+    }
+    if test_tokens_against(ENUM_DECLARATION, rest).is_some() {
+        return Ok(Item::Enum(parse_enum(cursor)?));
+    }
+    if test_tokens_against(TRAIT_DECLARATION, rest).is_some() {
+        return Ok(Item::Trait(parse_trait(cursor)?));
+    }
+    if test_tokens_against(IMPL_BLOCK, rest).is_some() {
+        return Ok(Item::Impl(parse_impl(cursor)?));
+    }
+    if test_tokens_against(FUNCTION_DECLARATION, rest).is_some() {
+        return Ok(Item::Function(parse_fn(cursor)?));
+    }
+    if test_tokens_against(VARIABLE_DECLARATION, rest).is_some() {
+        let decl = parse_var(cursor)?;
+        if decl.storage == Storage::Local && decl.mutability == Mutability::Var {
+            return Err(SyntaxError { message: "only `static` and `const` variables can be declared outside a function".to_string(), span: decl.span });
+        }
+        return Ok(Item::Var(decl));
+    }
+    Err(cursor.error("expected a declaration"))
+}
+
+pub fn parse_struct(cursor: &mut Cursor) -> ParseResult<StructDecl> {
+    let docs = cursor.docs();
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    let visibility = parse_visibility(cursor);
+    if cursor.eat_keyword(Keyword::Struct).is_none() {
+        return Err(cursor.error("expected `struct`"));
+    }
+    let name = cursor.expect_ident("a struct name")?;
+    let generics = parse_generics(cursor)?;
+    let mut implements = Vec::new();
+    if cursor.eat_keyword(Keyword::Implements).is_some() {
+        implements.push(cursor.expect_ident("a trait name after `implements`")?);
+        while cursor.eat(&TokenData::Colon).is_some() {
+            implements.push(cursor.expect_ident("a trait name after `,`")?);
+        }
+    }
+    let where_clause = parse_where_clause(cursor)?;
+
+    let body = cursor.eat_bracket(b'{').ok_or_else(|| cursor.error("expected `{` to start the struct body"))?;
+    let fields = parse_fields(&mut Cursor::enter(body))?;
+
+    let span = cursor.span_from(start);
+    Ok(StructDecl { docs, decorators, visibility, name, generics, implements, where_clause, fields, span, id: NodeId::UNSET })
+}
+
+pub fn parse_enum(cursor: &mut Cursor) -> ParseResult<EnumDecl> {
+    let docs = cursor.docs();
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    let visibility = parse_visibility(cursor);
+    if cursor.eat_keyword(Keyword::Enum).is_none() {
+        return Err(cursor.error("expected `enum`"));
+    }
+
+    // `enum u8 Name` has a backing type, `enum Name` does not
+    let before = cursor.position();
+    let backing = match parse_type(cursor) {
+        Ok(backing) if matches!(cursor.peek(), Some(Token { data: TokenData::TextCluster(_), .. })) => Some(backing),
+        _ => {
+            cursor.reset(before);
+            None
+        }
+    };
+    let name = cursor.expect_ident("an enum name")?;
+    let generics = parse_generics(cursor)?;
+    let where_clause = parse_where_clause(cursor)?;
+
+    let body = cursor.eat_bracket(b'{').ok_or_else(|| cursor.error("expected `{` to start the enum body"))?;
+    let mut inner = Cursor::enter(body);
+    let mut variants = Vec::new();
+    loop {
+        let docs = inner.docs();
+        if inner.is_at_end() {
+            break;
+        }
+        variants.push(parse_variant(&mut inner, docs)?);
+        if inner.is_at_end() {
+            break;
+        }
+        inner.expect(&TokenData::Colon, "`,` after a variant")?;
+    }
+
+    Ok(EnumDecl { docs, decorators, visibility, backing, name, generics, where_clause, variants, span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+// `Name`, `Name = 4`, `Name(T, U)` or `Name { T a, U b }`
+fn parse_variant(cursor: &mut Cursor, docs: Vec<String>) -> ParseResult<VariantDecl> {
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    let name = cursor.expect_ident("a variant name")?;
+    let payload = if let Some(body) = cursor.eat_bracket(b'(') {
+        let mut inner = Cursor::enter(body);
+        let mut types = Vec::new();
+        while !inner.is_at_end() {
+            types.push(parse_type(&mut inner)?);
+            if inner.is_at_end() {
+                break;
+            }
+            inner.expect(&TokenData::Colon, "`,` between payload types")?;
+        }
+        VariantPayload::Tuple(types)
+    } else if let Some(body) = cursor.eat_bracket(b'{') {
+        VariantPayload::Struct(parse_fields(&mut Cursor::enter(body))?)
+    } else {
+        VariantPayload::Unit
+    };
+    let discriminant = match cursor.eat(&TokenData::Operator(Operator::Assign)) {
+        Some(_) => Some(parse_expr(cursor)?),
+        None => None,
+    };
+    Ok(VariantDecl { docs, decorators, name, payload, discriminant, span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+// The inside of a struct body, comma separated with an optional trailing comma
+fn parse_fields(cursor: &mut Cursor) -> ParseResult<Vec<FieldDecl>> {
+    let mut fields = Vec::new();
+    loop {
+        let docs = cursor.docs();
+        if cursor.is_at_end() {
+            return Ok(fields);
+        }
+        fields.push(parse_field(cursor, docs)?);
+        if cursor.is_at_end() {
+            return Ok(fields);
+        }
+        cursor.expect(&TokenData::Colon, "`,` after a field")?;
+    }
+}
+
+pub fn parse_trait(cursor: &mut Cursor) -> ParseResult<TraitDecl> {
+    let docs = cursor.docs();
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    let visibility = parse_visibility(cursor);
+    if cursor.eat_keyword(Keyword::Trait).is_none() {
+        return Err(cursor.error("expected `trait`"));
+    }
+    let name = cursor.expect_ident("a trait name")?;
+    let generics = parse_generics(cursor)?;
+    let where_clause = parse_where_clause(cursor)?;
+    let body = cursor.eat_bracket(b'{').ok_or_else(|| cursor.error("expected `{` to start the trait body"))?;
+    let items = parse_assoc_items(&mut Cursor::enter(body))?;
+    Ok(TraitDecl { docs, decorators, visibility, name, generics, where_clause, items, span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+pub fn parse_impl(cursor: &mut Cursor) -> ParseResult<ImplDecl> {
+    let docs = cursor.docs();
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    if cursor.eat_keyword(Keyword::Impl).is_none() {
+        return Err(cursor.error("expected `impl`"));
+    }
+    let generics = parse_generics(cursor)?;
+
+    // `impl Trait for Type` names the trait first
+    let first = parse_type(cursor)?;
+    let (trait_name, self_ty) = if cursor.eat_keyword(Keyword::For).is_some() {
+        let trait_name = match first.kind {
+            TypeExprKind::Path(mut segments, args) if segments.len() == 1 && args.is_empty() => segments.pop().unwrap(),
+            _ => return Err(SyntaxError { message: "expected a trait name before `for`".to_string(), span: first.span }),
+        };
+        (Some(trait_name), parse_type(cursor)?)
+    } else {
+        (None, first)
+    };
+    let where_clause = parse_where_clause(cursor)?;
+    let body = cursor.eat_bracket(b'{').ok_or_else(|| cursor.error("expected `{` to start the impl body"))?;
+    let items = parse_assoc_items(&mut Cursor::enter(body))?;
+    Ok(ImplDecl { docs, decorators, generics, trait_name, self_ty, where_clause, items, span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+// Methods, `typedef`s and `const`s of a trait or impl body
+fn parse_assoc_items(cursor: &mut Cursor) -> ParseResult<Vec<AssocItem>> {
+    let mut items = Vec::new();
+    while !cursor.is_at_end() {
+        let before = cursor.position();
+        let docs = cursor.docs();
+        let start = cursor.start();
+        if cursor.eat_keyword(Keyword::Typedef).is_some() {
+            // `typedef Name;` or `typedef Type Name;`
+            let ty = parse_type_before_name(cursor);
+            let name = cursor.expect_ident("a type name")?;
+            cursor.expect(&TokenData::Semicolon, "`;` after the typedef")?;
+            items.push(AssocItem::Type(AssocType { docs, ty, name, span: cursor.span_from(start), id: NodeId::UNSET }));
+        } else if cursor.eat_keyword(Keyword::Const).is_some() {
+            let ty = parse_type(cursor)?;
+            let name = cursor.expect_ident("a constant name after its type")?;
+            let value = match cursor.eat(&TokenData::Operator(Operator::Assign)) {
+                Some(_) => Some(parse_expr(cursor)?),
+                None => None,
+            };
+            cursor.expect(&TokenData::Semicolon, "`;` after the constant")?;
+            items.push(AssocItem::Const(AssocConst { docs, ty, name, value, span: cursor.span_from(start), id: NodeId::UNSET }));
+        } else {
+            cursor.reset(before);
+            items.push(AssocItem::Method(parse_fn(cursor)?));
+        }
+    }
+    Ok(items)
+}
+
+// `@decorators pub static Type name<T>(Type arg, ...) where ... { }`, with `=> expr;` or `;` instead of a block
+pub fn parse_fn(cursor: &mut Cursor) -> ParseResult<FnDecl> {
+    let docs = cursor.docs();
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    let mut visibility = Visibility::Default;
+    let mut modifiers = Vec::new();
+    while let Some(Token { data: TokenData::Keyword(keyword), .. }) = cursor.peek() {
+        match keyword {
+            Keyword::Pub => visibility = Visibility::Pub,
+            Keyword::Private => visibility = Visibility::Private,
+            // The return type starts here
+            _ if starts_type(cursor) => break,
+            keyword => modifiers.push(keyword.clone()),
+        }
+        cursor.bump();
+    }
+    let return_type = parse_type(cursor)?;
+    let name = cursor.expect_ident("a function name after its return type")?;
+    let generics = parse_generics(cursor)?;
+
+    let args = cursor.eat_bracket(b'(').ok_or_else(|| cursor.error("expected `(` to start the parameters"))?;
+    let mut inner = Cursor::enter(args);
+    let mut params = Vec::new();
+    while !inner.is_at_end() {
+        params.push(parse_param(&mut inner)?);
+        if inner.is_at_end() {
+            break;
+        }
+        inner.expect(&TokenData::Colon, "`,` between parameters")?;
+    }
+
+    let where_clause = parse_where_clause(cursor)?;
+    let body = if let Some(brace) = cursor.eat_bracket(b'{') {
+        Some(FnBody::Block(parse_block_recovering(brace)))
+    } else if cursor.eat(&TokenData::Operator(Operator::EqualsArrow)).is_some() {
+        let expr = parse_expr(cursor)?;
+        cursor.expect(&TokenData::Semicolon, "`;` after the function body")?;
+        Some(FnBody::Expr(expr))
+    } else if cursor.eat(&TokenData::Semicolon).is_some() {
+        None
+    } else {
+        return Err(cursor.error("expected `{`, `=>` or `;` after the function signature"));
+    };
+
+    Ok(FnDecl {
+        docs,
+        decorators,
+        visibility,
+        modifiers,
+        return_type,
+        name,
+        generics,
+        params,
+        where_clause,
+        body,
+        span: cursor.span_from(start),
+        id: NodeId::UNSET,
+    })
+}
+
+// Whether the keyword at the cursor is part of a type, `extern` only is in `extern fn`
+fn starts_type(cursor: &Cursor) -> bool {
+    let mut ahead = cursor.clone();
+    match ahead.next().map(|token| &token.data) {
+        Some(TokenData::Keyword(Keyword::Const | Keyword::Impl | Keyword::Fn)) => true,
+        Some(TokenData::Keyword(Keyword::Extern)) => matches!(ahead.peek(), Some(Token { data: TokenData::Keyword(Keyword::Fn), .. })),
+        _ => false,
+    }
+}
+
+fn parse_param(cursor: &mut Cursor) -> ParseResult<Param> {
+    let start = cursor.start();
+    let mut modifiers = Vec::new();
+    while let Some(Token { data: TokenData::Keyword(keyword), .. }) = cursor.peek() {
+        if starts_type(cursor) {
+            break;
+        }
+        modifiers.push(keyword.clone());
+        cursor.bump();
+    }
+    let ty = parse_type(cursor)?;
+    let name = cursor.expect_ident("a parameter name after its type")?;
+    Ok(Param { modifiers, ty, name, span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+// `@decorators pub Type name = default`
+fn parse_field(cursor: &mut Cursor, docs: Vec<String>) -> ParseResult<FieldDecl> {
+    let start = cursor.start();
+    let decorators = parse_decorators(cursor)?;
+    let visibility = parse_visibility(cursor);
+    let ty = parse_type(cursor)?;
+    let name = cursor.expect_ident("a field name after its type")?;
+    let default = match cursor.eat(&TokenData::Operator(Operator::Assign)) {
+        Some(_) => Some(parse_expr(cursor)?),
+        None => None,
+    };
+    Ok(FieldDecl { docs, decorators, visibility, ty, name, default, span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+// A type only if a name follows it, `Name` alone is the name
+pub fn parse_type_before_name(cursor: &mut Cursor) -> Option<TypeExpr> {
+    let before = cursor.position();
+    match parse_type(cursor) {
+        Ok(ty) if matches!(cursor.peek(), Some(Token { data: TokenData::TextCluster(_), .. })) => Some(ty),
+        _ => {
+            cursor.reset(before);
+            None
+        }
+    }
+}
+
+// `<T, U: Add + Copy, const usize N>`
+fn parse_generics(cursor: &mut Cursor) -> ParseResult<Vec<GenericParam>> {
+    let mut generics = Vec::new();
+    if cursor.eat(&TokenData::Operator(Operator::LesserThan)).is_none() {
+        return Ok(generics);
+    }
+    loop {
+        generics.push(parse_generic_param(cursor)?);
+        if cursor.eat(&TokenData::Operator(Operator::GreaterThan)).is_some() {
+            return Ok(generics);
+        }
+        cursor.expect(&TokenData::Colon, "`,` or `>` in the generic parameters")?;
+    }
+}
+
+fn parse_generic_param(cursor: &mut Cursor) -> ParseResult<GenericParam> {
+    let start = cursor.start();
+    if cursor.eat_keyword(Keyword::Const).is_some() {
+        let ty = parse_type(cursor)?;
+        // `const N: usize` reads the name as the type
+        if matches!(cursor.peek(), Some(Token { data: TokenData::ColonSign, .. })) {
+            return Err(cursor.error("write the type before the name, `const usize N`"));
+        }
+        let name = cursor.expect_ident("a name after the type of a `const` parameter")?;
+        return Ok(GenericParam { name, kind: GenericParamKind::Const(ty), span: cursor.span_from(start), id: NodeId::UNSET });
+    }
+    let name = cursor.expect_ident("a generic parameter name")?;
+    let bounds = match cursor.eat(&TokenData::ColonSign) {
+        Some(_) => parse_bounds(cursor)?,
+        None => Vec::new(),
+    };
+    Ok(GenericParam { name, kind: GenericParamKind::Type(bounds), span: cursor.span_from(start), id: NodeId::UNSET })
+}
+
+// `Add + Copy`
+fn parse_bounds(cursor: &mut Cursor) -> ParseResult<Vec<Ident>> {
+    let mut bounds = vec![cursor.expect_ident("a trait name")?];
+    while cursor.eat(&TokenData::Operator(Operator::Add)).is_some() {
+        bounds.push(cursor.expect_ident("a trait name after `+`")?);
+    }
+    Ok(bounds)
+}
+
+// `where T implements Copy, U: Add + Copy` up to the body
+fn parse_where_clause(cursor: &mut Cursor) -> ParseResult<Option<WhereClause>> {
+    if cursor.eat_keyword(Keyword::Where).is_none() {
+        return Ok(None);
+    }
+    let start = cursor.start();
+    let mut predicates = Vec::new();
+    loop {
+        let predicate_start = cursor.start();
+        let ty = parse_type(cursor)?;
+        if cursor.eat_keyword(Keyword::Implements).is_none() && cursor.eat(&TokenData::ColonSign).is_none() {
+            return Err(cursor.error("expected `implements` or `:` after the type in a `where` clause"));
+        }
+        let bounds = parse_bounds(cursor)?;
+        predicates.push(WherePredicate { ty, bounds, span: cursor.span_from(predicate_start) });
+        if cursor.eat(&TokenData::Colon).is_none() {
+            break;
+        }
+    }
+    Ok(Some(WhereClause { predicates, span: cursor.span_from(start) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        parsing::tokenizer::tokenize_text,
+        treegen::ast::{ExprKind, GenericParamKind, Span, Stmt, StmtKind, Visibility},
+    };
+
+    fn parse(text: &str) -> ParseResult<Vec<Item>> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        parse_items(&tokens, text.len())
+    }
+
+    #[test]
+    fn struct_with_everything() {
+        let text = "/// A pair of things
+// not documentation
+@packed @align(8)
+pub struct Pair<T, U> where T implements Copy {
+    /// The first one
+    pub T first = 4,
+    @deprecated private u8* second,
+    U done,
+}";
+        let [Item::Struct(decl)] = &parse(text).unwrap()[..] else { panic!() };
+
+        assert_eq!(decl.docs, ["A pair of things"]);
+        assert_eq!(decl.visibility, Visibility::Pub);
+        assert_eq!(decl.name.name, "Pair");
+        assert_eq!(decl.span, Span::new(text.find('@').unwrap(), text.len()));
+        assert_eq!(decl.decorators.iter().map(|d| d.name.name.as_str()).collect::<Vec<_>>(), ["packed", "align"]);
+        assert!(decl.decorators[0].args.is_none());
+        assert!(matches!(decl.decorators[1].args.as_deref(), Some([arg]) if matches!(&arg.kind, ExprKind::Number(n) if n.text_content == "8")));
+        assert_eq!(decl.generics.iter().map(|g| g.name.name.as_str()).collect::<Vec<_>>(), ["T", "U"]);
+        let predicates = &decl.where_clause.as_ref().unwrap().predicates;
+        assert_eq!(predicates.iter().map(|p| (matches!(p.ty.kind, TypeExprKind::Path(..)), p.bounds[0].name.as_str())).collect::<Vec<_>>(), [(true, "Copy")]);
+
+        let [first, second, third] = &decl.fields[..] else { panic!() };
+        assert_eq!(first.docs, ["The first one"]);
+        assert_eq!(first.visibility, Visibility::Pub);
+        assert_eq!(first.name.name, "first");
+        assert!(matches!(&first.default, Some(expr) if matches!(&expr.kind, ExprKind::Number(_))));
+        assert_eq!(&text[first.span.start..first.span.end], "pub T first = 4");
+
+        assert_eq!(second.visibility, Visibility::Private);
+        assert_eq!(second.decorators[0].name.name, "deprecated");
+        assert_eq!(&text[second.ty.span.start..second.ty.span.end], "u8*");
+
+        assert_eq!(third.visibility, Visibility::Default);
+        assert_eq!(third.name.name, "done");
+        assert!(third.default.is_none());
+    }
+
+    #[test]
+    fn enums() {
+        let text = "enum u8 Color { Red = 1, Green, /// Last\n Blue = 4, }
+pub enum Shape { Circle(f32), Rect { f32 w, f32 h = 1 }, Empty }";
+        let [Item::Enum(color), Item::Enum(shape)] = &parse(text).unwrap()[..] else { panic!() };
+
+        assert_eq!(color.name.name, "Color");
+        assert_eq!(&text[color.backing.as_ref().unwrap().span.start..color.backing.as_ref().unwrap().span.end], "u8");
+        assert_eq!(color.variants.iter().map(|v| v.name.name.as_str()).collect::<Vec<_>>(), ["Red", "Green", "Blue"]);
+        assert!(color.variants[0].discriminant.is_some() && color.variants[1].discriminant.is_none());
+        assert_eq!(color.variants[2].docs, ["Last"]);
+
+        assert!(shape.backing.is_none());
+        assert_eq!(shape.visibility, Visibility::Pub);
+        assert!(matches!(&shape.variants[0].payload, VariantPayload::Tuple(types) if types.len() == 1));
+        assert!(matches!(&shape.variants[1].payload, VariantPayload::Struct(fields) if fields[1].default.is_some()));
+        assert_eq!(shape.variants[2].payload, VariantPayload::Unit);
+        assert_eq!(&text[shape.variants[1].span.start..shape.variants[1].span.end], "Rect { f32 w, f32 h = 1 }");
+    }
+
+    #[test]
+    fn struct_errors() {
+        assert!(parse("struct Empty {}").is_ok());
+        assert_eq!(parse("struct Bad { u8 }").unwrap_err().message, "expected a field name after its type");
+        assert_eq!(parse("struct Bad { u8 a u8 b }").unwrap_err().message, "expected `,` after a field");
+        assert_eq!(parse("struct Bad { u8 a, }; ").unwrap_err().message, "expected a declaration");
+        assert_eq!(parse("enum Bad { A(u8 x) }").unwrap_err().message, "expected `,` between payload types");
+    }
+
+    #[test]
+    fn globals() {
+        let [Item::Var(counter), Item::Var(limit)] = &parse("/// Calls so far\nstatic u64 COUNTER = 0; const LIMIT = 8;").unwrap()[..] else { panic!() };
+        assert_eq!((counter.storage, counter.docs.as_slice()), (Storage::Static, ["Calls so far".to_string()].as_slice()));
+        assert_eq!((limit.mutability, limit.ty.is_none()), (Mutability::Const, true));
+        assert_eq!(parse("var x = 1;").unwrap_err().message, "only `static` and `const` variables can be declared outside a function");
+
+        // The name can come first, as long as the type isn't a plain name of its own
+        let [Item::Var(counter), Item::Var(table)] = &parse("static COUNTER u64 = 0; const TABLE [u8; 4] = [1, 2, 3, 4];").unwrap()[..] else { panic!() };
+        assert_eq!(counter.name.name, "COUNTER");
+        assert!(matches!(&counter.ty.as_ref().unwrap().kind, TypeExprKind::Path(path, _) if path[0].name == "u64"));
+        assert_eq!(table.name.name, "TABLE");
+        assert!(matches!(table.ty.as_ref().unwrap().kind, TypeExprKind::Array(..)));
+        assert_eq!(parse("static u64 u8 = 0;").unwrap_err().message, "`u8` is a type, it can't name a variable");
+        // Or when it is a type the file declares
+        let items = parse("static COUNTER Counter = c; static Counter other = c; struct Counter {}").unwrap();
+        let [Item::Var(counter), Item::Var(other), _] = &items[..] else { panic!() };
+        assert_eq!((counter.name.name.as_str(), other.name.name.as_str()), ("COUNTER", "other"));
+        assert!(matches!(&counter.ty.as_ref().unwrap().kind, TypeExprKind::Path(path, _) if path[0].name == "Counter"));
+    }
+
+    #[test]
+    fn traits_impls_and_functions() {
+        let text = "trait Shape<T> { typedef Unit; const u32 SIDES; f32 area(Self* self); f32 twice(Self* self) => 2.0; }
+impl<T> Shape for Circle { typedef f32 Unit; const u32 SIDES = 0; f32 area(Circle* self) { 1.0; } }
+impl Circle { }
+pub static inline u8 add<T>(u8 a, comptime u8 b) => a + b;";
+        let [Item::Trait(shape), Item::Impl(circle), Item::Impl(inherent), Item::Function(add)] = &parse(text).unwrap()[..] else { panic!() };
+
+        assert_eq!(shape.name.name, "Shape");
+        assert_eq!(shape.generics.len(), 1);
+        let [AssocItem::Type(unit), AssocItem::Const(sides), AssocItem::Method(area), AssocItem::Method(twice)] = &shape.items[..] else { panic!() };
+        assert!(unit.ty.is_none() && unit.name.name == "Unit");
+        assert!(sides.value.is_none());
+        assert!(area.body.is_none());
+        assert!(matches!(twice.body, Some(FnBody::Expr(_))));
+
+        assert_eq!(circle.trait_name.as_ref().unwrap().name, "Shape");
+        assert_eq!(&text[circle.self_ty.span.start..circle.self_ty.span.end], "Circle");
+        assert!(matches!(&circle.items[0], AssocItem::Type(AssocType { ty: Some(_), .. })));
+        assert!(matches!(&circle.items[2], AssocItem::Method(FnDecl { body: Some(FnBody::Block(block)), .. }) if block.stmts.len() == 1));
+        assert!(inherent.trait_name.is_none() && inherent.items.is_empty());
+
+        assert_eq!(add.visibility, Visibility::Pub);
+        assert_eq!(add.modifiers, [Keyword::Static, Keyword::Inline]);
+        assert_eq!(add.params.iter().map(|p| p.name.name.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(add.params[1].modifiers, [Keyword::Comptime]);
+        assert_eq!(parse("impl X { u8 f(u8 a u8 b); }").unwrap_err().message, "expected `,` between parameters");
+    }
+
+    #[test]
+    fn generics_and_where_clauses() {
+        let text = "u8 fill<T: Add + Copy, const usize N, U>(T value) where *U implements Copy, U: Add { }
+impl<T: Copy> Shape for T where T: Shape { }
+enum Maybe<T: Copy> where T implements Copy + Add { Some(T), Nothing }";
+        let [Item::Function(fill), Item::Impl(wrapper), Item::Enum(maybe)] = &parse(text).unwrap()[..] else { panic!() };
+
+        let [t, n, u] = &fill.generics[..] else { panic!() };
+        assert!(matches!(&t.kind, GenericParamKind::Type(bounds) if bounds.iter().map(|b| b.name.as_str()).collect::<Vec<_>>() == ["Add", "Copy"]));
+        assert!(matches!(&n.kind, GenericParamKind::Const(ty) if matches!(&ty.kind, TypeExprKind::Path(..))));
+        assert_eq!(n.name.name, "N");
+        assert_eq!(&text[n.span.start..n.span.end], "const usize N");
+        assert!(matches!(&u.kind, GenericParamKind::Type(bounds) if bounds.is_empty()));
+
+        let clause = fill.where_clause.as_ref().unwrap();
+        let [pointer, plain] = &clause.predicates[..] else { panic!() };
+        assert_eq!(&text[pointer.span.start..pointer.span.end], "*U implements Copy");
+        assert!(matches!(&pointer.ty.kind, TypeExprKind::Pointer(_)));
+        assert_eq!(plain.bounds[0].name, "Add");
+        assert_eq!(&text[clause.span.start..clause.span.end], "*U implements Copy, U: Add");
+
+        assert_eq!(wrapper.generics[0].name.name, "T");
+        assert_eq!(wrapper.where_clause.as_ref().unwrap().predicates.len(), 1);
+        assert_eq!(maybe.where_clause.as_ref().unwrap().predicates[0].bounds.len(), 2);
+
+        let message = |text: &str| parse(text).unwrap_err().message;
+        assert_eq!(message("struct A<const N: usize> { }"), "write the type before the name, `const usize N`");
+        assert_eq!(message("struct A<T: , U> { }"), "expected a trait name");
+        assert_eq!(message("struct A<T: Copy + , U> { }"), "expected a trait name after `+`");
+        assert_eq!(message("struct A<T> where T Copy { }"), "expected `implements` or `:` after the type in a `where` clause");
+    }
+
+    #[test]
+    fn error_recovery() {
+        let text = "struct A { u8 }
+u8 f() { var x = ; x = 1 +; return x; }
+strct C { }
+@inline u8 g() { if (x) { y = ; } else { z; } while (x) w + ; return 0; }
+struct B { u8 b }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let (items, errors) = parse_file(&tokens, text.len());
+        assert!(matches!(&items[..], [Item::Error(..), Item::Function(_), Item::Error(..), Item::Function(_), Item::Struct(_)]));
+        assert_eq!(
+            errors.iter().map(|error| error.message.as_str()).collect::<Vec<_>>(),
+            [
+                "expected a field name after its type",
+                "expected an expression",
+                "expected an expression",
+                "expected a declaration",
+                "expected an expression",
+                "expected an expression",
+            ]
+        );
+
+        let Item::Error(_, skipped) = &items[2] else { panic!() };
+        assert_eq!(&text[skipped.start..skipped.end], "strct C { }");
+        let Item::Function(f) = &items[1] else { panic!() };
+        let Some(FnBody::Block(body)) = &f.body else { panic!() };
+        let spans = body.stmts.iter().map(|stmt| (matches!(stmt.kind, StmtKind::Error(_)), &text[stmt.span.start..stmt.span.end])).collect::<Vec<_>>();
+        assert_eq!(spans, [(true, "var x = ;"), (true, "x = 1 +;"), (false, "return x;")]);
+        // The error inside the braces of the `if` stays inside them, a single statement body takes its loop down with it
+        let Item::Function(g) = &items[3] else { panic!() };
+        let Some(FnBody::Block(body)) = &g.body else { panic!() };
+        assert!(matches!(&body.stmts[..], [Stmt { kind: StmtKind::Expr(_), .. }, Stmt { kind: StmtKind::Error(_), .. }, Stmt { kind: StmtKind::Return(_), .. }]));
+
+        assert_eq!(parse(text).unwrap_err().message, "expected a field name after its type");
+
+        // A statement keyword inside the failed statement is not where the next one starts
+        for (text, message) in [
+            ("void f() { if (x) return 1 +; return 0; }", "expected an expression"),
+            ("void f() { while (x) break 1; return 0; }", "expected `;` after `break`"),
+        ] {
+            let tokens = tokenize_text(text.to_string()).unwrap();
+            let (items, errors) = parse_file(&tokens, text.len());
+            assert_eq!(errors.iter().map(|error| error.message.as_str()).collect::<Vec<_>>(), [message], "{}", text);
+            let Item::Function(f) = &items[0] else { panic!() };
+            let Some(FnBody::Block(body)) = &f.body else { panic!() };
+            assert!(matches!(&body.stmts[..], [Stmt { kind: StmtKind::Error(_), .. }, Stmt { kind: StmtKind::Return(_), .. }]));
+        }
+    }
+}
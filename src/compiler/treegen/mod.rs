@@ -1 +1,12 @@
-mod comp_stages;
\ No newline at end of file
+mod comp_stages;
+pub mod ast;
+pub mod parser;
+pub mod expr;
+pub mod pattern;
+pub mod stmt;
+pub mod items;
+pub mod types;
+pub mod recovery;
+pub mod unparse;
+pub mod visit;
+pub mod fold;
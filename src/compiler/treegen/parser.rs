@@ -0,0 +1,304 @@
+use std::{collections::HashMap, fmt};
+
+use super::ast::{Block, Decorator, Expr, Ident, Item, NodeId, Span, Visibility};
+use super::expr::parse_expr_list;
+use super::visit::{Visit, VisitMut};
+use crate::compiler::{
+    keywords::Keyword,
+    parsing::tokenizer::{Token, TokenData},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}: {}", self.span.start, self.span.end, self.message)
+    }
+}
+
+pub type ParseResult<T> = Result<T, SyntaxError>;
+
+// A position in one level of the token tree, brackets are entered with `Cursor::enter`
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    // Where errors at the end of these tokens point, the closing bracket or the end of the file
+    end: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(tokens: &'a [Token], end: usize) -> Self {
+        Self { tokens, pos: 0, end }
+    }
+
+    // A cursor over the contents of a bracket token
+    pub fn enter(bracket: &'a Token) -> Self {
+        let inner = match &bracket.data {
+            TokenData::Bracket(_, Some(inner)) => inner.as_slice(),
+            _ => &[],
+        };
+        Self::new(inner, bracket.index + bracket.length.saturating_sub(1))
+    }
+
+    pub fn rest(&self) -> &'a [Token] {
+        &self.tokens[self.pos..]
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn reset(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    // Skips whitespace, comments and doc comments
+    pub fn skip_whitespace(&mut self) {
+        while matches!(
+            self.tokens.get(self.pos).map(|token| &token.data),
+            Some(TokenData::Whitespace(_) | TokenData::DocComment(_))
+        ) {
+            self.pos += 1;
+        }
+    }
+
+    // Skips whitespace and returns the doc comments found along the way
+    pub fn docs(&mut self) -> Vec<String> {
+        let mut docs = Vec::new();
+        while let Some(token) = self.tokens.get(self.pos) {
+            match &token.data {
+                TokenData::DocComment(doc) => docs.push(doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string()),
+                TokenData::Whitespace(_) => {}
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        docs
+    }
+
+    // Only whitespace is left, nothing is consumed so doc comments can still be read
+    pub fn is_at_end(&self) -> bool {
+        self.rest().iter().all(|token| matches!(token.data, TokenData::Whitespace(_) | TokenData::DocComment(_)))
+    }
+
+    // The next token, without skipping anything
+    pub fn peek_raw(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub fn peek(&mut self) -> Option<&'a Token> {
+        self.skip_whitespace();
+        self.peek_raw()
+    }
+
+    pub fn bump(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    pub fn next(&mut self) -> Option<&'a Token> {
+        self.skip_whitespace();
+        self.bump()
+    }
+
+    // Consumes the next token if it equals `data`
+    pub fn eat(&mut self, data: &TokenData) -> Option<&'a Token> {
+        if self.peek()?.data == *data {
+            return self.bump();
+        }
+        None
+    }
+
+    pub fn eat_keyword(&mut self, keyword: Keyword) -> Option<&'a Token> {
+        self.eat(&TokenData::Keyword(keyword))
+    }
+
+    pub fn eat_bracket(&mut self, opener: u8) -> Option<&'a Token> {
+        self.eat(&TokenData::Bracket(opener, None))
+    }
+
+    pub fn expect(&mut self, data: &TokenData, what: &str) -> ParseResult<&'a Token> {
+        self.eat(data).ok_or_else(|| self.error(format!("expected {}", what)))
+    }
+
+    pub fn expect_ident(&mut self, what: &str) -> ParseResult<Ident> {
+        match self.peek() {
+            Some(Token { data: TokenData::TextCluster(Some(name)), .. }) => {
+                let token = self.bump().unwrap();
+                Ok(Ident { name: name.clone(), span: Span::of_token(token) })
+            }
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    // Span of the next token, or the end of these tokens
+    pub fn here(&mut self) -> Span {
+        match self.peek() {
+            Some(token) => Span::of_token(token),
+            None => Span::new(self.end, self.end),
+        }
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError { message: message.into(), span: self.here() }
+    }
+
+    // From the start of the token at `start` up to the last consumed token
+    pub fn span_from(&self, start: usize) -> Span {
+        let first = self.tokens.get(start).map_or(self.end, |token| token.index);
+        let last = self.tokens[start..self.pos.max(start)]
+            .iter()
+            .rev()
+            .find(|token| !matches!(token.data, TokenData::Whitespace(_) | TokenData::DocComment(_)));
+        match last {
+            Some(last) => Span::new(first, last.index + last.length),
+            None => Span::new(first, first),
+        }
+    }
+
+    // The tokens between `start` and the cursor, without whitespace
+    pub fn consumed_since(&self, start: usize) -> Vec<Token> {
+        self.tokens[start..self.pos]
+            .iter()
+            .filter(|token| !matches!(token.data, TokenData::Whitespace(_) | TokenData::DocComment(_)))
+            .cloned()
+            .collect()
+    }
+
+    // Index of the next token that is not whitespace, for use with `span_from`
+    pub fn start(&mut self) -> usize {
+        self.skip_whitespace();
+        self.pos
+    }
+}
+
+// `@name` and `@name(args)`, as many as are written
+pub fn parse_decorators(cursor: &mut Cursor) -> ParseResult<Vec<Decorator>> {
+    let mut decorators = Vec::new();
+    loop {
+        let start = cursor.start();
+        if cursor.eat(&TokenData::AtSign).is_none() {
+            return Ok(decorators);
+        }
+        // `@inline` is named by a keyword
+        let name = match cursor.peek() {
+            Some(token @ Token { data: TokenData::Keyword(keyword), .. }) => {
+                cursor.bump();
+                Ident { name: keyword.text().to_string(), span: Span::of_token(token) }
+            }
+            _ => cursor.expect_ident("a decorator name after `@`")?,
+        };
+        let args = match cursor.peek_raw() {
+            Some(token @ Token { data: TokenData::Bracket(b'(', _), .. }) => {
+                cursor.bump();
+                Some(parse_expr_list(&mut Cursor::enter(token))?)
+            }
+            _ => None,
+        };
+        decorators.push(Decorator { name, args, span: cursor.span_from(start), id: NodeId::UNSET });
+    }
+}
+
+pub fn parse_visibility(cursor: &mut Cursor) -> Visibility {
+    if cursor.eat_keyword(Keyword::Pub).is_some() {
+        Visibility::Pub
+    } else if cursor.eat_keyword(Keyword::Private).is_some() {
+        Visibility::Private
+    } else {
+        Visibility::Default
+    }
+}
+
+// Numbers every node of a finished tree, children before their parents
+struct NumberNodes(usize);
+
+impl VisitMut for NumberNodes {
+    fn visit_node_id(&mut self, id: &mut NodeId) {
+        *id = NodeId::new(self.0);
+        self.0 += 1;
+    }
+}
+
+pub fn number_items(items: &mut [Item]) {
+    let mut numbering = NumberNodes(0);
+    items.iter_mut().for_each(|item| numbering.visit_item(item));
+}
+
+pub fn number_block(block: &mut Block) {
+    NumberNodes(0).visit_block(block);
+}
+
+// Hands out the ids of nodes made after parsing, none of them taken by the tree it was made for. Copies of written
+// code get fresh ids too, and remember the id they were copied from since side tables only know that one
+#[derive(Debug, Clone, Default)]
+pub struct FreshIds {
+    next: usize,
+    copies: HashMap<NodeId, NodeId>,
+}
+
+struct LargestId(Option<NodeId>);
+
+impl Visit for LargestId {
+    fn visit_node_id(&mut self, id: &NodeId) {
+        if *id != NodeId::UNSET {
+            self.0 = self.0.max(Some(*id));
+        }
+    }
+}
+
+struct Recopy<'f>(&'f mut FreshIds);
+
+impl VisitMut for Recopy<'_> {
+    fn visit_node_id(&mut self, id: &mut NodeId) {
+        let original = self.0.original(*id);
+        *id = self.0.next();
+        self.0.copies.insert(*id, original);
+    }
+}
+
+impl FreshIds {
+    pub fn after_items(items: &[Item]) -> Self {
+        let mut largest = LargestId(None);
+        items.iter().for_each(|item| largest.visit_item(item));
+        Self::after(largest.0)
+    }
+
+    pub fn after_block(block: &Block) -> Self {
+        let mut largest = LargestId(None);
+        largest.visit_block(block);
+        Self::after(largest.0)
+    }
+
+    fn after(largest: Option<NodeId>) -> Self {
+        Self { next: largest.map_or(0, |id| id.index() + 1), copies: HashMap::new() }
+    }
+
+    pub fn next(&mut self) -> NodeId {
+        self.next += 1;
+        NodeId::new(self.next - 1)
+    }
+
+    // `block` with fresh ids all through it
+    pub fn copy_block(&mut self, block: &Block) -> Block {
+        let mut copy = block.clone();
+        Recopy(self).visit_block(&mut copy);
+        copy
+    }
+
+    pub fn copy_expr(&mut self, expr: &Expr) -> Expr {
+        let mut copy = expr.clone();
+        Recopy(self).visit_expr(&mut copy);
+        copy
+    }
+
+    // The id of the written node `id` is a copy of, or `id` itself
+    pub fn original(&self, id: NodeId) -> NodeId {
+        self.copies.get(&id).copied().unwrap_or(id)
+    }
+}
@@ -0,0 +1,196 @@
+use super::ast::{Ident, MatchArm, NodeId, Pattern, PatternArg, PatternKind, Span};
+use super::expr::parse_expr;
+use super::parser::{Cursor, ParseResult};
+use crate::compiler::{
+    keywords::Keyword,
+    operators::Operator,
+    parsing::tokenizer::{Token, TokenData},
+};
+
+pub fn parse_pattern(cursor: &mut Cursor) -> ParseResult<Pattern> {
+    let Some(token) = cursor.peek() else {
+        return Err(cursor.error("expected a pattern"));
+    };
+    let span = Span::of_token(token);
+    let kind = match &token.data {
+        TokenData::NumberLiteral(start) => {
+            cursor.bump();
+            let inclusive = match cursor.peek() {
+                Some(Token { data: TokenData::Operator(Operator::Range), .. }) => false,
+                Some(Token { data: TokenData::Operator(Operator::RangeInclusive), .. }) => true,
+                _ => return Ok(Pattern::new(PatternKind::Literal(start.clone()), span)),
+            };
+            cursor.bump();
+            return match cursor.peek() {
+                Some(token @ Token { data: TokenData::NumberLiteral(end), .. }) => {
+                    cursor.bump();
+                    Ok(Pattern::new(PatternKind::Range(start.clone(), end.clone(), inclusive), span.to(Span::of_token(token))))
+                }
+                _ => Err(cursor.error("expected a number to end the range")),
+            };
+        }
+        TokenData::TextCluster(Some(name)) if name == "_" => PatternKind::Wildcard,
+        TokenData::TextCluster(Some(name)) => {
+            cursor.bump();
+            let ident = Ident { name: name.clone(), span };
+            if let Some(Token { data: TokenData::Dot, .. }) = cursor.peek_raw() {
+                cursor.bump();
+                let variant = cursor.expect_ident("a variant name after `.`")?;
+                let mut span = span.to(variant.span);
+                let args = match cursor.peek_raw() {
+                    Some(paren @ Token { data: TokenData::Bracket(b'(', _), .. }) => {
+                        cursor.bump();
+                        span = span.to(Span::of_token(paren));
+                        Some(parse_pattern_args(paren)?)
+                    }
+                    _ => None,
+                };
+                return Ok(Pattern::new(PatternKind::Variant(ident, variant, args), span));
+            }
+            return Ok(match cursor.peek_raw() {
+                Some(paren @ Token { data: TokenData::Bracket(b'(', _), .. }) => {
+                    cursor.bump();
+                    Pattern::new(PatternKind::Struct(ident, parse_pattern_args(paren)?), span.to(Span::of_token(paren)))
+                }
+                _ => Pattern::new(PatternKind::Binding(ident), span),
+            });
+        }
+        TokenData::Bracket(b'(', Some(tokens)) => {
+            let mut inner = Cursor::enter(token);
+            let mut patterns = Vec::new();
+            while !inner.is_at_end() {
+                patterns.push(parse_pattern(&mut inner)?);
+                if inner.is_at_end() {
+                    break;
+                }
+                inner.expect(&TokenData::Colon, "`,` between patterns")?;
+            }
+            cursor.bump();
+            // `(a)` only groups, `(a,)` is a tuple
+            if patterns.len() == 1 && !tokens.iter().any(|token| token.data == TokenData::Colon) {
+                let mut pattern = patterns.pop().unwrap();
+                pattern.span = span;
+                return Ok(pattern);
+            }
+            PatternKind::Tuple(patterns)
+        }
+        _ => return Err(cursor.error("expected a pattern")),
+    };
+    cursor.bump();
+    Ok(Pattern::new(kind, span))
+}
+
+// The inside of `Name(a, b = pattern)`
+fn parse_pattern_args(paren: &Token) -> ParseResult<Vec<PatternArg>> {
+    let mut cursor = Cursor::enter(paren);
+    let mut args = Vec::new();
+    while !cursor.is_at_end() {
+        let start = cursor.start();
+        let name = match cursor.expect_ident("") {
+            Ok(name) if cursor.eat(&TokenData::Operator(Operator::Assign)).is_some() => Some(name),
+            _ => {
+                cursor.reset(start);
+                None
+            }
+        };
+        let pattern = parse_pattern(&mut cursor)?;
+        args.push(PatternArg { name, pattern, span: cursor.span_from(start) });
+        if cursor.is_at_end() {
+            break;
+        }
+        cursor.expect(&TokenData::Colon, "`,` between patterns")?;
+    }
+    Ok(args)
+}
+
+// The arms inside the braces of `match (value) { ... }`
+pub fn parse_match_arms(brace: &Token) -> ParseResult<Vec<MatchArm>> {
+    let mut cursor = Cursor::enter(brace);
+    let mut arms = Vec::new();
+    while !cursor.is_at_end() {
+        let start = cursor.start();
+        let pattern = parse_pattern(&mut cursor)?;
+        let guard = match cursor.eat_keyword(Keyword::Where) {
+            Some(_) => Some(parse_expr(&mut cursor)?),
+            None => None,
+        };
+        cursor.expect(&TokenData::Operator(Operator::EqualsArrow), "`=>` after the pattern")?;
+        let body = parse_expr(&mut cursor)?;
+        arms.push(MatchArm { pattern, guard, body, span: cursor.span_from(start), id: NodeId::UNSET });
+        if cursor.is_at_end() {
+            break;
+        }
+        cursor.expect(&TokenData::Colon, "`,` between match arms")?;
+    }
+    Ok(arms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::ast::ExprKind};
+
+    fn parse(text: &str) -> ParseResult<Pattern> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let mut cursor = Cursor::new(&tokens, text.len());
+        let pattern = parse_pattern(&mut cursor)?;
+        assert!(cursor.is_at_end(), "{} was not fully parsed", text);
+        Ok(pattern)
+    }
+
+    // A compact rendering, to check structure
+    fn shape(pattern: &Pattern) -> String {
+        let args = |args: &[PatternArg]| {
+            args.iter()
+                .map(|arg| match &arg.name {
+                    Some(name) => format!("{}={}", name.name, shape(&arg.pattern)),
+                    None => shape(&arg.pattern),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &pattern.kind {
+            PatternKind::Wildcard => "_".to_string(),
+            PatternKind::Binding(name) => format!("${}", name.name),
+            PatternKind::Literal(number) => number.text_content.clone(),
+            PatternKind::Range(start, end, inclusive) => format!("{}{}{}", start.text_content, if *inclusive { "..=" } else { ".." }, end.text_content),
+            PatternKind::Tuple(items) => format!("tuple({})", items.iter().map(shape).collect::<Vec<_>>().join(", ")),
+            PatternKind::Struct(name, fields) => format!("{}({})", name.name, args(fields)),
+            PatternKind::Variant(enum_name, variant, None) => format!("{}.{}", enum_name.name, variant.name),
+            PatternKind::Variant(enum_name, variant, Some(payload)) => format!("{}.{}({})", enum_name.name, variant.name, args(payload)),
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(shape(&parse("_").unwrap()), "_");
+        assert_eq!(shape(&parse("value").unwrap()), "$value");
+        assert_eq!(shape(&parse("-4").unwrap()), "-4");
+        assert_eq!(shape(&parse("1..5").unwrap()), "1..5");
+        assert_eq!(shape(&parse("0 ..= 9").unwrap()), "0..=9");
+        assert_eq!(shape(&parse("(a, _, (b))").unwrap()), "tuple($a, _, $b)");
+        assert_eq!(shape(&parse("(a,)").unwrap()), "tuple($a)");
+        assert_eq!(shape(&parse("Point(x, y = 0)").unwrap()), "Point($x, y=0)");
+        assert_eq!(shape(&parse("Shape.Circle(Point(x = 1..=3), _)").unwrap()), "Shape.Circle(Point(x=1..=3), _)");
+        assert_eq!(shape(&parse("Shape.Empty").unwrap()), "Shape.Empty");
+
+        let span = parse("Shape.Rect(w)").unwrap().span;
+        assert_eq!(span, Span::new(0, 13));
+        assert_eq!(parse("1..").unwrap_err().message, "expected a number to end the range");
+        assert_eq!(parse("[a]").unwrap_err().message, "expected a pattern");
+    }
+
+    #[test]
+    fn match_arms() {
+        let text = "{ Shape.Circle(_, r) where r > 1.0 => r, _ => 0.0, }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let arms = parse_match_arms(&tokens[0]).unwrap();
+        assert_eq!(arms.len(), 2);
+        assert!(matches!(&arms[0].guard, Some(guard) if matches!(guard.kind, ExprKind::Binary(Operator::GreaterThan, ..))));
+        assert!(arms[1].guard.is_none());
+        assert_eq!(&text[arms[0].span.start..arms[0].span.end], "Shape.Circle(_, r) where r > 1.0 => r");
+
+        let tokens = tokenize_text("{ a b }".to_string()).unwrap();
+        assert_eq!(parse_match_arms(&tokens[0]).unwrap_err().message, "expected `=>` after the pattern");
+    }
+}
@@ -0,0 +1,82 @@
+use super::ast::{Block, Item, Stmt, StmtKind};
+use super::parser::{Cursor, SyntaxError};
+use super::visit::{walk, Visit};
+use crate::compiler::{keywords::Keyword, parsing::tokenizer::TokenData};
+
+// Panic mode recovery: what failed to parse is skipped up to where the next statement or declaration can start,
+// and left in the tree as an error node
+
+// Skips what is left of something that failed to parse, always at least one token. It stops after a `;`, after a
+// `{ }` body that isn't followed by `else`, or before a token that `starts_next` says begins something new. A
+// token in front of the error was read as part of what failed, `if (x) return 1 +;` is skipped up to its `;`
+pub fn synchronize(cursor: &mut Cursor, starts_next: fn(&TokenData) -> bool, error: &SyntaxError) {
+    cursor.next();
+    while let Some(token) = cursor.peek() {
+        match &token.data {
+            TokenData::Semicolon => {
+                cursor.bump();
+                return;
+            }
+            TokenData::Bracket(b'{', _) => {
+                cursor.bump();
+                match cursor.peek().map(|token| &token.data) {
+                    Some(TokenData::Keyword(Keyword::Else | Keyword::ElseIf)) => {}
+                    // `var x = match (y) { ... };`
+                    Some(TokenData::Semicolon) => {
+                        cursor.bump();
+                        return;
+                    }
+                    _ => return,
+                }
+            }
+            data if starts_next(data) && token.index >= error.span.start => return,
+            _ => {
+                cursor.bump();
+            }
+        }
+    }
+}
+
+pub fn starts_stmt(data: &TokenData) -> bool {
+    use Keyword::*;
+    matches!(
+        data,
+        TokenData::Label(_) | TokenData::Keyword(Var | Const | Static | If | While | For | Do | Return | Break | Continue | Defer)
+    )
+}
+
+pub fn starts_item(data: &TokenData) -> bool {
+    use Keyword::*;
+    matches!(data, TokenData::AtSign | TokenData::Keyword(Struct | Enum | Trait | Impl | Pub | Private | Static | Const | Import))
+}
+
+// Every syntax error left in the tree, in source order
+pub fn syntax_errors(items: &[Item]) -> Vec<SyntaxError> {
+    let mut collector = ErrorCollector(Vec::new());
+    items.iter().for_each(|item| collector.visit_item(item));
+    collector.0
+}
+
+pub fn block_errors(block: &Block) -> Vec<SyntaxError> {
+    let mut collector = ErrorCollector(Vec::new());
+    collector.visit_block(block);
+    collector.0
+}
+
+struct ErrorCollector(Vec<SyntaxError>);
+
+impl Visit for ErrorCollector {
+    fn visit_item(&mut self, item: &Item) {
+        if let Item::Error(error, _) = item {
+            self.0.push(error.clone());
+        }
+        walk::walk_item(self, item);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let StmtKind::Error(error) = &stmt.kind {
+            self.0.push(error.clone());
+        }
+        walk::walk_stmt(self, stmt);
+    }
+}
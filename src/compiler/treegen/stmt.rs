@@ -0,0 +1,584 @@
+use std::collections::HashSet;
+
+use super::ast::{
+    AssocType, Block, CondBranch, Conditional, EnumDecl, Expr, ExprKind, ForKind, ForLoop, GenericParam, Ident, Item, Mutability, NodeId, Span, Stmt,
+    StmtKind, Storage, StructDecl, TraitDecl, TypeExpr, TypeExprKind, VarDecl, WhileLoop,
+};
+use super::expr::parse_expr;
+use super::items::parse_type_before_name;
+use super::parser::{number_block, Cursor, ParseResult, SyntaxError};
+use super::recovery::{block_errors, starts_stmt, synchronize};
+use super::types::parse_type;
+use super::visit::{walk, walk_mut, Visit, VisitMut};
+use crate::compiler::{
+    keywords::Keyword,
+    objects::gurn_objects::Primitive,
+    operators::Operator,
+    parsing::{
+        pattern_constants::{FOR_IN_LOOP, FOR_LOOP, IF_STATEMENT, VARIABLE_DECLARATION, WHILE_LOOP},
+        test_tokens_against,
+        tokenizer::{Token, TokenData},
+    },
+};
+
+// What a line inside a block turned out to be
+enum BlockItem {
+    Stmt(Box<Stmt>),
+    // The last expression of the block, without a `;`
+    Value(Expr),
+}
+
+// The statements inside a `{ }` bracket token, failing on the first syntax error in them
+pub fn parse_block(brace: &Token) -> ParseResult<Block> {
+    let mut block = parse_block_recovering(brace);
+    number_block(&mut block);
+    match block_errors(&block).into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(block),
+    }
+}
+
+// A statement that fails to parse becomes an error statement, and parsing picks up at the next one
+pub fn parse_block_recovering(brace: &Token) -> Block {
+    let mut cursor = Cursor::enter(brace);
+    let mut stmts = Vec::new();
+    let mut value = None;
+    while !cursor.is_at_end() {
+        let before = cursor.position();
+        match parse_block_item(&mut cursor) {
+            Ok(BlockItem::Stmt(stmt)) => stmts.push(*stmt),
+            Ok(BlockItem::Value(expr)) => value = Some(Box::new(expr)),
+            Err(error) => {
+                cursor.reset(before);
+                let start = cursor.start();
+                synchronize(&mut cursor, starts_stmt, &error);
+                stmts.push(Stmt::new(StmtKind::Error(error), cursor.span_from(start)));
+            }
+        }
+    }
+    Block::new(stmts, value, Span::of_token(brace))
+}
+
+pub fn parse_stmt(cursor: &mut Cursor) -> ParseResult<Stmt> {
+    match parse_block_item(cursor)? {
+        BlockItem::Stmt(stmt) => Ok(*stmt),
+        BlockItem::Value(_) => Err(cursor.error("expected `;` after the expression")),
+    }
+}
+
+fn parse_block_item(cursor: &mut Cursor) -> ParseResult<BlockItem> {
+    let start = cursor.start();
+    let stmt = |kind, span| Ok(BlockItem::Stmt(Box::new(Stmt::new(kind, span))));
+    match cursor.peek() {
+        Some(Token { data: TokenData::Keyword(Keyword::If), .. }) => return parse_if_stmt(cursor),
+        Some(Token { data: TokenData::Keyword(Keyword::Comptime), .. }) if test_tokens_against(IF_STATEMENT, cursor.rest()).is_some() => {
+            return parse_if_stmt(cursor)
+        }
+        Some(token @ Token { data: TokenData::Keyword(Keyword::Else | Keyword::ElseIf), .. }) => {
+            return Err(SyntaxError { message: "`else` without a preceding `if`".to_string(), span: Span::of_token(token) })
+        }
+        _ => {}
+    }
+    match cursor.peek().map(|token| &token.data) {
+        Some(TokenData::Label(_) | TokenData::Keyword(Keyword::While | Keyword::For)) => return parse_loop(cursor).map(|stmt| BlockItem::Stmt(Box::new(stmt))),
+        Some(TokenData::Keyword(Keyword::Comptime)) if [WHILE_LOOP, FOR_LOOP, FOR_IN_LOOP].iter().any(|pattern| test_tokens_against(pattern, cursor.rest()).is_some()) => {
+            return parse_loop(cursor).map(|stmt| BlockItem::Stmt(Box::new(stmt)))
+        }
+        Some(TokenData::Bracket(b'{', _)) => {
+            let block = parse_block_recovering(cursor.bump().unwrap());
+            return stmt(StmtKind::Block(block), cursor.span_from(start));
+        }
+        Some(TokenData::Keyword(Keyword::Defer)) => {
+            cursor.bump();
+            if cursor.is_at_end() {
+                return Err(cursor.error("expected a statement after `defer`"));
+            }
+            let body = parse_body(cursor)?;
+            return stmt(StmtKind::Defer(body), cursor.span_from(start));
+        }
+        Some(TokenData::Keyword(Keyword::Return)) => {
+            cursor.bump();
+            let value = match cursor.peek() {
+                Some(Token { data: TokenData::Semicolon, .. }) => None,
+                _ => Some(parse_expr(cursor)?),
+            };
+            cursor.expect(&TokenData::Semicolon, "`;` after `return`")?;
+            return stmt(StmtKind::Return(value), cursor.span_from(start));
+        }
+        Some(TokenData::Keyword(keyword @ (Keyword::Break | Keyword::Continue))) => {
+            let keyword = keyword.clone();
+            cursor.bump();
+            let label = parse_label_use(cursor);
+            cursor.expect(&TokenData::Semicolon, &format!("`;` after `{}`", keyword.text()))?;
+            let kind = match keyword {
+                Keyword::Break => StmtKind::Break(label),
+                _ => StmtKind::Continue(label),
+            };
+            return stmt(kind, cursor.span_from(start));
+        }
+        _ => {}
+    }
+    // A declaration keyword commits to a declaration, so its errors say what is wrong with it
+    let keyword = matches!(cursor.peek(), Some(Token { data: TokenData::Keyword(Keyword::Var | Keyword::Const | Keyword::Static), .. }));
+    if keyword || test_tokens_against(VARIABLE_DECLARATION, cursor.rest()).is_some() {
+        let decl = parse_var(cursor)?;
+        let span = decl.span;
+        return stmt(StmtKind::Var(decl), span);
+    }
+    let expr = parse_expr(cursor)?;
+    if cursor.eat(&TokenData::Semicolon).is_none() {
+        if cursor.is_at_end() {
+            return Ok(BlockItem::Value(expr));
+        }
+        return Err(cursor.error("expected `;` after the expression"));
+    }
+    stmt(StmtKind::Expr(expr), cursor.span_from(start))
+}
+
+// An `if` chain in statement position needs no `;`, at the end of a block it is the block's value
+fn parse_if_stmt(cursor: &mut Cursor) -> ParseResult<BlockItem> {
+    let expr = parse_conditional(cursor, true)?;
+    if cursor.eat(&TokenData::Semicolon).is_none() && cursor.is_at_end() {
+        return Ok(BlockItem::Value(expr));
+    }
+    let span = expr.span;
+    Ok(BlockItem::Stmt(Box::new(Stmt::new(StmtKind::Expr(expr), span))))
+}
+
+// `comptime if (a) x else if (b) y else z`. The branches of a statement are statements,
+// the branches of a value are expressions
+pub fn parse_conditional(cursor: &mut Cursor, as_stmt: bool) -> ParseResult<Expr> {
+    let start = cursor.start();
+    let comptime = cursor.eat_keyword(Keyword::Comptime).is_some();
+    if cursor.eat_keyword(Keyword::If).is_none() {
+        return Err(cursor.error("expected `if` after `comptime`"));
+    }
+    let mut branches = vec![parse_branch(cursor, start, as_stmt)?];
+    let mut else_branch = None;
+    loop {
+        let branch_start = cursor.start();
+        if cursor.eat_keyword(Keyword::ElseIf).is_some() {
+            branches.push(parse_branch(cursor, branch_start, as_stmt)?);
+        } else if cursor.eat_keyword(Keyword::Else).is_some() {
+            // `else` and `if` split by a comment
+            if cursor.eat_keyword(Keyword::If).is_some() {
+                branches.push(parse_branch(cursor, branch_start, as_stmt)?);
+                continue;
+            }
+            else_branch = Some(parse_branch_body(cursor, as_stmt)?);
+            break;
+        } else {
+            break;
+        }
+    }
+    let span = cursor.span_from(start);
+    Ok(Expr::new(ExprKind::If(Conditional { comptime, branches, else_branch, span }), span))
+}
+
+// `(cond) body`, after the `if` or `else if`
+fn parse_branch(cursor: &mut Cursor, start: usize, as_stmt: bool) -> ParseResult<CondBranch> {
+    let Some(paren) = cursor.eat_bracket(b'(') else {
+        return Err(cursor.error("expected `(` after `if`"));
+    };
+    let mut inner = Cursor::enter(paren);
+    let cond = parse_expr(&mut inner)?;
+    if !inner.is_at_end() {
+        return Err(inner.error("expected `)` after the condition"));
+    }
+    let body = parse_branch_body(cursor, as_stmt)?;
+    Ok(CondBranch { cond, body, span: cursor.span_from(start) })
+}
+
+fn parse_branch_body(cursor: &mut Cursor, as_stmt: bool) -> ParseResult<Block> {
+    if as_stmt {
+        return parse_body(cursor);
+    }
+    if let Some(brace) = cursor.eat_bracket(b'{') {
+        return Ok(parse_block_recovering(brace));
+    }
+    let value = parse_expr(cursor)?;
+    let span = value.span;
+    Ok(Block::new(Vec::new(), Some(Box::new(value)), span))
+}
+
+// The `'outer` after `break` or `continue`
+fn parse_label_use(cursor: &mut Cursor) -> Option<Ident> {
+    match cursor.peek() {
+        Some(token @ Token { data: TokenData::Label(name), .. }) => {
+            cursor.bump();
+            Some(Ident { name: name.clone(), span: Span::of_token(token) })
+        }
+        _ => None,
+    }
+}
+
+// `'outer: comptime while (cond) body` or a `for` loop, where the label and `comptime` are optional
+fn parse_loop(cursor: &mut Cursor) -> ParseResult<Stmt> {
+    let start = cursor.start();
+    let label = parse_label_use(cursor);
+    if let Some(label) = &label {
+        cursor.expect(&TokenData::ColonSign, &format!("`:` after `'{}`", label.name))?;
+    }
+    let comptime = cursor.eat_keyword(Keyword::Comptime).is_some();
+    let keyword = match cursor.peek() {
+        Some(Token { data: TokenData::Keyword(keyword @ (Keyword::While | Keyword::For)), .. }) => keyword.clone(),
+        _ => {
+            return Err(match &label {
+                Some(label) => SyntaxError { message: format!("`'{}` labels something that is not a loop", label.name), span: label.span },
+                None => cursor.error("expected a loop after `comptime`"),
+            })
+        }
+    };
+    cursor.bump();
+    let Some(paren) = cursor.eat_bracket(b'(') else {
+        return Err(cursor.error(format!("expected `(` after `{}`", keyword.text())));
+    };
+    let mut inner = Cursor::enter(paren);
+    if keyword == Keyword::While {
+        let cond = parse_expr(&mut inner)?;
+        if !inner.is_at_end() {
+            return Err(inner.error("expected `)` after the loop condition"));
+        }
+        let body = parse_body(cursor)?;
+        let span = cursor.span_from(start);
+        return Ok(Stmt::new(StmtKind::While(WhileLoop { label, comptime, cond, body, span }), span));
+    }
+
+    let kind = parse_for_header(&mut inner)?;
+    let body = parse_body(cursor)?;
+    let span = cursor.span_from(start);
+    Ok(Stmt::new(StmtKind::For(ForLoop { label, comptime, kind, body, span }), span))
+}
+
+// The inside of the parentheses after `for`
+fn parse_for_header(cursor: &mut Cursor) -> ParseResult<ForKind> {
+    let start = cursor.start();
+    if let Ok(binding) = cursor.expect_ident("") {
+        if cursor.eat_keyword(Keyword::In).is_some() {
+            let iterable = parse_expr(cursor)?;
+            if !cursor.is_at_end() {
+                return Err(cursor.error("expected `)` after the value to loop over"));
+            }
+            return Ok(ForKind::In { binding, iterable });
+        }
+    }
+    cursor.reset(start);
+
+    // The first clause brings its own `;`
+    let init = match cursor.eat(&TokenData::Semicolon) {
+        Some(_) => None,
+        None => Some(Box::new(parse_stmt(cursor)?)),
+    };
+    let cond = match cursor.peek() {
+        Some(Token { data: TokenData::Semicolon, .. }) => None,
+        _ => Some(parse_expr(cursor)?),
+    };
+    cursor.expect(&TokenData::Semicolon, "`;` after the loop condition")?;
+    let step = match cursor.is_at_end() {
+        true => None,
+        false => Some(parse_expr(cursor)?),
+    };
+    if !cursor.is_at_end() {
+        return Err(cursor.error("expected `)` after the step of the loop"));
+    }
+    Ok(ForKind::Clauses { init, cond, step })
+}
+
+// A `{ }` block, or a single statement standing in for one
+pub fn parse_body(cursor: &mut Cursor) -> ParseResult<Block> {
+    if let Some(brace) = cursor.eat_bracket(b'{') {
+        return Ok(parse_block_recovering(brace));
+    }
+    let stmt = parse_stmt(cursor)?;
+    let span = stmt.span;
+    Ok(Block::new(vec![stmt], None, span))
+}
+
+// `static const Type name = value;`, `var name = value;`, `static name Type = value;` or `Type name;`
+pub fn parse_var(cursor: &mut Cursor) -> ParseResult<VarDecl> {
+    let docs = cursor.docs();
+    let start = cursor.start();
+    let storage = match cursor.eat_keyword(Keyword::Static) {
+        Some(_) => Storage::Static,
+        None => Storage::Local,
+    };
+    let (mutability, keyword) = if cursor.eat_keyword(Keyword::Const).is_some() {
+        (Mutability::Const, true)
+    } else {
+        (Mutability::Var, cursor.eat_keyword(Keyword::Var).is_some() || storage == Storage::Static)
+    };
+    let (ty, name) = match keyword.then(|| parse_name_first(cursor)).flatten() {
+        Some((name, ty)) => (Some(ty), name),
+        None => (parse_type_before_name(cursor), cursor.expect_ident("a variable name")?),
+    };
+    if is_builtin_type(&name.name) {
+        return Err(SyntaxError { message: format!("`{}` is a type, it can't name a variable", name.name), span: name.span });
+    }
+    let value = match cursor.eat(&TokenData::Operator(Operator::Assign)) {
+        Some(_) => Some(parse_expr(cursor)?),
+        None => None,
+    };
+    cursor.expect(&TokenData::Semicolon, "`;` after the declaration")?;
+
+    let span = cursor.span_from(start);
+    let missing = match (&ty, &value) {
+        (_, None) if mutability == Mutability::Const => Some(format!("`{}` is `const`, it needs a value", name.name)),
+        (_, None) if storage == Storage::Static => Some(format!("`{}` is `static`, it needs a value", name.name)),
+        (None, None) => Some(format!("`{}` needs a type or a value to infer it from", name.name)),
+        _ => None,
+    };
+    if let Some(message) = missing {
+        return Err(SyntaxError { message, span });
+    }
+    Ok(VarDecl { docs, storage, mutability, ty, name, value, span, id: NodeId::UNSET })
+}
+
+// `COUNTER u64` after a declaration keyword. `origin Point` reads the same as `Point origin`, so the name only
+// comes first here when the type after it is a builtin or more than a plain name, `reorder_name_first` settles
+// the rest once the file's types are known
+fn parse_name_first(cursor: &mut Cursor) -> Option<(Ident, TypeExpr)> {
+    let before = cursor.position();
+    let name = match cursor.peek() {
+        Some(Token { data: TokenData::TextCluster(Some(name)), .. }) if !is_builtin_type(name) => cursor.expect_ident("a variable name").ok()?,
+        _ => return None,
+    };
+    let user_type = matches!(cursor.peek(), Some(Token { data: TokenData::TextCluster(Some(name)), .. }) if !is_builtin_type(name));
+    if !user_type {
+        if let Ok(ty) = parse_type(cursor) {
+            if matches!(cursor.peek(), Some(Token { data: TokenData::Semicolon | TokenData::Operator(Operator::Assign), .. })) {
+                return Some((name, ty));
+            }
+        }
+    }
+    cursor.reset(before);
+    None
+}
+
+fn is_builtin_type(name: &str) -> bool {
+    Primitive::from_name(name).is_some() || name == "bool" || name == "void"
+}
+
+// `var origin Point;` is parsed as `Point origin` until the file is read. A declaration after a keyword is turned
+// around when its name is a type the file declares and its type is not
+pub fn reorder_name_first(items: &mut [Item]) {
+    let mut types = DeclaredTypes(HashSet::from(["Self".to_string()]));
+    items.iter().for_each(|item| types.visit_item(item));
+    let mut reorder = ReorderNameFirst(types.0);
+    items.iter_mut().for_each(|item| reorder.visit_item(item));
+}
+
+// Every type name in a file, generic parameters and typedefs included, whatever scope they are in
+struct DeclaredTypes(HashSet<String>);
+
+impl Visit for DeclaredTypes {
+    fn visit_struct(&mut self, decl: &StructDecl) {
+        self.0.insert(decl.name.name.clone());
+        walk::walk_struct(self, decl);
+    }
+
+    fn visit_enum(&mut self, decl: &EnumDecl) {
+        self.0.insert(decl.name.name.clone());
+        walk::walk_enum(self, decl);
+    }
+
+    fn visit_trait(&mut self, decl: &TraitDecl) {
+        self.0.insert(decl.name.name.clone());
+        walk::walk_trait(self, decl);
+    }
+
+    fn visit_assoc_type(&mut self, decl: &AssocType) {
+        self.0.insert(decl.name.name.clone());
+        walk::walk_assoc_type(self, decl);
+    }
+
+    fn visit_generic_param(&mut self, param: &GenericParam) {
+        self.0.insert(param.name.name.clone());
+        walk::walk_generic_param(self, param);
+    }
+}
+
+struct ReorderNameFirst(HashSet<String>);
+
+impl VisitMut for ReorderNameFirst {
+    fn visit_var(&mut self, decl: &mut VarDecl) {
+        if let Some(TypeExpr { kind: TypeExprKind::Path(path, args), span, .. }) = &decl.ty {
+            // Only a declaration keyword comes before the type
+            let after_keyword = span.start > decl.span.start;
+            let plain = path.len() == 1 && args.is_empty();
+            if after_keyword && plain && self.0.contains(&decl.name.name) && !self.0.contains(&path[0].name) {
+                let name = path[0].clone();
+                decl.ty = Some(TypeExpr::new(TypeExprKind::Path(vec![decl.name.clone()], Vec::new()), decl.name.span));
+                decl.name = name;
+            }
+        }
+        walk_mut::walk_var(self, decl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{
+        parsing::tokenizer::tokenize_text,
+        treegen::{
+            ast::{Expr, ExprKind, FnBody},
+            items::parse_items,
+        },
+    };
+
+    fn parse(text: &str) -> ParseResult<Vec<Stmt>> {
+        let text = format!("{{{}}}", text);
+        let tokens = tokenize_text(text).unwrap();
+        parse_block(&tokens[0]).map(|block| block.stmts)
+    }
+
+    fn var(stmt: &Stmt) -> &VarDecl {
+        match &stmt.kind {
+            StmtKind::Var(decl) => decl,
+            _ => panic!("{:?} is not a declaration", stmt),
+        }
+    }
+
+    #[test]
+    fn declarations() {
+        let stmts = parse("var x = 4; u32* y; const N = 4; static u64 counter = 0; static const u8 LIMIT = 9; x = y;").unwrap();
+        let [x, y, n, counter, limit, assign] = &stmts[..] else { panic!() };
+
+        let x = var(x);
+        assert_eq!((x.storage, x.mutability, x.ty.is_none()), (Storage::Local, Mutability::Var, true));
+        assert_eq!(x.name.name, "x");
+        let y = var(y);
+        assert_eq!((y.mutability, y.value.is_none()), (Mutability::Var, true));
+        assert!(matches!(&y.ty.as_ref().unwrap().kind, TypeExprKind::Pointer(_)));
+        assert_eq!((var(n).mutability, var(n).ty.is_none()), (Mutability::Const, true));
+        assert_eq!((var(counter).storage, var(counter).mutability), (Storage::Static, Mutability::Var));
+        assert_eq!((var(limit).storage, var(limit).mutability), (Storage::Static, Mutability::Const));
+        let text = "{var x = 4; u32* y; const N = 4; static u64 counter = 0; static const u8 LIMIT = 9; x = y;}";
+        assert_eq!(&text[var(limit).span.start..var(limit).span.end], "static const u8 LIMIT = 9;");
+        assert!(matches!(&assign.kind, StmtKind::Expr(Expr { kind: ExprKind::Binary(Operator::Assign, ..), .. })));
+
+        // `static COUNTER u64` names the variable first
+        let stmts = parse("static COUNTER u64 = 0; var p *u8 = q; const N usize = 4; var x = 1;").unwrap();
+        let names: Vec<&str> = stmts.iter().map(|stmt| var(stmt).name.name.as_str()).collect();
+        assert_eq!(names, ["COUNTER", "p", "N", "x"]);
+        assert!(matches!(&var(&stmts[1]).ty.as_ref().unwrap().kind, TypeExprKind::Pointer(_)));
+        assert_eq!(parse("static COUNTER u64 junk = 0;").unwrap_err().message, "`u64` is a type, it can't name a variable");
+
+        // Two plain names are told apart by the types the file declares
+        let text = "struct Point {} void f<T>() { var origin Point; var Point p; const ZERO T = z; Point q; var a b; }";
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let items = parse_items(&tokens, text.len()).unwrap();
+        let Item::Function(f) = &items[1] else { panic!() };
+        let Some(FnBody::Block(body)) = &f.body else { panic!() };
+        let decls: Vec<(&str, String)> = body
+            .stmts
+            .iter()
+            .map(|stmt| {
+                let decl = var(stmt);
+                let TypeExprKind::Path(path, _) = &decl.ty.as_ref().unwrap().kind else { panic!() };
+                (decl.name.name.as_str(), path[0].name.clone())
+            })
+            .collect();
+        let expected = [("origin", "Point"), ("p", "Point"), ("ZERO", "T"), ("q", "Point"), ("b", "a")];
+        assert_eq!(decls, expected.map(|(name, ty)| (name, ty.to_string())));
+    }
+
+    #[test]
+    fn loops() {
+        let stmts = parse("'outer: while (a) { comptime while (b) break 'outer; continue; } while(c){}").unwrap();
+        let [outer, plain] = &stmts[..] else { panic!() };
+        let StmtKind::While(outer) = &outer.kind else { panic!() };
+        assert_eq!(outer.label.as_ref().unwrap().name, "outer");
+        assert!(!outer.comptime);
+        let [inner, next] = &outer.body.stmts[..] else { panic!() };
+        let StmtKind::While(inner) = &inner.kind else { panic!() };
+        assert!(inner.comptime && inner.label.is_none());
+        assert!(matches!(&inner.body.stmts[..], [Stmt { kind: StmtKind::Break(Some(label)), .. }] if label.name == "outer"));
+        assert!(matches!(next.kind, StmtKind::Continue(None)));
+        assert!(matches!(&plain.kind, StmtKind::While(lp) if lp.body.stmts.is_empty()));
+
+        assert_eq!(parse("'a: x = 1;").unwrap_err().message, "`'a` labels something that is not a loop");
+        assert_eq!(parse("'a while (b) {}").unwrap_err().message, "expected `:` after `'a`");
+        assert_eq!(parse("break").unwrap_err().message, "expected `;` after `break`");
+        assert_eq!(parse("while (a b) {}").unwrap_err().message, "expected `)` after the loop condition");
+    }
+
+    #[test]
+    fn for_loops() {
+        let stmts = parse("for (u32 i = 0; i < n; i += 1) {} for (;;) break; 'rows: comptime for (row in 0..n + 1) {}").unwrap();
+        let [clauses, empty, rows] = &stmts[..] else { panic!() };
+        let StmtKind::For(ForLoop { kind: ForKind::Clauses { init: Some(init), cond: Some(_), step: Some(step) }, .. }) = &clauses.kind else { panic!() };
+        assert!(matches!(init.kind, StmtKind::Var(_)));
+        assert!(matches!(step.kind, ExprKind::Binary(Operator::AddEq, ..)));
+        let StmtKind::For(ForLoop { kind: ForKind::Clauses { init: None, cond: None, step: None }, body, .. }) = &empty.kind else { panic!() };
+        assert!(matches!(body.stmts[..], [Stmt { kind: StmtKind::Break(None), .. }]));
+        let StmtKind::For(ForLoop { label: Some(label), comptime: true, kind: ForKind::In { binding, iterable }, .. }) = &rows.kind else { panic!() };
+        assert_eq!((label.name.as_str(), binding.name.as_str()), ("rows", "row"));
+        // Ranges bind looser than arithmetic
+        let ExprKind::Binary(Operator::Range, _, end) = &iterable.kind else { panic!() };
+        assert!(matches!(end.kind, ExprKind::Binary(Operator::Add, ..)));
+
+        assert_eq!(parse("for (x in) {}").unwrap_err().message, "expected an expression");
+        assert_eq!(parse("for (var i = 0; i < 3) {}").unwrap_err().message, "expected `;` after the loop condition");
+        assert_eq!(parse("for (;; a b) {}").unwrap_err().message, "expected `)` after the step of the loop");
+        assert_eq!(parse("comptime x = 1;").unwrap_err().message, "expected `if` after `comptime`");
+    }
+
+    #[test]
+    fn conditionals() {
+        let stmts = parse("if (a) b = 1; else if (c) { d; } else e; if (f) {} else {}; x").unwrap();
+        let [chain, second] = &stmts[..] else { panic!() };
+        let StmtKind::Expr(Expr { kind: ExprKind::If(chain), .. }) = &chain.kind else { panic!() };
+        assert_eq!(chain.branches.len(), 2);
+        assert!(matches!(chain.branches[0].body.stmts[..], [Stmt { kind: StmtKind::Expr(_), .. }]));
+        assert!(matches!(&chain.else_branch, Some(block) if block.stmts.len() == 1));
+        assert!(matches!(second.kind, StmtKind::Expr(Expr { kind: ExprKind::If(_), .. })));
+
+        // The last expression, or a trailing `if`, is the block's value
+        let tokens = tokenize_text("{ a; if (b) { 1 } else { 2 } }".to_string()).unwrap();
+        let block = parse_block(&tokens[0]).unwrap();
+        assert_eq!(block.stmts.len(), 1);
+        assert!(matches!(block.value.as_deref(), Some(Expr { kind: ExprKind::If(conditional), .. }) if conditional.branches[0].body.value.is_some()));
+        let tokens = tokenize_text("{ a; b }".to_string()).unwrap();
+        assert!(matches!(parse_block(&tokens[0]).unwrap().value.as_deref(), Some(Expr { kind: ExprKind::Name(name), .. }) if name == "b"));
+
+        let tokens = tokenize_text("else if".to_string()).unwrap();
+        assert_eq!((&tokens[0].data, tokens.len()), (&TokenData::Keyword(Keyword::ElseIf), 1));
+
+        assert_eq!(parse("a; else b;").unwrap_err().message, "`else` without a preceding `if`");
+        assert_eq!(parse("if (a) {} b; else if (c) {}").unwrap_err().message, "`else` without a preceding `if`");
+        assert_eq!(parse("if (a b) {}").unwrap_err().message, "expected `)` after the condition");
+        assert_eq!(parse("a b").unwrap_err().message, "expected `;` after the expression");
+    }
+
+    #[test]
+    fn defer_and_return() {
+        let stmts = parse("defer close(f); defer { a; b; } { c; } return; return x + 1;").unwrap();
+        let [one, many, scope, bare, value] = &stmts[..] else { panic!() };
+        assert!(matches!(&one.kind, StmtKind::Defer(body) if body.stmts.len() == 1));
+        assert!(matches!(&many.kind, StmtKind::Defer(body) if body.stmts.len() == 2));
+        assert!(matches!(&scope.kind, StmtKind::Block(block) if block.stmts.len() == 1));
+        assert!(matches!(bare.kind, StmtKind::Return(None)));
+        assert!(matches!(&value.kind, StmtKind::Return(Some(Expr { kind: ExprKind::Binary(Operator::Add, ..), .. }))));
+
+        // `*` and `&` after a keyword are prefix operators
+        let stmts = parse("return *p; return &y; defer *p = 0;").unwrap();
+        let [deref, reference, deferred] = &stmts[..] else { panic!() };
+        assert!(matches!(&deref.kind, StmtKind::Return(Some(Expr { kind: ExprKind::Unary(Operator::Dereference, _), .. }))));
+        assert!(matches!(&reference.kind, StmtKind::Return(Some(Expr { kind: ExprKind::Unary(Operator::Reference, _), .. }))));
+        let StmtKind::Defer(body) = &deferred.kind else { panic!() };
+        assert!(matches!(&body.stmts[0].kind, StmtKind::Expr(Expr { kind: ExprKind::Binary(Operator::Assign, target, _), .. })
+            if matches!(target.kind, ExprKind::Unary(Operator::Dereference, _))));
+
+        assert_eq!(parse("defer").unwrap_err().message, "expected a statement after `defer`");
+        assert_eq!(parse("return 1").unwrap_err().message, "expected `;` after `return`");
+    }
+
+    #[test]
+    fn declaration_errors() {
+        assert_eq!(parse("var x;").unwrap_err().message, "`x` needs a type or a value to infer it from");
+        assert_eq!(parse("const u8 N;").unwrap_err().message, "`N` is `const`, it needs a value");
+        assert_eq!(parse("static u8 n;").unwrap_err().message, "`n` is `static`, it needs a value");
+        assert_eq!(parse("var x = 4").unwrap_err().message, "expected `;` after the declaration");
+    }
+}
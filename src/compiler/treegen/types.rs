@@ -0,0 +1,368 @@
+use super::ast::{ArrayLen, Span, TypeExpr, TypeExprKind};
+use super::parser::{Cursor, ParseResult, SyntaxError};
+use crate::compiler::{
+    keywords::Keyword,
+    objects::types::Abi,
+    operators::Operator,
+    parsing::tokenizer::{Token, TokenData},
+};
+
+// A type at the cursor. Something that is clearly meant as a type but written wrong, like `*u8*`, is an error
+pub fn parse_type(cursor: &mut Cursor) -> ParseResult<TypeExpr> {
+    let mut parser = TypeParser { cursor, pending_close: false, problem: None };
+    let Some(ty) = parser.ty() else {
+        return Err(parser.cursor.error("expected a type"));
+    };
+    match parser.problem {
+        Some(problem) => Err(problem),
+        None => Ok(ty),
+    }
+}
+
+// How many tokens the type at the start of `tokens` takes, mistakes included, for the grammar patterns
+pub fn type_length(tokens: &[Token]) -> Option<usize> {
+    let mut cursor = Cursor::new(tokens, 0);
+    let mut parser = TypeParser { cursor: &mut cursor, pending_close: false, problem: None };
+    parser.ty()?;
+    Some(cursor.position())
+}
+
+enum Prefix {
+    Pointer,
+    Reference,
+    Const,
+}
+
+// Fails without a diagnostic when the tokens are not a type at all, so callers can try something else
+struct TypeParser<'c, 'a> {
+    cursor: &'c mut Cursor<'a>,
+    // The second half of a `>>` that closed two generic argument lists
+    pending_close: bool,
+    // The first mistake in something that is still read as a type
+    problem: Option<SyntaxError>,
+}
+
+impl TypeParser<'_, '_> {
+    fn report(&mut self, message: &str, span: Span) {
+        self.problem.get_or_insert(SyntaxError { message: message.to_string(), span });
+    }
+
+    // Prefixes apply to everything after them, trailing `*` to what is before them, so `*u8*` can't be read both ways
+    fn ty(&mut self) -> Option<TypeExpr> {
+        let start = self.cursor.start();
+        let mut prefixes = Vec::new();
+        loop {
+            let prefix = match self.cursor.peek()?.data {
+                TokenData::Operator(Operator::Dereference | Operator::Mult) => Prefix::Pointer,
+                TokenData::Operator(Operator::Reference) => Prefix::Reference,
+                TokenData::Keyword(Keyword::Const) => Prefix::Const,
+                _ => break,
+            };
+            prefixes.push((prefix, self.cursor.start()));
+            self.cursor.bump();
+        }
+
+        let unit_start = self.cursor.start();
+        let mut ty = self.unit()?;
+        let mut trailing = false;
+        // Trailing pointers are written right after the type
+        while !self.pending_close {
+            match self.cursor.peek_raw() {
+                Some(Token { data: TokenData::Operator(Operator::Dereference | Operator::Mult), .. }) => self.cursor.bump(),
+                _ => break,
+            };
+            trailing = true;
+            ty = TypeExpr::new(TypeExprKind::Pointer(Box::new(ty)), self.cursor.span_from(unit_start));
+        }
+        if trailing && prefixes.iter().any(|(prefix, _)| matches!(prefix, Prefix::Pointer)) {
+            let span = self.cursor.span_from(start);
+            self.report("`*` is written on both sides of this type, keep its pointers on one side", span);
+        }
+
+        for (prefix, at) in prefixes.into_iter().rev() {
+            let inner = Box::new(ty);
+            let kind = match prefix {
+                Prefix::Pointer => TypeExprKind::Pointer(inner),
+                Prefix::Reference => TypeExprKind::Reference(inner),
+                Prefix::Const => TypeExprKind::Const(inner),
+            };
+            ty = TypeExpr::new(kind, self.cursor.span_from(at));
+        }
+        Some(ty)
+    }
+
+    fn unit(&mut self) -> Option<TypeExpr> {
+        let start = self.cursor.start();
+        let token = self.cursor.peek()?;
+        let kind = match &token.data {
+            TokenData::TextCluster(Some(_)) => return self.path(),
+            TokenData::Keyword(Keyword::Impl) => {
+                self.cursor.bump();
+                TypeExprKind::Impl(self.cursor.expect_ident("a trait name after `impl`").ok()?)
+            }
+            TokenData::Bracket(b'(', _) => {
+                self.cursor.bump();
+                let (mut items, trailing_comma) = self.list(token)?;
+                // `(A)` is only grouped, `(A,)` is a tuple
+                if items.len() == 1 && !trailing_comma {
+                    return items.pop();
+                }
+                TypeExprKind::Tuple(items)
+            }
+            TokenData::Bracket(b'[', _) => {
+                self.cursor.bump();
+                self.array(token)?
+            }
+            TokenData::Keyword(Keyword::Fn) => {
+                self.cursor.bump();
+                self.function(Abi::Gurn)?
+            }
+            TokenData::Keyword(Keyword::Extern) => {
+                self.cursor.bump();
+                self.cursor.eat_keyword(Keyword::Fn)?;
+                self.function(Abi::C)?
+            }
+            _ => return None,
+        };
+        Some(TypeExpr::new(kind, self.cursor.span_from(start)))
+    }
+
+    // `u8`, `io.File`, `Maybe<T>`
+    fn path(&mut self) -> Option<TypeExpr> {
+        let start = self.cursor.start();
+        let mut segments = vec![self.cursor.expect_ident("a type").ok()?];
+        while let Some(Token { data: TokenData::Dot, .. }) = self.cursor.peek_raw() {
+            let before = self.cursor.position();
+            self.cursor.bump();
+            match self.cursor.peek_raw() {
+                Some(Token { data: TokenData::TextCluster(Some(_)), .. }) => segments.push(self.cursor.expect_ident("a type").ok()?),
+                _ => {
+                    self.cursor.reset(before);
+                    break;
+                }
+            }
+        }
+
+        let mut args = Vec::new();
+        if let Some(Token { data: TokenData::Operator(Operator::LesserThan), .. }) = self.cursor.peek_raw() {
+            // `a < b` in an expression is not a generic type
+            let before = self.cursor.position();
+            let problem = self.problem.clone();
+            self.cursor.bump();
+            match self.generic_args() {
+                Some(found) => args = found,
+                None => {
+                    self.cursor.reset(before);
+                    self.problem = problem;
+                    self.pending_close = false;
+                }
+            }
+        }
+        Some(TypeExpr::new(TypeExprKind::Path(segments, args), self.cursor.span_from(start)))
+    }
+
+    fn generic_args(&mut self) -> Option<Vec<TypeExpr>> {
+        let mut args = Vec::new();
+        loop {
+            args.push(self.ty()?);
+            if self.close_angle() {
+                return Some(args);
+            }
+            self.cursor.eat(&TokenData::Colon)?;
+        }
+    }
+
+    // `>`, or one half of the `>>` that ends nested generic arguments
+    fn close_angle(&mut self) -> bool {
+        if self.pending_close {
+            self.pending_close = false;
+            return true;
+        }
+        match self.cursor.peek().map(|token| &token.data) {
+            Some(TokenData::Operator(Operator::GreaterThan)) => {
+                self.cursor.bump();
+                true
+            }
+            Some(TokenData::Operator(Operator::BitwiseShiftRight)) => {
+                self.cursor.bump();
+                self.pending_close = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Parses the inside of a bracket with a parser of its own, keeping its first mistake
+    fn inside<T>(&mut self, bracket: &Token, parse: impl FnOnce(&mut TypeParser) -> Option<T>) -> Option<T> {
+        let mut cursor = Cursor::enter(bracket);
+        let mut inner = TypeParser { cursor: &mut cursor, pending_close: false, problem: None };
+        let result = parse(&mut inner);
+        if let Some(problem) = inner.problem {
+            self.problem.get_or_insert(problem);
+        }
+        result
+    }
+
+    // The comma separated types of a tuple, and whether a comma ends them
+    fn list(&mut self, bracket: &Token) -> Option<(Vec<TypeExpr>, bool)> {
+        self.inside(bracket, |inner| {
+            let mut items = Vec::new();
+            let mut trailing_comma = false;
+            while !inner.cursor.is_at_end() {
+                items.push(inner.ty()?);
+                trailing_comma = false;
+                if inner.cursor.is_at_end() {
+                    break;
+                }
+                inner.cursor.eat(&TokenData::Colon)?;
+                trailing_comma = true;
+            }
+            Some((items, trailing_comma))
+        })
+    }
+
+    // `(u8, *T) -> bool` after `fn`
+    fn function(&mut self, abi: Abi) -> Option<TypeExprKind> {
+        let params = self.cursor.eat_bracket(b'(')?;
+        let (params, _) = self.list(params)?;
+        let before = self.cursor.position();
+        if self.cursor.eat(&TokenData::Operator(Operator::LineArrow)).is_none() {
+            // The whitespace after the type is not part of it
+            self.cursor.reset(before);
+            return Some(TypeExprKind::Function(abi, params, None));
+        }
+        let ret = self.ty()?;
+        Some(TypeExprKind::Function(abi, params, Some(Box::new(ret))))
+    }
+
+    // `[T]` or `[T; N]`
+    fn array(&mut self, bracket: &Token) -> Option<TypeExprKind> {
+        self.inside(bracket, |inner| {
+            let element = Box::new(inner.ty()?);
+            if inner.cursor.is_at_end() {
+                return Some(TypeExprKind::Slice(element));
+            }
+            inner.cursor.eat(&TokenData::Semicolon)?;
+            let len_start = inner.cursor.start();
+            let len = match inner.cursor.peek().map(|token| &token.data) {
+                Some(TokenData::NumberLiteral(number)) => {
+                    let number = number.clone();
+                    inner.cursor.bump();
+                    Some(ArrayLen::Number(number))
+                }
+                Some(TokenData::TextCluster(Some(_))) => inner.cursor.expect_ident("a length").ok().map(ArrayLen::Param),
+                _ => None,
+            };
+            match len {
+                Some(len) if inner.cursor.is_at_end() => Some(TypeExprKind::Array(element, len)),
+                _ => {
+                    while inner.cursor.next().is_some() {}
+                    let span = inner.cursor.span_from(len_start);
+                    inner.report("an array length must be a number or a `const` parameter", span);
+                    Some(TypeExprKind::Slice(element))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::parsing::tokenizer::tokenize_text;
+
+    // Fully parenthesized, to check what applies to what
+    fn shape(ty: &TypeExpr) -> String {
+        match &ty.kind {
+            TypeExprKind::Path(segments, args) => {
+                let path = segments.iter().map(|segment| segment.name.as_str()).collect::<Vec<_>>().join(".");
+                match args.is_empty() {
+                    true => path,
+                    false => format!("{}<{}>", path, args.iter().map(shape).collect::<Vec<_>>().join(", ")),
+                }
+            }
+            TypeExprKind::Pointer(inner) => format!("ptr({})", shape(inner)),
+            TypeExprKind::Reference(inner) => format!("ref({})", shape(inner)),
+            TypeExprKind::Const(inner) => format!("const({})", shape(inner)),
+            TypeExprKind::Impl(name) => format!("impl({})", name.name),
+            TypeExprKind::Array(element, ArrayLen::Number(number)) => format!("[{}; {}]", shape(element), number.text_content),
+            TypeExprKind::Array(element, ArrayLen::Param(name)) => format!("[{}; {}]", shape(element), name.name),
+            TypeExprKind::Slice(element) => format!("[{}]", shape(element)),
+            TypeExprKind::Tuple(items) => format!("({})", items.iter().map(|item| format!("{},", shape(item))).collect::<String>()),
+            TypeExprKind::Function(abi, params, ret) => format!(
+                "{}fn({}){}",
+                if *abi == Abi::C { "extern " } else { "" },
+                params.iter().map(shape).collect::<Vec<_>>().join(", "),
+                ret.as_ref().map_or(String::new(), |ret| format!(" -> {}", shape(ret)))
+            ),
+        }
+    }
+
+    fn parse(text: &str) -> ParseResult<(TypeExpr, usize)> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        let mut cursor = Cursor::new(&tokens, text.len());
+        let ty = parse_type(&mut cursor)?;
+        Ok((ty, cursor.rest().len()))
+    }
+
+    fn parse_all(text: &str) -> String {
+        let (ty, left) = parse(text).unwrap();
+        assert_eq!(left, 0, "{} was not fully parsed", text);
+        shape(&ty)
+    }
+
+    #[test]
+    fn type_shapes() {
+        assert_eq!(parse_all("u8"), "u8");
+        assert_eq!(parse_all("*u8"), "ptr(u8)");
+        assert_eq!(parse_all("u8**"), "ptr(ptr(u8))");
+        assert_eq!(parse_all("&const u8"), "ref(const(u8))");
+        assert_eq!(parse_all("const u8*"), "const(ptr(u8))");
+        assert_eq!(parse_all("&Maybe<u8>*"), "ref(ptr(Maybe<u8>))");
+        assert_eq!(parse_all("Pair<Maybe<Maybe<T>>, u8>"), "Pair<Maybe<Maybe<T>>, u8>");
+        assert_eq!(parse_all("Maybe<Maybe<*u8>>*"), "ptr(Maybe<Maybe<ptr(u8)>>)");
+        assert_eq!(parse_all("io.File"), "io.File");
+        assert_eq!(parse_all("impl Iterator"), "impl(Iterator)");
+        assert_eq!(parse_all("[u8; 4]"), "[u8; 4]");
+        assert_eq!(parse_all("[*T; N]"), "[ptr(T); N]");
+        assert_eq!(parse_all("[u8]"), "[u8]");
+        assert_eq!(parse_all("(u8, (u16,), ())"), "(u8,(u16,),(),)");
+        assert_eq!(parse_all("(u8)"), "u8");
+        assert_eq!(parse_all("fn(u8, *T) -> bool"), "fn(u8, ptr(T)) -> bool");
+        assert_eq!(parse_all("*fn()"), "ptr(fn())");
+        assert_eq!(parse_all("fn(u8) -> u8*"), "fn(u8) -> ptr(u8)");
+        assert_eq!(parse_all("extern fn(i32, *u8) -> fn(i32)"), "extern fn(i32, ptr(u8)) -> fn(i32)");
+
+        let (ty, _) = parse("  Maybe<u8>* name").unwrap();
+        assert_eq!(ty.span, Span::new(2, 12));
+    }
+
+    #[test]
+    fn type_lengths() {
+        let length = |text: &str| type_length(&tokenize_text(text.to_string()).unwrap());
+        // Whatever follows the type is left alone, whitespace included
+        assert_eq!(length("u8* x"), Some(2));
+        assert_eq!(length(" Maybe<u8> x"), Some(5));
+        // Not generic arguments, so only the name is the type
+        assert_eq!(length("a < b;"), Some(1));
+        assert_eq!(length("; 4"), None);
+        // Mistakes are still types, the parser reports them
+        assert_eq!(length("*u8* x"), Some(3));
+        assert_eq!(length("fn(u8) -> bool callback"), Some(6));
+        assert_eq!(length("fn(u8) callback"), Some(2));
+        // A closure, not a type
+        assert_eq!(length("fn(u8 x) => x"), None);
+    }
+
+    #[test]
+    fn type_errors() {
+        let error = |text: &str| parse(text).unwrap_err();
+        let both = error("*u8*");
+        assert_eq!(both.message, "`*` is written on both sides of this type, keep its pointers on one side");
+        assert_eq!(both.span, Span::new(0, 4));
+        assert_eq!(error("[u8; 2 + 2]").message, "an array length must be a number or a `const` parameter");
+        assert_eq!(error("(u8, *[u8; f()])").message, "an array length must be a number or a `const` parameter");
+        assert_eq!(error("; 4").message, "expected a type");
+        assert_eq!(error("impl 4").message, "expected a type");
+    }
+}
@@ -0,0 +1,871 @@
+use super::ast::{
+    ArrayLen, AssocItem, Block, Closure, Conditional, Decorator, EnumDecl, Expr, ExprKind, FieldDecl, FnBody, FnDecl, ForKind, GenericParam,
+    GenericParamKind, Ident, ImplDecl, Item, MatchArm, Mutability, Pattern, PatternArg, PatternKind, Stmt, StmtKind, Storage, StructDecl,
+    TraitDecl, TypeExpr, TypeExprKind, VarDecl, VariantDecl, VariantPayload, Visibility, WhereClause,
+};
+use crate::compiler::{objects::types::Abi, operators::PREFIX_PRECEDENCE};
+
+// Gurn source for a tree, parsing it again gives the same tree up to spans. Parentheses are only written where
+// precedence needs them, and error nodes are written as comments since the tokens they skipped are gone
+pub fn unparse(items: &[Item]) -> String {
+    let mut printer = Printer::default();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            printer.out.push('\n');
+        }
+        printer.item(item);
+    }
+    printer.out
+}
+
+pub fn unparse_expr(expr: &Expr) -> String {
+    let mut printer = Printer::default();
+    printer.expr(expr);
+    printer.out
+}
+
+pub fn unparse_type(ty: &TypeExpr) -> String {
+    let mut printer = Printer::default();
+    printer.ty(ty);
+    printer.out
+}
+
+// How tightly an expression holds together when something is written around it, above the binary operators
+const UNARY: u8 = PREFIX_PRECEDENCE;
+const POSTFIX: u8 = UNARY + 1;
+const ATOM: u8 = POSTFIX + 1;
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"    ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // Starts a line to be finished by `push` and `end_line`
+    fn begin(&mut self) {
+        self.out.push_str(&"    ".repeat(self.indent));
+    }
+
+    fn push(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    fn end_line(&mut self) {
+        self.out.push('\n');
+    }
+
+    fn docs(&mut self, docs: &[String]) {
+        for doc in docs {
+            match doc.is_empty() {
+                true => self.line("///"),
+                false => self.line(&format!("/// {}", doc)),
+            }
+        }
+    }
+
+    fn decorators(&mut self, decorators: &[Decorator]) {
+        for decorator in decorators {
+            self.begin();
+            self.decorator(decorator);
+            self.end_line();
+        }
+    }
+
+    fn decorator(&mut self, decorator: &Decorator) {
+        self.push("@");
+        self.push(&decorator.name.name);
+        if let Some(args) = &decorator.args {
+            self.push("(");
+            self.comma_separated(args, Self::expr);
+            self.push(")");
+        }
+    }
+
+    fn visibility(&mut self, visibility: Visibility) {
+        match visibility {
+            Visibility::Default => {}
+            Visibility::Pub => self.push("pub "),
+            Visibility::Private => self.push("private "),
+        }
+    }
+
+    fn comma_separated<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            write(self, item);
+        }
+    }
+
+    fn names(&mut self, names: &[Ident], separator: &str) {
+        let names: Vec<&str> = names.iter().map(|name| name.name.as_str()).collect();
+        self.push(&names.join(separator));
+    }
+
+    // Writes `{`, the lines `contents` writes one level deeper, and `}` without ending the line
+    fn braced(&mut self, contents: impl FnOnce(&mut Self)) {
+        self.push("{");
+        self.end_line();
+        self.indent += 1;
+        contents(self);
+        self.indent -= 1;
+        self.begin();
+        self.push("}");
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Struct(decl) => self.struct_decl(decl),
+            Item::Enum(decl) => self.enum_decl(decl),
+            Item::Trait(decl) => self.trait_decl(decl),
+            Item::Impl(decl) => self.impl_decl(decl),
+            Item::Function(decl) => self.fn_decl(decl),
+            Item::Var(decl) => {
+                self.begin();
+                self.var_decl(decl);
+                self.end_line();
+            }
+            Item::Error(error, _) => self.line(&format!("// syntax error: {}", error.message)),
+        }
+    }
+
+    fn struct_decl(&mut self, decl: &StructDecl) {
+        self.docs(&decl.docs);
+        self.decorators(&decl.decorators);
+        self.begin();
+        self.visibility(decl.visibility);
+        self.push("struct ");
+        self.push(&decl.name.name);
+        self.generics(&decl.generics);
+        if !decl.implements.is_empty() {
+            self.push(" implements ");
+            self.names(&decl.implements, ", ");
+        }
+        self.where_clause(&decl.where_clause);
+        self.push(" ");
+        self.braced(|printer| printer.fields(&decl.fields));
+        self.end_line();
+    }
+
+    fn fields(&mut self, fields: &[FieldDecl]) {
+        for field in fields {
+            self.docs(&field.docs);
+            self.decorators(&field.decorators);
+            self.begin();
+            self.visibility(field.visibility);
+            self.ty(&field.ty);
+            self.push(" ");
+            self.push(&field.name.name);
+            if let Some(default) = &field.default {
+                self.push(" = ");
+                self.expr(default);
+            }
+            self.push(",");
+            self.end_line();
+        }
+    }
+
+    fn enum_decl(&mut self, decl: &EnumDecl) {
+        self.docs(&decl.docs);
+        self.decorators(&decl.decorators);
+        self.begin();
+        self.visibility(decl.visibility);
+        self.push("enum ");
+        if let Some(backing) = &decl.backing {
+            self.ty(backing);
+            self.push(" ");
+        }
+        self.push(&decl.name.name);
+        self.generics(&decl.generics);
+        self.where_clause(&decl.where_clause);
+        self.push(" ");
+        self.braced(|printer| decl.variants.iter().for_each(|variant| printer.variant(variant)));
+        self.end_line();
+    }
+
+    fn variant(&mut self, variant: &VariantDecl) {
+        self.docs(&variant.docs);
+        self.decorators(&variant.decorators);
+        self.begin();
+        self.push(&variant.name.name);
+        match &variant.payload {
+            VariantPayload::Unit => {}
+            VariantPayload::Tuple(types) => {
+                self.push("(");
+                self.comma_separated(types, Self::ty);
+                self.push(")");
+            }
+            VariantPayload::Struct(fields) => {
+                self.push(" ");
+                self.braced(|printer| printer.fields(fields));
+            }
+        }
+        if let Some(discriminant) = &variant.discriminant {
+            self.push(" = ");
+            self.expr(discriminant);
+        }
+        self.push(",");
+        self.end_line();
+    }
+
+    fn trait_decl(&mut self, decl: &TraitDecl) {
+        self.docs(&decl.docs);
+        self.decorators(&decl.decorators);
+        self.begin();
+        self.visibility(decl.visibility);
+        self.push("trait ");
+        self.push(&decl.name.name);
+        self.generics(&decl.generics);
+        self.where_clause(&decl.where_clause);
+        self.push(" ");
+        self.braced(|printer| printer.assoc_items(&decl.items));
+        self.end_line();
+    }
+
+    fn impl_decl(&mut self, decl: &ImplDecl) {
+        self.docs(&decl.docs);
+        self.decorators(&decl.decorators);
+        self.begin();
+        self.push("impl");
+        self.generics(&decl.generics);
+        self.push(" ");
+        if let Some(trait_name) = &decl.trait_name {
+            self.push(&trait_name.name);
+            self.push(" for ");
+        }
+        self.ty(&decl.self_ty);
+        self.where_clause(&decl.where_clause);
+        self.push(" ");
+        self.braced(|printer| printer.assoc_items(&decl.items));
+        self.end_line();
+    }
+
+    fn assoc_items(&mut self, items: &[AssocItem]) {
+        for item in items {
+            match item {
+                AssocItem::Method(decl) => self.fn_decl(decl),
+                AssocItem::Type(decl) => {
+                    self.docs(&decl.docs);
+                    self.begin();
+                    self.push("typedef ");
+                    if let Some(ty) = &decl.ty {
+                        self.ty(ty);
+                        self.push(" ");
+                    }
+                    self.push(&decl.name.name);
+                    self.push(";");
+                    self.end_line();
+                }
+                AssocItem::Const(decl) => {
+                    self.docs(&decl.docs);
+                    self.begin();
+                    self.push("const ");
+                    self.ty(&decl.ty);
+                    self.push(" ");
+                    self.push(&decl.name.name);
+                    if let Some(value) = &decl.value {
+                        self.push(" = ");
+                        self.expr(value);
+                    }
+                    self.push(";");
+                    self.end_line();
+                }
+            }
+        }
+    }
+
+    fn fn_decl(&mut self, decl: &FnDecl) {
+        self.docs(&decl.docs);
+        self.decorators(&decl.decorators);
+        self.begin();
+        self.visibility(decl.visibility);
+        for modifier in &decl.modifiers {
+            self.push(modifier.text());
+            self.push(" ");
+        }
+        self.ty(&decl.return_type);
+        self.push(" ");
+        self.push(&decl.name.name);
+        self.generics(&decl.generics);
+        self.push("(");
+        self.comma_separated(&decl.params, |printer, param| {
+            for modifier in &param.modifiers {
+                printer.push(modifier.text());
+                printer.push(" ");
+            }
+            printer.ty(&param.ty);
+            printer.push(" ");
+            printer.push(&param.name.name);
+        });
+        self.push(")");
+        self.where_clause(&decl.where_clause);
+        match &decl.body {
+            Some(FnBody::Block(block)) => {
+                self.push(" ");
+                self.block(block);
+            }
+            Some(FnBody::Expr(expr)) => {
+                self.push(" => ");
+                self.expr(expr);
+                self.push(";");
+            }
+            None => self.push(";"),
+        }
+        self.end_line();
+    }
+
+    fn generics(&mut self, generics: &[GenericParam]) {
+        if generics.is_empty() {
+            return;
+        }
+        self.push("<");
+        self.comma_separated(generics, |printer, param| match &param.kind {
+            GenericParamKind::Type(bounds) => {
+                printer.push(&param.name.name);
+                if !bounds.is_empty() {
+                    printer.push(": ");
+                    printer.names(bounds, " + ");
+                }
+            }
+            GenericParamKind::Const(ty) => {
+                printer.push("const ");
+                printer.ty(ty);
+                printer.push(" ");
+                printer.push(&param.name.name);
+            }
+        });
+        self.push(">");
+    }
+
+    fn where_clause(&mut self, clause: &Option<WhereClause>) {
+        let Some(clause) = clause else { return };
+        self.push(" where ");
+        self.comma_separated(&clause.predicates, |printer, predicate| {
+            printer.ty(&predicate.ty);
+            printer.push(": ");
+            printer.names(&predicate.bounds, " + ");
+        });
+    }
+
+    fn var_decl(&mut self, decl: &VarDecl) {
+        if decl.storage == Storage::Static {
+            self.push("static ");
+        }
+        match (decl.mutability, &decl.ty) {
+            (Mutability::Const, _) => self.push("const "),
+            // `u8 x` is already a variable
+            (Mutability::Var, None) => self.push("var "),
+            (Mutability::Var, Some(_)) => {}
+        }
+        if let Some(ty) = &decl.ty {
+            self.ty(ty);
+            self.push(" ");
+        }
+        self.push(&decl.name.name);
+        if let Some(value) = &decl.value {
+            self.push(" = ");
+            self.expr(value);
+        }
+        self.push(";");
+    }
+
+    fn ty(&mut self, ty: &TypeExpr) {
+        match &ty.kind {
+            TypeExprKind::Path(segments, args) => {
+                self.names(segments, ".");
+                if !args.is_empty() {
+                    self.push("<");
+                    self.comma_separated(args, Self::ty);
+                    self.push(">");
+                }
+            }
+            // Pointers are always written in front, `*u8` rather than `u8*`
+            TypeExprKind::Pointer(inner) => {
+                self.push("*");
+                self.ty(inner);
+            }
+            TypeExprKind::Reference(inner) => {
+                self.push("&");
+                self.ty(inner);
+            }
+            TypeExprKind::Const(inner) => {
+                self.push("const ");
+                self.ty(inner);
+            }
+            TypeExprKind::Impl(name) => {
+                self.push("impl ");
+                self.push(&name.name);
+            }
+            TypeExprKind::Array(element, len) => {
+                self.push("[");
+                self.ty(element);
+                self.push("; ");
+                match len {
+                    ArrayLen::Number(number) => self.push(&number.text_content),
+                    ArrayLen::Param(name) => self.push(&name.name),
+                }
+                self.push("]");
+            }
+            TypeExprKind::Slice(element) => {
+                self.push("[");
+                self.ty(element);
+                self.push("]");
+            }
+            TypeExprKind::Tuple(items) => {
+                self.push("(");
+                self.comma_separated(items, Self::ty);
+                // `(A)` is only grouped
+                if items.len() == 1 {
+                    self.push(",");
+                }
+                self.push(")");
+            }
+            TypeExprKind::Function(abi, params, ret) => {
+                if *abi == Abi::C {
+                    self.push("extern ");
+                }
+                self.push("fn(");
+                self.comma_separated(params, Self::ty);
+                self.push(")");
+                if let Some(ret) = ret {
+                    self.push(" -> ");
+                    self.ty(ret);
+                }
+            }
+        }
+    }
+
+    // The statements one level deeper, between braces
+    fn block(&mut self, block: &Block) {
+        match (&block.value, block.stmts.is_empty()) {
+            (None, true) => return self.push("{}"),
+            (Some(value), true) => {
+                self.push("{ ");
+                self.expr(value);
+                return self.push(" }");
+            }
+            _ => {}
+        }
+        self.braced(|printer| {
+            for (i, stmt) in block.stmts.iter().enumerate() {
+                // An `if` last in a block would be read as its value
+                let last = i + 1 == block.stmts.len() && block.value.is_none();
+                printer.begin();
+                printer.stmt(stmt, last);
+                printer.end_line();
+            }
+            if let Some(value) = &block.value {
+                printer.begin();
+                printer.expr(value);
+                printer.end_line();
+            }
+        });
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, last: bool) {
+        match &stmt.kind {
+            StmtKind::Expr(expr) if matches!(expr.kind, ExprKind::If(_)) => {
+                self.expr(expr);
+                if last {
+                    self.push(";");
+                }
+            }
+            StmtKind::Expr(expr) => {
+                // Operators are spaced, so `a < b > c;` can't be read as a declaration of `c`
+                self.expr(expr);
+                self.push(";");
+            }
+            StmtKind::Var(decl) => self.var_decl(decl),
+            StmtKind::While(lp) => {
+                self.loop_head(&lp.label, lp.comptime, "while");
+                self.expr(&lp.cond);
+                self.push(") ");
+                self.block(&lp.body);
+            }
+            StmtKind::For(lp) => {
+                self.loop_head(&lp.label, lp.comptime, "for");
+                match &lp.kind {
+                    ForKind::Clauses { init, cond, step } => {
+                        match init {
+                            Some(init) => self.stmt(init, false),
+                            None => self.push(";"),
+                        }
+                        if let Some(cond) = cond {
+                            self.push(" ");
+                            self.expr(cond);
+                        }
+                        self.push(";");
+                        if let Some(step) = step {
+                            self.push(" ");
+                            self.expr(step);
+                        }
+                    }
+                    ForKind::In { binding, iterable } => {
+                        self.push(&binding.name);
+                        self.push(" in ");
+                        self.expr(iterable);
+                    }
+                }
+                self.push(") ");
+                self.block(&lp.body);
+            }
+            StmtKind::Break(label) | StmtKind::Continue(label) => {
+                self.push(match stmt.kind {
+                    StmtKind::Break(_) => "break",
+                    _ => "continue",
+                });
+                if let Some(label) = label {
+                    self.push(&format!(" '{}", label.name));
+                }
+                self.push(";");
+            }
+            StmtKind::Return(value) => {
+                self.push("return");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.expr(value);
+                }
+                self.push(";");
+            }
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::Defer(block) => {
+                self.push("defer ");
+                self.block(block);
+            }
+            StmtKind::Error(error) => self.push(&format!("// syntax error: {}", error.message)),
+        }
+    }
+
+    // `'outer: comptime while (`
+    fn loop_head(&mut self, label: &Option<Ident>, comptime: bool, keyword: &str) {
+        if let Some(label) = label {
+            self.push(&format!("'{}: ", label.name));
+        }
+        if comptime {
+            self.push("comptime ");
+        }
+        self.push(keyword);
+        self.push(" (");
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.expr_in(expr, 0, true);
+    }
+
+    // `min` is how tightly the expression must hold together where it is written, `open` whether nothing follows it,
+    // so an `if` or closure can run to the end without taking anything else into its last branch
+    fn expr_in(&mut self, expr: &Expr, min: u8, open: bool) {
+        let (holds, trailing) = match &expr.kind {
+            ExprKind::Binary(operator, ..) => (operator.binary_precedence().unwrap_or(0), false),
+            ExprKind::Unary(..) => (UNARY, false),
+            ExprKind::Postfix(..) | ExprKind::Call(..) | ExprKind::Index(..) | ExprKind::Field(..) | ExprKind::Propagate(..) => (POSTFIX, false),
+            ExprKind::If(_) | ExprKind::Closure(_) => (ATOM, true),
+            ExprKind::Number(_) | ExprKind::Str(_) | ExprKind::Name(_) | ExprKind::Tuple(_) | ExprKind::Array(_) | ExprKind::Match(..) => (ATOM, false),
+        };
+        if holds < min || (trailing && !open) {
+            self.push("(");
+            self.expr_kind(expr, true);
+            self.push(")");
+        } else {
+            self.expr_kind(expr, open);
+        }
+    }
+
+    fn expr_kind(&mut self, expr: &Expr, open: bool) {
+        match &expr.kind {
+            ExprKind::Number(number) => self.push(&number.text_content),
+            ExprKind::Str(string) => self.push(&string.source()),
+            ExprKind::Name(name) => self.push(name),
+            ExprKind::Unary(operator, operand) => {
+                self.push(operator.text());
+                self.expr_in(operand, POSTFIX, open);
+            }
+            ExprKind::Postfix(operator, operand) => {
+                self.expr_in(operand, POSTFIX, false);
+                self.push(operator.text());
+            }
+            // Never produced by the parser, written as the `!` it was lowered from
+            ExprKind::Propagate(operand, _) => {
+                self.expr_in(operand, POSTFIX, false);
+                self.push("!");
+            }
+            ExprKind::Binary(operator, lhs, rhs) => {
+                let precedence = operator.binary_precedence().unwrap_or(0);
+                // The side that groups with the operator may hold it again, `a - b - c` and `a = b = c`
+                let (left, right) = match operator.is_right_associative() {
+                    true => (precedence + 1, precedence),
+                    false => (precedence, precedence + 1),
+                };
+                self.expr_in(lhs, left, false);
+                self.push(&format!(" {} ", operator.text()));
+                self.expr_in(rhs, right, open);
+            }
+            ExprKind::Call(callee, args) => {
+                self.expr_in(callee, POSTFIX, false);
+                self.push("(");
+                self.comma_separated(args, Self::expr);
+                self.push(")");
+            }
+            ExprKind::Index(value, index) => {
+                self.expr_in(value, POSTFIX, false);
+                self.push("[");
+                self.expr(index);
+                self.push("]");
+            }
+            ExprKind::Field(value, name) => {
+                // `1.x` would be read as a number
+                match value.kind {
+                    ExprKind::Number(_) => self.expr_in(value, ATOM + 1, false),
+                    _ => self.expr_in(value, POSTFIX, false),
+                }
+                self.push(".");
+                self.push(&name.name);
+            }
+            ExprKind::Tuple(items) => {
+                self.push("(");
+                self.comma_separated(items, Self::expr);
+                if items.len() == 1 {
+                    self.push(",");
+                }
+                self.push(")");
+            }
+            ExprKind::Array(items) => {
+                self.push("[");
+                self.comma_separated(items, Self::expr);
+                self.push("]");
+            }
+            ExprKind::Match(value, arms) => {
+                self.push("match (");
+                self.expr(value);
+                self.push(") ");
+                self.braced(|printer| {
+                    for arm in arms {
+                        printer.begin();
+                        printer.arm(arm);
+                        printer.push(",");
+                        printer.end_line();
+                    }
+                });
+            }
+            ExprKind::If(conditional) => self.conditional(conditional),
+            ExprKind::Closure(closure) => self.closure(closure),
+        }
+    }
+
+    // Every branch in braces, which reads the same as a statement or as a value
+    fn conditional(&mut self, conditional: &Conditional) {
+        if conditional.comptime {
+            self.push("comptime ");
+        }
+        for (i, branch) in conditional.branches.iter().enumerate() {
+            if i > 0 {
+                self.push(" else ");
+            }
+            self.push("if (");
+            self.expr(&branch.cond);
+            self.push(") ");
+            self.block(&branch.body);
+        }
+        if let Some(body) = &conditional.else_branch {
+            self.push(" else ");
+            self.block(body);
+        }
+    }
+
+    fn closure(&mut self, closure: &Closure) {
+        self.push("fn(");
+        self.comma_separated(&closure.params, |printer, param| {
+            if let Some(ty) = &param.ty {
+                printer.ty(ty);
+                printer.push(" ");
+            }
+            printer.push(&param.name.name);
+        });
+        self.push(")");
+        match (&closure.body.value, closure.body.stmts.is_empty()) {
+            (Some(value), true) => {
+                self.push(" => ");
+                self.expr(value);
+            }
+            _ => {
+                self.push(" ");
+                self.block(&closure.body);
+            }
+        }
+    }
+
+    fn arm(&mut self, arm: &MatchArm) {
+        self.pattern(&arm.pattern);
+        if let Some(guard) = &arm.guard {
+            self.push(" where ");
+            self.expr(guard);
+        }
+        self.push(" => ");
+        self.expr(&arm.body);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Wildcard => self.push("_"),
+            PatternKind::Binding(name) => self.push(&name.name),
+            PatternKind::Literal(number) => self.push(&number.text_content),
+            PatternKind::Range(start, end, inclusive) => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                self.push(&format!("{}{}{}", start.text_content, operator, end.text_content));
+            }
+            PatternKind::Tuple(patterns) => {
+                self.push("(");
+                self.comma_separated(patterns, Self::pattern);
+                if patterns.len() == 1 {
+                    self.push(",");
+                }
+                self.push(")");
+            }
+            PatternKind::Struct(name, args) => {
+                self.push(&name.name);
+                self.pattern_args(args);
+            }
+            PatternKind::Variant(ty, variant, args) => {
+                self.push(&format!("{}.{}", ty.name, variant.name));
+                if let Some(args) = args {
+                    self.pattern_args(args);
+                }
+            }
+        }
+    }
+
+    fn pattern_args(&mut self, args: &[PatternArg]) {
+        self.push("(");
+        self.comma_separated(args, |printer, arg| {
+            if let Some(name) = &arg.name {
+                printer.push(&name.name);
+                printer.push(" = ");
+            }
+            printer.pattern(&arg.pattern);
+        });
+        self.push(")");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::{expr::parse_expr, items::parse_items, parser::Cursor}};
+
+    fn parse(text: &str) -> Vec<Item> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        parse_items(&tokens, text.len()).unwrap_or_else(|error| panic!("{}\n{}", error, text))
+    }
+
+    fn expr(text: &str) -> String {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        unparse_expr(&parse_expr(&mut Cursor::new(&tokens, text.len())).unwrap())
+    }
+
+    // The tree as `Debug` writes it, with every span left out
+    fn without_spans(items: &[Item]) -> String {
+        let mut text = format!("{:?}", items);
+        while let Some(start) = text.find("Span { start: ") {
+            let end = start + text[start..].find('}').unwrap() + 1;
+            text.replace_range(start..end, "Span");
+        }
+        text
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "
+            /// A pair
+            ///
+            @packed
+            pub struct Pair<T: Add + Copy, const usize N> implements Copy, Eq where *T: Copy {
+                /// The first one
+                @align(8) pub T a = 1 + 2,
+                [T; N] b,
+                (u8,) c,
+                Maybe<Maybe<*u8>> d,
+                extern fn(u8, *T) -> bool e,
+            }
+            enum u8 Shape { Empty, Circle(f32, f32) = 4, Rect { f32 w, f32 h = 1.0 } }
+            trait Iterator<T> {
+                typedef Item;
+                typedef u8 Size;
+                const u32 LIMIT = 4;
+                Item next(&Self this);
+            }
+            impl<T> Iterator for Pair<T, u8> where T: Copy {
+                inline u8 next(&Self this) => this.a;
+            }
+            static const u64 LIMIT = 9;
+            @deprecated(\"use \\\"less\\\"\\n\") pub static u8 smaller<T>(const T a, T b) {
+                var x = 4;
+                u32* y;
+                a * b;
+                *p = (a + b) * -1;
+                x = y = z;
+                (a < b) > c;
+                f(x)?.g[1]!;
+                'outer: comptime while (a < b) {
+                    for (u32 i = 0; i < n; i += 1) continue 'outer;
+                    for (; ; ) { break; }
+                    for (x in 0..n + 1) {}
+                }
+                defer free(p);
+                defer *p = 0;
+                { inner(); }
+                var m = match (x) {
+                    0 => a,
+                    1..=4 where x != 2 => b,
+                    (a, _) => c,
+                    Point(x, y = 0) => d,
+                    Shape.Circle(r, _) => e,
+                    Shape.Empty => f,
+                };
+                if (a) { f(); } else if (b) g(); else { h(); }
+                apply(fn(u8 a, b) => a + b, fn(x) { x; }, (1, 2), (1,), (), [1, 2]);
+                var v = (if (a) 1 else 2) + (fn(x) => x)(1);
+                if (a) { f(); };
+                return if (c) { 1 } else { 2 };
+            }
+        ";
+        let items = parse(text);
+        let printed = unparse(&items);
+        let reparsed = parse(&printed);
+        assert_eq!(without_spans(&reparsed), without_spans(&items), "{}", printed);
+        assert_eq!(unparse(&reparsed), printed);
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(expr("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(expr("1 + (2 * 3)"), "1 + 2 * 3");
+        assert_eq!(expr("a - (b - c)"), "a - (b - c)");
+        assert_eq!(expr("(a - b) - c"), "a - b - c");
+        assert_eq!(expr("a = (b = c)"), "a = b = c");
+        assert_eq!(expr("(a = b) = c"), "(a = b) = c");
+        assert_eq!(expr("*(a + b)"), "*(a + b)");
+        assert_eq!(expr("(*p).x"), "(*p).x");
+        assert_eq!(expr("(a)(b)[(c)]"), "a(b)[c]");
+        assert_eq!(expr("(if (a) 1 else 2) + 1"), "(if (a) { 1 } else { 2 }) + 1");
+        assert_eq!(expr("1 + if (a) 1 else 2"), "1 + if (a) { 1 } else { 2 }");
+        assert_eq!(expr("(fn(x) => x)(1)"), "(fn(x) => x)(1)");
+        assert_eq!(expr("f(fn(x) => x, (a, b))"), "f(fn(x) => x, (a, b))");
+    }
+
+    #[test]
+    fn formatting() {
+        let text = "u8 f(u8 x){if(x){return 1;}while(x)x=x - 1;return 0;} struct P{u8 a}";
+        assert_eq!(
+            unparse(&parse(text)),
+            "u8 f(u8 x) {\n    if (x) {\n        return 1;\n    }\n    while (x) {\n        x = x - 1;\n    }\n    return 0;\n}\n\nstruct P {\n    u8 a,\n}\n"
+        );
+    }
+}
@@ -0,0 +1,514 @@
+// Walking the tree. `Visit` reads it, `VisitMut` changes it in place, and each has a method per node type that
+// recurses into the children by default. A pass overrides the methods for the nodes it cares about, and calls
+// the matching `walk` function to keep going below them. Both traits come from the one `make_visitor!`, so they
+// can't drift apart
+
+// Generates a visitor trait and the `walk` functions its methods default to, `iter_mut, mut` makes them take `&mut` nodes
+macro_rules! make_visitor {
+    ($visitor:ident, $walk:ident, $iter:ident, $($mutability:ident)?) => {
+        pub trait $visitor: Sized {
+            fn visit_item(&mut self, item: &$($mutability)? Item) { $walk::walk_item(self, item) }
+            fn visit_struct(&mut self, decl: &$($mutability)? StructDecl) { $walk::walk_struct(self, decl) }
+            fn visit_field(&mut self, field: &$($mutability)? FieldDecl) { $walk::walk_field(self, field) }
+            fn visit_enum(&mut self, decl: &$($mutability)? EnumDecl) { $walk::walk_enum(self, decl) }
+            fn visit_variant(&mut self, variant: &$($mutability)? VariantDecl) { $walk::walk_variant(self, variant) }
+            fn visit_trait(&mut self, decl: &$($mutability)? TraitDecl) { $walk::walk_trait(self, decl) }
+            fn visit_impl(&mut self, decl: &$($mutability)? ImplDecl) { $walk::walk_impl(self, decl) }
+            fn visit_assoc_item(&mut self, item: &$($mutability)? AssocItem) { $walk::walk_assoc_item(self, item) }
+            fn visit_assoc_type(&mut self, decl: &$($mutability)? AssocType) { $walk::walk_assoc_type(self, decl) }
+            fn visit_assoc_const(&mut self, decl: &$($mutability)? AssocConst) { $walk::walk_assoc_const(self, decl) }
+            fn visit_fn(&mut self, decl: &$($mutability)? FnDecl) { $walk::walk_fn(self, decl) }
+            fn visit_param(&mut self, param: &$($mutability)? Param) { $walk::walk_param(self, param) }
+            fn visit_generic_param(&mut self, param: &$($mutability)? GenericParam) { $walk::walk_generic_param(self, param) }
+            fn visit_where_clause(&mut self, clause: &$($mutability)? WhereClause) { $walk::walk_where_clause(self, clause) }
+            fn visit_where_predicate(&mut self, predicate: &$($mutability)? WherePredicate) { $walk::walk_where_predicate(self, predicate) }
+            fn visit_decorator(&mut self, decorator: &$($mutability)? Decorator) { $walk::walk_decorator(self, decorator) }
+            fn visit_var(&mut self, decl: &$($mutability)? VarDecl) { $walk::walk_var(self, decl) }
+            fn visit_type(&mut self, ty: &$($mutability)? TypeExpr) { $walk::walk_type(self, ty) }
+            fn visit_block(&mut self, block: &$($mutability)? Block) { $walk::walk_block(self, block) }
+            fn visit_stmt(&mut self, stmt: &$($mutability)? Stmt) { $walk::walk_stmt(self, stmt) }
+            fn visit_while(&mut self, lp: &$($mutability)? WhileLoop) { $walk::walk_while(self, lp) }
+            fn visit_for(&mut self, lp: &$($mutability)? ForLoop) { $walk::walk_for(self, lp) }
+            fn visit_expr(&mut self, expr: &$($mutability)? Expr) { $walk::walk_expr(self, expr) }
+            fn visit_conditional(&mut self, conditional: &$($mutability)? Conditional) { $walk::walk_conditional(self, conditional) }
+            fn visit_cond_branch(&mut self, branch: &$($mutability)? CondBranch) { $walk::walk_cond_branch(self, branch) }
+            fn visit_closure(&mut self, closure: &$($mutability)? Closure) { $walk::walk_closure(self, closure) }
+            fn visit_closure_param(&mut self, param: &$($mutability)? ClosureParam) { $walk::walk_closure_param(self, param) }
+            fn visit_match_arm(&mut self, arm: &$($mutability)? MatchArm) { $walk::walk_match_arm(self, arm) }
+            fn visit_pattern(&mut self, pattern: &$($mutability)? Pattern) { $walk::walk_pattern(self, pattern) }
+            fn visit_pattern_arg(&mut self, arg: &$($mutability)? PatternArg) { $walk::walk_pattern_arg(self, arg) }
+            // Every name written in the tree, declared or used, except the `Name` expressions which are plain strings
+            fn visit_ident(&mut self, ident: &$($mutability)? Ident) { $walk::walk_ident(self, ident) }
+            fn visit_span(&mut self, _span: &$($mutability)? Span) {}
+            // After the node's children and span
+            fn visit_node_id(&mut self, _id: &$($mutability)? NodeId) {}
+        }
+
+        // Children are walked in the order they are written
+        pub mod $walk {
+            use super::$visitor;
+            use crate::compiler::treegen::ast::*;
+
+            pub fn walk_item<V: $visitor>(visitor: &mut V, item: &$($mutability)? Item) {
+                match item {
+                    Item::Struct(decl) => visitor.visit_struct(decl),
+                    Item::Enum(decl) => visitor.visit_enum(decl),
+                    Item::Trait(decl) => visitor.visit_trait(decl),
+                    Item::Impl(decl) => visitor.visit_impl(decl),
+                    Item::Function(decl) => visitor.visit_fn(decl),
+                    Item::Var(decl) => visitor.visit_var(decl),
+                    Item::Error(_, span) => visitor.visit_span(span),
+                }
+            }
+
+            pub fn walk_struct<V: $visitor>(visitor: &mut V, decl: &$($mutability)? StructDecl) {
+                decl.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                visitor.visit_ident(&$($mutability)? decl.name);
+                decl.generics.$iter().for_each(|param| visitor.visit_generic_param(param));
+                decl.implements.$iter().for_each(|name| visitor.visit_ident(name));
+                if let Some(clause) = &$($mutability)? decl.where_clause {
+                    visitor.visit_where_clause(clause);
+                }
+                decl.fields.$iter().for_each(|field| visitor.visit_field(field));
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_field<V: $visitor>(visitor: &mut V, field: &$($mutability)? FieldDecl) {
+                field.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                visitor.visit_type(&$($mutability)? field.ty);
+                visitor.visit_ident(&$($mutability)? field.name);
+                if let Some(default) = &$($mutability)? field.default {
+                    visitor.visit_expr(default);
+                }
+                visitor.visit_span(&$($mutability)? field.span);
+                visitor.visit_node_id(&$($mutability)? field.id);
+            }
+
+            pub fn walk_enum<V: $visitor>(visitor: &mut V, decl: &$($mutability)? EnumDecl) {
+                decl.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                if let Some(backing) = &$($mutability)? decl.backing {
+                    visitor.visit_type(backing);
+                }
+                visitor.visit_ident(&$($mutability)? decl.name);
+                decl.generics.$iter().for_each(|param| visitor.visit_generic_param(param));
+                if let Some(clause) = &$($mutability)? decl.where_clause {
+                    visitor.visit_where_clause(clause);
+                }
+                decl.variants.$iter().for_each(|variant| visitor.visit_variant(variant));
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_variant<V: $visitor>(visitor: &mut V, variant: &$($mutability)? VariantDecl) {
+                variant.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                visitor.visit_ident(&$($mutability)? variant.name);
+                match &$($mutability)? variant.payload {
+                    VariantPayload::Unit => {}
+                    VariantPayload::Tuple(types) => types.$iter().for_each(|ty| visitor.visit_type(ty)),
+                    VariantPayload::Struct(fields) => fields.$iter().for_each(|field| visitor.visit_field(field)),
+                }
+                if let Some(discriminant) = &$($mutability)? variant.discriminant {
+                    visitor.visit_expr(discriminant);
+                }
+                visitor.visit_span(&$($mutability)? variant.span);
+                visitor.visit_node_id(&$($mutability)? variant.id);
+            }
+
+            pub fn walk_trait<V: $visitor>(visitor: &mut V, decl: &$($mutability)? TraitDecl) {
+                decl.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                visitor.visit_ident(&$($mutability)? decl.name);
+                decl.generics.$iter().for_each(|param| visitor.visit_generic_param(param));
+                if let Some(clause) = &$($mutability)? decl.where_clause {
+                    visitor.visit_where_clause(clause);
+                }
+                decl.items.$iter().for_each(|item| visitor.visit_assoc_item(item));
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_impl<V: $visitor>(visitor: &mut V, decl: &$($mutability)? ImplDecl) {
+                decl.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                decl.generics.$iter().for_each(|param| visitor.visit_generic_param(param));
+                if let Some(name) = &$($mutability)? decl.trait_name {
+                    visitor.visit_ident(name);
+                }
+                visitor.visit_type(&$($mutability)? decl.self_ty);
+                if let Some(clause) = &$($mutability)? decl.where_clause {
+                    visitor.visit_where_clause(clause);
+                }
+                decl.items.$iter().for_each(|item| visitor.visit_assoc_item(item));
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_assoc_item<V: $visitor>(visitor: &mut V, item: &$($mutability)? AssocItem) {
+                match item {
+                    AssocItem::Method(decl) => visitor.visit_fn(decl),
+                    AssocItem::Type(decl) => visitor.visit_assoc_type(decl),
+                    AssocItem::Const(decl) => visitor.visit_assoc_const(decl),
+                }
+            }
+
+            pub fn walk_assoc_type<V: $visitor>(visitor: &mut V, decl: &$($mutability)? AssocType) {
+                if let Some(ty) = &$($mutability)? decl.ty {
+                    visitor.visit_type(ty);
+                }
+                visitor.visit_ident(&$($mutability)? decl.name);
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_assoc_const<V: $visitor>(visitor: &mut V, decl: &$($mutability)? AssocConst) {
+                visitor.visit_type(&$($mutability)? decl.ty);
+                visitor.visit_ident(&$($mutability)? decl.name);
+                if let Some(value) = &$($mutability)? decl.value {
+                    visitor.visit_expr(value);
+                }
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_fn<V: $visitor>(visitor: &mut V, decl: &$($mutability)? FnDecl) {
+                decl.decorators.$iter().for_each(|decorator| visitor.visit_decorator(decorator));
+                visitor.visit_type(&$($mutability)? decl.return_type);
+                visitor.visit_ident(&$($mutability)? decl.name);
+                decl.generics.$iter().for_each(|param| visitor.visit_generic_param(param));
+                decl.params.$iter().for_each(|param| visitor.visit_param(param));
+                if let Some(clause) = &$($mutability)? decl.where_clause {
+                    visitor.visit_where_clause(clause);
+                }
+                match &$($mutability)? decl.body {
+                    Some(FnBody::Block(block)) => visitor.visit_block(block),
+                    Some(FnBody::Expr(expr)) => visitor.visit_expr(expr),
+                    None => {}
+                }
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_param<V: $visitor>(visitor: &mut V, param: &$($mutability)? Param) {
+                visitor.visit_type(&$($mutability)? param.ty);
+                visitor.visit_ident(&$($mutability)? param.name);
+                visitor.visit_span(&$($mutability)? param.span);
+                visitor.visit_node_id(&$($mutability)? param.id);
+            }
+
+            pub fn walk_generic_param<V: $visitor>(visitor: &mut V, param: &$($mutability)? GenericParam) {
+                match &$($mutability)? param.kind {
+                    GenericParamKind::Type(bounds) => {
+                        visitor.visit_ident(&$($mutability)? param.name);
+                        bounds.$iter().for_each(|bound| visitor.visit_ident(bound));
+                    }
+                    GenericParamKind::Const(ty) => {
+                        visitor.visit_type(ty);
+                        visitor.visit_ident(&$($mutability)? param.name);
+                    }
+                }
+                visitor.visit_span(&$($mutability)? param.span);
+                visitor.visit_node_id(&$($mutability)? param.id);
+            }
+
+            pub fn walk_where_clause<V: $visitor>(visitor: &mut V, clause: &$($mutability)? WhereClause) {
+                clause.predicates.$iter().for_each(|predicate| visitor.visit_where_predicate(predicate));
+                visitor.visit_span(&$($mutability)? clause.span);
+            }
+
+            pub fn walk_where_predicate<V: $visitor>(visitor: &mut V, predicate: &$($mutability)? WherePredicate) {
+                visitor.visit_type(&$($mutability)? predicate.ty);
+                predicate.bounds.$iter().for_each(|bound| visitor.visit_ident(bound));
+                visitor.visit_span(&$($mutability)? predicate.span);
+            }
+
+            pub fn walk_decorator<V: $visitor>(visitor: &mut V, decorator: &$($mutability)? Decorator) {
+                visitor.visit_ident(&$($mutability)? decorator.name);
+                if let Some(args) = &$($mutability)? decorator.args {
+                    args.$iter().for_each(|arg| visitor.visit_expr(arg));
+                }
+                visitor.visit_span(&$($mutability)? decorator.span);
+                visitor.visit_node_id(&$($mutability)? decorator.id);
+            }
+
+            pub fn walk_var<V: $visitor>(visitor: &mut V, decl: &$($mutability)? VarDecl) {
+                if let Some(ty) = &$($mutability)? decl.ty {
+                    visitor.visit_type(ty);
+                }
+                visitor.visit_ident(&$($mutability)? decl.name);
+                if let Some(value) = &$($mutability)? decl.value {
+                    visitor.visit_expr(value);
+                }
+                visitor.visit_span(&$($mutability)? decl.span);
+                visitor.visit_node_id(&$($mutability)? decl.id);
+            }
+
+            pub fn walk_type<V: $visitor>(visitor: &mut V, ty: &$($mutability)? TypeExpr) {
+                match &$($mutability)? ty.kind {
+                    TypeExprKind::Path(segments, args) => {
+                        segments.$iter().for_each(|segment| visitor.visit_ident(segment));
+                        args.$iter().for_each(|arg| visitor.visit_type(arg));
+                    }
+                    TypeExprKind::Pointer(inner) | TypeExprKind::Reference(inner) | TypeExprKind::Const(inner) | TypeExprKind::Slice(inner) => {
+                        visitor.visit_type(inner)
+                    }
+                    TypeExprKind::Impl(name) => visitor.visit_ident(name),
+                    TypeExprKind::Array(element, len) => {
+                        visitor.visit_type(element);
+                        if let ArrayLen::Param(name) = len {
+                            visitor.visit_ident(name);
+                        }
+                    }
+                    TypeExprKind::Tuple(items) => items.$iter().for_each(|item| visitor.visit_type(item)),
+                    TypeExprKind::Function(_, params, ret) => {
+                        params.$iter().for_each(|param| visitor.visit_type(param));
+                        if let Some(ret) = ret {
+                            visitor.visit_type(ret);
+                        }
+                    }
+                }
+                visitor.visit_span(&$($mutability)? ty.span);
+                visitor.visit_node_id(&$($mutability)? ty.id);
+            }
+
+            pub fn walk_block<V: $visitor>(visitor: &mut V, block: &$($mutability)? Block) {
+                block.stmts.$iter().for_each(|stmt| visitor.visit_stmt(stmt));
+                if let Some(value) = &$($mutability)? block.value {
+                    visitor.visit_expr(value);
+                }
+                visitor.visit_span(&$($mutability)? block.span);
+                visitor.visit_node_id(&$($mutability)? block.id);
+            }
+
+            pub fn walk_stmt<V: $visitor>(visitor: &mut V, stmt: &$($mutability)? Stmt) {
+                match &$($mutability)? stmt.kind {
+                    StmtKind::Expr(expr) => visitor.visit_expr(expr),
+                    StmtKind::Var(decl) => visitor.visit_var(decl),
+                    StmtKind::While(lp) => visitor.visit_while(lp),
+                    StmtKind::For(lp) => visitor.visit_for(lp),
+                    StmtKind::Break(label) | StmtKind::Continue(label) => {
+                        if let Some(label) = label {
+                            visitor.visit_ident(label);
+                        }
+                    }
+                    StmtKind::Return(value) => {
+                        if let Some(value) = value {
+                            visitor.visit_expr(value);
+                        }
+                    }
+                    StmtKind::Block(block) | StmtKind::Defer(block) => visitor.visit_block(block),
+                    StmtKind::Error(_) => {}
+                }
+                visitor.visit_span(&$($mutability)? stmt.span);
+                visitor.visit_node_id(&$($mutability)? stmt.id);
+            }
+
+            pub fn walk_while<V: $visitor>(visitor: &mut V, lp: &$($mutability)? WhileLoop) {
+                if let Some(label) = &$($mutability)? lp.label {
+                    visitor.visit_ident(label);
+                }
+                visitor.visit_expr(&$($mutability)? lp.cond);
+                visitor.visit_block(&$($mutability)? lp.body);
+                visitor.visit_span(&$($mutability)? lp.span);
+            }
+
+            pub fn walk_for<V: $visitor>(visitor: &mut V, lp: &$($mutability)? ForLoop) {
+                if let Some(label) = &$($mutability)? lp.label {
+                    visitor.visit_ident(label);
+                }
+                match &$($mutability)? lp.kind {
+                    ForKind::Clauses { init, cond, step } => {
+                        if let Some(init) = init {
+                            visitor.visit_stmt(init);
+                        }
+                        if let Some(cond) = cond {
+                            visitor.visit_expr(cond);
+                        }
+                        if let Some(step) = step {
+                            visitor.visit_expr(step);
+                        }
+                    }
+                    ForKind::In { binding, iterable } => {
+                        visitor.visit_ident(binding);
+                        visitor.visit_expr(iterable);
+                    }
+                }
+                visitor.visit_block(&$($mutability)? lp.body);
+                visitor.visit_span(&$($mutability)? lp.span);
+            }
+
+            pub fn walk_expr<V: $visitor>(visitor: &mut V, expr: &$($mutability)? Expr) {
+                match &$($mutability)? expr.kind {
+                    ExprKind::Number(_) | ExprKind::Str(_) | ExprKind::Name(_) => {}
+                    ExprKind::Unary(_, operand) | ExprKind::Postfix(_, operand) => visitor.visit_expr(operand),
+                    ExprKind::Binary(_, lhs, rhs) | ExprKind::Index(lhs, rhs) => {
+                        visitor.visit_expr(lhs);
+                        visitor.visit_expr(rhs);
+                    }
+                    ExprKind::Call(callee, args) => {
+                        visitor.visit_expr(callee);
+                        args.$iter().for_each(|arg| visitor.visit_expr(arg));
+                    }
+                    ExprKind::Field(value, name) => {
+                        visitor.visit_expr(value);
+                        visitor.visit_ident(name);
+                    }
+                    ExprKind::Tuple(items) | ExprKind::Array(items) => items.$iter().for_each(|item| visitor.visit_expr(item)),
+                    ExprKind::Match(value, arms) => {
+                        visitor.visit_expr(value);
+                        arms.$iter().for_each(|arm| visitor.visit_match_arm(arm));
+                    }
+                    ExprKind::If(conditional) => visitor.visit_conditional(conditional),
+                    ExprKind::Closure(closure) => visitor.visit_closure(closure),
+                    ExprKind::Propagate(value, cleanup) => {
+                        visitor.visit_expr(value);
+                        visitor.visit_block(cleanup);
+                    }
+                }
+                visitor.visit_span(&$($mutability)? expr.span);
+                visitor.visit_node_id(&$($mutability)? expr.id);
+            }
+
+            pub fn walk_conditional<V: $visitor>(visitor: &mut V, conditional: &$($mutability)? Conditional) {
+                conditional.branches.$iter().for_each(|branch| visitor.visit_cond_branch(branch));
+                if let Some(body) = &$($mutability)? conditional.else_branch {
+                    visitor.visit_block(body);
+                }
+                visitor.visit_span(&$($mutability)? conditional.span);
+            }
+
+            pub fn walk_cond_branch<V: $visitor>(visitor: &mut V, branch: &$($mutability)? CondBranch) {
+                visitor.visit_expr(&$($mutability)? branch.cond);
+                visitor.visit_block(&$($mutability)? branch.body);
+                visitor.visit_span(&$($mutability)? branch.span);
+            }
+
+            pub fn walk_closure<V: $visitor>(visitor: &mut V, closure: &$($mutability)? Closure) {
+                closure.params.$iter().for_each(|param| visitor.visit_closure_param(param));
+                visitor.visit_block(&$($mutability)? closure.body);
+                visitor.visit_span(&$($mutability)? closure.span);
+            }
+
+            pub fn walk_closure_param<V: $visitor>(visitor: &mut V, param: &$($mutability)? ClosureParam) {
+                if let Some(ty) = &$($mutability)? param.ty {
+                    visitor.visit_type(ty);
+                }
+                visitor.visit_ident(&$($mutability)? param.name);
+                visitor.visit_node_id(&$($mutability)? param.id);
+            }
+
+            pub fn walk_match_arm<V: $visitor>(visitor: &mut V, arm: &$($mutability)? MatchArm) {
+                visitor.visit_pattern(&$($mutability)? arm.pattern);
+                if let Some(guard) = &$($mutability)? arm.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_expr(&$($mutability)? arm.body);
+                visitor.visit_span(&$($mutability)? arm.span);
+                visitor.visit_node_id(&$($mutability)? arm.id);
+            }
+
+            pub fn walk_pattern<V: $visitor>(visitor: &mut V, pattern: &$($mutability)? Pattern) {
+                match &$($mutability)? pattern.kind {
+                    PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range(..) => {}
+                    PatternKind::Binding(name) => visitor.visit_ident(name),
+                    PatternKind::Tuple(patterns) => patterns.$iter().for_each(|pattern| visitor.visit_pattern(pattern)),
+                    PatternKind::Struct(name, args) => {
+                        visitor.visit_ident(name);
+                        args.$iter().for_each(|arg| visitor.visit_pattern_arg(arg));
+                    }
+                    PatternKind::Variant(ty, variant, args) => {
+                        visitor.visit_ident(ty);
+                        visitor.visit_ident(variant);
+                        if let Some(args) = args {
+                            args.$iter().for_each(|arg| visitor.visit_pattern_arg(arg));
+                        }
+                    }
+                }
+                visitor.visit_span(&$($mutability)? pattern.span);
+                visitor.visit_node_id(&$($mutability)? pattern.id);
+            }
+
+            pub fn walk_ident<V: $visitor>(visitor: &mut V, ident: &$($mutability)? Ident) {
+                visitor.visit_span(&$($mutability)? ident.span);
+            }
+
+            pub fn walk_pattern_arg<V: $visitor>(visitor: &mut V, arg: &$($mutability)? PatternArg) {
+                if let Some(name) = &$($mutability)? arg.name {
+                    visitor.visit_ident(name);
+                }
+                visitor.visit_pattern(&$($mutability)? arg.pattern);
+                visitor.visit_span(&$($mutability)? arg.span);
+            }
+        }
+    };
+}
+
+use super::ast::*;
+
+make_visitor!(Visit, walk, iter,);
+make_visitor!(VisitMut, walk_mut, iter_mut, mut);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{parsing::tokenizer::tokenize_text, treegen::items::parse_items};
+
+    fn parse(text: &str) -> Vec<Item> {
+        let tokens = tokenize_text(text.to_string()).unwrap();
+        parse_items(&tokens, text.len()).unwrap()
+    }
+
+    struct Names(Vec<String>);
+
+    impl Visit for Names {
+        fn visit_ident(&mut self, ident: &Ident) {
+            self.0.push(ident.name.clone());
+        }
+    }
+
+    struct NodeIds(Vec<NodeId>);
+
+    impl Visit for NodeIds {
+        fn visit_node_id(&mut self, id: &NodeId) {
+            self.0.push(*id);
+        }
+    }
+
+    struct ClearSpans;
+
+    impl VisitMut for ClearSpans {
+        fn visit_span(&mut self, span: &mut Span) {
+            *span = Span::default();
+        }
+    }
+
+    #[test]
+    fn visit_in_source_order() {
+        let items = parse("struct P<T> { T a } u8 f(P p) { for (x in p.a) g(x); return match (p) { Point(v) => v }; }");
+        let mut names = Names(Vec::new());
+        items.iter().for_each(|item| names.visit_item(item));
+        assert_eq!(names.0, ["P", "T", "T", "a", "u8", "f", "P", "p", "x", "a", "Point", "v"]);
+    }
+
+    #[test]
+    fn visit_mut_in_place() {
+        let mut spaced = parse("u8   f( u8 x )   {  return x  +  1 ; }");
+        let mut tight = parse("u8 f(u8 x) { return x + 1; }");
+        assert_ne!(spaced, tight);
+        spaced.iter_mut().chain(tight.iter_mut()).for_each(|item| ClearSpans.visit_item(item));
+        assert_eq!(spaced, tight);
+    }
+
+    #[test]
+    fn node_ids() {
+        let text = "u8 f(P p) { var x = p.a; { g(x); } match (p) { Point(v) => v + x } }";
+        let items = parse(text);
+        let mut ids = NodeIds(Vec::new());
+        items.iter().for_each(|item| ids.visit_item(item));
+        // Dense, and every node is numbered after its children
+        assert_eq!(ids.0, (0..ids.0.len()).map(NodeId::new).collect::<Vec<_>>());
+        assert_eq!(parse(text), items);
+        // Declarations, parameters and types have ids too
+        let [Item::Function(decl)] = &items[..] else { panic!() };
+        assert_eq!((decl.return_type.id, decl.params[0].ty.id, decl.params[0].id), (NodeId::new(0), NodeId::new(1), NodeId::new(2)));
+        assert_eq!(decl.id, *ids.0.last().unwrap());
+    }
+}
@@ -8,11 +8,115 @@ use compiler::parsing::*;
 
 const HELLO : &str = "Type get_half_word() => halfWordSize;";
 
+const USAGE : &str = "Usage:
+    gurn grammar [--svg <output dir>]                   Print the grammar as EBNF, optionally writing railroad diagrams
+    gurn debug-pattern <PATTERN> <file.gurn> [--json]   Trace every attempt of a pattern against a file
+    gurn dump <tokens|ast|hir> [--format json|sexpr] <file.gurn>
+                                                        Print a file's tokens, syntax tree or HIR with spans, see docs/dump-format.md";
+
+// gurn grammar [--svg <output dir>]
+fn grammar_command(args : &[String]) -> Result<(), String>{
+    print!("{}", grammar_export::patterns_to_ebnf(ALL_PATTERNS));
+
+    match args{
+        [] => Ok(()),
+        [flag, dir] if flag == "--svg" => {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            for (name, pattern) in ALL_PATTERNS{
+                let path = std::path::Path::new(dir).join(format!("{}.svg", name));
+                std::fs::write(&path, grammar_export::pattern_to_railroad_svg(name, pattern)).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        },
+        _ => Err(USAGE.to_string())
+    }
+}
+
+// gurn debug-pattern <PATTERN> <file.gurn> [--json]
+fn debug_pattern_command(args : &[String]) -> Result<(), String>{
+    let (name, path, json) = match args{
+        [name, path] => (name, path, false),
+        [name, path, flag] if flag == "--json" => (name, path, true),
+        _ => return Err(USAGE.to_string())
+    };
+    let pattern = ALL_PATTERNS.iter()
+        .find(|(pattern_name, _)| pattern_name == name)
+        .ok_or_else(|| format!("Unknown pattern {}, expected one of: {}", name, ALL_PATTERNS.iter().map(|p| p.0).collect::<Vec<_>>().join(", ")))?
+        .1;
+
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let tokens = compiler::parsing::tokenizer::tokenize_text(text).map_err(|e| format!("{}: {:?}", path, e))?;
+
+    let mut trace = match_trace::MatchTrace::new();
+    let result = test_tokens_against_traced(pattern, &tokens, &mut trace);
+
+    if json{
+        let remaining = match &result{
+            Some((rest, _)) => rest.len().to_string(),
+            None => "null".to_string()
+        };
+        println!("{{\"pattern\":\"{}\",\"matched\":{},\"remaining_tokens\":{},\"trace\":{}}}", name, result.is_some(), remaining, trace.render_json());
+    }else{
+        print!("{}", trace.render_tree());
+        match &result{
+            Some((rest, _)) => println!("{} matched, {} top level tokens left over", name, rest.len()),
+            None => println!("{} did not match", name)
+        }
+    }
+    Ok(())
+}
+
+// gurn dump <tokens|ast|hir> [--format json|sexpr] <file.gurn>
+fn dump_command(args : &[String]) -> Result<(), String>{
+    use compiler::dump::{self, Format};
+
+    let (stage, format, path) = match args{
+        [stage, path] => (stage, "sexpr", path),
+        [stage, flag, format, path] if flag == "--format" => (stage, format.as_str(), path),
+        _ => return Err(USAGE.to_string())
+    };
+    let format = Format::from_name(format).ok_or_else(|| format!("Unknown format {}, expected json or sexpr", format))?;
+
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let length = text.len();
+    let tokens = compiler::parsing::tokenizer::tokenize_text(text).map_err(|e| format!("{}: {:?}", path, e))?;
+
+    let nodes = match stage.as_str(){
+        "tokens" => dump::tokens::dump_tokens(&tokens),
+        "ast" | "hir" => {
+            // What failed to parse is dumped as an error node, the errors themselves go to stderr
+            let (items, errors) = compiler::treegen::items::parse_file(&tokens, length);
+            for error in &errors{
+                eprintln!("{}: {}", path, error);
+            }
+            match stage.as_str(){
+                "ast" => dump::ast::dump_items(&items),
+                _ => dump::hir::dump_hir(&compiler::hir::lower::lower_items(&items))
+            }
+        },
+        _ => return Err(USAGE.to_string())
+    };
+    print!("{}", dump::render(nodes, format));
+    Ok(())
+}
+
 fn main() {
-    
-    // Either()
-    let ts = compiler::parsing::tokenizer::tokenize_text(HELLO.to_string()).unwrap();
-    // println!("{:?}", ts);
-    println!("{:?}", test_tokens_against(FUNCTION_DECLARATION, &ts));
-    
+    let args : Vec<String> = std::env::args().collect();
+    let result = match args.get(1).map(String::as_str){
+        Some("grammar") => grammar_command(&args[2..]),
+        Some("debug-pattern") => debug_pattern_command(&args[2..]),
+        Some("dump") => dump_command(&args[2..]),
+        Some(_) => Err(USAGE.to_string()),
+        None => {
+            // Either()
+            let ts = compiler::parsing::tokenizer::tokenize_text(HELLO.to_string()).unwrap();
+            // println!("{:?}", ts);
+            println!("{:?}", test_tokens_against(FUNCTION_DECLARATION, &ts));
+            Ok(())
+        }
+    };
+    if let Err(message) = result{
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
 }